@@ -0,0 +1,78 @@
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use ethers::core::utils::keccak256;
+use ethers::types::{Address, H256, U256};
+use oz_merkle_rs::MerkleTree;
+
+/// The fold as it was before the stack-buffer pair hash: sort twice, allocate per level.
+fn reference_verify(element: H256, proof: Vec<H256>, root: H256) -> bool {
+    let computed = proof.iter().fold(element, |hash, sibling| {
+        let mut pair = [&hash, sibling];
+        pair.sort();
+        H256(keccak256([pair[0].as_bytes(), pair[1].as_bytes()].concat()))
+    });
+    computed == root
+}
+
+fn proofs() -> (MerkleTree, Vec<(H256, Vec<H256>)>) {
+    let tree = MerkleTree::new(
+        (1..=4096u64)
+            .map(|i| (Address::from_low_u64_be(i), U256::from(i)))
+            .collect(),
+    );
+    let proofs = tree
+        .sorted_leaves()
+        .iter()
+        .step_by(16)
+        .map(|leaf| (*leaf, tree.get_proof(*leaf).unwrap()))
+        .collect();
+    (tree, proofs)
+}
+
+/// Every arm gets its own copy of the proofs, made outside the timed routine, so the
+/// arms taking the proof by value pay no clone the borrowing one does not.
+fn verify(c: &mut Criterion) {
+    let (tree, proofs) = proofs();
+    let root = tree.get_root().unwrap();
+    let mut group = c.benchmark_group("verify 256 proofs of a 4096-leaf tree");
+    group.bench_function("reference", |b| {
+        b.iter_batched(
+            || proofs.clone(),
+            |proofs| {
+                for (leaf, proof) in proofs {
+                    assert!(reference_verify(black_box(leaf), black_box(proof), root));
+                }
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_function("verify_proof", |b| {
+        b.iter_batched(
+            || proofs.clone(),
+            |proofs| {
+                for (leaf, proof) in proofs {
+                    assert!(tree.verify_proof(black_box(leaf), black_box(proof), root));
+                }
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_function("verify_proof_inline", |b| {
+        b.iter_batched(
+            || proofs.clone(),
+            |proofs| {
+                for (leaf, proof) in &proofs {
+                    assert!(MerkleTree::verify_proof_inline(
+                        black_box(*leaf),
+                        black_box(proof),
+                        root
+                    ));
+                }
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, verify);
+criterion_main!(benches);
@@ -0,0 +1,38 @@
+// SPDX-License-Identifier: MIT
+pragma solidity 0.8.24;
+
+import {MerkleProof} from "@openzeppelin/contracts/utils/cryptography/MerkleProof.sol";
+
+/// A minimal distributor for the end-to-end test of oz_merkle_rs, in tests/e2e.rs.
+///
+/// `claim` succeeds once per leaf when OpenZeppelin's `MerkleProof.verify` accepts the
+/// proof for `root`, and reverts otherwise.
+contract Distributor {
+    bytes32 public immutable root;
+    /// 0 for OZ's standard leaves, or the byte width of the amount of packed leaves.
+    uint256 public immutable width;
+    mapping(bytes32 => bool) public claimed;
+
+    constructor(bytes32 root_, uint256 width_) {
+        require(width_ == 0 || width_ == 12 || width_ == 32, "unsupported width");
+        root = root_;
+        width = width_;
+    }
+
+    function leaf(address account, uint256 amount) public view returns (bytes32) {
+        if (width == 0) {
+            return keccak256(bytes.concat(keccak256(abi.encode(account, amount))));
+        }
+        if (width == 12) {
+            return keccak256(abi.encodePacked(account, uint96(amount)));
+        }
+        return keccak256(abi.encodePacked(account, amount));
+    }
+
+    function claim(address account, uint256 amount, bytes32[] calldata proof) external {
+        bytes32 node = leaf(account, amount);
+        require(MerkleProof.verify(proof, root, node), "invalid proof");
+        require(!claimed[node], "already claimed");
+        claimed[node] = true;
+    }
+}
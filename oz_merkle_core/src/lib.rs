@@ -0,0 +1,99 @@
+//! The proof fold of `oz_merkle_rs`, usable under `no_std` without an allocator.
+//!
+//! Hashes are plain 32-byte arrays, proofs are borrowed, and pairs are hashed by feeding
+//! keccak256 directly, so nothing here allocates. Every proof check of `oz_merkle_rs`
+//! folds through [`process_proof`].
+
+#![no_std]
+
+use tiny_keccak::{Hasher, Keccak};
+
+/// The most hashes a proof accepted by the allocation-free verifiers may have, the depth
+/// of a tree of `2^64` leaves.
+pub const MAX_PROOF_DEPTH: usize = 64;
+
+/// Hashes two nodes in the given order, `keccak256(left ++ sep ++ right)`.
+#[inline]
+pub fn hash_ordered_pair(left: &[u8; 32], right: &[u8; 32], sep: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak::v256();
+    hasher.update(left);
+    hasher.update(sep);
+    hasher.update(right);
+    let mut output = [0u8; 32];
+    hasher.finalize(&mut output);
+    output
+}
+
+/// Hashes two nodes smaller first, `keccak256(min ++ sep ++ max)`. With an empty `sep`
+/// this is the pair hash of OpenZeppelin's `MerkleProof`.
+#[inline]
+pub fn hash_pair(a: &[u8; 32], b: &[u8; 32], sep: &[u8]) -> [u8; 32] {
+    if a <= b {
+        hash_ordered_pair(a, b, sep)
+    } else {
+        hash_ordered_pair(b, a, sep)
+    }
+}
+
+/// Folds a proof of a sorted-pair tree from the leaf up.
+///
+/// # Arguments
+///
+/// * `leaf` - The hash of the leaf.
+/// * `proof` - The sibling hashes, starting with the sibling of the leaf.
+/// * `sep` - The bytes hashed between the two children of every pair, empty for none.
+///
+/// # Returns
+///
+/// The root the proof commits to.
+#[inline]
+pub fn process_proof<'a>(
+    leaf: [u8; 32],
+    proof: impl IntoIterator<Item = &'a [u8; 32]>,
+    sep: &[u8],
+) -> [u8; 32] {
+    proof
+        .into_iter()
+        .fold(leaf, |computed, sibling| hash_pair(&computed, sibling, sep))
+}
+
+/// Folds a proof of a tree whose leaf index decides the side of every sibling, where an
+/// odd last node is promoted without a sibling.
+///
+/// # Arguments
+///
+/// * `leaf` - The hash of the leaf.
+/// * `index` - The position of the leaf in the tree.
+/// * `leaves` - The number of leaves of the tree.
+/// * `proof` - The sibling hashes, starting with the sibling of the leaf.
+///
+/// # Returns
+///
+/// The root the proof commits to, or `None` if `index` is out of range or the proof has
+/// a different number of hashes than the path of the leaf.
+pub fn process_ordered_proof<'a>(
+    leaf: [u8; 32],
+    mut index: usize,
+    mut leaves: usize,
+    proof: impl IntoIterator<Item = &'a [u8; 32]>,
+) -> Option<[u8; 32]> {
+    if index >= leaves {
+        return None;
+    }
+    let mut siblings = proof.into_iter();
+    let mut computed = leaf;
+    while leaves > 1 {
+        let sibling = index ^ 1;
+        if sibling < leaves {
+            let sibling_hash = siblings.next()?;
+            computed = if sibling > index {
+                hash_ordered_pair(&computed, sibling_hash, &[])
+            } else {
+                hash_ordered_pair(sibling_hash, &computed, &[])
+            };
+        }
+        index /= 2;
+        leaves = leaves.div_ceil(2);
+    }
+    siblings.next().is_none().then_some(computed)
+}
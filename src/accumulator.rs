@@ -0,0 +1,205 @@
+use crate::{MerkleError, MerkleTree};
+use ethers::types::H256;
+use std::collections::{HashMap, HashSet};
+
+/// A streaming root accumulator in the style of a Merkle mountain range.
+///
+/// Leaves are absorbed one by one while only keeping one "peak" hash per set bit of the
+/// leaf count, i.e. O(log n) memory. Peaks are merged with the same sorted pair hash as
+/// [`MerkleTree`], and the root bags them from the smallest peak upwards, which is
+/// exactly how the tree promotes odd nodes. Absorbing the sorted leaves of a tree
+/// therefore reproduces its root for any number of leaves.
+#[derive(Debug, Clone, Default)]
+pub struct MerkleAccumulator {
+    // peaks[h] holds the root of a complete subtree of 2^h leaves, if any
+    peaks: Vec<Option<H256>>,
+    len: usize,
+}
+
+impl MerkleAccumulator {
+    /// Creates an empty accumulator.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Absorbs the next leaf.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf` - The hash of the leaf to append.
+    pub fn absorb(&mut self, leaf: H256) {
+        let mut carry = leaf;
+        for peak in self.peaks.iter_mut() {
+            match peak.take() {
+                Some(left) => carry = MerkleTree::hash_pair(&left, &carry),
+                None => {
+                    *peak = Some(carry);
+                    self.len += 1;
+                    return;
+                }
+            }
+        }
+        self.peaks.push(Some(carry));
+        self.len += 1;
+    }
+
+    /// Returns the number of leaves absorbed so far.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether no leaf has been absorbed yet.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the root of all leaves absorbed so far.
+    ///
+    /// # Returns
+    ///
+    /// An `Option` containing the bagged root, or `None` if no leaf has been absorbed.
+    pub fn root(&self) -> Option<H256> {
+        self.peaks
+            .iter()
+            .flatten()
+            .copied()
+            .reduce(|right, left| MerkleTree::hash_pair(&left, &right))
+    }
+}
+
+/// A subtree root with the partial proofs of the watched leaves below it.
+type WatchedNode = (H256, Vec<(H256, Vec<H256>)>);
+
+impl MerkleTree {
+    /// Computes the root of a stream of sorted leaves, keeping proofs for a few of them.
+    ///
+    /// The leaves are absorbed like in [`MerkleAccumulator`], so only O(log n) peaks and
+    /// the proofs of the watched leaves are held in memory, never the whole tree. Each
+    /// proof is identical to the one `get_proof` returns on a tree built from all leaves.
+    ///
+    /// # Arguments
+    ///
+    /// * `sorted_leaves` - Leaf hashes in strictly ascending order.
+    /// * `watch` - The leaves to capture proofs for; leaves missing from the stream are ignored.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the root and the proofs of the watched leaves found in the
+    /// stream, or `MerkleError::EmptyTree` for an empty stream, or
+    /// `MerkleError::UnsortedLeaves` with the index of the first out-of-order leaf.
+    pub fn build_with_watchlist(
+        sorted_leaves: impl IntoIterator<Item = H256>,
+        watch: &HashSet<H256>,
+    ) -> Result<(H256, HashMap<H256, Vec<H256>>), MerkleError> {
+        // peaks[h] holds the root of a complete subtree of 2^h leaves, if any
+        let mut peaks: Vec<Option<WatchedNode>> = Vec::new();
+        let mut previous: Option<H256> = None;
+        for (index, leaf) in sorted_leaves.into_iter().enumerate() {
+            if previous.is_some_and(|previous| previous >= leaf) {
+                return Err(MerkleError::UnsortedLeaves(index));
+            }
+            previous = Some(leaf);
+
+            let watched = if watch.contains(&leaf) {
+                vec![(leaf, Vec::new())]
+            } else {
+                Vec::new()
+            };
+            let mut carry = Some((leaf, watched));
+            for peak in peaks.iter_mut() {
+                match peak.take() {
+                    Some(left) => carry = Some(Self::join_watched(left, carry.take().unwrap())),
+                    None => {
+                        *peak = carry.take();
+                        break;
+                    }
+                }
+            }
+            if carry.is_some() {
+                peaks.push(carry);
+            }
+        }
+
+        let (root, watched) = peaks
+            .into_iter()
+            .flatten()
+            .reduce(|right, left| Self::join_watched(left, right))
+            .ok_or(MerkleError::EmptyTree)?;
+        Ok((root, watched.into_iter().collect()))
+    }
+
+    /// Hashes two adjacent subtrees, extending the proofs below each with the other's root.
+    fn join_watched(left: WatchedNode, right: WatchedNode) -> WatchedNode {
+        let (left_hash, mut watched) = left;
+        let (right_hash, right_watched) = right;
+        for (_, proof) in watched.iter_mut() {
+            proof.push(right_hash);
+        }
+        watched.extend(right_watched.into_iter().map(|(leaf, mut proof)| {
+            proof.push(left_hash);
+            (leaf, proof)
+        }));
+        (MerkleTree::hash_pair(&left_hash, &right_hash), watched)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ethers::types::{Address, U256};
+
+    #[test]
+    fn accumulator_root_matches_tree_root() {
+        for count in [1u64, 2, 4, 8, 16, 64, 3, 5, 7, 13, 33] {
+            let data: Vec<(Address, U256)> = (1..=count)
+                .map(|i| (Address::from_low_u64_be(i), U256::from(i)))
+                .collect();
+            let tree = MerkleTree::new(data);
+            let mut accumulator = MerkleAccumulator::new();
+            for leaf in tree.sorted_leaves() {
+                accumulator.absorb(*leaf);
+            }
+
+            assert_eq!(accumulator.len(), count as usize);
+            assert_eq!(accumulator.root(), tree.get_root(), "{} leaves", count);
+        }
+        assert_eq!(MerkleAccumulator::new().root(), None);
+    }
+
+    #[test]
+    fn watchlist_proofs_match_full_tree() {
+        for count in [1u64, 2, 5, 8, 13, 33] {
+            let data: Vec<(Address, U256)> = (1..=count)
+                .map(|i| (Address::from_low_u64_be(i), U256::from(i)))
+                .collect();
+            let tree = MerkleTree::new(data);
+            let leaves = tree.sorted_leaves();
+            let watch: HashSet<H256> = leaves
+                .iter()
+                .step_by(3)
+                .chain(leaves.last())
+                .copied()
+                .chain([H256::zero()])
+                .collect();
+
+            let (root, proofs) =
+                MerkleTree::build_with_watchlist(leaves.iter().copied(), &watch).unwrap();
+            assert_eq!(Some(root), tree.get_root(), "{} leaves", count);
+            assert_eq!(proofs.len(), watch.len() - 1);
+            for (leaf, proof) in &proofs {
+                assert_eq!(Some(proof), tree.get_proof(*leaf).as_ref());
+            }
+        }
+
+        let watch = HashSet::new();
+        assert_eq!(
+            MerkleTree::build_with_watchlist([], &watch),
+            Err(MerkleError::EmptyTree)
+        );
+        let unsorted = [H256::repeat_byte(2), H256::repeat_byte(1)];
+        assert_eq!(
+            MerkleTree::build_with_watchlist(unsorted, &watch),
+            Err(MerkleError::UnsortedLeaves(1))
+        );
+    }
+}
@@ -0,0 +1,234 @@
+use crate::{MerkleError, MerkleTree};
+use ethers::types::U256;
+
+/// Helpers for token amounts written by humans.
+#[derive(Debug, Clone, Copy)]
+pub struct Amount;
+
+/// The token a tree distributes, used to display amounts in whole tokens.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TokenMetadata {
+    /// The ticker shown after amounts, such as `"USDC"`.
+    pub symbol: String,
+    /// The number of decimals of one whole token.
+    pub decimals: u8,
+}
+
+impl TokenMetadata {
+    pub fn new(symbol: impl Into<String>, decimals: u8) -> Self {
+        Self {
+            symbol: symbol.into(),
+            decimals,
+        }
+    }
+
+    /// Formats an amount of base units as whole tokens followed by the symbol.
+    ///
+    /// See `Amount::format_units`.
+    pub fn format(&self, amount: U256) -> String {
+        format!(
+            "{} {}",
+            Amount::format_units(amount, self.decimals),
+            self.symbol
+        )
+    }
+}
+
+impl Amount {
+    /// Parses an amount with an optional `wei`, `gwei` or `ether` unit, such as `"1.5 ether"`.
+    ///
+    /// The number is scaled by the unit with exact decimal arithmetic, so it may have at
+    /// most as many fractional digits as the unit has decimals. A bare number is in wei.
+    /// Units are case-insensitive and may be separated from the number by whitespace.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The amount string, e.g. `"2500 gwei"`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the amount in wei, or `MerkleError::InvalidAmount` on an
+    /// unknown unit, excess precision or overflow.
+    pub fn parse_units(value: &str) -> Result<U256, MerkleError> {
+        let value = value.trim();
+        let split = value
+            .find(|c: char| c.is_ascii_alphabetic())
+            .unwrap_or(value.len());
+        let (number, unit) = value.split_at(split);
+        let decimals = match unit.to_ascii_lowercase().as_str() {
+            "" | "wei" => 0,
+            "gwei" => 9,
+            "ether" => 18,
+            _ => {
+                return Err(MerkleError::InvalidAmount(format!(
+                    "unknown unit {:?} in {:?}",
+                    unit, value
+                )))
+            }
+        };
+        parse_decimal(number.trim_end(), decimals).map_err(MerkleError::InvalidAmount)
+    }
+
+    /// Formats an amount of base units as a decimal number of whole tokens.
+    ///
+    /// The conversion is exact: no digit is rounded away, however small the amount.
+    /// Trailing fractional zeros are dropped and the whole part is grouped by thousands,
+    /// so `1234567890000` with 6 decimals becomes `"1,234,567.89"`.
+    ///
+    /// # Arguments
+    ///
+    /// * `amount` - The amount in base units.
+    /// * `decimals` - The number of decimals of one whole token.
+    pub fn format_units(amount: U256, decimals: u8) -> String {
+        let decimals = decimals as usize;
+        let digits = format!("{:0>width$}", amount.to_string(), width = decimals + 1);
+        let (whole, fraction) = digits.split_at(digits.len() - decimals);
+
+        let mut grouped = String::with_capacity(whole.len() + whole.len() / 3);
+        for (i, digit) in whole.chars().enumerate() {
+            if i > 0 && (whole.len() - i) % 3 == 0 {
+                grouped.push(',');
+            }
+            grouped.push(digit);
+        }
+        let fraction = fraction.trim_end_matches('0');
+        if !fraction.is_empty() {
+            grouped.push('.');
+            grouped.push_str(fraction);
+        }
+        grouped
+    }
+}
+
+impl MerkleTree {
+    /// Attaches the metadata of the distributed token, used when amounts are displayed.
+    ///
+    /// The metadata does not affect any hash.
+    pub fn with_token(mut self, token: TokenMetadata) -> Self {
+        self.config.token = Some(token);
+        self
+    }
+
+    /// Returns the token metadata attached with `with_token`, if any.
+    pub fn token(&self) -> Option<&TokenMetadata> {
+        self.config.token.as_ref()
+    }
+}
+
+/// Parses an amount given either as `0x`-prefixed hex or as a decimal string.
+///
+/// Hex values are taken as base units. Decimal values may carry up to `decimals`
+/// fractional digits and are scaled by `10^decimals` with exact integer arithmetic;
+/// without `decimals` they must be whole base units. With `units`, values carrying a
+/// unit suffix are parsed by `Amount::parse_units` instead.
+#[cfg(any(feature = "csv", feature = "serde"))]
+pub(crate) fn parse_amount(value: &str, decimals: Option<u8>, units: bool) -> Result<U256, String> {
+    if let Some(hex) = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        return U256::from_str_radix(hex, 16)
+            .map_err(|_| format!("invalid hex amount {:?}", value));
+    }
+    if units && value.ends_with(|c: char| c.is_ascii_alphabetic()) {
+        return Amount::parse_units(value).map_err(|err| match err {
+            MerkleError::InvalidAmount(reason) => reason,
+            err => err.to_string(),
+        });
+    }
+    parse_decimal(value, decimals.unwrap_or(0))
+}
+
+/// Scales a decimal string such as `"1250.75"` by `10^decimals` without floating point.
+pub(crate) fn parse_decimal(value: &str, decimals: u8) -> Result<U256, String> {
+    let (whole, fraction) = value.split_once('.').unwrap_or((value, ""));
+    let is_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    if whole.is_empty() || !is_digits(whole) || !is_digits(fraction) || value.ends_with('.') {
+        return Err(format!("invalid decimal amount {:?}", value));
+    }
+    if fraction.len() > decimals as usize {
+        return Err(format!(
+            "amount {:?} has {} fractional digits but only {} are allowed",
+            value,
+            fraction.len(),
+            decimals
+        ));
+    }
+
+    let digits = format!("{}{:0<width$}", whole, fraction, width = decimals as usize);
+    U256::from_dec_str(&digits).map_err(|_| format!("amount {:?} overflows uint256", value))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_decimal_scales_exactly() {
+        assert_eq!(
+            parse_decimal("1250.75", 18).unwrap(),
+            U256::from_dec_str("1250750000000000000000").unwrap()
+        );
+        assert_eq!(parse_decimal("0.000001", 6).unwrap(), U256::one());
+        assert_eq!(parse_decimal("42", 0).unwrap(), U256::from(42));
+        assert!(parse_decimal("1.5", 0).is_err());
+        assert!(parse_decimal("1.", 6).is_err());
+        assert!(parse_decimal("-1", 6).is_err());
+        #[cfg(feature = "csv")]
+        assert!(parse_amount("0xzz", None, false).is_err());
+    }
+
+    #[test]
+    fn format_units_is_exact() {
+        let format = Amount::format_units;
+        assert_eq!(format(U256::from(1_234_567_890_000u64), 6), "1,234,567.89");
+        assert_eq!(format(U256::one(), 18), "0.000000000000000001");
+        assert_eq!(format(U256::from(100_000), 0), "100,000");
+        assert_eq!(format(U256::zero(), 6), "0");
+        assert_eq!(format(U256::exp10(18) * 999, 18), "999");
+        let max = format(U256::MAX, 0);
+        assert!(max.starts_with("115,792,089,237,"));
+        assert_eq!(
+            parse_decimal(&format(U256::MAX, 18).replace(',', ""), 18).unwrap(),
+            U256::MAX
+        );
+        assert_eq!(
+            TokenMetadata::new("USDC", 6).format(U256::from(2_500_001)),
+            "2.500001 USDC"
+        );
+    }
+
+    #[test]
+    fn parse_units_scales_by_unit() {
+        assert_eq!(
+            Amount::parse_units("1.5 ether").unwrap(),
+            U256::from(1_500_000_000_000_000_000u64)
+        );
+        assert_eq!(
+            Amount::parse_units("2500 gwei").unwrap(),
+            U256::from(2_500_000_000_000u64)
+        );
+        assert_eq!(
+            Amount::parse_units("0.5gwei").unwrap(),
+            U256::from(500_000_000)
+        );
+        assert_eq!(Amount::parse_units("42 wei").unwrap(), U256::from(42));
+        assert_eq!(Amount::parse_units("42").unwrap(), U256::from(42));
+        assert_eq!(Amount::parse_units("3 ETHER").unwrap(), U256::exp10(18) * 3);
+
+        assert!(matches!(
+            Amount::parse_units("1.5 wei"),
+            Err(MerkleError::InvalidAmount(reason)) if reason.contains("fractional digits")
+        ));
+        assert!(matches!(
+            Amount::parse_units("0.0000000001 gwei"),
+            Err(MerkleError::InvalidAmount(_))
+        ));
+        assert!(matches!(
+            Amount::parse_units("1 finney"),
+            Err(MerkleError::InvalidAmount(reason)) if reason.contains("unknown unit")
+        ));
+        assert!(Amount::parse_units("ether").is_err());
+    }
+}
@@ -0,0 +1,282 @@
+//! Zero-copy archived trees, available with the `rkyv` feature.
+//!
+//! A tree is written once with [`MerkleTree::to_archive_bytes`] and later accessed in
+//! place through [`ArchivedMerkleTree`], without deserializing its layers. The archive
+//! keeps the options that decide how the tree hashes its entries and pairs and how its
+//! leaves are found: the chain ID, the amount width of packed leaves, the node separator
+//! and whether the tree is ordered.
+
+use crate::{AmountWidth, EntryScheme, MerkleError, MerkleTree, NodeSep};
+use ethers::types::{Address, H256, U256};
+use rkyv::{AlignedVec, Archive, Serialize};
+
+/// The archived layout of a tree: every layer, from the leaves up to the root, and the
+/// options it was built with.
+#[derive(Archive, Serialize)]
+#[archive(archived = "ArchivedMerkleTree", check_bytes)]
+pub struct TreeLayout {
+    layers: Vec<Vec<[u8; 32]>>,
+    chain_id: Option<u64>,
+    // the byte width of packed amounts
+    amount_width: Option<u8>,
+    node_sep: Vec<u8>,
+    ordered: bool,
+}
+
+impl MerkleTree {
+    /// Serializes the tree into an rkyv archive.
+    ///
+    /// The returned bytes can be stored as-is and reopened with
+    /// [`ArchivedMerkleTree::from_bytes`] without rebuilding the tree.
+    pub fn to_archive_bytes(&self) -> AlignedVec {
+        let layout = TreeLayout {
+            layers: self
+                .layers
+                .iter()
+                .map(|layer| layer.iter().map(|node| node.to_fixed_bytes()).collect())
+                .collect(),
+            chain_id: self.config.chain_id,
+            amount_width: self.config.amount_width.map(|width| width.bytes() as u8),
+            node_sep: self.config.node_sep.as_bytes().to_vec(),
+            ordered: self.config.ordered,
+        };
+        rkyv::to_bytes::<_, 4096>(&layout).expect("serializing into memory cannot fail")
+    }
+}
+
+impl ArchivedMerkleTree {
+    /// Validates and opens an archived tree in place.
+    ///
+    /// The bytes are checked with `rkyv::check_archived_root`, so this is safe to call on
+    /// untrusted input. The buffer must be aligned, e.g. an `AlignedVec` or a page-aligned
+    /// memory map.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a reference to the archived tree, or
+    /// `MerkleError::InvalidArchive` if the bytes are not a valid archive, including one
+    /// whose amount width is not that of a packed tree.
+    pub fn from_bytes(bytes: &[u8]) -> Result<&ArchivedMerkleTree, MerkleError> {
+        let archived = rkyv::check_archived_root::<TreeLayout>(bytes)
+            .map_err(|err| MerkleError::InvalidArchive(err.to_string()))?;
+        if let Some(bytes) = archived.amount_width.as_ref() {
+            if amount_width(*bytes).is_none() {
+                return Err(MerkleError::InvalidArchive(format!(
+                    "invalid amount width of {} bytes",
+                    bytes
+                )));
+            }
+        }
+        Ok(archived)
+    }
+
+    /// Retrieves the root hash of the archived tree.
+    ///
+    /// # Returns
+    ///
+    /// An `Option` containing either the root hash if the tree is not empty,
+    /// or `None` if the tree is empty.
+    pub fn get_root(&self) -> Option<H256> {
+        self.layers
+            .last()
+            .and_then(|layer| layer.first())
+            .map(|node| H256::from(*node))
+    }
+
+    /// Retrieves the Merkle proof for a given element, reading directly from the archive.
+    ///
+    /// The leaves of sorted trees are found by binary search. Those of a tree built with
+    /// `new_ordered` are in input order and are scanned; a leaf given more than once is
+    /// proven at its first position, and `get_proof_at` proves the others.
+    ///
+    /// # Returns
+    ///
+    /// An `Option` containing either the Merkle proof if the element is found,
+    /// or `None` if the element is not present in the tree.
+    pub fn get_proof(&self, element: H256) -> Option<Vec<H256>> {
+        self.get_proof_at(self.position(element)?)
+    }
+
+    /// Retrieves the Merkle proof of the leaf at a given index, like
+    /// `MerkleTree::get_proof_at`.
+    ///
+    /// # Returns
+    ///
+    /// An `Option` containing the sibling hashes from the leaf up, or `None` if the index
+    /// is out of bounds.
+    pub fn get_proof_at(&self, mut index: usize) -> Option<Vec<H256>> {
+        if index >= self.leaves_length() {
+            return None;
+        }
+        let mut proof = Vec::new();
+        for layer in &self.layers[..self.layers.len() - 1] {
+            let pair_index = index ^ 1;
+            if pair_index < layer.len() {
+                proof.push(H256::from(layer[pair_index]));
+            }
+            index /= 2;
+        }
+        Some(proof)
+    }
+
+    /// Retrieves the Merkle proof for a leaf given its address and amount, hashed with
+    /// the archived chain ID and amount width, like `MerkleTree::get_entry_proof`.
+    ///
+    /// # Returns
+    ///
+    /// An `Option` containing either the Merkle proof if the leaf is found,
+    /// or `None` if the leaf is not present in the tree.
+    pub fn get_entry_proof(&self, leaf_data: (Address, U256)) -> Option<Vec<H256>> {
+        self.get_proof(self.entry_scheme().hash_entry(leaf_data).ok()?)
+    }
+
+    /// Returns how the archived tree hashes its entries and pairs, like
+    /// `MerkleTree::entry_scheme`, to verify its proofs.
+    pub fn entry_scheme(&self) -> EntryScheme {
+        EntryScheme {
+            chain_id: self.chain_id.as_ref().copied(),
+            amount_width: self
+                .amount_width
+                .as_ref()
+                .and_then(|bytes| amount_width(*bytes)),
+            node_sep: NodeSep(self.node_sep.to_vec()),
+        }
+    }
+
+    /// Returns whether the archived tree was built with `new_ordered`.
+    pub fn is_ordered(&self) -> bool {
+        self.ordered
+    }
+
+    /// Returns whether the element is one of the archived leaves.
+    pub fn contains(&self, element: H256) -> bool {
+        self.position(element).is_some()
+    }
+
+    /// Returns the number of leaves in the archived tree.
+    pub fn leaves_length(&self) -> usize {
+        self.layers.first().map_or(0, |layer| layer.len())
+    }
+
+    fn position(&self, element: H256) -> Option<usize> {
+        let leaves = self.layers.first()?;
+        if self.ordered {
+            return leaves
+                .iter()
+                .position(|leaf| leaf == element.as_fixed_bytes());
+        }
+        leaves.binary_search(element.as_fixed_bytes()).ok()
+    }
+}
+
+/// The amount width of packed amounts of `bytes` bytes.
+fn amount_width(bytes: u8) -> Option<AmountWidth> {
+    [
+        AmountWidth::U256,
+        AmountWidth::U128,
+        AmountWidth::U96,
+        AmountWidth::U64,
+    ]
+    .into_iter()
+    .find(|width| width.bytes() == usize::from(bytes))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ethers::types::{Address, U256};
+
+    fn sample_tree() -> MerkleTree {
+        MerkleTree::new(
+            (1..=11u64)
+                .map(|i| (Address::from_low_u64_be(i), U256::from(i * 7)))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn archived_tree_serves_live_proofs() {
+        let tree = sample_tree();
+        let bytes = tree.to_archive_bytes();
+        let archived = ArchivedMerkleTree::from_bytes(&bytes).unwrap();
+
+        assert_eq!(archived.get_root(), tree.get_root());
+        assert_eq!(archived.leaves_length(), tree.leaves_length());
+        for i in 1..=11u64 {
+            let leaf = MerkleTree::hash_node((Address::from_low_u64_be(i), U256::from(i * 7)));
+            let proof = archived.get_proof(leaf).unwrap();
+            assert_eq!(Some(proof.clone()), tree.get_proof(leaf));
+            assert!(tree.verify_proof(leaf, proof, tree.get_root().unwrap()));
+        }
+        assert!(!archived.contains(H256::repeat_byte(0x42)));
+    }
+
+    #[test]
+    fn archived_tree_keeps_the_tree_options() {
+        let data: Vec<(Address, U256)> = (1..=7u64)
+            .map(|i| (Address::from_low_u64_be(i), U256::from(i * 7)))
+            .collect();
+        let trees = [
+            MerkleTree::new_with_node_sep(data.clone(), NodeSep(b"sep".to_vec())),
+            MerkleTree::new_for_chain(data.clone(), 10),
+            MerkleTree::new_packed(data.clone(), AmountWidth::U96).unwrap(),
+        ];
+        for tree in trees {
+            let bytes = tree.to_archive_bytes();
+            let archived = ArchivedMerkleTree::from_bytes(&bytes).unwrap();
+            let root = tree.get_root().unwrap();
+
+            assert_eq!(archived.entry_scheme(), tree.entry_scheme());
+            for leaf_data in &data {
+                let proof = archived.get_entry_proof(*leaf_data).unwrap();
+                assert_eq!(Some(proof.clone()), tree.get_entry_proof(*leaf_data));
+                assert!(archived.entry_scheme().verify(*leaf_data, &proof, root));
+            }
+        }
+    }
+
+    #[test]
+    fn archived_ordered_tree_finds_leaves_in_place() {
+        let leaves: Vec<H256> = [9u8, 3, 7, 3, 1]
+            .iter()
+            .map(|byte| H256::repeat_byte(*byte))
+            .collect();
+        let tree = MerkleTree::new_ordered(leaves.clone());
+        let bytes = tree.to_archive_bytes();
+        let archived = ArchivedMerkleTree::from_bytes(&bytes).unwrap();
+        let root = tree.get_root().unwrap();
+
+        assert!(archived.is_ordered());
+        assert_eq!(archived.get_root(), tree.get_root());
+        for (index, leaf) in leaves.iter().enumerate() {
+            let proof = archived.get_proof_at(index).unwrap();
+            assert_eq!(Some(proof.clone()), tree.get_proof_at(index));
+            assert!(MerkleTree::verify_ordered_proof(
+                *leaf,
+                index,
+                leaves.len(),
+                &proof,
+                root
+            ));
+            assert!(archived.contains(*leaf));
+        }
+        // the repeated leaf is proven at its first position
+        assert_eq!(archived.get_proof(leaves[3]), tree.get_proof_at(1));
+        assert_eq!(archived.get_proof(leaves[4]), tree.get_proof_at(4));
+        assert!(!archived.contains(H256::repeat_byte(0x42)));
+        assert_eq!(archived.get_proof_at(leaves.len()), None);
+    }
+
+    #[test]
+    fn archived_tree_rejects_invalid_bytes() {
+        let bytes = sample_tree().to_archive_bytes();
+        let mut corrupted = AlignedVec::new();
+        corrupted.extend_from_slice(&bytes[..bytes.len() - 16]);
+        corrupted.extend_from_slice(&[0xff; 16]);
+
+        assert!(matches!(
+            ArchivedMerkleTree::from_bytes(&corrupted),
+            Err(MerkleError::InvalidArchive(_))
+        ));
+    }
+}
@@ -0,0 +1,195 @@
+use crate::{MerkleError, MerkleTree};
+use ethers::types::{Address, H256, U256};
+
+/// A view over a Merkle tree whose nodes live in a caller-provided buffer.
+///
+/// Returned by [`MerkleTree::build_in`]. The layers are stored back to back in the
+/// buffer, starting with the sorted leaves and ending with the root.
+#[derive(Debug, Clone, Copy)]
+pub struct RootView<'a> {
+    nodes: &'a [H256],
+    leaves: usize,
+}
+
+impl<'a> RootView<'a> {
+    /// Returns the root hash of the tree.
+    pub fn root(&self) -> H256 {
+        self.nodes[self.nodes.len() - 1]
+    }
+
+    /// Returns the sorted and deduplicated leaf layer.
+    pub fn leaves(&self) -> &'a [H256] {
+        &self.nodes[..self.leaves]
+    }
+
+    /// Returns the layer at the given level, where level `0` holds the leaves.
+    ///
+    /// # Returns
+    ///
+    /// An `Option` containing the layer, or `None` if the level is above the root.
+    pub fn layer(&self, level: usize) -> Option<&'a [H256]> {
+        let mut offset = 0;
+        let mut len = self.leaves;
+        for _ in 0..level {
+            if len == 1 {
+                return None;
+            }
+            offset += len;
+            len = len.div_ceil(2);
+        }
+        Some(&self.nodes[offset..offset + len])
+    }
+
+    /// Returns every node of the tree, layer by layer from the leaves up to the root.
+    pub fn nodes(&self) -> &'a [H256] {
+        self.nodes
+    }
+}
+
+impl MerkleTree {
+    /// Returns the number of nodes a scratch buffer needs for [`MerkleTree::build_in`].
+    ///
+    /// This is the sum of every layer's length. It equals `2n - 1` when `n` is a power
+    /// of two and is slightly larger otherwise, because odd nodes are promoted into the
+    /// next layer as-is.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaves` - The number of entries the tree will be built from.
+    pub fn scratch_len(leaves: usize) -> usize {
+        if leaves == 0 {
+            return 0;
+        }
+        let mut total = leaves;
+        let mut len = leaves;
+        while len > 1 {
+            len = len.div_ceil(2);
+            total += len;
+        }
+        total
+    }
+
+    /// Builds a Merkle tree inside a caller-provided buffer.
+    ///
+    /// This function hashes, sorts and deduplicates the data exactly like
+    /// [`MerkleTree::new`], but stores every node in `scratch` instead of allocating
+    /// per-layer vectors. This is useful on targets where heap fragmentation matters.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - A slice containing tuples of addresses and amounts to be stored in the Merkle tree.
+    /// * `scratch` - A buffer of at least [`MerkleTree::scratch_len`]`(data.len())` nodes.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a [`RootView`] borrowing the buffer, or a `MerkleError` if the
+    /// data is empty or the buffer is too small.
+    pub fn build_in<'a>(
+        data: &[(Address, U256)],
+        scratch: &'a mut [H256],
+    ) -> Result<RootView<'a>, MerkleError> {
+        if data.is_empty() {
+            return Err(MerkleError::EmptyTree);
+        }
+        let required = Self::scratch_len(data.len());
+        if scratch.len() < required {
+            return Err(MerkleError::ScratchTooSmall {
+                required,
+                provided: scratch.len(),
+            });
+        }
+
+        for (slot, leaf) in scratch.iter_mut().zip(data) {
+            *slot = Self::hash_node(*leaf);
+        }
+        // sort and deduplicate in place to get the correct order of elements
+        let elements = &mut scratch[..data.len()];
+        elements.sort_unstable();
+        let mut leaves = 1;
+        for i in 1..elements.len() {
+            if elements[i] != elements[leaves - 1] {
+                elements[leaves] = elements[i];
+                leaves += 1;
+            }
+        }
+
+        let mut offset = 0;
+        let mut len = leaves;
+        while len > 1 {
+            let (lower, upper) = scratch[offset..].split_at_mut(len);
+            for (parent, chunk) in upper.iter_mut().zip(lower.chunks(2)) {
+                *parent = if chunk.len() == 2 {
+                    Self::hash_pair(&chunk[0], &chunk[1])
+                } else {
+                    chunk[0]
+                };
+            }
+            offset += len;
+            len = len.div_ceil(2);
+        }
+
+        Ok(RootView {
+            nodes: &scratch[..offset + 1],
+            leaves,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+
+    fn sample_data(count: u64) -> Vec<(Address, U256)> {
+        (1..=count)
+            .map(|i| (Address::from_low_u64_be(i), U256::from(i * 1000)))
+            .collect()
+    }
+
+    #[test]
+    fn build_in_matches_tree_root() {
+        for count in 1..=9 {
+            let data = sample_data(count);
+            let mut scratch = [H256::zero(); 32];
+            let view = MerkleTree::build_in(&data, &mut scratch).unwrap();
+            let tree = MerkleTree::new(data);
+
+            assert_eq!(Some(view.root()), tree.get_root());
+            assert_eq!(view.leaves().len(), tree.leaves_length());
+        }
+    }
+
+    #[test]
+    fn build_in_deduplicates_leaves() {
+        let entry = (
+            Address::from_str("0x00393d62f17b07e64f7cdcdf9bdc2fd925b20bba").unwrap(),
+            U256::from_dec_str("1840233889215604334017").unwrap(),
+        );
+        let data = vec![entry, entry, (Address::zero(), U256::one())];
+        let mut scratch = vec![H256::zero(); MerkleTree::scratch_len(data.len())];
+        let view = MerkleTree::build_in(&data, &mut scratch).unwrap();
+
+        assert_eq!(view.leaves().len(), 2);
+        assert_eq!(view.layer(1).unwrap(), &[view.root()]);
+        assert!(view.layer(2).is_none());
+        assert_eq!(Some(view.root()), MerkleTree::new(data).get_root());
+    }
+
+    #[test]
+    fn build_in_rejects_small_scratch() {
+        let data = sample_data(5);
+        let mut scratch = [H256::zero(); 10];
+
+        assert_eq!(
+            MerkleTree::build_in(&data, &mut scratch).unwrap_err(),
+            MerkleError::ScratchTooSmall {
+                required: 11,
+                provided: 10
+            }
+        );
+        assert_eq!(
+            MerkleTree::build_in(&[], &mut scratch).unwrap_err(),
+            MerkleError::EmptyTree
+        );
+    }
+}
@@ -0,0 +1,219 @@
+use crate::MerkleTree;
+use ethers::types::H256;
+use std::io::Write;
+
+/// Why a proof of a batch failed to verify.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureReason {
+    /// The proof is well formed but folds to another root.
+    RootMismatch,
+    /// The proof has more hashes than the tree has layers above the leaves.
+    ProofTooLong { len: usize, max: usize },
+    /// The proof hash at this position is zero, which points to an uninitialized slot.
+    MalformedElement { position: usize },
+}
+
+impl FailureReason {
+    /// Returns the snake case name of the reason, as written to NDJSON reports.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FailureReason::RootMismatch => "root_mismatch",
+            FailureReason::ProofTooLong { .. } => "proof_too_long",
+            FailureReason::MalformedElement { .. } => "malformed_element",
+        }
+    }
+}
+
+/// A proof of a batch that failed to verify.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FailureDetail {
+    /// The position of the pair in the batch.
+    pub index: usize,
+    /// The leaf of the pair.
+    pub leaf: H256,
+    /// The root the proof folds to.
+    pub computed_root: H256,
+    /// Why the proof was rejected.
+    pub reason: FailureReason,
+}
+
+impl FailureDetail {
+    /// Formats the failure as one line of JSON, without the trailing newline.
+    pub fn to_json_line(&self) -> String {
+        let details = match self.reason {
+            FailureReason::RootMismatch => String::new(),
+            FailureReason::ProofTooLong { len, max } => {
+                format!(r#","proof_len":{},"max_len":{}"#, len, max)
+            }
+            FailureReason::MalformedElement { position } => {
+                format!(r#","position":{}"#, position)
+            }
+        };
+        format!(
+            r#"{{"index":{},"leaf":"{:?}","computed_root":"{:?}","reason":"{}"{}}}"#,
+            self.index,
+            self.leaf,
+            self.computed_root,
+            self.reason.as_str(),
+            details
+        )
+    }
+}
+
+/// The outcome of `MerkleTree::verify_batch`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BatchReport {
+    /// The number of pairs that verified.
+    pub passed: usize,
+    /// The number of pairs that failed.
+    pub failed: usize,
+    /// Every failure in input order, empty when the failures were written out instead.
+    pub failures: Vec<FailureDetail>,
+}
+
+impl MerkleTree {
+    /// Verifies many `(leaf, proof)` pairs against the tree and reports every failure.
+    ///
+    /// Proofs are folded with the tree's separator. A failure is classified by the first
+    /// check it fails: a proof longer than the tree is deep, then a zero proof hash, then
+    /// a root that does not match.
+    ///
+    /// # Arguments
+    ///
+    /// * `pairs` - The leaves and their proofs.
+    ///
+    /// # Returns
+    ///
+    /// A `BatchReport` listing the failures ordered by their index in `pairs`.
+    pub fn verify_batch(&self, pairs: &[(H256, Vec<H256>)]) -> BatchReport {
+        let mut report = BatchReport::default();
+        for (index, (leaf, proof)) in pairs.iter().enumerate() {
+            match self.check_pair(index, *leaf, proof) {
+                Some(failure) => report.failures.push(failure),
+                None => report.passed += 1,
+            }
+        }
+        report.failed = report.failures.len();
+        report
+    }
+
+    /// Verifies many pairs like `verify_batch`, writing each failure as an NDJSON line.
+    ///
+    /// Failures are written as they are found, so memory stays flat however many there
+    /// are. Each line is the output of `FailureDetail::to_json_line`.
+    ///
+    /// # Arguments
+    ///
+    /// * `pairs` - The leaves and their proofs.
+    /// * `writer` - The destination of the NDJSON lines.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the report with counts only, or the first write error.
+    pub fn verify_batch_to_writer<W: Write>(
+        &self,
+        pairs: &[(H256, Vec<H256>)],
+        mut writer: W,
+    ) -> std::io::Result<BatchReport> {
+        let mut report = BatchReport::default();
+        for (index, (leaf, proof)) in pairs.iter().enumerate() {
+            match self.check_pair(index, *leaf, proof) {
+                Some(failure) => {
+                    writeln!(writer, "{}", failure.to_json_line())?;
+                    report.failed += 1;
+                }
+                None => report.passed += 1,
+            }
+        }
+        writer.flush()?;
+        Ok(report)
+    }
+
+    fn check_pair(&self, index: usize, leaf: H256, proof: &[H256]) -> Option<FailureDetail> {
+        let computed_root = Self::process_proof_with(leaf, proof, self.config.node_sep.as_bytes());
+        let max = self.layers.len() - 1;
+        let reason = if proof.len() > max {
+            FailureReason::ProofTooLong {
+                len: proof.len(),
+                max,
+            }
+        } else if let Some(position) = proof.iter().position(H256::is_zero) {
+            FailureReason::MalformedElement { position }
+        } else if Some(computed_root) != self.get_root() {
+            FailureReason::RootMismatch
+        } else {
+            return None;
+        };
+        Some(FailureDetail {
+            index,
+            leaf,
+            computed_root,
+            reason,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ethers::types::{Address, U256};
+
+    #[test]
+    fn failures_are_classified_in_input_order() {
+        let tree = MerkleTree::new(
+            (1..=9u64)
+                .map(|i| (Address::from_low_u64_be(i), U256::from(i)))
+                .collect(),
+        );
+        let mut pairs: Vec<(H256, Vec<H256>)> = tree
+            .sorted_leaves()
+            .iter()
+            .map(|leaf| (*leaf, tree.get_proof(*leaf).unwrap()))
+            .collect();
+        pairs[1].1.push(H256::repeat_byte(7));
+        pairs[2].1[0] = H256::zero();
+        pairs[4].1.swap(0, 1);
+        pairs[6].0 = H256::repeat_byte(1);
+
+        let report = tree.verify_batch(&pairs);
+        assert_eq!(report.passed, 5);
+        assert_eq!(report.failed, 4);
+        let reasons: Vec<(usize, FailureReason)> = report
+            .failures
+            .iter()
+            .map(|failure| (failure.index, failure.reason))
+            .collect();
+        assert_eq!(
+            reasons,
+            [
+                (1, FailureReason::ProofTooLong { len: 5, max: 4 }),
+                (2, FailureReason::MalformedElement { position: 0 }),
+                (4, FailureReason::RootMismatch),
+                (6, FailureReason::RootMismatch),
+            ]
+        );
+        assert_eq!(report.failures[3].leaf, H256::repeat_byte(1));
+        assert_eq!(
+            report.failures[2].computed_root,
+            MerkleTree::process_proof(pairs[4].0, &pairs[4].1)
+        );
+
+        let mut ndjson = Vec::new();
+        let streamed = tree.verify_batch_to_writer(&pairs, &mut ndjson).unwrap();
+        assert_eq!((streamed.passed, streamed.failed), (5, 4));
+        assert!(streamed.failures.is_empty());
+        let lines: Vec<String> = String::from_utf8(ndjson)
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect();
+        let expected: Vec<String> = report
+            .failures
+            .iter()
+            .map(FailureDetail::to_json_line)
+            .collect();
+        assert_eq!(lines, expected);
+        assert!(lines[0].ends_with(r#""reason":"proof_too_long","proof_len":5,"max_len":4}"#));
+        assert!(lines[1].starts_with(r#"{"index":2,"leaf":"0x"#));
+    }
+}
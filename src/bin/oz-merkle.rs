@@ -0,0 +1,45 @@
+//! Builds a tree from a CSV file of address and amount rows and prints its `BuildReport`.
+//!
+//! ```text
+//! oz-merkle [--json] <balances.csv>
+//! ```
+//!
+//! The file is read with the default `CsvOptions`: a header row, then the address and the
+//! amount in base units in the first two columns. The report is printed for people to
+//! read, after the root, or as JSON with `--json`.
+
+use anyhow::{bail, Context};
+use oz_merkle_rs::import::{from_csv, CsvOptions};
+use oz_merkle_rs::MerkleTreeBuilder;
+use std::fs::File;
+
+const USAGE: &str = "usage: oz-merkle [--json] <balances.csv>";
+
+fn main() -> anyhow::Result<()> {
+    let mut json = false;
+    let mut path = None;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--json" => json = true,
+            _ if path.is_none() && !arg.starts_with('-') => path = Some(arg),
+            _ => bail!(USAGE),
+        }
+    }
+    let Some(path) = path else {
+        bail!(USAGE);
+    };
+
+    let file = File::open(&path).with_context(|| format!("cannot open {}", path))?;
+    let entries = from_csv(file, &CsvOptions::default())?;
+    let (_, report) = MerkleTreeBuilder::new().build_with_report(entries)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        match report.root {
+            Some(root) => println!("root: {:?}", root),
+            None => println!("root: none"),
+        }
+        println!("{}", report.format_human());
+    }
+    Ok(())
+}
@@ -0,0 +1,111 @@
+use crate::MerkleTree;
+use ethers::types::H256;
+
+/// A Bloom filter over leaf hashes.
+///
+/// Leaf hashes are keccak outputs, so their bytes are already uniformly distributed and
+/// the probe positions are derived from them directly instead of rehashing.
+#[derive(Debug, Clone)]
+pub(crate) struct BloomFilter {
+    bits: Vec<u64>,
+    hashes: u32,
+    bits_per_leaf: usize,
+}
+
+impl BloomFilter {
+    pub(crate) fn new(elements: &[H256], bits_per_leaf: usize) -> Self {
+        let len = (elements.len() * bits_per_leaf.max(1)).max(64);
+        // k = ln(2) * m / n minimizes the false positive rate
+        let hashes = ((bits_per_leaf as f64) * std::f64::consts::LN_2).round() as u32;
+        let mut filter = BloomFilter {
+            bits: vec![0; len.div_ceil(64)],
+            hashes: hashes.clamp(1, 16),
+            bits_per_leaf,
+        };
+        for element in elements {
+            filter.insert(element);
+        }
+        filter
+    }
+
+    /// Builds a filter over other leaves with the same bits per leaf.
+    pub(crate) fn rebuild(&self, elements: &[H256]) -> Self {
+        Self::new(elements, self.bits_per_leaf)
+    }
+
+    pub(crate) fn insert(&mut self, element: &H256) {
+        for bit in self.probes(element) {
+            self.bits[bit / 64] |= 1 << (bit % 64);
+        }
+    }
+
+    pub(crate) fn check(&self, element: &H256) -> bool {
+        self.probes(element)
+            .all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    fn probes(&self, element: &H256) -> impl Iterator<Item = usize> {
+        let bytes = element.as_bytes();
+        let h1 = u64::from_le_bytes(bytes[..8].try_into().unwrap());
+        let h2 = u64::from_le_bytes(bytes[8..16].try_into().unwrap()) | 1;
+        let len = (self.bits.len() * 64) as u64;
+        (0..self.hashes as u64).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % len) as usize)
+    }
+}
+
+impl MerkleTree {
+    /// Builds a Bloom filter over the leaves for fast negative membership checks.
+    ///
+    /// Once built, `contains` and `maybe_contains` consult the filter before searching the
+    /// leaves, which makes lookups of absent elements cheap.
+    ///
+    /// # Arguments
+    ///
+    /// * `bits_per_leaf` - The filter size per leaf; 10 bits gives about a 1% false positive rate.
+    ///
+    /// # Returns
+    ///
+    /// The same tree with the Bloom filter attached.
+    pub fn with_bloom_filter(mut self, bits_per_leaf: usize) -> Self {
+        self.bloom = Some(BloomFilter::new(&self.elements, bits_per_leaf));
+        self
+    }
+
+    /// Returns `false` if the element is definitely not a leaf of the tree.
+    ///
+    /// With a Bloom filter attached this only probes the filter, so it can return `true`
+    /// for absent elements but never `false` for present ones. Without a filter it falls
+    /// back to the exact `contains` check.
+    pub fn maybe_contains(&self, element: H256) -> bool {
+        match &self.bloom {
+            Some(bloom) => bloom.check(&element),
+            None => self.contains(element),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ethers::core::utils::keccak256;
+    use ethers::types::{Address, U256};
+
+    #[test]
+    fn bloom_filter_has_no_false_negatives() {
+        let data: Vec<(Address, U256)> = (1..=500u64)
+            .map(|i| (Address::from_low_u64_be(i), U256::from(i)))
+            .collect();
+        let tree = MerkleTree::new(data.clone()).with_bloom_filter(10);
+
+        for leaf in data {
+            let node = MerkleTree::hash_node(leaf);
+            assert!(tree.maybe_contains(node));
+            assert!(tree.contains(node));
+        }
+        let false_positives = (1000..2000u64)
+            .filter(|&i| tree.maybe_contains(H256::from(keccak256(i.to_be_bytes()))))
+            .count();
+        assert!(false_positives < 50, "{} false positives", false_positives);
+        assert!(!tree.contains(H256::from_low_u64_be(1)));
+    }
+}
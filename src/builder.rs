@@ -0,0 +1,556 @@
+use crate::{Amount, MerkleError, MerkleTree, TokenMetadata};
+use ethers::types::{Address, H256, U256};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// How the builder treats an address that appears more than once in the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicatePolicy {
+    /// Drop repeated identical entries and keep distinct amounts as separate leaves.
+    /// This is the behaviour of `MerkleTree::new`.
+    #[default]
+    Dedup,
+    /// Merge every entry of an address into a single leaf holding the summed amount.
+    Merge,
+    /// Reject the input as soon as an address repeats.
+    Error,
+}
+
+/// What a tree built with `MerkleTreeBuilder::self_check` does with a proof that fails
+/// its own verification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SelfCheckMode {
+    /// Panic with the index and hash of the leaf.
+    #[default]
+    Panic,
+    /// Return `MerkleError::SelfCheckFailed` from the getters that return a `Result`, and
+    /// `None` from those that return an `Option`. Exports that cannot fail, such as
+    /// `claim_records`, still panic.
+    Error,
+}
+
+/// The kind of duplication that triggered a [`DuplicateEvent`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateKind {
+    /// The entry repeats an earlier entry exactly, so it hashes to the same leaf.
+    Leaf,
+    /// The entry repeats an earlier address with a different amount.
+    Address,
+}
+
+/// The action the builder took for a [`DuplicateEvent`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateAction {
+    /// The entry was dropped.
+    Dropped,
+    /// The entry was kept as a separate leaf.
+    Kept,
+    /// The amount was added to the earlier leaf of the same address.
+    Merged,
+    /// The input was rejected.
+    Rejected,
+}
+
+/// A duplicate encountered while building a tree, reported to the `on_duplicate` hook.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateEvent {
+    /// The repeated address.
+    pub account: Address,
+    /// The amounts seen earlier for the address, in input order.
+    pub previous_amounts: Vec<U256>,
+    /// The amount of the repeated entry.
+    pub amount: U256,
+    /// Whether the entry repeats a whole leaf or only its address.
+    pub kind: DuplicateKind,
+    /// What the builder did with the entry.
+    pub action: DuplicateAction,
+}
+
+/// What `MerkleTreeBuilder::build_with_report` did to the raw input.
+///
+/// Every adjustment is recorded whatever the policies are, so fixes applied silently by a
+/// permissive policy still show up in the report.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct BuildReport {
+    /// The number of input entries.
+    pub rows: usize,
+    /// Addresses of exact repeats that were dropped, once per dropped entry.
+    pub dropped_duplicates: Vec<Address>,
+    /// Addresses repeated with another amount and kept as separate leaves.
+    pub kept_duplicates: Vec<Address>,
+    /// Addresses whose amounts were merged into an earlier leaf, once per merged entry.
+    pub merged_duplicates: Vec<Address>,
+    /// Addresses of zero-amount entries that were dropped.
+    pub rejected_zero_amounts: Vec<Address>,
+    /// Addresses of zero-amount entries that were kept as leaves.
+    pub kept_zero_amounts: Vec<Address>,
+    /// The number of leaves of the tree.
+    pub leaves: usize,
+    /// The sum of the amounts of all leaves, saturating at `U256::MAX`.
+    pub total_amount: U256,
+    /// The smallest amount of a leaf, zero if the tree is empty.
+    pub min_amount: U256,
+    /// The largest amount of a leaf, zero if the tree is empty.
+    pub max_amount: U256,
+    /// The token set with `MerkleTreeBuilder::token`, used by `format_human`.
+    pub token: Option<TokenMetadata>,
+    /// The root of the tree, `None` if it is empty.
+    pub root: Option<H256>,
+    /// Time spent applying the duplicate and zero-amount policies.
+    pub validation_time: Duration,
+    /// Time spent hashing the leaves and building the layers.
+    pub construction_time: Duration,
+}
+
+impl BuildReport {
+    /// Renders the leaf counts and allocation statistics for people to read.
+    ///
+    /// Amounts are formatted exactly by `Amount::format_units` in whole tokens when the
+    /// report has token metadata, and in base units otherwise. The mean is the total
+    /// divided by the number of leaves, rounded down to a base unit.
+    ///
+    /// # Returns
+    ///
+    /// One `label: value` line per statistic, separated by newlines.
+    pub fn format_human(&self) -> String {
+        let format = |amount: U256| match &self.token {
+            Some(token) => token.format(amount),
+            None => Amount::format_units(amount, 0),
+        };
+        let mean = match self.leaves {
+            0 => U256::zero(),
+            leaves => self.total_amount / leaves,
+        };
+        [
+            format!("rows: {}", self.rows),
+            format!("leaves: {}", self.leaves),
+            format!("total allocated: {}", format(self.total_amount)),
+            format!("min allocation: {}", format(self.min_amount)),
+            format!("max allocation: {}", format(self.max_amount)),
+            format!("mean allocation: {}", format(mean)),
+        ]
+        .join("\n")
+    }
+}
+
+/// A configurable constructor for [`MerkleTree`].
+///
+/// # Example
+///
+/// ```rust
+/// use oz_merkle_rs::{DuplicatePolicy, MerkleTreeBuilder};
+/// use ethers::types::{Address, U256};
+///
+/// let data = vec![
+///     (Address::repeat_byte(0x11), U256::from(100)),
+///     (Address::repeat_byte(0x11), U256::from(50)),
+/// ];
+///
+/// let mut duplicates = Vec::new();
+/// let tree = MerkleTreeBuilder::new()
+///     .duplicate_policy(DuplicatePolicy::Merge)
+///     .on_duplicate(|event| duplicates.push(event))
+///     .build(data)
+///     .unwrap();
+///
+/// assert_eq!(tree.leaves_length(), 1);
+/// assert_eq!(duplicates.len(), 1);
+/// ```
+#[derive(Default)]
+pub struct MerkleTreeBuilder<'a> {
+    duplicate_policy: DuplicatePolicy,
+    drop_zero_amounts: bool,
+    token: Option<TokenMetadata>,
+    self_check: Option<SelfCheckMode>,
+    on_duplicate: Option<Box<dyn FnMut(DuplicateEvent) + 'a>>,
+}
+
+impl<'a> MerkleTreeBuilder<'a> {
+    /// Creates a builder with the default options, matching `MerkleTree::new`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how addresses that appear more than once are handled.
+    pub fn duplicate_policy(mut self, policy: DuplicatePolicy) -> Self {
+        self.duplicate_policy = policy;
+        self
+    }
+
+    /// Sets whether entries with a zero amount are dropped instead of becoming leaves.
+    pub fn drop_zero_amounts(mut self, drop: bool) -> Self {
+        self.drop_zero_amounts = drop;
+        self
+    }
+
+    /// Sets the token the tree distributes, attached with `MerkleTree::with_token`.
+    pub fn token(mut self, token: TokenMetadata) -> Self {
+        self.token = Some(token);
+        self
+    }
+
+    /// Sets whether every proof the tree hands out is verified against its root first,
+    /// e.g. for a pre-launch run over all claims. Off by default.
+    ///
+    /// This covers `get_proof` and the other getters, the claim records and the exports
+    /// built on them. Verifying hashes every level of the proof, which costs far more
+    /// than looking the proof up, so it is not turned on for debug builds.
+    pub fn self_check(mut self, enabled: bool) -> Self {
+        self.self_check = match (enabled, self.self_check) {
+            (false, _) => None,
+            (true, mode) => Some(mode.unwrap_or_default()),
+        };
+        self
+    }
+
+    /// Enables self-checking and sets what happens when a proof fails it.
+    pub fn self_check_mode(mut self, mode: SelfCheckMode) -> Self {
+        self.self_check = Some(mode);
+        self
+    }
+
+    /// Registers a hook called for every duplicate leaf or duplicate address in the input.
+    ///
+    /// The hook fires whatever the policy is; with `DuplicatePolicy::Error` it is called
+    /// for the offending entry before `build` returns the error.
+    pub fn on_duplicate(mut self, hook: impl FnMut(DuplicateEvent) + 'a) -> Self {
+        self.on_duplicate = Some(Box::new(hook));
+        self
+    }
+
+    /// Builds a Merkle tree from the given data.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - A vector containing tuples of addresses and amounts to be stored in the Merkle tree.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the tree, or a `MerkleError` if the duplicate policy rejected
+    /// the input or merged amounts overflowed.
+    pub fn build(self, data: Vec<(Address, U256)>) -> Result<MerkleTree, MerkleError> {
+        self.build_with_report(data).map(|(tree, _)| tree)
+    }
+
+    /// Builds a Merkle tree and reports what the policies did to the input.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - A vector containing tuples of addresses and amounts to be stored in the Merkle tree.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the tree and its `BuildReport`, or a `MerkleError` if the
+    /// duplicate policy rejected the input or merged amounts overflowed.
+    pub fn build_with_report(
+        mut self,
+        data: Vec<(Address, U256)>,
+    ) -> Result<(MerkleTree, BuildReport), MerkleError> {
+        let start = Instant::now();
+        let mut report = BuildReport {
+            rows: data.len(),
+            ..Default::default()
+        };
+        let mut seen: HashMap<Address, (usize, Vec<U256>)> = HashMap::new();
+        let mut entries = Vec::with_capacity(data.len());
+
+        for (account, amount) in data {
+            if amount.is_zero() {
+                if self.drop_zero_amounts {
+                    report.rejected_zero_amounts.push(account);
+                    continue;
+                }
+                report.kept_zero_amounts.push(account);
+            }
+            let Some((first, previous)) = seen.get_mut(&account) else {
+                seen.insert(account, (entries.len(), vec![amount]));
+                entries.push((account, amount));
+                continue;
+            };
+            let kind = if previous.contains(&amount) {
+                DuplicateKind::Leaf
+            } else {
+                DuplicateKind::Address
+            };
+            let action = match (self.duplicate_policy, kind) {
+                (DuplicatePolicy::Dedup, DuplicateKind::Leaf) => DuplicateAction::Dropped,
+                (DuplicatePolicy::Dedup, DuplicateKind::Address) => DuplicateAction::Kept,
+                (DuplicatePolicy::Merge, _) => DuplicateAction::Merged,
+                (DuplicatePolicy::Error, _) => DuplicateAction::Rejected,
+            };
+            if let Some(hook) = &mut self.on_duplicate {
+                hook(DuplicateEvent {
+                    account,
+                    previous_amounts: previous.clone(),
+                    amount,
+                    kind,
+                    action,
+                });
+            }
+            previous.push(amount);
+
+            match action {
+                DuplicateAction::Dropped => report.dropped_duplicates.push(account),
+                DuplicateAction::Kept => {
+                    report.kept_duplicates.push(account);
+                    entries.push((account, amount));
+                }
+                DuplicateAction::Merged => {
+                    report.merged_duplicates.push(account);
+                    let total = &mut entries[*first].1;
+                    *total = total
+                        .checked_add(amount)
+                        .ok_or(MerkleError::AmountOverflow(account))?;
+                }
+                DuplicateAction::Rejected => return Err(MerkleError::DuplicateAddress(account)),
+            }
+        }
+        report.validation_time = start.elapsed();
+
+        let start = Instant::now();
+        let mut tree = MerkleTree::new(entries);
+        report.construction_time = start.elapsed();
+        // dropped and merged duplicates were inputs too, unlike rejected zero amounts
+        tree.inputs = report.rows - report.rejected_zero_amounts.len();
+        tree.config.self_check = self.self_check;
+        if let Some(token) = self.token {
+            tree = tree.with_token(token);
+        }
+        report.leaves = tree.leaves_length();
+        report.total_amount = tree.entries().iter().fold(U256::zero(), |total, entry| {
+            total.saturating_add(entry.amount)
+        });
+        let amounts = tree.entries().iter().map(|entry| entry.amount);
+        report.min_amount = amounts.clone().min().unwrap_or_default();
+        report.max_amount = amounts.max().unwrap_or_default();
+        report.token = tree.token().cloned();
+        report.root = tree.get_root();
+        Ok((tree, report))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn duplicated_data() -> Vec<(Address, U256)> {
+        let (a, b, c) = (
+            Address::repeat_byte(0xaa),
+            Address::repeat_byte(0xbb),
+            Address::repeat_byte(0xcc),
+        );
+        vec![
+            (a, U256::from(10)),
+            (b, U256::from(20)),
+            (a, U256::from(10)),
+            (b, U256::from(25)),
+            (c, U256::from(1)),
+            (c, U256::from(2)),
+            (c, U256::from(1)),
+        ]
+    }
+
+    #[test]
+    fn on_duplicate_reports_every_duplicate() {
+        let mut events = Vec::new();
+        let tree = MerkleTreeBuilder::new()
+            .on_duplicate(|event| events.push(event))
+            .build(duplicated_data())
+            .unwrap();
+
+        assert_eq!(tree.leaves_length(), 5);
+        assert_eq!(events.len(), 4);
+        assert_eq!(
+            events[0],
+            DuplicateEvent {
+                account: Address::repeat_byte(0xaa),
+                previous_amounts: vec![U256::from(10)],
+                amount: U256::from(10),
+                kind: DuplicateKind::Leaf,
+                action: DuplicateAction::Dropped,
+            }
+        );
+        assert_eq!(events[1].kind, DuplicateKind::Address);
+        assert_eq!(events[1].action, DuplicateAction::Kept);
+        assert_eq!(
+            events[3].previous_amounts,
+            vec![U256::from(1), U256::from(2)]
+        );
+        assert_eq!(events[3].action, DuplicateAction::Dropped);
+    }
+
+    #[test]
+    fn merge_policy_sums_amounts() {
+        let mut events = Vec::new();
+        let tree = MerkleTreeBuilder::new()
+            .duplicate_policy(DuplicatePolicy::Merge)
+            .on_duplicate(|event| events.push(event))
+            .build(duplicated_data())
+            .unwrap();
+        let expected = MerkleTree::new(vec![
+            (Address::repeat_byte(0xaa), U256::from(20)),
+            (Address::repeat_byte(0xbb), U256::from(45)),
+            (Address::repeat_byte(0xcc), U256::from(4)),
+        ]);
+
+        assert_eq!(tree.get_root(), expected.get_root());
+        assert_eq!(events.len(), 4);
+        assert!(events.iter().all(|e| e.action == DuplicateAction::Merged));
+    }
+
+    #[test]
+    fn error_policy_fires_hook_before_failing() {
+        let mut events = Vec::new();
+        let result = MerkleTreeBuilder::new()
+            .duplicate_policy(DuplicatePolicy::Error)
+            .on_duplicate(|event| events.push(event))
+            .build(duplicated_data());
+
+        assert_eq!(
+            result.unwrap_err(),
+            MerkleError::DuplicateAddress(Address::repeat_byte(0xaa))
+        );
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].action, DuplicateAction::Rejected);
+    }
+
+    #[test]
+    fn build_report_records_every_adjustment() {
+        let mut data = duplicated_data();
+        data.push((Address::repeat_byte(0xdd), U256::zero()));
+        data.push((Address::repeat_byte(0xee), U256::zero()));
+
+        let (tree, report) = MerkleTreeBuilder::new()
+            .drop_zero_amounts(true)
+            .build_with_report(data.clone())
+            .unwrap();
+        assert_eq!(report.rows, 9);
+        assert_eq!(
+            report.dropped_duplicates,
+            vec![Address::repeat_byte(0xaa), Address::repeat_byte(0xcc)]
+        );
+        assert_eq!(
+            report.kept_duplicates,
+            vec![Address::repeat_byte(0xbb), Address::repeat_byte(0xcc)]
+        );
+        assert!(report.merged_duplicates.is_empty());
+        assert_eq!(
+            report.rejected_zero_amounts,
+            vec![Address::repeat_byte(0xdd), Address::repeat_byte(0xee)]
+        );
+        assert_eq!(report.leaves, 5);
+        assert_eq!((tree.input_length(), tree.deduped_count()), (7, 2));
+        assert_eq!(report.total_amount, U256::from(10 + 20 + 25 + 1 + 2));
+        assert_eq!(report.root, tree.get_root());
+
+        let (tree, report) = MerkleTreeBuilder::new()
+            .duplicate_policy(DuplicatePolicy::Merge)
+            .build_with_report(data)
+            .unwrap();
+        assert_eq!(report.merged_duplicates.len(), 4);
+        assert_eq!(
+            report.kept_zero_amounts,
+            vec![Address::repeat_byte(0xdd), Address::repeat_byte(0xee)]
+        );
+        assert_eq!(report.leaves, tree.leaves_length());
+        assert_eq!((tree.input_length(), tree.deduped_count()), (9, 4));
+        assert_eq!(report.total_amount, U256::from(20 + 45 + 4));
+    }
+
+    #[test]
+    fn format_human_uses_token_decimals() {
+        let data = vec![
+            (Address::repeat_byte(0x01), U256::from(1_234_567_890_000u64)),
+            (Address::repeat_byte(0x02), U256::from(2_500_000)),
+            (Address::repeat_byte(0x03), U256::from(7)),
+        ];
+        let (tree, report) = MerkleTreeBuilder::new()
+            .token(TokenMetadata::new("TOKEN", 6))
+            .build_with_report(data.clone())
+            .unwrap();
+        assert_eq!(tree.token(), Some(&TokenMetadata::new("TOKEN", 6)));
+        assert_eq!(
+            report.format_human(),
+            "rows: 3\n\
+             leaves: 3\n\
+             total allocated: 1,234,570.390007 TOKEN\n\
+             min allocation: 0.000007 TOKEN\n\
+             max allocation: 1,234,567.89 TOKEN\n\
+             mean allocation: 411,523.463335 TOKEN"
+        );
+
+        let (_, report) = MerkleTreeBuilder::new().build_with_report(data).unwrap();
+        assert!(report
+            .format_human()
+            .contains("\nmin allocation: 7\nmax allocation: 1,234,567,890,000\n"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn build_report_serializes() {
+        let (_, report) = MerkleTreeBuilder::new()
+            .build_with_report(duplicated_data())
+            .unwrap();
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["rows"], 7);
+        assert_eq!(json["leaves"], 5);
+        assert_eq!(json["total_amount"], "0x3a");
+    }
+
+    #[test]
+    fn self_check_catches_corrupt_layers() {
+        use crate::test_utils::corrupt_node;
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        let data: Vec<(Address, U256)> = (1..=5u64)
+            .map(|i| (Address::from_low_u64_be(i), U256::from(i)))
+            .collect();
+        let build = |mode: Option<SelfCheckMode>| {
+            let builder = MerkleTreeBuilder::new();
+            let builder = match mode {
+                Some(mode) => builder.self_check_mode(mode),
+                None => builder.self_check(false),
+            };
+            let mut tree = builder.build(data.clone()).unwrap();
+            // the sibling of the first leaf
+            corrupt_node(&mut tree, 0, 1);
+            tree
+        };
+        let unchecked = build(None);
+        let leaf = unchecked.sorted_leaves()[0];
+        let root = unchecked.get_root().unwrap();
+        let proof = unchecked.get_proof(leaf).unwrap();
+        assert!(!unchecked.verify_proof(leaf, proof, root));
+
+        let panicking = build(Some(SelfCheckMode::Panic));
+        let err = catch_unwind(AssertUnwindSafe(|| panicking.get_proof(leaf))).unwrap_err();
+        let message = err.downcast_ref::<String>().unwrap();
+        assert_eq!(
+            *message,
+            MerkleError::SelfCheckFailed { index: 0, leaf }.to_string()
+        );
+        assert!(catch_unwind(AssertUnwindSafe(|| panicking.claim_records())).is_err());
+
+        let erroring = build(Some(SelfCheckMode::Error));
+        assert_eq!(erroring.get_proof(leaf), None);
+        assert_eq!(erroring.get_proof_at(0), None);
+        assert_eq!(
+            erroring.get_proof_strict(leaf),
+            Err(MerkleError::SelfCheckFailed { index: 0, leaf })
+        );
+        assert_eq!(erroring.get_claim(erroring.entries()[0].account), None);
+        // proofs that do not go through the corrupt node are still handed out
+        let last = erroring.sorted_leaves()[4];
+        let proof = erroring.get_proof(last).unwrap();
+        assert!(erroring.verify_proof(last, proof, root));
+
+        assert_eq!(
+            MerkleTreeBuilder::new()
+                .self_check(true)
+                .self_check
+                .unwrap(),
+            SelfCheckMode::Panic
+        );
+    }
+}
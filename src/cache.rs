@@ -0,0 +1,472 @@
+use crate::{MerkleError, MerkleTree, ProofBundle};
+use ethers::types::{Address, H256, U256};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// Sizing of the proof cache of a [`CachedTree`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProofCacheOptions {
+    /// The maximum number of cached proofs, split evenly across the shards.
+    pub capacity: usize,
+    /// The number of independently locked shards; readers only contend within a shard.
+    pub shards: usize,
+}
+
+impl Default for ProofCacheOptions {
+    fn default() -> Self {
+        Self {
+            capacity: 1024,
+            shards: 16,
+        }
+    }
+}
+
+/// Hit and miss counters of a [`CachedTree`], for metrics exporters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+/// A cache event of a [`CachedTree`], reported to its `on_event` hook.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheEvent {
+    /// A claim was served from the cache.
+    Hit,
+    /// A claim was not cached and was proven from the tree.
+    Miss,
+    /// A cached claim was dropped to make room for another.
+    Eviction,
+}
+
+/// A tree serving by-address claims through a bounded LRU cache.
+///
+/// A few hot addresses often account for most proof requests. Their bundles are kept in
+/// a sharded cache keyed by address, so concurrent readers of different shards never wait
+/// on each other, and each shard evicts its least recently used proof when full. Every
+/// mutation goes through `&mut self` and clears the cache, so a stale proof is never served.
+/// Proofs are computed with the shard unlocked, so a miss does not hold up the hits of its
+/// shard.
+pub struct CachedTree {
+    tree: MerkleTree,
+    shards: Vec<Mutex<LruShard>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+    on_event: Option<Box<dyn Fn(CacheEvent) + Send + Sync>>,
+}
+
+impl fmt::Debug for CachedTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CachedTree")
+            .field("tree", &self.tree)
+            .field("shards", &self.shards)
+            .field("stats", &self.stats())
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, Default)]
+struct LruShard {
+    capacity: usize,
+    tick: u64,
+    // the bundle of every cached address with the tick of its last use
+    entries: HashMap<Address, (u64, ProofBundle)>,
+    // the cached addresses by last use, oldest first
+    order: BTreeMap<u64, Address>,
+}
+
+impl LruShard {
+    fn get(&mut self, account: &Address) -> Option<ProofBundle> {
+        self.tick += 1;
+        let (tick, bundle) = self.entries.get_mut(account)?;
+        self.order.remove(tick);
+        self.order.insert(self.tick, *account);
+        *tick = self.tick;
+        Some(bundle.clone())
+    }
+
+    /// Caches a bundle, returning whether the oldest one was evicted to make room.
+    fn insert(&mut self, account: Address, bundle: ProofBundle) -> bool {
+        self.tick += 1;
+        if let Some((tick, _)) = self.entries.insert(account, (self.tick, bundle)) {
+            self.order.remove(&tick);
+        }
+        self.order.insert(self.tick, account);
+        if self.entries.len() <= self.capacity {
+            return false;
+        }
+        let (_, oldest) = self.order.pop_first().unwrap();
+        self.entries.remove(&oldest);
+        true
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+}
+
+impl CachedTree {
+    /// Wraps a tree with an empty proof cache.
+    ///
+    /// # Arguments
+    ///
+    /// * `tree` - The tree to serve claims from; it must retain its entries.
+    /// * `options` - The cache capacity and shard count. A zero shard count is taken as one.
+    pub fn new(tree: MerkleTree, options: ProofCacheOptions) -> Self {
+        let shards = options.shards.max(1);
+        let capacity = options.capacity.div_ceil(shards);
+        Self {
+            tree,
+            shards: (0..shards)
+                .map(|_| {
+                    Mutex::new(LruShard {
+                        capacity,
+                        ..Default::default()
+                    })
+                })
+                .collect(),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+            on_event: None,
+        }
+    }
+
+    /// Registers a hook called on every hit, miss and eviction, e.g. to feed a metrics
+    /// exporter's counters.
+    ///
+    /// The hook runs on the reading thread, after the counters of `stats` are updated and
+    /// with no shard locked.
+    pub fn on_event(mut self, hook: impl Fn(CacheEvent) + Send + Sync + 'static) -> Self {
+        self.on_event = Some(Box::new(hook));
+        self
+    }
+
+    /// Returns the wrapped tree.
+    pub fn tree(&self) -> &MerkleTree {
+        &self.tree
+    }
+
+    /// Retrieves the proof bundle of an account, from the cache if possible.
+    ///
+    /// # Returns
+    ///
+    /// An `Option` containing the same bundle as `MerkleTree::get_claim`, or `None` if the
+    /// account has no retained entry. Misses are not cached.
+    pub fn get_claim(&self, account: Address) -> Option<ProofBundle> {
+        let shard = self.shard(&account);
+        let cached = shard.lock().unwrap().get(&account);
+        if let Some(bundle) = cached {
+            self.record(&self.hits, CacheEvent::Hit);
+            return Some(bundle);
+        }
+        self.record(&self.misses, CacheEvent::Miss);
+        // two readers missing the same account both prove it; the second insert replaces
+        // the first with the same bundle
+        let bundle = self.tree.get_claim(account)?;
+        let evicted = shard.lock().unwrap().insert(account, bundle.clone());
+        if evicted {
+            self.record(&self.evictions, CacheEvent::Eviction);
+        }
+        Some(bundle)
+    }
+
+    /// Changes the amount of an address like `MerkleTree::set_amount`, clearing the cache.
+    pub fn set_amount(&mut self, account: Address, new_amount: U256) -> Result<(), MerkleError> {
+        self.tree.set_amount(account, new_amount)?;
+        self.clear();
+        Ok(())
+    }
+
+    /// Replaces the whole tree, clearing the cache.
+    ///
+    /// # Returns
+    ///
+    /// The previous tree.
+    pub fn replace(&mut self, tree: MerkleTree) -> MerkleTree {
+        self.clear();
+        std::mem::replace(&mut self.tree, tree)
+    }
+
+    /// Returns the number of cached proofs.
+    pub fn cached_len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.lock().unwrap().entries.len())
+            .sum()
+    }
+
+    /// Returns the cache counters accumulated since the tree was wrapped.
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+        }
+    }
+
+    fn record(&self, counter: &AtomicU64, event: CacheEvent) {
+        counter.fetch_add(1, Ordering::Relaxed);
+        if let Some(hook) = &self.on_event {
+            hook(event);
+        }
+    }
+
+    fn clear(&mut self) {
+        for shard in &mut self.shards {
+            shard.get_mut().unwrap().clear();
+        }
+    }
+
+    fn shard(&self, account: &Address) -> &Mutex<LruShard> {
+        // addresses are uniformly distributed, so their last bytes pick a shard evenly
+        let bytes = account.as_bytes();
+        let key = u32::from_be_bytes(bytes[16..].try_into().unwrap()) as usize;
+        &self.shards[key % self.shards.len()]
+    }
+}
+
+/// Memoizes successful proof verifications, e.g. for a gateway that re-checks the same
+/// claim on every request.
+///
+/// Only successes are cached, keyed by `(leaf, root)` together with the proof that
+/// verified, so a hit costs a hash lookup and a comparison instead of a fold. A different
+/// proof for a cached leaf is a miss and is verified from scratch, so a tampered proof is
+/// never accepted on the strength of an earlier valid one. Failures are never cached, since
+/// anyone can produce them.
+#[derive(Debug)]
+pub struct VerificationCache {
+    capacity: usize,
+    verified: HashMap<(H256, H256), Vec<H256>>,
+    stats: CacheStats,
+}
+
+impl VerificationCache {
+    /// Creates an empty cache.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - The maximum number of cached successes. When full, the whole cache
+    ///   is dropped before the next success is stored.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            verified: HashMap::new(),
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// Verifies a proof like `MerkleTree::verify_proof_inline`, answering from the cache
+    /// when the same proof already verified for the leaf and root. Like it, pairs are
+    /// hashed without a node separator.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf` - The hash of the leaf to be verified.
+    /// * `proof` - The hashes forming the Merkle proof.
+    /// * `root` - The root hash of the Merkle tree.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the proof is valid for the given leaf and root hash.
+    pub fn verify_cached(&mut self, leaf: H256, proof: &[H256], root: H256) -> bool {
+        if self
+            .verified
+            .get(&(leaf, root))
+            .is_some_and(|cached| cached.as_slice() == proof)
+        {
+            self.stats.hits += 1;
+            return true;
+        }
+        self.stats.misses += 1;
+        if !MerkleTree::verify_proof_inline(leaf, proof, root) {
+            return false;
+        }
+        if self.verified.len() >= self.capacity && !self.verified.contains_key(&(leaf, root)) {
+            self.stats.evictions += self.verified.len() as u64;
+            self.verified.clear();
+        }
+        self.verified.insert((leaf, root), proof.to_vec());
+        true
+    }
+
+    /// Returns the number of cached successes.
+    pub fn len(&self) -> usize {
+        self.verified.len()
+    }
+
+    /// Returns whether no success is cached.
+    pub fn is_empty(&self) -> bool {
+        self.verified.is_empty()
+    }
+
+    /// Returns the hit, miss and eviction counters since the cache was created.
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn tree() -> MerkleTree {
+        MerkleTree::new(
+            (1..=10u64)
+                .map(|i| (Address::from_low_u64_be(i), U256::from(i)))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn hits_return_identical_proofs() {
+        let cached = CachedTree::new(tree(), ProofCacheOptions::default());
+        let account = Address::from_low_u64_be(4);
+        let first = cached.get_claim(account).unwrap();
+        let second = cached.get_claim(account).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(Some(first), tree().get_claim(account));
+        assert_eq!(cached.get_claim(Address::zero()), None);
+        assert_eq!(
+            cached.stats(),
+            CacheStats {
+                hits: 1,
+                misses: 2,
+                evictions: 0
+            }
+        );
+        assert_eq!(cached.cached_len(), 1);
+    }
+
+    #[test]
+    fn mutations_invalidate_the_cache() {
+        let mut cached = CachedTree::new(tree(), ProofCacheOptions::default());
+        let account = Address::from_low_u64_be(4);
+        let before = cached.get_claim(account).unwrap();
+
+        cached.set_amount(account, U256::from(400)).unwrap();
+        assert_eq!(cached.cached_len(), 0);
+        let after = cached.get_claim(account).unwrap();
+        assert_eq!(after.amount, U256::from(400));
+        assert_ne!(after.proof, before.proof);
+        assert!(after.verify(cached.tree().get_root().unwrap()));
+
+        let previous = cached.replace(MerkleTree::new(vec![(account, U256::one())]));
+        assert_eq!(previous.get_claim(account), Some(after));
+        assert_eq!(cached.get_claim(account).unwrap().amount, U256::one());
+        assert_eq!(cached.stats().hits, 0);
+    }
+
+    #[test]
+    fn capacity_evicts_least_recently_used() {
+        let options = ProofCacheOptions {
+            capacity: 3,
+            shards: 1,
+        };
+        let cached = CachedTree::new(tree(), options);
+        let account = Address::from_low_u64_be;
+        for i in [1, 2, 3] {
+            cached.get_claim(account(i));
+        }
+        // touching 1 makes 2 the oldest
+        cached.get_claim(account(1));
+        cached.get_claim(account(4));
+        assert_eq!(cached.cached_len(), 3);
+        assert_eq!(cached.stats().evictions, 1);
+
+        let hits = cached.stats().hits;
+        for i in [1, 3, 4] {
+            cached.get_claim(account(i));
+        }
+        assert_eq!(cached.stats().hits, hits + 3);
+        cached.get_claim(account(2));
+        assert_eq!(cached.stats().hits, hits + 3);
+    }
+
+    #[test]
+    fn event_hook_sees_every_counted_event() {
+        let events = std::sync::Arc::new(Mutex::new(Vec::new()));
+        let seen = events.clone();
+        let options = ProofCacheOptions {
+            capacity: 2,
+            shards: 1,
+        };
+        let cached = CachedTree::new(tree(), options)
+            .on_event(move |event| seen.lock().unwrap().push(event));
+        let account = Address::from_low_u64_be;
+        for i in [1, 1, 2, 3] {
+            cached.get_claim(account(i));
+        }
+
+        use CacheEvent::*;
+        assert_eq!(*events.lock().unwrap(), [Miss, Hit, Miss, Miss, Eviction]);
+        assert_eq!(
+            cached.stats(),
+            CacheStats {
+                hits: 1,
+                misses: 3,
+                evictions: 1
+            }
+        );
+    }
+
+    #[test]
+    fn concurrent_readers_share_the_cache() {
+        let cached = CachedTree::new(tree(), ProofCacheOptions::default());
+        let expected = tree();
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for i in 1..=10u64 {
+                        let account = Address::from_low_u64_be(i);
+                        assert_eq!(cached.get_claim(account), expected.get_claim(account));
+                    }
+                });
+            }
+        });
+        let stats = cached.stats();
+        assert_eq!(stats.hits + stats.misses, 40);
+        assert_eq!(cached.cached_len(), 10);
+    }
+
+    #[test]
+    fn verification_cache_only_trusts_the_verified_proof() {
+        let tree = tree();
+        let root = tree.get_root().unwrap();
+        let leaf = tree.sorted_leaves()[3];
+        let proof = tree.get_proof(leaf).unwrap();
+        let mut cache = VerificationCache::new(8);
+
+        assert!(cache.verify_cached(leaf, &proof, root));
+        assert!(cache.verify_cached(leaf, &proof, root));
+        assert_eq!(cache.stats().hits, 1);
+
+        let mut tampered = proof.clone();
+        tampered[1].0[5] ^= 1;
+        assert!(!cache.verify_cached(leaf, &tampered, root));
+        assert!(!cache.verify_cached(leaf, &proof[..1], root));
+        assert!(!cache.verify_cached(leaf, &proof, H256::zero()));
+        assert!(cache.verify_cached(leaf, &proof, root));
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 2,
+                misses: 4,
+                evictions: 0
+            }
+        );
+        assert_eq!(cache.len(), 1);
+
+        let mut small = VerificationCache::new(2);
+        for leaf in tree.sorted_leaves() {
+            assert!(small.verify_cached(*leaf, &tree.get_proof(*leaf).unwrap(), root));
+        }
+        assert!(small.len() <= 2);
+        assert_eq!(small.stats().evictions, 8);
+    }
+}
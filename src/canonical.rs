@@ -0,0 +1,153 @@
+//! A canonical encoding of a tree's definition, for identifying distributions by one hash.
+//!
+//! `canonical_bytes` encodes everything that determines the leaves and the root, and
+//! nothing else, as follows (integers are big-endian):
+//!
+//! | size        | content                                                          |
+//! |-------------|------------------------------------------------------------------|
+//! | 4           | magic bytes `OZMC`                                               |
+//! | 1           | encoding version, currently `1`                                  |
+//! | 1           | flags: bit 0 ordered, bit 1 `empty_as_two_field`, bit 2 entries, |
+//! |             | bit 3 packed leaves                                              |
+//! | 1           | the amount width in bytes, present only if bit 3 is set          |
+//! | 1           | `1` if the leaves are bound to a chain, `0` otherwise            |
+//! | 8           | the chain ID, present only if the previous byte is `1`           |
+//! | 4           | length `s` of the node separator                                 |
+//! | s           | the node separator                                               |
+//! | 8           | record count `n`                                                 |
+//! | 52 * n      | with entries: address (20) and amount (32), sorted by both       |
+//! | 32 * n      | without entries: the leaf hashes in the order of the tree        |
+//!
+//! Bit 2 tells which of the last two rows applies. Entries are sorted by address, then
+//! amount, so the input order of the entries does not matter; leaf hashes are already
+//! sorted unless the tree was built with `new_ordered`, whose order is part of its root.
+//! Token metadata only affects how amounts are displayed and is not encoded.
+
+use crate::MerkleTree;
+use ethers::core::utils::keccak256;
+use ethers::types::H256;
+
+const MAGIC: &[u8; 4] = b"OZMC";
+const VERSION: u8 = 1;
+
+const ORDERED: u8 = 1;
+const EMPTY_AS_TWO_FIELD: u8 = 1 << 1;
+const ENTRIES: u8 = 1 << 2;
+const PACKED: u8 = 1 << 3;
+
+impl MerkleTree {
+    /// Encodes the options and contents of the tree in the canonical format of this module.
+    ///
+    /// Trees built from the same entries with the same options encode identically,
+    /// whichever constructor built them and whatever the input order was.
+    pub fn canonical_bytes(&self) -> Vec<u8> {
+        let config = &self.config;
+        let mut flags = 0;
+        if config.ordered {
+            flags |= ORDERED;
+        }
+        if config.payload.empty_as_two_field {
+            flags |= EMPTY_AS_TWO_FIELD;
+        }
+        if !self.entries.is_empty() {
+            flags |= ENTRIES;
+        }
+        if config.amount_width.is_some() {
+            flags |= PACKED;
+        }
+
+        let mut bytes = Vec::with_capacity(64 + 52 * self.leaves);
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        bytes.push(flags);
+        if let Some(width) = config.amount_width {
+            bytes.push(width.bytes() as u8);
+        }
+        match config.chain_id {
+            Some(chain_id) => {
+                bytes.push(1);
+                bytes.extend_from_slice(&chain_id.to_be_bytes());
+            }
+            None => bytes.push(0),
+        }
+        let node_sep = config.node_sep.as_bytes();
+        bytes.extend_from_slice(&(node_sep.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(node_sep);
+
+        if self.entries.is_empty() {
+            bytes.extend_from_slice(&(self.elements.len() as u64).to_be_bytes());
+            for leaf in &self.elements {
+                bytes.extend_from_slice(leaf.as_bytes());
+            }
+        } else {
+            let mut entries = self.entries.clone();
+            entries.sort_by_key(|entry| (entry.account, entry.amount));
+            bytes.extend_from_slice(&(entries.len() as u64).to_be_bytes());
+            let mut amount = [0u8; 32];
+            for entry in entries {
+                entry.amount.to_big_endian(&mut amount);
+                bytes.extend_from_slice(entry.account.as_bytes());
+                bytes.extend_from_slice(&amount);
+            }
+        }
+        bytes
+    }
+
+    /// Returns the keccak256 of `canonical_bytes`, a single hash identifying the distribution.
+    pub fn content_id(&self) -> H256 {
+        H256(keccak256(self.canonical_bytes()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{AmountWidth, MerkleTreeBuilder, NodeSep};
+    use ethers::types::{Address, U256};
+    use std::collections::HashMap;
+
+    fn data() -> Vec<(Address, U256)> {
+        (1..=6u64)
+            .map(|i| (Address::from_low_u64_be(i * 11), U256::from(i * 1000)))
+            .collect()
+    }
+
+    #[test]
+    fn constructors_agree_on_the_content_id() {
+        let id = MerkleTree::new(data()).content_id();
+        let reversed = MerkleTree::new(data().into_iter().rev().collect());
+        let balances: HashMap<Address, U256> = data().into_iter().collect();
+        let built = MerkleTreeBuilder::new().build(data()).unwrap();
+        assert_eq!(reversed.content_id(), id);
+        assert_eq!(MerkleTree::from_balances(balances).content_id(), id);
+        assert_eq!(built.content_id(), id);
+
+        let bytes = MerkleTree::new(data()).canonical_bytes();
+        assert_eq!(&bytes[..8], b"OZMC\x01\x04\x00\x00");
+        assert_eq!(bytes.len(), 4 + 1 + 1 + 1 + 4 + 8 + 52 * 6);
+        assert_eq!(bytes[19..39], *Address::from_low_u64_be(11).as_bytes());
+    }
+
+    #[test]
+    fn options_and_amounts_change_the_content_id() {
+        let id = MerkleTree::new(data()).content_id();
+        let mut changed = data();
+        changed[2].1 += U256::one();
+        let leaves = MerkleTree::new(data()).sorted_leaves().to_vec();
+
+        let others = [
+            MerkleTree::new(changed),
+            MerkleTree::new_for_chain(data(), 1),
+            MerkleTree::new_with_node_sep(data(), NodeSep(vec![0])),
+            MerkleTree::from_leaves(leaves.clone()),
+            MerkleTree::new_ordered(leaves),
+            MerkleTree::new_packed(data(), AmountWidth::U96).unwrap(),
+            MerkleTree::new_packed(data(), AmountWidth::U64).unwrap(),
+        ];
+        let mut ids: Vec<H256> = others.iter().map(MerkleTree::content_id).collect();
+        ids.push(id);
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), others.len() + 1);
+    }
+}
@@ -0,0 +1,416 @@
+//! Resumable construction for builds that outlive the process running them.
+//!
+//! A checkpointed build hashes its input in chunks and regularly saves the leaves hashed
+//! so far to a directory:
+//!
+//! - `leaves.bin` holds the leaf hashes in input order, 32 raw bytes each. It only grows,
+//!   so a checkpoint appends the leaves hashed since the previous one.
+//! - `manifest` is a `key=value` text file with the leaf encoding, the chunk size, the
+//!   number of entries consumed, the keccak256 of those entries and the keccak256 of the
+//!   stored leaves. It is replaced atomically once the leaves are on disk.
+//!
+//! A process killed between the two steps leaves extra bytes in `leaves.bin`, which are
+//! ignored on resume. Everything else that does not match the manifest is rejected.
+
+use crate::{MerkleError, MerkleTree};
+use ethers::types::{Address, H256, U256};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+use tiny_keccak::{Hasher, Keccak};
+
+const MANIFEST: &str = "manifest";
+const LEAVES: &str = "leaves.bin";
+const MANIFEST_VERSION: u32 = 1;
+
+/// How a checkpointed build hashes its input and how often it saves progress.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CheckpointOptions {
+    /// The number of entries hashed between two calls of the cancellation hook.
+    pub chunk_size: usize,
+    /// The number of chunks between two checkpoints; zero only saves on cancellation.
+    pub chunks_per_checkpoint: usize,
+    /// The chain ID leaves are bound to, as with `MerkleTree::new_for_chain`.
+    pub chain_id: Option<u64>,
+}
+
+impl Default for CheckpointOptions {
+    fn default() -> Self {
+        Self {
+            chunk_size: 65536,
+            chunks_per_checkpoint: 16,
+            chain_id: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Manifest {
+    chain_id: Option<u64>,
+    chunk_size: usize,
+    entries: usize,
+    input: H256,
+    leaves: H256,
+}
+
+impl Manifest {
+    fn encode(&self) -> String {
+        let chain_id = self
+            .chain_id
+            .map_or_else(|| "none".to_string(), |id| id.to_string());
+        format!(
+            "version={}\nchain_id={}\nchunk_size={}\nentries={}\ninput={:?}\nleaves={:?}\n",
+            MANIFEST_VERSION, chain_id, self.chunk_size, self.entries, self.input, self.leaves
+        )
+    }
+
+    fn decode(text: &str) -> Result<Self, MerkleError> {
+        let mut fields = text
+            .lines()
+            .map(|line| line.split_once('=').unwrap_or((line, "")));
+        let mut field = |key: &str| match fields.next() {
+            Some((name, value)) if name == key => Ok(value),
+            _ => Err(invalid(format!("manifest has no {} field", key))),
+        };
+        let number = |value: &str| {
+            usize::from_str(value).map_err(|_| invalid(format!("invalid number {:?}", value)))
+        };
+        let hash = |value: &str| {
+            H256::from_str(value).map_err(|_| invalid(format!("invalid hash {:?}", value)))
+        };
+
+        if field("version")? != MANIFEST_VERSION.to_string() {
+            return Err(invalid("unsupported manifest version"));
+        }
+        let chain_id = match field("chain_id")? {
+            "none" => None,
+            value => Some(number(value)? as u64),
+        };
+        Ok(Self {
+            chain_id,
+            chunk_size: number(field("chunk_size")?)?,
+            entries: number(field("entries")?)?,
+            input: hash(field("input")?)?,
+            leaves: hash(field("leaves")?)?,
+        })
+    }
+}
+
+/// The state of a build between chunks.
+struct Progress<'a> {
+    dir: &'a Path,
+    options: &'a CheckpointOptions,
+    leaves: Vec<H256>,
+    // running hashes of the consumed entries and of the leaves written to `leaves.bin`
+    input: Keccak,
+    stored: Keccak,
+    persisted: usize,
+    file: File,
+}
+
+impl MerkleTree {
+    /// Constructs a new Merkle tree, saving progress to a directory as it goes.
+    ///
+    /// Any checkpoint already in `dir` is discarded. The entries are hashed in chunks of
+    /// `options.chunk_size`, and after every chunk `should_stop` is called with the number
+    /// of chunks hashed so far. When it returns `true`, progress is saved and the build
+    /// stops with `MerkleError::Cancelled`; continue it with `resume_from_checkpoint`.
+    /// The checkpoint files are removed once the tree is built.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The addresses and amounts, in an order that can be replayed on resume.
+    /// * `dir` - The checkpoint directory, created if missing.
+    /// * `options` - The chunking, checkpoint interval and leaf encoding.
+    /// * `should_stop` - The cancellation hook.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a tree that retains no entries, `MerkleError::Cancelled` with
+    /// the number of entries consumed, or `MerkleError::Checkpoint` if the directory
+    /// cannot be written.
+    pub fn build_checkpointed<I>(
+        data: I,
+        dir: &Path,
+        options: &CheckpointOptions,
+        should_stop: impl FnMut(usize) -> bool,
+    ) -> Result<MerkleTree, MerkleError>
+    where
+        I: IntoIterator<Item = (Address, U256)>,
+    {
+        fs::create_dir_all(dir).map_err(io)?;
+        match fs::remove_file(dir.join(MANIFEST)) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(io(err)),
+            _ => {}
+        }
+        let progress = Progress {
+            dir,
+            options,
+            leaves: Vec::new(),
+            input: Keccak::v256(),
+            stored: Keccak::v256(),
+            persisted: 0,
+            file: File::create(dir.join(LEAVES)).map_err(io)?,
+        };
+        progress.run(data.into_iter(), should_stop)
+    }
+
+    /// Continues a build saved by `build_checkpointed`.
+    ///
+    /// The stored leaves are checked against the manifest, and the entries the checkpoint
+    /// covers are read again from `data` and checked against the manifest's input hash,
+    /// so a checkpoint of another input or with other options is never reused. Hashing
+    /// then continues with the next entry.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The checkpoint directory.
+    /// * `data` - The same entries in the same order as the interrupted build.
+    /// * `options` - The options of the interrupted build.
+    /// * `should_stop` - The cancellation hook, called with the chunk count since the start.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the tree, `MerkleError::Cancelled` if stopped again, or
+    /// `MerkleError::Checkpoint` if the checkpoint is missing, damaged, or does not match
+    /// the input or options.
+    pub fn resume_from_checkpoint<I>(
+        dir: &Path,
+        data: I,
+        options: &CheckpointOptions,
+        should_stop: impl FnMut(usize) -> bool,
+    ) -> Result<MerkleTree, MerkleError>
+    where
+        I: IntoIterator<Item = (Address, U256)>,
+    {
+        let manifest = Manifest::decode(&fs::read_to_string(dir.join(MANIFEST)).map_err(io)?)?;
+        if manifest.chain_id != options.chain_id || manifest.chunk_size != options.chunk_size {
+            return Err(invalid("the checkpoint was written with other options"));
+        }
+
+        let mut bytes = Vec::new();
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(dir.join(LEAVES))
+            .map_err(io)?;
+        file.read_to_end(&mut bytes).map_err(io)?;
+        let len = manifest.entries * 32;
+        if bytes.len() < len {
+            return Err(invalid("the stored leaves are truncated"));
+        }
+        // drop leaves appended after the last manifest was written
+        file.set_len(len as u64).map_err(io)?;
+        let mut stored = Keccak::v256();
+        stored.update(&bytes[..len]);
+        if finalize(&stored) != manifest.leaves {
+            return Err(invalid("the stored leaves do not match the manifest"));
+        }
+
+        let mut data = data.into_iter();
+        let mut input = Keccak::v256();
+        for _ in 0..manifest.entries {
+            let entry = data
+                .next()
+                .ok_or_else(|| invalid("the input is shorter than the checkpoint"))?;
+            update_input(&mut input, entry);
+        }
+        if finalize(&input) != manifest.input {
+            return Err(invalid("the input does not match the checkpoint"));
+        }
+
+        let progress = Progress {
+            dir,
+            options,
+            leaves: bytes[..len]
+                .chunks_exact(32)
+                .map(H256::from_slice)
+                .collect(),
+            input,
+            stored,
+            persisted: manifest.entries,
+            file: OpenOptions::new()
+                .append(true)
+                .open(dir.join(LEAVES))
+                .map_err(io)?,
+        };
+        progress.run(data, should_stop)
+    }
+}
+
+impl Progress<'_> {
+    fn run(
+        mut self,
+        mut data: impl Iterator<Item = (Address, U256)>,
+        mut should_stop: impl FnMut(usize) -> bool,
+    ) -> Result<MerkleTree, MerkleError> {
+        let chunk_size = self.options.chunk_size.max(1);
+        let mut chunks = self.leaves.len().div_ceil(chunk_size);
+        loop {
+            let before = self.leaves.len();
+            for entry in data.by_ref().take(chunk_size) {
+                update_input(&mut self.input, entry);
+                self.leaves.push(match self.options.chain_id {
+                    Some(chain_id) => MerkleTree::hash_chain_node(chain_id, entry),
+                    None => MerkleTree::hash_node(entry),
+                });
+            }
+            if self.leaves.len() == before {
+                break;
+            }
+            chunks += 1;
+            let stop = should_stop(chunks);
+            if stop || chunks.is_multiple_of(self.options.chunks_per_checkpoint) {
+                self.checkpoint()?;
+            }
+            if stop {
+                return Err(MerkleError::Cancelled {
+                    entries: self.leaves.len(),
+                });
+            }
+        }
+
+        fs::remove_file(self.dir.join(MANIFEST))
+            .or_else(|err| match err.kind() {
+                std::io::ErrorKind::NotFound => Ok(()),
+                _ => Err(err),
+            })
+            .and_then(|_| fs::remove_file(self.dir.join(LEAVES)))
+            .map_err(io)?;
+        let mut tree = MerkleTree::from_leaves(self.leaves);
+        tree.config.chain_id = self.options.chain_id;
+        Ok(tree)
+    }
+
+    fn checkpoint(&mut self) -> Result<(), MerkleError> {
+        let bytes: Vec<u8> = self.leaves[self.persisted..]
+            .iter()
+            .flat_map(|leaf| leaf.to_fixed_bytes())
+            .collect();
+        self.file.write_all(&bytes).map_err(io)?;
+        self.file.sync_all().map_err(io)?;
+        self.stored.update(&bytes);
+        self.persisted = self.leaves.len();
+
+        let manifest = Manifest {
+            chain_id: self.options.chain_id,
+            chunk_size: self.options.chunk_size,
+            entries: self.leaves.len(),
+            input: finalize(&self.input),
+            leaves: finalize(&self.stored),
+        };
+        let temporary = self.dir.join(format!("{}.tmp", MANIFEST));
+        let mut file = File::create(&temporary).map_err(io)?;
+        file.write_all(manifest.encode().as_bytes()).map_err(io)?;
+        file.sync_all().map_err(io)?;
+        fs::rename(temporary, self.dir.join(MANIFEST)).map_err(io)
+    }
+}
+
+fn update_input(hasher: &mut Keccak, (account, amount): (Address, U256)) {
+    let mut amount_bytes = [0u8; 32];
+    amount.to_big_endian(&mut amount_bytes);
+    hasher.update(account.as_bytes());
+    hasher.update(&amount_bytes);
+}
+
+fn finalize(hasher: &Keccak) -> H256 {
+    let mut output = [0u8; 32];
+    hasher.clone().finalize(&mut output);
+    H256(output)
+}
+
+fn invalid(reason: impl Into<String>) -> MerkleError {
+    MerkleError::Checkpoint(reason.into())
+}
+
+fn io(err: std::io::Error) -> MerkleError {
+    MerkleError::Checkpoint(err.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn data() -> Vec<(Address, U256)> {
+        (1..=1000u64)
+            .map(|i| (Address::from_low_u64_be(i), U256::from(i * 3)))
+            .collect()
+    }
+
+    fn options() -> CheckpointOptions {
+        CheckpointOptions {
+            chunk_size: 64,
+            chunks_per_checkpoint: 2,
+            chain_id: Some(5),
+        }
+    }
+
+    #[test]
+    fn resumed_build_matches_uninterrupted_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let expected = MerkleTree::new_for_chain(data(), 5).get_root();
+        let tree =
+            MerkleTree::build_checkpointed(data(), dir.path(), &options(), |_| false).unwrap();
+        assert_eq!(tree.get_root(), expected);
+        assert_eq!(tree.chain_id(), Some(5));
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+
+        let result = MerkleTree::build_checkpointed(data(), dir.path(), &options(), |n| n == 5);
+        assert_eq!(result.unwrap_err(), MerkleError::Cancelled { entries: 320 });
+        let result = MerkleTree::resume_from_checkpoint(dir.path(), data(), &options(), |n| n == 9);
+        assert_eq!(result.unwrap_err(), MerkleError::Cancelled { entries: 576 });
+
+        // a process killed while appending leaves more leaves than the manifest covers
+        let mut leaves = OpenOptions::new()
+            .append(true)
+            .open(dir.path().join(LEAVES))
+            .unwrap();
+        leaves.write_all(&[0xab; 40]).unwrap();
+        let tree =
+            MerkleTree::resume_from_checkpoint(dir.path(), data(), &options(), |_| false).unwrap();
+        assert_eq!(tree.get_root(), expected);
+    }
+
+    #[test]
+    fn mismatched_checkpoints_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let resume = |data: Vec<(Address, U256)>, options: &CheckpointOptions| {
+            MerkleTree::resume_from_checkpoint(dir.path(), data, options, |_| false)
+        };
+        assert!(matches!(
+            resume(data(), &options()),
+            Err(MerkleError::Checkpoint(_))
+        ));
+
+        let result = MerkleTree::build_checkpointed(data(), dir.path(), &options(), |n| n == 3);
+        assert!(result.is_err());
+        let mut changed = data();
+        changed[10].1 += U256::one();
+        assert_eq!(
+            resume(changed, &options()).unwrap_err(),
+            invalid("the input does not match the checkpoint")
+        );
+        assert_eq!(
+            resume(data()[..100].to_vec(), &options()).unwrap_err(),
+            invalid("the input is shorter than the checkpoint")
+        );
+        let other_chain = CheckpointOptions {
+            chain_id: None,
+            ..options()
+        };
+        assert_eq!(
+            resume(data(), &other_chain).unwrap_err(),
+            invalid("the checkpoint was written with other options")
+        );
+
+        let mut bytes = fs::read(dir.path().join(LEAVES)).unwrap();
+        bytes[0] ^= 1;
+        fs::write(dir.path().join(LEAVES), bytes).unwrap();
+        assert_eq!(
+            resume(data(), &options()).unwrap_err(),
+            invalid("the stored leaves do not match the manifest")
+        );
+    }
+}
@@ -0,0 +1,888 @@
+use crate::{verify_proof_no_alloc, EntryScheme, MerkleError, MerkleTree};
+use ethers::types::{Address, H256, U256};
+
+/// An account and the amount it is entitled to, i.e. the data behind a leaf.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct Entry {
+    /// The claiming account.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::address"))]
+    #[cfg_attr(
+        feature = "utoipa",
+        schema(
+            value_type = String,
+            pattern = "^0x[0-9a-fA-F]{40}$",
+            example = "0x00393d62f17b07e64f7cdcdf9bdc2fd925b20bba"
+        )
+    )]
+    pub account: Address,
+    /// The claimable amount in base units.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::amount"))]
+    #[cfg_attr(
+        feature = "utoipa",
+        schema(value_type = String, pattern = "^0x[0-9a-f]{1,64}$", example = "0x63c2601c43bba41dc1")
+    )]
+    pub amount: U256,
+}
+
+impl Entry {
+    /// Creates an entry for `account` and `amount`.
+    pub fn new(account: Address, amount: U256) -> Self {
+        Self { account, amount }
+    }
+
+    /// Returns the leaf hash of the entry, as computed by `MerkleTree::hash_node`.
+    pub fn leaf(&self) -> H256 {
+        MerkleTree::hash_node((self.account, self.amount))
+    }
+}
+
+impl From<(Address, U256)> for Entry {
+    fn from((account, amount): (Address, U256)) -> Self {
+        Self { account, amount }
+    }
+}
+
+/// A Merkle proof: the sibling hashes from a leaf up to the root.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct Proof(
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::hashes"))] Vec<H256>,
+);
+
+impl Proof {
+    /// Returns the sibling hashes of the proof.
+    pub fn as_slice(&self) -> &[H256] {
+        &self.0
+    }
+
+    /// Consumes the proof, returning its sibling hashes.
+    pub fn into_inner(self) -> Vec<H256> {
+        self.0
+    }
+
+    /// Returns the number of sibling hashes.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns whether the proof has no sibling hashes, as for a single-leaf tree.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Verifies the proof for `leaf` against `root`.
+    pub fn verify(&self, leaf: H256, root: H256) -> bool {
+        verify_proof_no_alloc(&root, &leaf, &self.0)
+    }
+}
+
+impl From<Vec<H256>> for Proof {
+    fn from(proof: Vec<H256>) -> Self {
+        Self(proof)
+    }
+}
+
+impl From<Proof> for Vec<H256> {
+    fn from(proof: Proof) -> Self {
+        proof.0
+    }
+}
+
+/// A claim as consumed by airdrop frontends: the account, its amount and its proof.
+pub type ClaimRecord = ProofBundle;
+
+/// Everything a claimant needs to submit a claim: the entry, its leaf index and its proof.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct ProofBundle {
+    /// The claiming account.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::address"))]
+    #[cfg_attr(
+        feature = "utoipa",
+        schema(
+            value_type = String,
+            pattern = "^0x[0-9a-fA-F]{40}$",
+            example = "0x00393d62f17b07e64f7cdcdf9bdc2fd925b20bba"
+        )
+    )]
+    pub account: Address,
+    /// The claimable amount in base units.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::amount"))]
+    #[cfg_attr(
+        feature = "utoipa",
+        schema(value_type = String, pattern = "^0x[0-9a-f]{1,64}$", example = "0x63c2601c43bba41dc1")
+    )]
+    pub amount: U256,
+    /// The index of the leaf among the sorted leaves of the tree.
+    pub index: usize,
+    /// The proof of the leaf.
+    pub proof: Proof,
+    /// How the tree hashes the entry and the pairs of the proof.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub scheme: EntryScheme,
+}
+
+impl ProofBundle {
+    /// Returns the entry the bundle claims.
+    pub fn entry(&self) -> Entry {
+        Entry::new(self.account, self.amount)
+    }
+
+    /// Verifies the bundle against `root`.
+    ///
+    /// The leaf and the pairs are hashed with the scheme of the tree the bundle came
+    /// from, so bundles of chain-bound, packed or separated trees verify as well.
+    pub fn verify(&self, root: H256) -> bool {
+        self.scheme
+            .verify((self.account, self.amount), self.proof.as_slice(), root)
+    }
+}
+
+/// Limits applied by [`MerkleTree::find_by_address_prefix_with`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrefixSearchOptions {
+    /// The minimum number of hex digits in the prefix, excluding `0x`.
+    pub min_len: usize,
+    /// The maximum number of entries returned.
+    pub max_results: usize,
+}
+
+impl Default for PrefixSearchOptions {
+    fn default() -> Self {
+        Self {
+            min_len: 4,
+            max_results: 20,
+        }
+    }
+}
+
+/// The order of the claims produced by the exporters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ExportOrder {
+    /// Ascending address, then amount for repeated addresses.
+    #[default]
+    Address,
+    /// Ascending leaf index, i.e. the order of `sorted_leaves()`.
+    Index,
+}
+
+/// Options applied by [`MerkleTree::claim_records_with`] and the exporters built on it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ExportOptions {
+    /// The order of the exported claims.
+    pub sort_by: ExportOrder,
+}
+
+/// A root and a handful of proofs, for light verifiers that never see the full tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProofSet {
+    root: H256,
+    // sorted by leaf for lookups
+    proofs: Vec<(H256, Proof)>,
+}
+
+impl ProofSet {
+    /// Creates a proof set for `root`.
+    ///
+    /// # Arguments
+    ///
+    /// * `root` - The trusted root hash.
+    /// * `proofs` - Leaf hashes with their proofs. If a leaf is given more than once, only
+    ///   its first proof is kept.
+    pub fn new(root: H256, mut proofs: Vec<(H256, Proof)>) -> Self {
+        proofs.sort_by_key(|(leaf, _)| *leaf);
+        proofs.dedup_by_key(|(leaf, _)| *leaf);
+        Self { root, proofs }
+    }
+
+    /// Returns the trusted root.
+    pub fn root(&self) -> H256 {
+        self.root
+    }
+
+    /// Returns the proof of `leaf`, if the set holds one.
+    pub fn get(&self, leaf: H256) -> Option<&Proof> {
+        let index = self
+            .proofs
+            .binary_search_by_key(&leaf, |(leaf, _)| *leaf)
+            .ok()?;
+        Some(&self.proofs[index].1)
+    }
+
+    /// Returns whether every proof of the set verifies against the root.
+    pub fn verify_all(&self) -> bool {
+        self.proofs
+            .iter()
+            .all(|(leaf, proof)| proof.verify(*leaf, self.root))
+    }
+}
+
+impl MerkleTree {
+    /// Retrieves the proof bundle for an entry.
+    ///
+    /// # Arguments
+    ///
+    /// * `entry` - The account and amount of the leaf.
+    ///
+    /// # Returns
+    ///
+    /// An `Option` containing the bundle, or `None` if the entry is not a leaf of the tree.
+    pub fn get_proof_bundle(&self, entry: Entry) -> Option<ProofBundle> {
+        let index = self.position(self.hash_entry((entry.account, entry.amount)).ok()?)?;
+        self.try_bundle_at(index, entry).ok()
+    }
+
+    /// Retrieves the proof bundle of an account from the retained entries.
+    ///
+    /// # Arguments
+    ///
+    /// * `account` - The claiming address.
+    ///
+    /// # Returns
+    ///
+    /// An `Option` containing the bundle of the account's leaf with the smallest amount if
+    /// it has several, or `None` if the account has no retained entry.
+    pub fn get_claim(&self, account: Address) -> Option<ProofBundle> {
+        let (index, entry) = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.account == account)
+            .min_by_key(|(_, entry)| entry.amount)?;
+        self.try_bundle_at(index, *entry).ok()
+    }
+
+    /// Verifies a claim against a root and checks its amount against a per-claim cap.
+    ///
+    /// The leaf is hashed with the tree's encoding. A valid proof of an over-cap amount
+    /// still fails, which surfaces a tree built with the wrong amounts before it pays out.
+    ///
+    /// # Arguments
+    ///
+    /// * `account` - The claiming address.
+    /// * `amount` - The claimed amount.
+    /// * `proof` - A vector containing the hashes forming the Merkle proof.
+    /// * `root` - The root hash of the Merkle tree.
+    /// * `max_claim` - The largest amount a single claim may hold.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` that is `MerkleError::InvalidProof` if the proof does not lead to `root`,
+    /// `MerkleError::AmountExceedsCap` if it does but `amount` is above `max_claim`, or
+    /// `MerkleError::AmountOutOfRange` if `amount` does not fit the tree's packed leaves.
+    pub fn verify_claim(
+        &self,
+        account: Address,
+        amount: U256,
+        proof: Vec<H256>,
+        root: H256,
+        max_claim: U256,
+    ) -> Result<(), MerkleError> {
+        if !self.verify_proof(self.hash_entry((account, amount))?, proof, root) {
+            return Err(MerkleError::InvalidProof);
+        }
+        if amount > max_claim {
+            return Err(MerkleError::AmountExceedsCap {
+                amount,
+                cap: max_claim,
+            });
+        }
+        Ok(())
+    }
+
+    /// Verifies a claim packed by `ProofBundle::pack` and returns its amount.
+    ///
+    /// The blob is decoded like a claim fragment, and the leaf is rebuilt from the account
+    /// and the packed amount with the tree's scheme.
+    ///
+    /// # Arguments
+    ///
+    /// * `account` - The claiming address.
+    /// * `blob` - The packed index, amount and proof.
+    /// * `root` - The root hash of the Merkle tree.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the claimable amount, `MerkleError::InvalidMessage` if the
+    /// blob is not a packed claim, or `MerkleError::InvalidProof` if the claim does not
+    /// verify against `root`.
+    pub fn verify_packed_claim(
+        &self,
+        account: Address,
+        blob: &[u8],
+        root: H256,
+    ) -> Result<U256, MerkleError> {
+        let bundle = ProofBundle::unpack(account, blob, self.entry_scheme())?;
+        Self::verified_amount(bundle, root)
+    }
+
+    /// Encodes the claim of an account as a short base64url token, e.g. for a QR code.
+    ///
+    /// The token is the claim fragment of the account's bundle: it carries the index, the
+    /// amount and the proof but neither the account nor the root, which the verifier
+    /// supplies.
+    ///
+    /// # Arguments
+    ///
+    /// * `account` - The claiming address.
+    ///
+    /// # Returns
+    ///
+    /// An `Option` containing the token of the bundle returned by `get_claim`, or `None`
+    /// if the account has no retained entry.
+    pub fn claim_token(&self, account: Address) -> Option<String> {
+        Some(self.get_claim(account)?.to_claim_fragment())
+    }
+
+    /// Verifies a token produced by `claim_token` and returns its amount.
+    ///
+    /// The leaf is rebuilt from the account and the amount of the token with the tree's
+    /// scheme.
+    ///
+    /// # Arguments
+    ///
+    /// * `account` - The claiming address.
+    /// * `token` - The base64url claim token.
+    /// * `root` - The root hash of the Merkle tree.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the claimable amount, `MerkleError::InvalidMessage` if the
+    /// token cannot be decoded, or `MerkleError::InvalidProof` if the claim does not
+    /// verify against `root`.
+    pub fn verify_token(
+        &self,
+        account: Address,
+        token: &str,
+        root: H256,
+    ) -> Result<U256, MerkleError> {
+        let bundle = ProofBundle::from_claim_fragment(account, token, self.entry_scheme())?;
+        Self::verified_amount(bundle, root)
+    }
+
+    fn verified_amount(bundle: ProofBundle, root: H256) -> Result<U256, MerkleError> {
+        if !bundle.verify(root) {
+            return Err(MerkleError::InvalidProof);
+        }
+        Ok(bundle.amount)
+    }
+
+    /// Returns whether an address has exactly one leaf among the retained entries.
+    ///
+    /// Identical `(address, amount)` pairs collapse into one leaf, so only an address with
+    /// several different amounts has more than one.
+    ///
+    /// # Returns
+    ///
+    /// `true` if exactly one retained entry belongs to `account`; `false` if it has none or
+    /// several, or if the tree has no retained entries.
+    pub fn is_unique_address(&self, account: Address) -> bool {
+        self.entries
+            .iter()
+            .filter(|entry| entry.account == account)
+            .take(2)
+            .count()
+            == 1
+    }
+
+    /// Returns every address with more than one leaf among the retained entries.
+    ///
+    /// # Returns
+    ///
+    /// The addresses in ascending order, each listed once.
+    pub fn duplicate_addresses(&self) -> Vec<Address> {
+        let mut accounts: Vec<Address> = self.entries.iter().map(|entry| entry.account).collect();
+        accounts.sort();
+        let mut duplicates: Vec<Address> = accounts
+            .windows(2)
+            .filter(|pair| pair[0] == pair[1])
+            .map(|pair| pair[0])
+            .collect();
+        duplicates.dedup();
+        duplicates
+    }
+
+    /// Finds retained entries whose address starts with a hex prefix, with default limits.
+    ///
+    /// See `find_by_address_prefix_with`.
+    pub fn find_by_address_prefix(&self, prefix: &str) -> Result<Vec<&Entry>, MerkleError> {
+        self.find_by_address_prefix_with(prefix, &PrefixSearchOptions::default())
+    }
+
+    /// Finds retained entries whose address starts with a hex prefix.
+    ///
+    /// The prefix may start with `0x` and is matched case-insensitively against the hex
+    /// form of the address, so checksummed and lowercase input behave the same. The
+    /// matching addresses form one range of the tree's address index, found by binary
+    /// search, so only the returned entries are visited.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - The beginning of an address, such as `"0x8a3F"`.
+    /// * `options` - The minimum prefix length and the maximum number of results.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing at most `options.max_results` matching entries ordered by
+    /// address, or `MerkleError::PrefixTooShort` if the prefix has fewer than
+    /// `options.min_len` hex digits.
+    pub fn find_by_address_prefix_with(
+        &self,
+        prefix: &str,
+        options: &PrefixSearchOptions,
+    ) -> Result<Vec<&Entry>, MerkleError> {
+        let digits = prefix
+            .strip_prefix("0x")
+            .or_else(|| prefix.strip_prefix("0X"))
+            .unwrap_or(prefix)
+            .to_ascii_lowercase();
+        if digits.len() < options.min_len {
+            return Err(MerkleError::PrefixTooShort {
+                min: options.min_len,
+                len: digits.len(),
+            });
+        }
+
+        if digits.len() > 40 || !digits.bytes().all(|digit| digit.is_ascii_hexdigit()) {
+            return Ok(Vec::new());
+        }
+
+        // the addresses starting with the prefix are those between it padded with zeros
+        // and it padded with `f`s
+        let bound = |pad: char| -> Address {
+            let padding = std::iter::repeat_n(pad, 40 - digits.len());
+            digits
+                .chars()
+                .chain(padding)
+                .collect::<String>()
+                .parse()
+                .unwrap()
+        };
+        let (low, high) = (bound('0'), bound('f'));
+        let start = self
+            .address_order
+            .partition_point(|&index| self.entries[index].account < low);
+        Ok(self.address_order[start..]
+            .iter()
+            .map(|&index| &self.entries[index])
+            .take_while(|entry| entry.account <= high)
+            .take(options.max_results)
+            .collect())
+    }
+
+    /// Returns the data behind every leaf, in the order of `sorted_leaves()`.
+    ///
+    /// Entries are retained by the constructors taking addresses and amounts, such as
+    /// `new`; trees built from leaf hashes or loaded from a snapshot have none.
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Returns a claim record with a proof for every retained entry.
+    ///
+    /// # Returns
+    ///
+    /// The records ordered by ascending address (and amount, for repeated addresses), so
+    /// the output is reproducible whatever the input order. Empty if the tree has no
+    /// retained entries.
+    pub fn claim_records(&self) -> Vec<ClaimRecord> {
+        self.claim_records_with(&ExportOptions::default())
+    }
+
+    /// Returns a claim record with a proof for every retained entry, in a chosen order.
+    ///
+    /// Both orders are total, since leaves are deduplicated, so the same distribution
+    /// always yields the same records whatever the input order.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - The order of the records.
+    ///
+    /// # Returns
+    ///
+    /// The ordered records, empty if the tree has no retained entries.
+    pub fn claim_records_with(&self, options: &ExportOptions) -> Vec<ClaimRecord> {
+        let mut order: Vec<usize> = (0..self.entries.len()).collect();
+        if options.sort_by == ExportOrder::Address {
+            order.sort_by_key(|&index| self.entries[index]);
+        }
+        order
+            .into_iter()
+            .map(|index| self.bundle_at(index, self.entries[index]))
+            .collect()
+    }
+
+    fn bundle_at(&self, index: usize, entry: Entry) -> ProofBundle {
+        self.try_bundle_at(index, entry)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    fn try_bundle_at(&self, index: usize, entry: Entry) -> Result<ProofBundle, MerkleError> {
+        Ok(ProofBundle {
+            account: entry.account,
+            amount: entry.amount,
+            index,
+            proof: Proof(self.try_proof_at(index)?),
+            scheme: self.entry_scheme(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use base64::Engine;
+
+    #[test]
+    fn proof_bundle_verifies_against_root() {
+        let data: Vec<(Address, U256)> = (1..=5u64)
+            .map(|i| (Address::from_low_u64_be(i), U256::from(i * 100)))
+            .collect();
+        let tree = MerkleTree::new(data.clone());
+        let root = tree.get_root().unwrap();
+
+        for entry in data.into_iter().map(Entry::from) {
+            let bundle = tree.get_proof_bundle(entry).unwrap();
+            assert_eq!(tree.sorted_leaves()[bundle.index], entry.leaf());
+            assert!(bundle.verify(root));
+        }
+        let missing = Entry::new(Address::from_low_u64_be(1), U256::from(1));
+        assert_eq!(tree.get_proof_bundle(missing), None);
+    }
+
+    #[test]
+    fn bundles_verify_with_the_scheme_of_their_tree() {
+        let data: Vec<(Address, U256)> = (1..=6u64)
+            .map(|i| (Address::from_low_u64_be(i), U256::from(i * 100)))
+            .collect();
+        let trees = [
+            MerkleTree::new_for_chain(data.clone(), 10),
+            MerkleTree::new_packed(data.clone(), crate::AmountWidth::U96).unwrap(),
+            MerkleTree::new_with_leaf_mode(data.clone(), crate::LeafMode::PackedSingle),
+            MerkleTree::new_with_node_sep(data.clone(), crate::NodeSep(vec![1])),
+        ];
+        for tree in trees {
+            let root = tree.get_root().unwrap();
+            for (account, _) in &data {
+                let bundle = tree.get_claim(*account).unwrap();
+                assert_eq!(bundle.scheme, tree.entry_scheme());
+                assert!(bundle.verify(root));
+                let plain = ProofBundle {
+                    scheme: EntryScheme::default(),
+                    ..bundle.clone()
+                };
+                assert!(!plain.verify(root));
+                let wrong = ProofBundle {
+                    amount: bundle.amount + 1,
+                    ..bundle
+                };
+                assert!(!wrong.verify(root));
+            }
+        }
+    }
+
+    #[test]
+    fn verify_claim_enforces_the_cap() {
+        let data: Vec<(Address, U256)> = (1..=5u64)
+            .map(|i| (Address::from_low_u64_be(i), U256::from(i * 100)))
+            .collect();
+        let tree = MerkleTree::new(data);
+        let root = tree.get_root().unwrap();
+        let cap = U256::from(400);
+        let claim = |i: u64| tree.get_claim(Address::from_low_u64_be(i)).unwrap();
+
+        let within = claim(4);
+        assert_eq!(
+            tree.verify_claim(
+                within.account,
+                within.amount,
+                within.proof.clone().into_inner(),
+                root,
+                cap
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            tree.verify_claim(
+                within.account,
+                cap - 1,
+                within.proof.clone().into_inner(),
+                root,
+                cap
+            ),
+            Err(MerkleError::InvalidProof)
+        );
+
+        let over = claim(5);
+        assert_eq!(
+            tree.verify_claim(
+                over.account,
+                over.amount,
+                over.proof.clone().into_inner(),
+                root,
+                cap
+            ),
+            Err(MerkleError::AmountExceedsCap {
+                amount: U256::from(500),
+                cap
+            })
+        );
+    }
+
+    #[test]
+    fn packed_claims_round_trip() {
+        let data: Vec<(Address, U256)> = (1..=7u64)
+            .map(|i| (Address::from_low_u64_be(i), U256::exp10(18) * i))
+            .collect();
+        let tree = MerkleTree::new(data);
+        let root = tree.get_root().unwrap();
+
+        for bundle in tree.claim_records() {
+            let blob = bundle.pack();
+            // version, index, amount length, 8 amount bytes, then the hashes
+            assert_eq!(blob.len(), 11 + 32 * bundle.proof.len());
+            assert_eq!(URL_SAFE_NO_PAD.encode(&blob), bundle.to_claim_fragment());
+            assert_eq!(
+                tree.verify_packed_claim(bundle.account, &blob, root),
+                Ok(bundle.amount)
+            );
+            assert_eq!(
+                tree.verify_packed_claim(Address::zero(), &blob, root),
+                Err(MerkleError::InvalidProof)
+            );
+        }
+
+        let blob = tree.get_claim(Address::from_low_u64_be(3)).unwrap().pack();
+        assert!(matches!(
+            tree.verify_packed_claim(Address::from_low_u64_be(3), &blob[..40], root),
+            Err(MerkleError::InvalidMessage(_))
+        ));
+        assert!(tree
+            .verify_packed_claim(Address::zero(), &[], root)
+            .is_err());
+    }
+
+    #[test]
+    fn claim_tokens_round_trip() {
+        let data: Vec<(Address, U256)> = (1..=20u64)
+            .map(|i| (Address::from_low_u64_be(i), U256::exp10(18) * i))
+            .collect();
+        let tree = MerkleTree::new(data.clone());
+        let root = tree.get_root().unwrap();
+
+        for (account, amount) in data {
+            let token = tree.claim_token(account).unwrap();
+            assert!(token
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_'));
+            // version, index and length bytes, up to 9 amount bytes and 4 or 5 proof hashes
+            assert!(token.len() <= (3 + 9 + 32 * 5usize).div_ceil(3) * 4);
+            assert_eq!(tree.verify_token(account, &token, root), Ok(amount));
+            assert_eq!(
+                tree.verify_token(Address::zero(), &token, root),
+                Err(MerkleError::InvalidProof)
+            );
+        }
+        assert_eq!(tree.claim_token(Address::zero()), None);
+
+        let zero = MerkleTree::new(vec![(Address::zero(), U256::zero())]);
+        let token = zero.claim_token(Address::zero()).unwrap();
+        assert_eq!(token, "AwAA");
+        assert_eq!(
+            zero.verify_token(Address::zero(), &token, zero.get_root().unwrap()),
+            Ok(U256::zero())
+        );
+        for malformed in ["", "!!", "IQ", "AAE"] {
+            assert!(matches!(
+                tree.verify_token(Address::zero(), malformed, root),
+                Err(MerkleError::InvalidMessage(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn codecs_hash_with_the_tree_scheme() {
+        let data: Vec<(Address, U256)> = (1..=5u64)
+            .map(|i| (Address::from_low_u64_be(i), U256::from(i) << 80))
+            .collect();
+        let tree = MerkleTree::new_packed(data.clone(), crate::AmountWidth::U96).unwrap();
+        let plain = MerkleTree::new(data.clone());
+        let root = tree.get_root().unwrap();
+        for (account, amount) in data {
+            let bundle = tree.get_claim(account).unwrap();
+            let token = tree.claim_token(account).unwrap();
+            assert_eq!(token, bundle.to_claim_fragment());
+            assert_eq!(tree.verify_token(account, &token, root), Ok(amount));
+            assert_eq!(
+                tree.verify_packed_claim(account, &bundle.pack(), root),
+                Ok(amount)
+            );
+            let decoded =
+                ProofBundle::from_claim_fragment(account, &token, tree.entry_scheme()).unwrap();
+            assert_eq!(decoded, bundle);
+            assert!(decoded.verify(root));
+            assert_eq!(
+                plain.verify_token(account, &token, root),
+                Err(MerkleError::InvalidProof)
+            );
+        }
+    }
+
+    #[test]
+    fn claim_records_are_ordered_and_verify() {
+        let data: Vec<(Address, U256)> = [0x9c, 0x01, 0x5a, 0xff, 0x33, 0x5a]
+            .iter()
+            .enumerate()
+            .map(|(i, &byte)| (Address::repeat_byte(byte), U256::from(i + 1)))
+            .collect();
+        let tree = MerkleTree::new(data.clone());
+        let root = tree.get_root().unwrap();
+        let records = tree.claim_records();
+
+        assert_eq!(records.len(), data.len());
+        assert!(records
+            .windows(2)
+            .all(|pair| pair[0].entry() < pair[1].entry()));
+        assert!(records.iter().all(|record| record.verify(root)));
+        assert!(MerkleTree::from_leaves(tree.sorted_leaves().to_vec())
+            .claim_records()
+            .is_empty());
+    }
+
+    #[test]
+    fn proof_set_from_claim_records() {
+        let data: Vec<(Address, U256)> = (1..=11u64)
+            .map(|i| (Address::from_low_u64_be(i), U256::from(i)))
+            .collect();
+        let tree = MerkleTree::new(data);
+        let root = tree.get_root().unwrap();
+        let proofs: Vec<(H256, Proof)> = tree
+            .claim_records()
+            .into_iter()
+            .map(|record| (record.entry().leaf(), record.proof))
+            .collect();
+        let set = ProofSet::new(root, proofs.clone());
+
+        assert!(set.verify_all());
+        for (leaf, proof) in &proofs {
+            assert_eq!(set.get(*leaf), Some(proof));
+        }
+        assert_eq!(set.get(H256::zero()), None);
+
+        let mut tampered = proofs;
+        tampered[4].1 = Proof::from(vec![H256::zero()]);
+        assert!(!ProofSet::new(root, tampered).verify_all());
+    }
+
+    #[test]
+    fn duplicate_addresses_report_multi_leaf_accounts() {
+        let twice = Address::from_low_u64_be(2);
+        let data = vec![
+            (Address::from_low_u64_be(1), U256::from(10)),
+            (twice, U256::from(20)),
+            (Address::from_low_u64_be(3), U256::from(30)),
+            (twice, U256::from(25)),
+            // an identical pair collapses into one leaf
+            (Address::from_low_u64_be(3), U256::from(30)),
+        ];
+        let tree = MerkleTree::new(data);
+
+        assert_eq!(tree.duplicate_addresses(), vec![twice]);
+        assert!(!tree.is_unique_address(twice));
+        assert!(tree.is_unique_address(Address::from_low_u64_be(1)));
+        assert!(tree.is_unique_address(Address::from_low_u64_be(3)));
+        assert!(!tree.is_unique_address(Address::from_low_u64_be(4)));
+    }
+
+    #[test]
+    fn find_by_address_prefix_matches_case_insensitively() {
+        let accounts = [
+            "0x8a3f000000000000000000000000000000000001",
+            "0x8A3F100000000000000000000000000000000002",
+            "0x8a3e000000000000000000000000000000000003",
+            "0x1234000000000000000000000000000000000004",
+        ];
+        let data: Vec<(Address, U256)> = accounts
+            .iter()
+            .map(|account| (account.parse().unwrap(), U256::one()))
+            .collect();
+        let tree = MerkleTree::new(data.clone());
+
+        let found = tree.find_by_address_prefix("0x8A3f").unwrap();
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].account, data[0].0);
+        assert_eq!(found[1].account, data[1].0);
+        assert_eq!(tree.find_by_address_prefix("8a3e0").unwrap().len(), 1);
+        assert!(tree.find_by_address_prefix("0xffff").unwrap().is_empty());
+
+        let capped = PrefixSearchOptions {
+            min_len: 1,
+            max_results: 1,
+        };
+        assert_eq!(
+            tree.find_by_address_prefix_with("8", &capped).unwrap(),
+            vec![&Entry::from(data[2])]
+        );
+        assert_eq!(
+            tree.find_by_address_prefix("0x8a"),
+            Err(MerkleError::PrefixTooShort { min: 4, len: 2 })
+        );
+        assert!(tree.find_by_address_prefix("0x8a3g").unwrap().is_empty());
+        assert_eq!(
+            tree.find_by_address_prefix(accounts[3]).unwrap(),
+            vec![&Entry::from(data[3])]
+        );
+    }
+
+    #[test]
+    fn prefix_search_follows_mutations() {
+        let data: Vec<(Address, U256)> = (1..=300u64)
+            .map(|i| (Address::from_low_u64_be(i * 0x1_0000_0001), U256::from(i)))
+            .collect();
+        let options = PrefixSearchOptions {
+            min_len: 1,
+            max_results: usize::MAX,
+        };
+        let scan = |tree: &MerkleTree, prefix: &str| {
+            let mut matches: Vec<Entry> = tree
+                .entries()
+                .iter()
+                .filter(|entry| format!("{:#x}", entry.account).starts_with(prefix))
+                .copied()
+                .collect();
+            matches.sort();
+            matches
+        };
+        let check = |tree: &MerkleTree| {
+            for prefix in [
+                "0",
+                "00000000000000000000000000000000",
+                "0000000000000000000000000000000001",
+            ] {
+                let found: Vec<Entry> = tree
+                    .find_by_address_prefix_with(prefix, &options)
+                    .unwrap()
+                    .into_iter()
+                    .copied()
+                    .collect();
+                assert_eq!(found, scan(tree, &format!("0x{}", prefix)), "{}", prefix);
+            }
+        };
+
+        let mut tree = MerkleTree::new(data.clone());
+        check(&tree);
+        for (account, _) in data.iter().step_by(37) {
+            tree.set_amount(*account, U256::from(7_777)).unwrap();
+            check(&tree);
+        }
+        check(&tree.truncate(120));
+    }
+}
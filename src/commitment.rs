@@ -0,0 +1,88 @@
+use crate::MerkleTree;
+use ethers::core::utils::keccak256;
+use ethers::types::H256;
+
+impl MerkleTree {
+    /// Computes a hash committing to every leaf of the tree together with its proof.
+    ///
+    /// The root already commits to the leaves, but not to the proofs handed out for them:
+    /// a distributed proof file can be altered without changing the root, and the altered
+    /// proofs only fail when claimed. Anchoring this hash next to the root lets anyone
+    /// check the whole file at once with `commit_proofs`.
+    ///
+    /// # Returns
+    ///
+    /// The commitment to the `(leaf, proof)` pairs in the order of the leaves in the tree.
+    pub fn proofs_commitment(&self) -> H256 {
+        let pairs: Vec<(H256, Vec<H256>)> = (0..self.leaves)
+            .map(|index| (self.elements[index], self.proof_at(index)))
+            .collect();
+        Self::commit_proofs(&pairs)
+    }
+
+    /// Computes the commitment of `proofs_commitment` from a list of leaves and proofs.
+    ///
+    /// Each pair is hashed as `keccak256(leaf ++ proof[0] ++ proof[1] ++ ...)`, and the
+    /// commitment is `keccak256(count ++ digest[0] ++ digest[1] ++ ...)` with the count of
+    /// pairs as a big-endian u64.
+    ///
+    /// # Arguments
+    ///
+    /// * `pairs` - The leaves and their proofs, in the order of the leaves in the tree:
+    ///   `sorted_leaves()`, or the input order for trees built with `new_ordered`.
+    ///
+    /// # Returns
+    ///
+    /// The commitment to the pairs, which changes if any leaf or proof hash changes.
+    pub fn commit_proofs(pairs: &[(H256, Vec<H256>)]) -> H256 {
+        let mut digests = Vec::with_capacity(8 + 32 * pairs.len());
+        digests.extend_from_slice(&(pairs.len() as u64).to_be_bytes());
+        for (leaf, proof) in pairs {
+            let mut bytes = Vec::with_capacity(32 * (proof.len() + 1));
+            bytes.extend_from_slice(leaf.as_bytes());
+            for hash in proof {
+                bytes.extend_from_slice(hash.as_bytes());
+            }
+            digests.extend_from_slice(&keccak256(bytes));
+        }
+        H256(keccak256(digests))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ethers::types::{Address, U256};
+
+    #[test]
+    fn altering_one_proof_changes_the_commitment() {
+        let tree = MerkleTree::new(
+            (1..=7u64)
+                .map(|i| (Address::from_low_u64_be(i), U256::from(i)))
+                .collect(),
+        );
+        let mut exported: Vec<(H256, Vec<H256>)> = tree
+            .sorted_leaves()
+            .iter()
+            .map(|leaf| (*leaf, tree.get_proof(*leaf).unwrap()))
+            .collect();
+        let commitment = tree.proofs_commitment();
+        assert_eq!(MerkleTree::commit_proofs(&exported), commitment);
+
+        exported[3].1[1].0[0] ^= 1;
+        assert_ne!(MerkleTree::commit_proofs(&exported), commitment);
+        exported[3].1[1].0[0] ^= 1;
+        exported[6].1.push(H256::zero());
+        assert_ne!(MerkleTree::commit_proofs(&exported), commitment);
+        exported[6].1.pop();
+        exported.swap(0, 1);
+        assert_ne!(MerkleTree::commit_proofs(&exported), commitment);
+
+        let ordered = MerkleTree::new_ordered(tree.sorted_leaves().iter().rev().copied().collect());
+        assert_ne!(ordered.proofs_commitment(), commitment);
+        assert_eq!(
+            MerkleTree::new_ordered(Vec::new()).proofs_commitment(),
+            MerkleTree::commit_proofs(&[])
+        );
+    }
+}
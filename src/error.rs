@@ -0,0 +1,226 @@
+use crate::{AmountWidth, Entry};
+use ethers::types::{Address, H256, U256};
+use std::fmt;
+
+/// Errors returned by the fallible Merkle tree operations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MerkleError {
+    /// The operation requires at least one leaf.
+    EmptyTree,
+    /// A caller-provided buffer is too small to hold every node of the tree.
+    ScratchTooSmall { required: usize, provided: usize },
+    /// Bytes could not be validated as an archived tree.
+    InvalidArchive(String),
+    /// Bytes could not be read as a complete tree snapshot.
+    InvalidSnapshot(String),
+    /// A snapshot failed its integrity check: `expected` is the stored checksum and
+    /// `found` the checksum of the bytes actually read.
+    SnapshotCorrupted { expected: H256, found: H256 },
+    /// An address appears more than once and the duplicate policy rejects it.
+    DuplicateAddress(Address),
+    /// A 32-byte account id appears more than once in the data of a `MerkleTree32`.
+    DuplicateAccountId(H256),
+    /// Summing the amounts of an address overflowed `U256`.
+    AmountOverflow(Address),
+    /// Leaves given as sorted are not strictly ascending at this index.
+    UnsortedLeaves(usize),
+    /// An address prefix has fewer hex digits than the search requires.
+    PrefixTooShort { min: usize, len: usize },
+    /// An amount string could not be parsed.
+    InvalidAmount(String),
+    /// A row of an imported file could not be parsed.
+    InvalidRow { row: usize, reason: String },
+    /// An entry stream failed; `index` is the number of entries received before the error.
+    Stream { index: usize, reason: String },
+    /// The operation needs the entries behind the leaves, but the tree was built from hashes.
+    NoEntries,
+    /// A call to a node failed or returned unexpected data.
+    Rpc(String),
+    /// The hash is an internal node of the tree, not a leaf.
+    NotALeaf(H256),
+    /// A vesting schedule cannot split amounts.
+    InvalidSchedule(String),
+    /// The proof hash at this index is zero, which points to an uninitialized slot.
+    ZeroProofElement(usize),
+    /// A decoded message holds a field of the wrong length or range.
+    InvalidMessage(String),
+    /// No retained entry belongs to the address.
+    UnknownAddress(Address),
+    /// The proof does not lead from the leaf to the root.
+    InvalidProof,
+    /// A claim holds more than the per-claim cap allows.
+    AmountExceedsCap { amount: U256, cap: U256 },
+    /// Two trees combined by one operation were built with different options.
+    OptionsMismatch,
+    /// A checkpointed build was stopped by its cancellation hook after consuming this many entries.
+    Cancelled { entries: usize },
+    /// A build checkpoint could not be written, or is missing, damaged or stale.
+    Checkpoint(String),
+    /// An amount transform failed for the entry of this address.
+    AmountMapping { account: Address, reason: String },
+    /// A state dump or `alloc` section is not valid JSON of the expected shape.
+    InvalidAlloc(String),
+    /// The account under this key of a state dump or `alloc` section is malformed.
+    InvalidAllocEntry { key: String, reason: String },
+    /// A proof about to be handed out does not verify against the root of its tree.
+    SelfCheckFailed { index: usize, leaf: H256 },
+    /// A proof leads to another root than the expected one.
+    RootMismatch { expected: H256, computed: H256 },
+    /// The amount of this account does not fit in the amount width of a packed leaf.
+    AmountOutOfRange {
+        account: Address,
+        width: AmountWidth,
+    },
+}
+
+impl fmt::Display for MerkleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MerkleError::EmptyTree => write!(f, "the tree has no leaves"),
+            MerkleError::ScratchTooSmall { required, provided } => write!(
+                f,
+                "scratch buffer holds {} nodes but {} are required",
+                provided, required
+            ),
+            MerkleError::InvalidArchive(reason) => write!(f, "invalid tree archive: {}", reason),
+            MerkleError::InvalidSnapshot(reason) => write!(f, "invalid tree snapshot: {}", reason),
+            MerkleError::SnapshotCorrupted { expected, found } => write!(
+                f,
+                "corrupted tree snapshot: checksum {:?} does not match stored {:?}",
+                found, expected
+            ),
+            MerkleError::DuplicateAddress(account) => {
+                write!(f, "address {:?} appears more than once", account)
+            }
+            MerkleError::DuplicateAccountId(account) => {
+                write!(f, "account id {:?} appears more than once", account)
+            }
+            MerkleError::AmountOverflow(account) => {
+                write!(f, "total amount of {:?} overflows uint256", account)
+            }
+            MerkleError::UnsortedLeaves(index) => {
+                write!(f, "leaf {} is not greater than the previous leaf", index)
+            }
+            MerkleError::PrefixTooShort { min, len } => write!(
+                f,
+                "address prefix has {} hex digits but at least {} are required",
+                len, min
+            ),
+            MerkleError::InvalidAmount(reason) => write!(f, "invalid amount: {}", reason),
+            MerkleError::InvalidRow { row, reason } => write!(f, "row {}: {}", row, reason),
+            MerkleError::Stream { index, reason } => {
+                write!(f, "entry stream failed after {} entries: {}", index, reason)
+            }
+            MerkleError::NoEntries => write!(f, "the tree has no retained entries"),
+            MerkleError::Rpc(reason) => write!(f, "rpc call failed: {}", reason),
+            MerkleError::NotALeaf(hash) => {
+                write!(f, "{:?} is an internal node, not a leaf", hash)
+            }
+            MerkleError::InvalidSchedule(reason) => write!(f, "invalid schedule: {}", reason),
+            MerkleError::ZeroProofElement(index) => write!(f, "proof hash {} is zero", index),
+            MerkleError::InvalidMessage(reason) => write!(f, "invalid message: {}", reason),
+            MerkleError::UnknownAddress(account) => {
+                write!(f, "the tree has no entry for {:?}", account)
+            }
+            MerkleError::InvalidProof => write!(f, "the proof does not match the root"),
+            MerkleError::AmountExceedsCap { amount, cap } => {
+                write!(f, "claim amount {} exceeds the cap of {}", amount, cap)
+            }
+            MerkleError::OptionsMismatch => {
+                write!(f, "the trees were built with different options")
+            }
+            MerkleError::Cancelled { entries } => {
+                write!(f, "build cancelled after {} entries", entries)
+            }
+            MerkleError::Checkpoint(reason) => write!(f, "invalid checkpoint: {}", reason),
+            MerkleError::AmountMapping { account, reason } => {
+                write!(f, "cannot map the amount of {:?}: {}", account, reason)
+            }
+            MerkleError::InvalidAlloc(reason) => write!(f, "invalid alloc: {}", reason),
+            MerkleError::InvalidAllocEntry { key, reason } => {
+                write!(f, "invalid alloc entry {:?}: {}", key, reason)
+            }
+            MerkleError::SelfCheckFailed { index, leaf } => write!(
+                f,
+                "self-check failed: the proof of leaf {} ({:?}) does not verify",
+                index, leaf
+            ),
+            MerkleError::RootMismatch { expected, computed } => write!(
+                f,
+                "the proof leads to root {:?}, expected {:?}",
+                computed, expected
+            ),
+            MerkleError::AmountOutOfRange { account, width } => {
+                write!(f, "amount of {:?} does not fit in {}", account, width)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MerkleError {}
+
+/// Errors returned by [`TreeRegistry`](crate::TreeRegistry) lookups.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegistryError {
+    /// No campaign is registered under the ID.
+    UnknownCampaign(String),
+    /// The campaign exists but has no entry for the address.
+    UnknownAddress { campaign: String, account: Address },
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegistryError::UnknownCampaign(id) => write!(f, "unknown campaign {:?}", id),
+            RegistryError::UnknownAddress { campaign, account } => {
+                write!(f, "campaign {:?} has no entry for {:?}", campaign, account)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RegistryError {}
+
+/// Errors returned by [`verify_split`](crate::verify_split) when parts do not conserve the
+/// entries of the original tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SplitError {
+    /// A tree was built from hashes and retains no entries to compare.
+    NoEntries,
+    /// The part at this index was built with other options than the original.
+    OptionsMismatch { part: usize },
+    /// The entry of the original tree is in no part.
+    MissingEntry(Entry),
+    /// The entry of the part at this index is not in the original, or is already in
+    /// another part.
+    UnexpectedEntry { part: usize, entry: Entry },
+    /// The parts add up to another total; `None` stands for a sum overflowing `U256`.
+    TotalMismatch {
+        expected: Option<U256>,
+        found: Option<U256>,
+    },
+}
+
+impl fmt::Display for SplitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SplitError::NoEntries => write!(f, "a tree has no retained entries"),
+            SplitError::OptionsMismatch { part } => {
+                write!(f, "part {} was built with different options", part)
+            }
+            SplitError::MissingEntry(entry) => {
+                write!(f, "{:?} of {:?} is in no part", entry.amount, entry.account)
+            }
+            SplitError::UnexpectedEntry { part, entry } => write!(
+                f,
+                "part {} holds {:?} of {:?}, which is not in the original or in two parts",
+                part, entry.amount, entry.account
+            ),
+            SplitError::TotalMismatch { expected, found } => {
+                write!(f, "parts add up to {:?} instead of {:?}", found, expected)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SplitError {}
@@ -0,0 +1,498 @@
+//! JSON exports for claim frontends, available with the `serde` feature.
+
+use crate::{AmountWidth, EntryScheme, ExportOptions, MerkleError, MerkleTree, StandardMerkleTree};
+use ethers::types::{Address, H256, U256};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+impl MerkleTree {
+    /// Exports the claims with the keys of OpenZeppelin's `StandardMerkleTree` claims.
+    ///
+    /// Every retained entry becomes an object with OZ's `value` and `proof` keys:
+    ///
+    /// ```json
+    /// { "value": ["0x…address", "1000"], "proof": ["0x…", "0x…"] }
+    /// ```
+    ///
+    /// `value` holds the lowercase address and the decimal amount string, matching the
+    /// `["address", "uint256"]` leaf encoding, and every proof verifies against this
+    /// tree's root with OZ's `MerkleProof.verify`. OZ's own dumps also hold a `treeIndex`,
+    /// the position of the leaf in their flat tree array; this tree has another layout,
+    /// and for most leaf counts another root, so it has no such index and none is
+    /// exported. For claims identical to those of `StandardMerkleTree.of`, export a
+    /// `StandardMerkleTree` instead.
+    ///
+    /// Trees of packed leaves, built with `new_packed` or in `LeafMode::PackedSingle`, add
+    /// a `"packedLeaf": ["address", "uint96"]` key naming the width of the amount, so the
+    /// file says how its leaves are hashed.
+    ///
+    /// # Returns
+    ///
+    /// A JSON array of claims ordered by address, empty if the tree has no retained entries.
+    pub fn export_oz_claims(&self) -> Value {
+        self.export_oz_claims_with(&ExportOptions::default())
+    }
+
+    /// Exports the claims with OpenZeppelin's keys, in a chosen order.
+    ///
+    /// The output is deterministic: the same distribution exports to byte-identical JSON
+    /// whatever order the entries were given in. Claims follow `options.sort_by`, and
+    /// object keys are always serialized in alphabetical order.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - The order of the claims.
+    ///
+    /// # Returns
+    ///
+    /// A JSON array of claims, empty if the tree has no retained entries.
+    pub fn export_oz_claims_with(&self, options: &ExportOptions) -> Value {
+        let claims = self
+            .claim_records_with(options)
+            .into_iter()
+            .map(|record| {
+                let mut claim = json!({
+                    "value": [format!("{:?}", record.account), record.amount.to_string()],
+                    "proof": record
+                        .proof
+                        .as_slice()
+                        .iter()
+                        .map(|hash| format!("{:?}", hash))
+                        .collect::<Vec<_>>(),
+                });
+                if let Some(width) = self.config.amount_width {
+                    claim["packedLeaf"] = json!(["address", width.to_string()]);
+                }
+                claim
+            })
+            .collect();
+        Value::Array(claims)
+    }
+}
+
+impl StandardMerkleTree {
+    /// Exports the claims in the per-claim shape of OpenZeppelin's `StandardMerkleTree`.
+    ///
+    /// Every value becomes an object with OZ's exact keys, in the order the values were
+    /// given:
+    ///
+    /// ```json
+    /// { "value": ["0x…address", "1000"], "treeIndex": 3, "proof": ["0x…", "0x…"] }
+    /// ```
+    ///
+    /// `treeIndex` is the index of the leaf in `tree()` and `proof` is OZ's `getProof` of
+    /// it, so the claims are those of `StandardMerkleTree.of` for the same values and
+    /// verify against `root()`. A value given twice has two claims, like in OZ.
+    ///
+    /// # Returns
+    ///
+    /// A JSON array with one claim per value.
+    pub fn export_oz_claims(&self) -> Value {
+        let claims = self
+            .values()
+            .iter()
+            .map(|value| {
+                json!({
+                    "value": [format!("{:?}", value.value.account), value.value.amount.to_string()],
+                    "treeIndex": value.tree_index,
+                    "proof": self
+                        .proof_at(value.tree_index)
+                        .iter()
+                        .map(|hash| format!("{:?}", hash))
+                        .collect::<Vec<_>>(),
+                })
+            })
+            .collect();
+        Value::Array(claims)
+    }
+}
+
+/// Why a claim of an audited claims file was rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuditFailureReason {
+    /// The claim is not an object with an address and amount `value` and a `proof`.
+    Malformed(String),
+    /// The proof folds to another root than the on-chain one.
+    RootMismatch { computed_root: H256 },
+    /// The claim repeats the leaf of an earlier claim, which would be claimed only once.
+    Duplicate { first: usize },
+}
+
+/// A claim of an audited claims file that failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditFailure {
+    /// The position of the claim in the file.
+    pub index: usize,
+    /// The address of the claim, if it could be read.
+    pub account: Option<Address>,
+    /// Why the claim was rejected.
+    pub reason: AuditFailureReason,
+}
+
+/// The outcome of `audit_distribution`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct AuditReport {
+    /// The number of claims in the file.
+    pub claims: usize,
+    /// The number of claims whose proof verifies against the on-chain root.
+    pub verified: usize,
+    /// The sum of the amounts of the verified claims, saturating at `U256::MAX`.
+    pub total_amount: U256,
+    /// Every claim that failed, in file order.
+    pub failures: Vec<AuditFailure>,
+}
+
+impl AuditReport {
+    /// Returns whether every claim of the file verified.
+    pub fn is_clean(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// Verifies every claim of an exported claims file against the root deployed on chain,
+/// as a last check before launch.
+///
+/// The file is a JSON array in the shape of `export_oz_claims`. Each leaf is recomputed
+/// from the claim's `value` with `scheme` and its proof folded with the scheme's sorted
+/// pairs, like the distributor does on chain, so any tampered address, amount or proof
+/// hash shows up as a failure. A claim whose `packedLeaf` key names another width than
+/// the scheme is malformed. A `treeIndex`, as in the exports of `StandardMerkleTree`, is
+/// not needed and ignored.
+///
+/// # Arguments
+///
+/// * `claims_json` - The claims file.
+/// * `onchain_root` - The root stored by the distributor contract.
+/// * `scheme` - How the distribution hashes its leaves and pairs, i.e. the
+///   `MerkleTree::entry_scheme` of the tree it was exported from; the default is the
+///   scheme of `MerkleTree::new` and OpenZeppelin's `StandardMerkleTree`.
+///
+/// # Returns
+///
+/// A `Result` containing the report, or `MerkleError::InvalidMessage` if the file is not
+/// a JSON array.
+pub fn audit_distribution(
+    claims_json: &str,
+    onchain_root: H256,
+    scheme: &EntryScheme,
+) -> Result<AuditReport, MerkleError> {
+    let claims: Vec<Value> = serde_json::from_str(claims_json)
+        .map_err(|err| MerkleError::InvalidMessage(format!("claims file: {}", err)))?;
+    let mut report = AuditReport {
+        claims: claims.len(),
+        ..Default::default()
+    };
+    let mut first_claims: HashMap<H256, usize> = HashMap::new();
+    for (index, claim) in claims.iter().enumerate() {
+        let account = claim["value"][0]
+            .as_str()
+            .and_then(|account| Address::from_str(account).ok());
+        let reason = match parse_claim(claim, scheme) {
+            Err(reason) => AuditFailureReason::Malformed(reason),
+            Ok((amount, leaf, proof)) => {
+                let computed_root =
+                    MerkleTree::process_proof_with(leaf, &proof, scheme.node_sep.as_bytes());
+                if computed_root != onchain_root {
+                    AuditFailureReason::RootMismatch { computed_root }
+                } else if let Some(&first) = first_claims.get(&leaf) {
+                    AuditFailureReason::Duplicate { first }
+                } else {
+                    first_claims.insert(leaf, index);
+                    report.verified += 1;
+                    report.total_amount = report.total_amount.saturating_add(amount);
+                    continue;
+                }
+            }
+        };
+        report.failures.push(AuditFailure {
+            index,
+            account,
+            reason,
+        });
+    }
+    Ok(report)
+}
+
+/// Reads a claim and hashes its leaf, returning its amount, leaf and proof.
+fn parse_claim(claim: &Value, scheme: &EntryScheme) -> Result<(U256, H256, Vec<H256>), String> {
+    let account = claim["value"][0].as_str().ok_or("missing address")?;
+    let account =
+        Address::from_str(account).map_err(|_| format!("invalid address {:?}", account))?;
+    let amount = claim["value"][1].as_str().ok_or("missing amount")?;
+    let amount = U256::from_dec_str(amount).map_err(|_| format!("invalid amount {:?}", amount))?;
+    let width = match &claim["packedLeaf"] {
+        Value::Null => None,
+        encoding => {
+            let width = match encoding.as_array().map(Vec::as_slice) {
+                Some([field, width]) if field == "address" => width
+                    .as_str()
+                    .and_then(|width| AmountWidth::from_str(width).ok()),
+                _ => None,
+            }
+            .ok_or_else(|| format!("invalid packed leaf {}", encoding))?;
+            Some(width)
+        }
+    };
+    if width != scheme.amount_width {
+        return Err(format!(
+            "packed leaf {} does not match the scheme",
+            claim["packedLeaf"]
+        ));
+    }
+    let leaf = scheme
+        .hash_entry((account, amount))
+        .map_err(|err| err.to_string())?;
+    let proof = claim["proof"]
+        .as_array()
+        .ok_or("missing proof")?
+        .iter()
+        .map(|hash| {
+            hash.as_str()
+                .and_then(|hash| H256::from_str(hash).ok())
+                .ok_or_else(|| format!("invalid proof hash {}", hash))
+        })
+        .collect::<Result<_, _>>()?;
+    Ok((amount, leaf, proof))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ExportOrder, StandardMerkleTree};
+
+    fn proof_of(claim: &Value) -> Vec<H256> {
+        claim["proof"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|hash| H256::from_str(hash.as_str().unwrap()).unwrap())
+            .collect()
+    }
+
+    fn leaf_of(claim: &Value) -> H256 {
+        let account = Address::from_str(claim["value"][0].as_str().unwrap()).unwrap();
+        let amount = U256::from_dec_str(claim["value"][1].as_str().unwrap()).unwrap();
+        MerkleTree::hash_node((account, amount))
+    }
+
+    #[test]
+    fn oz_claims_use_oz_keys_and_verify() {
+        // five leaves, a count for which OZ's layout and root differ from this tree's
+        let data: Vec<(Address, U256)> = (1..=5u64)
+            .map(|i| (Address::from_low_u64_be(i * 7), U256::exp10(15) * i))
+            .collect();
+        let tree = MerkleTree::new(data.clone());
+        let root = tree.get_root().unwrap();
+        assert_ne!(StandardMerkleTree::of(data).unwrap().root(), root);
+        let claims = tree.export_oz_claims();
+        let claims = claims.as_array().unwrap();
+        assert_eq!(claims.len(), 5);
+
+        for claim in claims {
+            let mut keys: Vec<&String> = claim.as_object().unwrap().keys().collect();
+            keys.sort();
+            assert_eq!(keys, ["proof", "value"]);
+            // MerkleProof.verify folds with sorted pairs, like process_proof
+            assert_eq!(
+                MerkleTree::process_proof(leaf_of(claim), &proof_of(claim)),
+                root
+            );
+        }
+        assert_eq!(claims[0]["value"][1], "1000000000000000");
+    }
+
+    #[test]
+    fn standard_claims_match_openzeppelin() {
+        let mut data: Vec<(Address, U256)> = (1..=5u64)
+            .map(|i| (Address::from_low_u64_be(i), U256::exp10(18) * i))
+            .collect();
+        let tree = StandardMerkleTree::of(data.clone()).unwrap();
+        let claims = tree.export_oz_claims();
+        let claims = claims.as_array().unwrap();
+        let tree_indices: Vec<u64> = claims
+            .iter()
+            .map(|claim| claim["treeIndex"].as_u64().unwrap())
+            .collect();
+        // OZ's treeIndex for these values, see `standard::test`
+        assert_eq!(tree_indices, [5, 8, 4, 6, 7]);
+        for (claim, leaf_data) in claims.iter().zip(&data) {
+            let mut keys: Vec<&String> = claim.as_object().unwrap().keys().collect();
+            keys.sort();
+            assert_eq!(keys, ["proof", "treeIndex", "value"]);
+            let leaf = leaf_of(claim);
+            assert_eq!(leaf, MerkleTree::hash_node(*leaf_data));
+            assert_eq!(
+                tree.tree()[claim["treeIndex"].as_u64().unwrap() as usize],
+                leaf
+            );
+            assert!(StandardMerkleTree::verify_proof(
+                leaf,
+                &proof_of(claim),
+                tree.root()
+            ));
+        }
+
+        // a repeated value keeps a claim, and a leaf, of its own
+        data.push(data[1]);
+        let repeated = StandardMerkleTree::of(data).unwrap();
+        let claims = repeated.export_oz_claims();
+        let claims = claims.as_array().unwrap();
+        assert_eq!(claims.len(), 6);
+        assert_eq!(claims[1]["value"], claims[5]["value"]);
+        assert_ne!(claims[1]["treeIndex"], claims[5]["treeIndex"]);
+        for claim in claims {
+            let tree_index = claim["treeIndex"].as_u64().unwrap() as usize;
+            assert_eq!(repeated.tree()[tree_index], leaf_of(claim));
+            assert!(StandardMerkleTree::verify_proof(
+                leaf_of(claim),
+                &proof_of(claim),
+                repeated.root()
+            ));
+        }
+    }
+
+    #[test]
+    fn exports_are_byte_identical_whatever_the_input_order() {
+        let data: Vec<(Address, U256)> = (1..=9u64)
+            .map(|i| (Address::from_low_u64_be(i * 13), U256::from(i)))
+            .collect();
+        let tree = MerkleTree::new(data.clone());
+        let shuffled = MerkleTree::new(data.into_iter().rev().collect());
+
+        for sort_by in [ExportOrder::Address, ExportOrder::Index] {
+            let options = ExportOptions { sort_by };
+            let first = serde_json::to_string(&tree.export_oz_claims_with(&options)).unwrap();
+            let second = serde_json::to_string(&tree.export_oz_claims_with(&options)).unwrap();
+            let other = serde_json::to_string(&shuffled.export_oz_claims_with(&options)).unwrap();
+            assert_eq!(first, second);
+            assert_eq!(first, other);
+            assert!(first.starts_with(r#"[{"proof":["#));
+        }
+
+        let by_index = tree.export_oz_claims_with(&ExportOptions {
+            sort_by: ExportOrder::Index,
+        });
+        let leaves: Vec<H256> = by_index.as_array().unwrap().iter().map(leaf_of).collect();
+        assert_eq!(leaves, tree.sorted_leaves());
+    }
+
+    #[test]
+    fn audit_flags_tampered_claims() {
+        let data: Vec<(Address, U256)> = (1..=5u64)
+            .map(|i| (Address::from_low_u64_be(i), U256::exp10(18) * i))
+            .collect();
+        let tree = MerkleTree::new(data);
+        let root = tree.get_root().unwrap();
+        let scheme = EntryScheme::default();
+        let mut claims = tree.export_oz_claims();
+        let clean = audit_distribution(&claims.to_string(), root, &scheme).unwrap();
+        assert!(clean.is_clean());
+        assert_eq!(clean.verified, 5);
+        assert_eq!(clean.total_amount, U256::exp10(18) * 15);
+
+        claims[2]["value"][1] = json!("4000000000000000000");
+        let duplicate = claims[0].clone();
+        let list = claims.as_array_mut().unwrap();
+        list.push(duplicate);
+        list.push(json!({ "value": ["0x01", "1"], "proof": [] }));
+        let report = audit_distribution(&claims.to_string(), root, &scheme).unwrap();
+
+        let tampered = MerkleTree::hash_node((
+            Address::from_low_u64_be(3),
+            U256::from_dec_str("4000000000000000000").unwrap(),
+        ));
+        let proof = tree
+            .get_proof(MerkleTree::hash_node((
+                Address::from_low_u64_be(3),
+                U256::exp10(18) * 3,
+            )))
+            .unwrap();
+        assert_eq!(report.claims, 7);
+        assert_eq!(report.verified, 4);
+        assert_eq!(report.total_amount, U256::exp10(18) * 12);
+        assert_eq!(
+            report.failures,
+            [
+                AuditFailure {
+                    index: 2,
+                    account: Some(Address::from_low_u64_be(3)),
+                    reason: AuditFailureReason::RootMismatch {
+                        computed_root: MerkleTree::process_proof(tampered, &proof)
+                    },
+                },
+                AuditFailure {
+                    index: 5,
+                    account: Some(Address::from_low_u64_be(1)),
+                    reason: AuditFailureReason::Duplicate { first: 0 },
+                },
+                AuditFailure {
+                    index: 6,
+                    account: None,
+                    reason: AuditFailureReason::Malformed("invalid address \"0x01\"".to_string()),
+                },
+            ]
+        );
+        assert!(audit_distribution("{}", root, &scheme).is_err());
+    }
+
+    #[test]
+    fn packed_exports_audit_at_their_width() {
+        let data: Vec<(Address, U256)> = (1..=4u64)
+            .map(|i| (Address::from_low_u64_be(i), U256::exp10(18) * i))
+            .collect();
+        let tree = MerkleTree::new_packed(data, AmountWidth::U96).unwrap();
+        let root = tree.get_root().unwrap();
+        let scheme = tree.entry_scheme();
+        let mut claims = tree.export_oz_claims();
+        assert_eq!(claims[0]["packedLeaf"], json!(["address", "uint96"]));
+        let report = audit_distribution(&claims.to_string(), root, &scheme).unwrap();
+        assert!(report.is_clean());
+        assert_eq!(report.verified, 4);
+
+        let standard = audit_distribution(&claims.to_string(), root, &EntryScheme::default());
+        assert_eq!(standard.unwrap().verified, 0);
+
+        claims[1]["value"][1] = json!((U256::one() << 100).to_string());
+        claims[2]["packedLeaf"] = json!(["address", "uint95"]);
+        claims[3]["packedLeaf"] = json!(["address", "uint64"]);
+        let report = audit_distribution(&claims.to_string(), root, &scheme).unwrap();
+        assert_eq!(report.verified, 1);
+        let reasons: Vec<_> = report.failures.into_iter().map(|f| f.reason).collect();
+        assert_eq!(
+            reasons,
+            [
+                AuditFailureReason::Malformed(format!(
+                    "amount of {:?} does not fit in uint96",
+                    Address::from_low_u64_be(2)
+                )),
+                AuditFailureReason::Malformed(
+                    "invalid packed leaf [\"address\",\"uint95\"]".to_string()
+                ),
+                AuditFailureReason::Malformed(
+                    "packed leaf [\"address\",\"uint64\"] does not match the scheme".to_string()
+                ),
+            ]
+        );
+        let plain = MerkleTree::new(vec![(Address::zero(), U256::one())]);
+        assert!(plain.export_oz_claims()[0].get("packedLeaf").is_none());
+    }
+
+    #[test]
+    fn audit_follows_chain_ids_and_separators() {
+        let data: Vec<(Address, U256)> = (1..=6u64)
+            .map(|i| (Address::from_low_u64_be(i), U256::from(i)))
+            .collect();
+        let chained = MerkleTree::new_for_chain(data.clone(), 137);
+        let separated = MerkleTree::new_with_node_sep(data, crate::NodeSep(vec![0x01]));
+        for tree in [chained, separated] {
+            let root = tree.get_root().unwrap();
+            let claims = tree.export_oz_claims().to_string();
+            let report = audit_distribution(&claims, root, &tree.entry_scheme()).unwrap();
+            assert!(report.is_clean());
+            assert_eq!(report.verified, 6);
+            let standard = audit_distribution(&claims, root, &EntryScheme::default()).unwrap();
+            assert_eq!(standard.verified, 0);
+        }
+    }
+}
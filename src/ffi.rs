@@ -0,0 +1,272 @@
+//! Kotlin and Swift bindings for claim apps, available with the `uniffi` feature.
+//!
+//! The exported functions take and return strings so mobile code never handles raw
+//! bytes: addresses and hashes are `0x` hex, amounts are decimal strings of base units.
+//! Every input is validated and reported as a [`ClaimError`], which the bindings raise
+//! as a `ClaimException` in Kotlin and a thrown `ClaimError` in Swift; nothing panics
+//! across the FFI boundary.
+//!
+//! Build the library and generate the bindings with:
+//!
+//! ```text
+//! cargo rustc --lib --release --features uniffi --crate-type cdylib
+//! uniffi-bindgen generate --library target/release/liboz_merkle_rs.so --language kotlin --out-dir out
+//! uniffi-bindgen generate --library target/release/liboz_merkle_rs.so --language swift --out-dir out
+//! ```
+
+use crate::MerkleTree;
+use ethers::types::{Address, H256, U256};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Errors raised to Kotlin and Swift callers.
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Error)]
+pub enum ClaimError {
+    /// A string is not a 20-byte `0x` hex address.
+    InvalidAddress { value: String },
+    /// A string is not a decimal amount that fits in a `uint256`.
+    InvalidAmount { value: String },
+    /// A string is not a 32-byte `0x` hex hash.
+    InvalidHash { value: String },
+    /// An allowlist needs at least one entry.
+    EmptyTree,
+}
+
+impl fmt::Display for ClaimError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClaimError::InvalidAddress { value } => write!(f, "invalid address {:?}", value),
+            ClaimError::InvalidAmount { value } => write!(f, "invalid amount {:?}", value),
+            ClaimError::InvalidHash { value } => write!(f, "invalid hash {:?}", value),
+            ClaimError::EmptyTree => write!(f, "the allowlist has no entries"),
+        }
+    }
+}
+
+impl std::error::Error for ClaimError {}
+
+/// An `(address, amount)` pair of an [`AllowList`].
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct AllowListEntry {
+    pub address: String,
+    pub amount: String,
+}
+
+/// A small tree built on the device, for allowlists shipped with the app.
+#[derive(Debug, uniffi::Object)]
+pub struct AllowList {
+    tree: MerkleTree,
+}
+
+#[uniffi::export]
+impl AllowList {
+    /// Builds the tree of the entries, exactly like `MerkleTree::new`.
+    #[uniffi::constructor]
+    pub fn new(entries: Vec<AllowListEntry>) -> Result<Arc<Self>, ClaimError> {
+        if entries.is_empty() {
+            return Err(ClaimError::EmptyTree);
+        }
+        let data = entries
+            .iter()
+            .map(|entry| Ok((parse_address(&entry.address)?, parse_amount(&entry.amount)?)))
+            .collect::<Result<Vec<_>, ClaimError>>()?;
+        Ok(Arc::new(Self {
+            tree: MerkleTree::new(data),
+        }))
+    }
+
+    /// Returns the root as `0x` hex.
+    pub fn root(&self) -> String {
+        // the constructor rejects empty allowlists, so there is always a root
+        format!("{:?}", self.tree.get_root().unwrap_or_default())
+    }
+
+    /// Returns the proof of an entry as `0x` hex hashes, or `None` if it is not in the list.
+    pub fn proof(
+        &self,
+        address: String,
+        amount: String,
+    ) -> Result<Option<Vec<String>>, ClaimError> {
+        let leaf = MerkleTree::hash_node((parse_address(&address)?, parse_amount(&amount)?));
+        Ok(self
+            .tree
+            .get_proof(leaf)
+            .map(|proof| proof.iter().map(|hash| format!("{:?}", hash)).collect()))
+    }
+}
+
+/// Verifies a claim against a root.
+///
+/// # Arguments
+///
+/// * `root_hex` - The root hash, as `0x` hex.
+/// * `address` - The claiming address, as `0x` hex in any letter case.
+/// * `amount_decimal` - The claimed amount in base units, as a decimal string.
+/// * `proof_hex_list` - The proof hashes, as `0x` hex.
+///
+/// # Returns
+///
+/// A `Result` containing whether the proof is valid, or a `ClaimError` for the first
+/// input that cannot be parsed.
+#[uniffi::export]
+pub fn verify(
+    root_hex: String,
+    address: String,
+    amount_decimal: String,
+    proof_hex_list: Vec<String>,
+) -> Result<bool, ClaimError> {
+    let root = parse_hash(&root_hex)?;
+    let leaf = MerkleTree::hash_node((parse_address(&address)?, parse_amount(&amount_decimal)?));
+    let proof = proof_hex_list
+        .iter()
+        .map(|hash| parse_hash(hash))
+        .collect::<Result<Vec<_>, ClaimError>>()?;
+    Ok(MerkleTree::process_proof(leaf, &proof) == root)
+}
+
+/// Computes the leaf hash of a claim, as `0x` hex.
+#[uniffi::export]
+pub fn leaf_hash(address: String, amount: String) -> Result<String, ClaimError> {
+    let leaf = MerkleTree::hash_node((parse_address(&address)?, parse_amount(&amount)?));
+    Ok(format!("{:?}", leaf))
+}
+
+fn parse_address(value: &str) -> Result<Address, ClaimError> {
+    match value.strip_prefix("0x") {
+        Some(digits) if digits.len() == 40 => Address::from_str(digits).ok(),
+        _ => None,
+    }
+    .ok_or_else(|| ClaimError::InvalidAddress {
+        value: value.to_string(),
+    })
+}
+
+fn parse_amount(value: &str) -> Result<U256, ClaimError> {
+    let is_decimal = !value.is_empty() && value.bytes().all(|byte| byte.is_ascii_digit());
+    is_decimal
+        .then(|| U256::from_dec_str(value).ok())
+        .flatten()
+        .ok_or_else(|| ClaimError::InvalidAmount {
+            value: value.to_string(),
+        })
+}
+
+fn parse_hash(value: &str) -> Result<H256, ClaimError> {
+    match value.strip_prefix("0x") {
+        Some(digits) if digits.len() == 64 => H256::from_str(digits).ok(),
+        _ => None,
+    }
+    .ok_or_else(|| ClaimError::InvalidHash {
+        value: value.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entries() -> Vec<AllowListEntry> {
+        (1..=5u64)
+            .map(|i| AllowListEntry {
+                address: format!("{:?}", Address::from_low_u64_be(i)),
+                amount: (i * 1_000).to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn exported_functions_verify_allowlist_proofs() {
+        let list = AllowList::new(entries()).unwrap();
+        let root = list.root();
+        let entry = &entries()[2];
+        let proof = list
+            .proof(entry.address.clone(), entry.amount.clone())
+            .unwrap()
+            .unwrap();
+
+        let expected = MerkleTree::hash_node((Address::from_low_u64_be(3), U256::from(3_000)));
+        assert_eq!(
+            leaf_hash(
+                entry.address.to_uppercase().replace("0X", "0x"),
+                entry.amount.clone()
+            ),
+            Ok(format!("{:?}", expected))
+        );
+        assert_eq!(
+            verify(
+                root.clone(),
+                entry.address.clone(),
+                entry.amount.clone(),
+                proof.clone()
+            ),
+            Ok(true)
+        );
+        assert_eq!(
+            verify(
+                root.clone(),
+                entry.address.clone(),
+                "3001".to_string(),
+                proof.clone()
+            ),
+            Ok(false)
+        );
+        assert_eq!(list.proof(entry.address.clone(), "1".to_string()), Ok(None));
+    }
+
+    #[test]
+    fn leaf_hash_through_scaffolding() {
+        use crate::UniFfiTag;
+        use uniffi::{Lift, Lower, RustCallStatus, RustCallStatusCode};
+
+        let call = |address: &str, amount: &str| {
+            let mut status = RustCallStatus::default();
+            let returned = uniffi_oz_merkle_rs_fn_func_leaf_hash(
+                <String as Lower<UniFfiTag>>::lower(address.to_string()),
+                <String as Lower<UniFfiTag>>::lower(amount.to_string()),
+                &mut status,
+            );
+            (status, returned)
+        };
+
+        let address = format!("{:?}", Address::from_low_u64_be(1));
+        let (status, returned) = call(&address, "1000");
+        assert_eq!(status.code, RustCallStatusCode::Success);
+        assert_eq!(
+            <String as Lift<UniFfiTag>>::try_lift(returned).unwrap(),
+            leaf_hash(address, "1000".to_string()).unwrap()
+        );
+
+        let (status, _) = call("not an address", "1000");
+        assert_eq!(status.code, RustCallStatusCode::Error);
+    }
+
+    #[test]
+    fn invalid_inputs_map_to_errors() {
+        let root = AllowList::new(entries()).unwrap().root();
+        let address = format!("{:?}", Address::from_low_u64_be(1));
+        for (root, address, amount, proof, error) in [
+            ("0x12", &*address, "1", vec![], "hash"),
+            (&*root, "0x1234", "1", vec![], "address"),
+            (&*root, &*address, "-1", vec![], "amount"),
+            (&*root, &*address, "0x10", vec![], "amount"),
+            (&*root, &*address, &"9".repeat(80), vec![], "amount"),
+            (&*root, &*address, "1", vec!["zz".to_string()], "hash"),
+        ] {
+            let result = verify(
+                root.to_string(),
+                address.to_string(),
+                amount.to_string(),
+                proof,
+            );
+            assert!(result
+                .unwrap_err()
+                .to_string()
+                .starts_with(&format!("invalid {}", error)));
+        }
+        assert_eq!(
+            AllowList::new(Vec::new()).unwrap_err(),
+            ClaimError::EmptyTree
+        );
+    }
+}
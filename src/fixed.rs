@@ -0,0 +1,128 @@
+use crate::MerkleTree;
+use ethers::types::{Address, H256, U256};
+
+/// Verification for trees whose depth `D` is known at compile time.
+///
+/// Proofs are `[H256; D]` arrays, so verifying never touches the heap. Proofs of a tree
+/// with `2^D` leaves always hold exactly `D` hashes; smaller trees have shorter proofs for
+/// some leaves, because odd nodes are promoted without a sibling, and don't fit.
+#[derive(Debug, Clone, Copy)]
+pub struct FixedTree<const D: usize>;
+
+impl<const D: usize> FixedTree<D> {
+    /// The number of leaves of a full tree of depth `D`.
+    pub const MAX_LEAVES: usize = 1 << D;
+
+    /// Verifies a fixed-length proof for a given leaf.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf` - The hash of the leaf to be verified.
+    /// * `proof` - The `D` sibling hashes from the leaf up.
+    /// * `root` - The root hash of the Merkle tree.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the proof is valid for the given leaf and root hash.
+    pub fn verify(leaf: H256, proof: &[H256; D], root: H256) -> bool {
+        MerkleTree::process_proof(leaf, proof) == root
+    }
+
+    /// Retrieves the proof of a leaf as a fixed-length array.
+    ///
+    /// # Returns
+    ///
+    /// An `Option` containing the proof, or `None` if the leaf is not in the tree or its
+    /// proof does not have exactly `D` hashes.
+    pub fn proof(tree: &MerkleTree, leaf: H256) -> Option<[H256; D]> {
+        tree.get_proof(leaf)?.try_into().ok()
+    }
+}
+
+impl MerkleTree {
+    /// Computes the root of the data padded to a full tree, for verifiers that expect
+    /// `2^k` leaves.
+    ///
+    /// The leaves are hashed, sorted and deduplicated like in `new`, then copies of
+    /// `filler` are appended until the count is the next power of two, so the depth is
+    /// the smallest that fits the data. The fillers stay after the leaves, unsorted.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - A vector containing tuples of addresses and amounts.
+    /// * `filler` - The hash of the padding leaves, often the zero hash.
+    ///
+    /// # Returns
+    ///
+    /// The root of the padded tree; `filler` itself for empty data, padded to one leaf.
+    pub fn padded_root(data: Vec<(Address, U256)>, filler: H256) -> H256 {
+        let mut leaves = Self::hash_entries_sorted(&data, Default::default());
+        leaves.resize(leaves.len().next_power_of_two(), filler);
+        let mut layer = leaves;
+        while layer.len() > 1 {
+            layer = layer
+                .chunks_exact(2)
+                .map(|pair| Self::hash_pair(&pair[0], &pair[1]))
+                .collect();
+        }
+        layer[0]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn depth_three_verifies_eight_leaf_proofs() {
+        let data: Vec<(Address, U256)> = (1..=8u64)
+            .map(|i| (Address::from_low_u64_be(i), U256::from(i)))
+            .collect();
+        let tree = MerkleTree::new(data.clone());
+        let root = tree.get_root().unwrap();
+        assert_eq!(FixedTree::<3>::MAX_LEAVES, 8);
+
+        for leaf_data in data {
+            let leaf = MerkleTree::hash_node(leaf_data);
+            let proof = FixedTree::<3>::proof(&tree, leaf).unwrap();
+            assert!(FixedTree::<3>::verify(leaf, &proof, root));
+            assert!(!FixedTree::<3>::verify(leaf, &proof, H256::zero()));
+        }
+        let leaf = MerkleTree::hash_node((Address::from_low_u64_be(1), U256::one()));
+        assert_eq!(FixedTree::<4>::proof(&tree, leaf), None);
+    }
+
+    #[test]
+    fn padded_root_pads_to_the_next_power_of_two() {
+        let data: Vec<(Address, U256)> = (1..=3u64)
+            .map(|i| (Address::from_low_u64_be(i), U256::from(i)))
+            .collect();
+        let filler = H256::zero();
+        let leaves = MerkleTree::hash_entries_sorted(&data, Default::default());
+        let explicit = MerkleTree::hash_pair(
+            &MerkleTree::hash_pair(&leaves[0], &leaves[1]),
+            &MerkleTree::hash_pair(&leaves[2], &filler),
+        );
+        let root = MerkleTree::padded_root(data.clone(), filler);
+        assert_eq!(root, explicit);
+        assert_ne!(root, MerkleTree::new(data.clone()).get_root().unwrap());
+
+        // a full tree needs no padding, and a depth-3 verifier accepts 5 padded leaves
+        let full = &data[..2];
+        assert_eq!(
+            MerkleTree::padded_root(full.to_vec(), filler),
+            MerkleTree::new(full.to_vec()).get_root().unwrap()
+        );
+        let five: Vec<(Address, U256)> = (1..=5u64)
+            .map(|i| (Address::from_low_u64_be(i), U256::from(i)))
+            .collect();
+        let mut padded = MerkleTree::hash_entries_sorted(&five, Default::default());
+        padded.resize(8, filler);
+        let tree = MerkleTree::from_sorted_elements_with(padded.clone(), Default::default());
+        let root = MerkleTree::padded_root(five, filler);
+        assert_eq!(tree.get_root(), Some(root));
+        let proof: [H256; 3] = tree.get_proof_at(4).unwrap().try_into().unwrap();
+        assert!(FixedTree::<3>::verify(padded[4], &proof, root));
+        assert_eq!(MerkleTree::padded_root(Vec::new(), filler), filler);
+    }
+}
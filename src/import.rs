@@ -0,0 +1,550 @@
+//! Importers turning balance files into entries for [`MerkleTree::new`](crate::MerkleTree::new).
+//!
+//! Available with the `csv` feature.
+
+use crate::amount::parse_amount;
+use crate::MerkleError;
+use ethers::types::{Address, U256};
+use ethers::utils::to_checksum;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Read;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// A column of a CSV file, selected by its header or by its position.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CsvColumn {
+    /// The column whose header matches the name, ignoring case and surrounding spaces.
+    /// Requires a header row.
+    Name(String),
+    /// The column at this zero-based position.
+    Index(usize),
+}
+
+/// A predicate deciding whether a row of a CSV file is imported.
+pub type CsvRowFilter = Arc<dyn Fn(&csv::StringRecord) -> bool + Send + Sync>;
+
+/// Options for [`from_csv`].
+#[derive(Clone)]
+pub struct CsvOptions {
+    /// Token decimals used to scale human-readable amounts such as `"1250.75"` into base
+    /// units. Hex amounts are always taken as base units. When unset, decimal amounts
+    /// must already be whole base units.
+    pub decimals: Option<u8>,
+    /// Also accept amounts with a `wei`, `gwei` or `ether` unit such as `"1.5 ether"`, as
+    /// parsed by [`Amount::parse_units`](crate::Amount::parse_units). Amounts with a unit
+    /// ignore `decimals`.
+    pub units: bool,
+    /// The column holding the addresses, the first one by default.
+    pub address_column: CsvColumn,
+    /// The column holding the amounts, the second one by default.
+    pub amount_column: CsvColumn,
+    /// The field delimiter, `b','` by default; `b'\t'` reads tab-separated files.
+    pub delimiter: u8,
+    /// Whether the first row is a header, `true` by default.
+    pub has_headers: bool,
+    /// Whether spaces around fields are removed, `true` by default.
+    pub trim: bool,
+    /// Imports only the rows the predicate accepts, given all fields of the row. Rows
+    /// are filtered before their address and amount are parsed.
+    pub filter: Option<CsvRowFilter>,
+    /// Rejects mixed-case addresses that are not valid EIP-55 checksums, `false` by
+    /// default. All-lowercase and all-uppercase addresses carry no checksum and pass.
+    pub verify_checksums: bool,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            decimals: None,
+            units: false,
+            address_column: CsvColumn::Index(0),
+            amount_column: CsvColumn::Index(1),
+            delimiter: b',',
+            has_headers: true,
+            trim: true,
+            filter: None,
+            verify_checksums: false,
+        }
+    }
+}
+
+impl fmt::Debug for CsvOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CsvOptions")
+            .field("decimals", &self.decimals)
+            .field("units", &self.units)
+            .field("address_column", &self.address_column)
+            .field("amount_column", &self.amount_column)
+            .field("delimiter", &(self.delimiter as char))
+            .field("has_headers", &self.has_headers)
+            .field("trim", &self.trim)
+            .field("filter", &self.filter.as_ref().map(|_| ".."))
+            .field("verify_checksums", &self.verify_checksums)
+            .finish()
+    }
+}
+
+/// Reads address and amount rows from a CSV file.
+///
+/// By default the file is comma-separated with a header row, and the address and amount
+/// are its first two columns; `options` selects other columns, delimiters and layouts, and
+/// any other column is ignored. Amounts are either `0x`-prefixed hex or decimal strings,
+/// scaled according to `options.decimals`, or strings with a unit when `options.units` is
+/// set.
+///
+/// # Arguments
+///
+/// * `reader` - The source of the CSV data.
+/// * `options` - The layout of the file and how amounts are interpreted.
+///
+/// # Returns
+///
+/// A `Result` containing the entries in file order, or `MerkleError::InvalidRow` with the
+/// line number of the first row that cannot be parsed, or of the header if a named
+/// column is missing.
+pub fn from_csv<R: Read>(
+    reader: R,
+    options: &CsvOptions,
+) -> Result<Vec<(Address, U256)>, MerkleError> {
+    let mut entries = Vec::new();
+    for (row, _, parsed) in csv_rows(reader, options)? {
+        let entry = parsed.map_err(|(_, reason)| MerkleError::InvalidRow { row, reason })?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Why `ingest_with_report` rejected a row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RowErrorKind {
+    /// The row could not be read, or lacks the address or amount column.
+    Malformed,
+    /// The address is not 20 hex-encoded bytes.
+    BadAddress,
+    /// The amount cannot be parsed or scaled.
+    BadAmount,
+    /// The address mixes cases but is not a valid EIP-55 checksum.
+    ChecksumMismatch,
+    /// The address already appeared on an earlier accepted row.
+    Duplicate,
+}
+
+/// A row rejected by `ingest_with_report`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowError {
+    /// The line number of the row, the header being line 1 if there is one.
+    pub row: usize,
+    /// The fields of the row joined by the delimiter, empty if the row could not be read.
+    pub raw: String,
+    /// The class of the error.
+    pub kind: RowErrorKind,
+    /// A description of the error, as `from_csv` would report it.
+    pub reason: String,
+}
+
+/// The outcome of `ingest_with_report`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct IngestReport {
+    /// The entries of the accepted rows, in file order.
+    pub entries: Vec<(Address, U256)>,
+    /// Every rejected row, in file order.
+    pub errors: Vec<RowError>,
+}
+
+/// Reads a CSV file like `from_csv`, collecting every bad row instead of failing on the
+/// first one.
+///
+/// This is opt-in: callers that build a tree from `entries` accept that the rejected rows
+/// are left out, so the report should be checked or shown first. Besides the errors of
+/// `from_csv`, rows repeating the address of an earlier accepted row are rejected as
+/// duplicates, keeping the first amount.
+///
+/// # Arguments
+///
+/// * `reader` - The source of the CSV data.
+/// * `options` - The layout of the file and how amounts are interpreted.
+///
+/// # Returns
+///
+/// A `Result` containing the accepted entries and the rejected rows, or
+/// `MerkleError::InvalidRow` if the header does not allow reading any row.
+pub fn ingest_with_report<R: Read>(
+    reader: R,
+    options: &CsvOptions,
+) -> Result<IngestReport, MerkleError> {
+    let mut report = IngestReport::default();
+    let mut first_rows: HashMap<Address, usize> = HashMap::new();
+    for (row, raw, parsed) in csv_rows(reader, options)? {
+        let parsed = parsed.and_then(|(account, amount)| match first_rows.entry(account) {
+            Entry::Occupied(first) => Err((
+                RowErrorKind::Duplicate,
+                format!(
+                    "address {:?} already appears on row {}",
+                    account,
+                    first.get()
+                ),
+            )),
+            Entry::Vacant(slot) => {
+                slot.insert(row);
+                Ok((account, amount))
+            }
+        });
+        match parsed {
+            Ok(entry) => report.entries.push(entry),
+            Err((kind, reason)) => report.errors.push(RowError {
+                row,
+                raw,
+                kind,
+                reason,
+            }),
+        }
+    }
+    Ok(report)
+}
+
+type ParsedRow = (
+    usize,
+    String,
+    Result<(Address, U256), (RowErrorKind, String)>,
+);
+
+/// Reads the rows accepted by the filter, each with its line number, raw text and entry.
+fn csv_rows<'a, R: Read + 'a>(
+    reader: R,
+    options: &'a CsvOptions,
+) -> Result<impl Iterator<Item = ParsedRow> + 'a, MerkleError> {
+    let mut csv_reader = csv::ReaderBuilder::new()
+        .trim(if options.trim {
+            csv::Trim::All
+        } else {
+            csv::Trim::None
+        })
+        .delimiter(options.delimiter)
+        .has_headers(options.has_headers)
+        .flexible(true)
+        .from_reader(reader);
+
+    let address_column = column_index(&mut csv_reader, &options.address_column)?;
+    let amount_column = column_index(&mut csv_reader, &options.amount_column)?;
+    let delimiter = (options.delimiter as char).to_string();
+    Ok(csv_reader.into_records().filter_map(move |record| {
+        let record = match record {
+            Ok(record) => record,
+            Err(err) => {
+                let row = err
+                    .position()
+                    .map_or(0, |position| position.line() as usize);
+                let error = (RowErrorKind::Malformed, err.to_string());
+                return Some((row, String::new(), Err(error)));
+            }
+        };
+        if let Some(filter) = &options.filter {
+            if !filter(&record) {
+                return None;
+            }
+        }
+        let row = record
+            .position()
+            .map_or(0, |position| position.line() as usize);
+        let raw = record.iter().collect::<Vec<_>>().join(&delimiter);
+        let parsed = parse_record(&record, address_column, amount_column, options);
+        Some((row, raw, parsed))
+    }))
+}
+
+fn parse_record(
+    record: &csv::StringRecord,
+    address_column: usize,
+    amount_column: usize,
+    options: &CsvOptions,
+) -> Result<(Address, U256), (RowErrorKind, String)> {
+    let (account, amount) = match (record.get(address_column), record.get(amount_column)) {
+        (Some(account), Some(amount)) => (account, amount),
+        _ => {
+            return Err((
+                RowErrorKind::Malformed,
+                "expected an address and an amount".to_string(),
+            ))
+        }
+    };
+    let address = Address::from_str(account).map_err(|_| {
+        (
+            RowErrorKind::BadAddress,
+            format!("invalid address {:?}", account),
+        )
+    })?;
+    if options.verify_checksums {
+        let digits = account.strip_prefix("0x").unwrap_or(account);
+        let mixed = digits.chars().any(|c| c.is_ascii_lowercase())
+            && digits.chars().any(|c| c.is_ascii_uppercase());
+        if mixed && to_checksum(&address, None)[2..] != *digits {
+            return Err((
+                RowErrorKind::ChecksumMismatch,
+                format!("address {:?} has an invalid checksum", account),
+            ));
+        }
+    }
+    let amount = parse_amount(amount, options.decimals, options.units)
+        .map_err(|reason| (RowErrorKind::BadAmount, reason))?;
+    Ok((address, amount))
+}
+
+/// Resolves a column to its position, reading the header row if it is selected by name.
+fn column_index<R: Read>(
+    csv_reader: &mut csv::Reader<R>,
+    column: &CsvColumn,
+) -> Result<usize, MerkleError> {
+    let name = match column {
+        CsvColumn::Index(index) => return Ok(*index),
+        CsvColumn::Name(name) => name,
+    };
+    let invalid = |reason: String| MerkleError::InvalidRow { row: 1, reason };
+    if !csv_reader.has_headers() {
+        return Err(invalid(format!(
+            "column {:?} is selected by name but the file has no header row",
+            name
+        )));
+    }
+    let headers = csv_reader
+        .headers()
+        .map_err(|err| invalid(err.to_string()))?;
+    headers
+        .iter()
+        .position(|header| header.trim().eq_ignore_ascii_case(name.trim()))
+        .ok_or_else(|| invalid(format!("no column named {:?}", name)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn options(decimals: u8) -> CsvOptions {
+        CsvOptions {
+            decimals: Some(decimals),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn scales_amounts_by_decimals() {
+        let file = "address,amount\n\
+                    0x1111111111111111111111111111111111111111,1250.75\n\
+                    0x2222222222222222222222222222222222222222,3\n";
+
+        assert_eq!(
+            from_csv(file.as_bytes(), &options(18)).unwrap(),
+            vec![
+                (
+                    Address::repeat_byte(0x11),
+                    U256::from_dec_str("1250750000000000000000").unwrap()
+                ),
+                (
+                    Address::repeat_byte(0x22),
+                    U256::from_dec_str("3000000000000000000").unwrap()
+                ),
+            ]
+        );
+        assert_eq!(
+            from_csv(file.as_bytes(), &options(6)).unwrap()[0].1,
+            U256::from(1_250_750_000u64)
+        );
+    }
+
+    #[test]
+    fn rejects_excess_precision_with_row() {
+        let file = "address,amount\n\
+                    0x1111111111111111111111111111111111111111,1.5\n\
+                    0x2222222222222222222222222222222222222222,0.0000001\n";
+
+        match from_csv(file.as_bytes(), &options(6)) {
+            Err(MerkleError::InvalidRow { row, reason }) => {
+                assert_eq!(row, 3);
+                assert!(reason.contains("fractional digits"), "{}", reason);
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn mixes_hex_and_decimal_amounts() {
+        let file = "address,amount\n\
+                    0x1111111111111111111111111111111111111111,0x0de0b6b3a7640000\n\
+                    0x2222222222222222222222222222222222222222,1\n";
+        let entries = from_csv(file.as_bytes(), &options(18)).unwrap();
+
+        assert_eq!(entries[0].1, U256::exp10(18));
+        assert_eq!(entries[1].1, U256::exp10(18));
+        assert_eq!(
+            from_csv(file.as_bytes(), &CsvOptions::default()).unwrap()[1].1,
+            U256::one()
+        );
+    }
+
+    #[test]
+    fn accepts_unit_amounts_when_enabled() {
+        let file = "address,amount\n\
+                    0x1111111111111111111111111111111111111111,1.5 ether\n\
+                    0x2222222222222222222222222222222222222222,2500 gwei\n\
+                    0x3333333333333333333333333333333333333333,7\n\
+                    0x4444444444444444444444444444444444444444,0xff\n";
+        let options = CsvOptions {
+            units: true,
+            ..Default::default()
+        };
+        let entries = from_csv(file.as_bytes(), &options).unwrap();
+
+        assert_eq!(entries[0].1, U256::from(1_500_000_000_000_000_000u64));
+        assert_eq!(entries[1].1, U256::from(2_500_000_000_000u64));
+        assert_eq!(entries[2].1, U256::from(7));
+        assert_eq!(entries[3].1, U256::from(255));
+        assert!(matches!(
+            from_csv(file.as_bytes(), &CsvOptions::default()),
+            Err(MerkleError::InvalidRow { row: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn differently_shaped_files_import_the_same_data() {
+        let standard = "address,amount\n\
+                        0x1111111111111111111111111111111111111111,1.5\n\
+                        0x2222222222222222222222222222222222222222,20\n";
+        let reordered = "Note\tTokens\tWallet\n\
+                         first\t1.5\t0x1111111111111111111111111111111111111111\n\
+                         excluded\t99\t0x3333333333333333333333333333333333333333\n\
+                         second\t20\t0x2222222222222222222222222222222222222222\n";
+        let headless = "id;0x1111111111111111111111111111111111111111; 1.5 ;x\n\
+                        id;0x2222222222222222222222222222222222222222; 20 ;y\n";
+
+        let by_name = CsvOptions {
+            decimals: Some(6),
+            address_column: CsvColumn::Name("wallet".to_string()),
+            amount_column: CsvColumn::Name("TOKENS".to_string()),
+            delimiter: b'\t',
+            filter: Some(Arc::new(|record: &csv::StringRecord| {
+                record.get(0) != Some("excluded")
+            })),
+            ..Default::default()
+        };
+        let by_index = CsvOptions {
+            decimals: Some(6),
+            address_column: CsvColumn::Index(1),
+            amount_column: CsvColumn::Index(2),
+            delimiter: b';',
+            has_headers: false,
+            ..Default::default()
+        };
+
+        let roots: Vec<_> = [
+            from_csv(standard.as_bytes(), &options(6)),
+            from_csv(reordered.as_bytes(), &by_name),
+            from_csv(headless.as_bytes(), &by_index),
+        ]
+        .into_iter()
+        .map(|entries| {
+            let entries = entries.unwrap();
+            assert_eq!(entries.len(), 2);
+            crate::MerkleTree::new(entries).get_root()
+        })
+        .collect();
+        assert_eq!(roots[0], roots[1]);
+        assert_eq!(roots[0], roots[2]);
+
+        let untrimmed = CsvOptions {
+            trim: false,
+            ..by_index.clone()
+        };
+        assert!(matches!(
+            from_csv(headless.as_bytes(), &untrimmed),
+            Err(MerkleError::InvalidRow { row: 1, .. })
+        ));
+        let missing = CsvOptions {
+            amount_column: CsvColumn::Name("amount".to_string()),
+            ..by_name.clone()
+        };
+        assert!(matches!(
+            from_csv(reordered.as_bytes(), &missing),
+            Err(MerkleError::InvalidRow { row: 1, .. })
+        ));
+        let named_headless = CsvOptions {
+            has_headers: false,
+            ..by_name
+        };
+        assert!(matches!(
+            from_csv(headless.as_bytes(), &named_headless),
+            Err(MerkleError::InvalidRow { row: 1, .. })
+        ));
+        let tabs = CsvOptions {
+            address_column: CsvColumn::Index(2),
+            amount_column: CsvColumn::Index(1),
+            delimiter: b'\t',
+            ..Default::default()
+        };
+        let bad_row = "Note\tTokens\tWallet\nfirst\t1.5\t0x11\n";
+        assert!(matches!(
+            from_csv(bad_row.as_bytes(), &tabs),
+            Err(MerkleError::InvalidRow { row: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn ingest_with_report_collects_every_bad_row() {
+        let file = "address,amount\n\
+                    0x1111111111111111111111111111111111111111,1.5\n\
+                    0x12345,2\n\
+                    0x2222222222222222222222222222222222222222,abc\n\
+                    0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed,3\n\
+                    0x5aAeb6053f3E94C9b9A09f33669435E7Ef1BeAed,3\n\
+                    0x1111111111111111111111111111111111111111,9\n\
+                    0x3333333333333333333333333333333333333333\n\
+                    0x4444444444444444444444444444444444444444,0.25\n";
+        let options = CsvOptions {
+            decimals: Some(2),
+            verify_checksums: true,
+            ..Default::default()
+        };
+        let report = ingest_with_report(file.as_bytes(), &options).unwrap();
+
+        let errors: Vec<(usize, RowErrorKind)> = report
+            .errors
+            .iter()
+            .map(|error| (error.row, error.kind))
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                (3, RowErrorKind::BadAddress),
+                (4, RowErrorKind::BadAmount),
+                (6, RowErrorKind::ChecksumMismatch),
+                (7, RowErrorKind::Duplicate),
+                (8, RowErrorKind::Malformed),
+            ]
+        );
+        assert_eq!(report.errors[0].raw, "0x12345,2");
+        assert_eq!(
+            report.errors[3].reason,
+            format!(
+                "address {:?} already appears on row 2",
+                Address::repeat_byte(0x11)
+            )
+        );
+
+        let good = "address,amount\n\
+                    0x1111111111111111111111111111111111111111,1.5\n\
+                    0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed,3\n\
+                    0x4444444444444444444444444444444444444444,0.25\n";
+        let expected = from_csv(good.as_bytes(), &options).unwrap();
+        assert_eq!(report.entries, expected);
+        assert_eq!(
+            crate::MerkleTree::new(report.entries).get_root(),
+            crate::MerkleTree::new(expected).get_root()
+        );
+
+        assert!(matches!(
+            from_csv(file.as_bytes(), &options),
+            Err(MerkleError::InvalidRow { row: 3, .. })
+        ));
+    }
+}
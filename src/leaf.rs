@@ -0,0 +1,706 @@
+use crate::{EntryScheme, MerkleError, MerkleTree};
+use ethers::abi::ethabi;
+use ethers::core::utils::keccak256;
+use ethers::types::{Address, Bytes, H256, U256};
+use std::fmt;
+use std::str::FromStr;
+
+/// The encoding used to hash an `(address, amount)` pair into a leaf.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum LeafMode {
+    /// `keccak256(abi.encodePacked(account, amount))`, a single hash over 52 bytes.
+    PackedSingle,
+    /// `keccak256(bytes.concat(keccak256(abi.encode(account, amount))))`, the double hash of
+    /// OpenZeppelin's `StandardMerkleTree`. This is the encoding of `hash_node`.
+    #[default]
+    AbiDouble,
+}
+
+/// The width of the amount in packed leaves, `keccak256(abi.encodePacked(account, uintN(amount)))`.
+///
+/// Gas-optimized distributors often store the amount as a `uint96` next to the address in
+/// one storage slot, and hash it at that width.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub enum AmountWidth {
+    /// `uint256`, the encoding of `LeafMode::PackedSingle`.
+    #[default]
+    U256,
+    /// `uint128`.
+    U128,
+    /// `uint96`.
+    U96,
+    /// `uint64`.
+    U64,
+}
+
+impl AmountWidth {
+    /// Returns the number of bytes of the encoded amount.
+    pub fn bytes(self) -> usize {
+        match self {
+            AmountWidth::U256 => 32,
+            AmountWidth::U128 => 16,
+            AmountWidth::U96 => 12,
+            AmountWidth::U64 => 8,
+        }
+    }
+
+    /// Returns whether an amount can be encoded at this width.
+    pub fn fits(self, amount: U256) -> bool {
+        amount.bits() <= 8 * self.bytes()
+    }
+}
+
+impl fmt::Display for AmountWidth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "uint{}", 8 * self.bytes())
+    }
+}
+
+impl FromStr for AmountWidth {
+    type Err = MerkleError;
+
+    /// Parses the Solidity type of the amount, e.g. `uint96`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [
+            AmountWidth::U256,
+            AmountWidth::U128,
+            AmountWidth::U96,
+            AmountWidth::U64,
+        ]
+        .into_iter()
+        .find(|width| width.to_string() == s)
+        .ok_or_else(|| MerkleError::InvalidMessage(format!("unknown amount type {:?}", s)))
+    }
+}
+
+/// Options for leaves that carry an extra bytes payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PayloadOptions {
+    /// Hash leaves with an empty payload exactly like the two-field leaf of `hash_node`,
+    /// so they can coexist with trees built by `MerkleTree::new`. When unset, an empty
+    /// payload still uses the packed encoding and never collides with a two-field leaf.
+    pub empty_as_two_field: bool,
+}
+
+/// An account, its amount and the payload of its leaf, i.e. the data behind a leaf of
+/// `MerkleTree::new_with_payload`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PayloadEntry {
+    /// The claiming account.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::address"))]
+    pub account: Address,
+    /// The claimable amount in base units.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::amount"))]
+    pub amount: U256,
+    /// The extra bytes hashed after the amount.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::bytes"))]
+    pub payload: Bytes,
+}
+
+impl MerkleTree {
+    /// Computes the hash of a leaf with the given encoding.
+    ///
+    /// # Arguments
+    ///
+    /// * `mode` - The leaf encoding to apply.
+    /// * `leaf_data` - A tuple containing the address (`Address`) and amount (`U256`) of the leaf node.
+    ///
+    /// # Returns
+    ///
+    /// A `H256` value representing the hash of the leaf node.
+    pub fn hash_leaf(mode: LeafMode, leaf_data: (Address, U256)) -> H256 {
+        match mode {
+            LeafMode::PackedSingle => {
+                let (account, amount) = leaf_data;
+                let mut amount_bytes = [0u8; 32];
+                amount.to_big_endian(&mut amount_bytes);
+                H256::from(keccak256([account.as_bytes(), &amount_bytes].concat()))
+            }
+            LeafMode::AbiDouble => Self::hash_node(leaf_data),
+        }
+    }
+
+    /// Computes the sorted, deduplicated leaf layer of some data without building a tree.
+    ///
+    /// The leaves are exactly those `new` would build for `LeafMode::AbiDouble`, so
+    /// `from_leaves(hash_entries_sorted(data, LeafMode::AbiDouble))` has the same root.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - A slice containing tuples of addresses and amounts.
+    /// * `encoding` - The leaf encoding to apply.
+    ///
+    /// # Returns
+    ///
+    /// The leaf hashes in ascending order, without duplicates.
+    pub fn hash_entries_sorted(data: &[(Address, U256)], encoding: LeafMode) -> Vec<H256> {
+        let mut leaves: Vec<H256> = data
+            .iter()
+            .map(|leaf_data| Self::hash_leaf(encoding, *leaf_data))
+            .collect();
+        leaves.sort();
+        leaves.dedup();
+        leaves
+    }
+
+    /// Verifies a claim against a root, trying several leaf encodings in turn.
+    ///
+    /// This is meant for verifiers that still accept claims from historical trees built
+    /// with a different leaf encoding.
+    ///
+    /// # Arguments
+    ///
+    /// * `account` - The address of the claim.
+    /// * `amount` - The amount of the claim.
+    /// * `proof` - The hashes forming the Merkle proof.
+    /// * `root` - The root hash of the Merkle tree.
+    /// * `modes` - The accepted leaf encodings, in the order they are tried.
+    ///
+    /// # Returns
+    ///
+    /// An `Option` containing the first encoding whose leaf verifies, or `None` if none does.
+    pub fn verify_multi_encoding(
+        account: Address,
+        amount: U256,
+        proof: &[H256],
+        root: H256,
+        modes: &[LeafMode],
+    ) -> Option<LeafMode> {
+        modes.iter().copied().find(|&mode| {
+            Self::process_proof(Self::hash_leaf(mode, (account, amount)), proof) == root
+        })
+    }
+
+    /// Computes the hash of a leaf carrying an extra bytes payload.
+    ///
+    /// The leaf is `keccak256(abi.encodePacked(account, amount, data))`, i.e. the keccak of:
+    ///
+    /// * bytes `0..20` - the 20 address bytes, without padding,
+    /// * bytes `20..52` - the amount as a 32-byte big-endian `uint256`,
+    /// * bytes `52..` - the payload bytes as-is, without a length prefix.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_data` - A tuple containing the address, amount and payload of the leaf.
+    /// * `options` - Controls how a leaf with an empty payload is hashed.
+    ///
+    /// # Returns
+    ///
+    /// A `H256` value representing the hash of the leaf node.
+    pub fn hash_payload_node(leaf_data: (Address, U256, &[u8]), options: PayloadOptions) -> H256 {
+        let (account, amount, data) = leaf_data;
+        if data.is_empty() && options.empty_as_two_field {
+            return Self::hash_node((account, amount));
+        }
+
+        let mut amount_bytes = [0u8; 32];
+        amount.to_big_endian(&mut amount_bytes);
+        let encoded_data = [account.as_bytes(), &amount_bytes, data].concat();
+        H256::from(keccak256(encoded_data))
+    }
+
+    /// Constructs a new Merkle tree from leaves carrying an extra bytes payload.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - A vector containing tuples of addresses, amounts and payloads.
+    /// * `options` - Controls how leaves with an empty payload are hashed.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `MerkleTree` containing the constructed Merkle tree.
+    pub fn new_with_payload(data: Vec<(Address, U256, Bytes)>, options: PayloadOptions) -> Self {
+        let (mut tree, permutation) = Self::from_leaves_tracked(
+            data.iter()
+                .map(|(account, amount, payload)| {
+                    Self::hash_payload_node((*account, *amount, payload), options)
+                })
+                .collect(),
+        );
+        tree.retain_payload_entries(
+            data.into_iter()
+                .map(|(account, amount, payload)| PayloadEntry {
+                    account,
+                    amount,
+                    payload,
+                }),
+            &permutation,
+        );
+        tree.config.payload = options;
+        tree
+    }
+
+    /// Returns the data behind each leaf of a tree built with `new_with_payload`, in leaf
+    /// order, or an empty slice for other trees.
+    ///
+    /// Payload leaves are not two-field leaves, so these trees retain no `entries()` and
+    /// the operations built on them fail with `MerkleError::NoEntries`.
+    pub fn payload_entries(&self) -> &[PayloadEntry] {
+        &self.payload_entries
+    }
+
+    /// Computes the hash of a leaf bound to a chain, so it cannot be replayed on another chain.
+    ///
+    /// The leaf is `keccak256(bytes.concat(keccak256(abi.encode(chainId, account, amount))))`,
+    /// which a distributor checks with `block.chainid` as the first field.
+    ///
+    /// # Arguments
+    ///
+    /// * `chain_id` - The ID of the chain the claim is valid on.
+    /// * `leaf_data` - A tuple containing the address (`Address`) and amount (`U256`) of the leaf node.
+    ///
+    /// # Returns
+    ///
+    /// A `H256` value representing the hash of the leaf node.
+    pub fn hash_chain_node(chain_id: u64, leaf_data: (Address, U256)) -> H256 {
+        let (account, amount) = leaf_data;
+        let encoded_data = ethabi::encode(&[
+            ethabi::Token::Uint(U256::from(chain_id)),
+            ethabi::Token::Address(account),
+            ethabi::Token::Uint(amount),
+        ]);
+        H256::from(keccak256(keccak256(encoded_data)))
+    }
+
+    /// Constructs a new Merkle tree whose leaves are domain-separated by chain ID.
+    ///
+    /// Use one tree per chain for multichain distributions: a proof from one chain's tree
+    /// does not verify against another chain's root, even for identical data.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - A vector containing tuples of addresses and amounts to be stored in the Merkle tree.
+    /// * `chain_id` - The ID of the chain the claims are valid on.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `MerkleTree` containing the constructed Merkle tree.
+    pub fn new_for_chain(data: Vec<(Address, U256)>, chain_id: u64) -> Self {
+        let mut tree =
+            Self::from_entries(data, |leaf_data| Self::hash_chain_node(chain_id, leaf_data));
+        tree.config.chain_id = Some(chain_id);
+        tree
+    }
+
+    /// Returns the chain ID the tree was built for with `new_for_chain`, if any.
+    pub fn chain_id(&self) -> Option<u64> {
+        self.config.chain_id
+    }
+
+    /// Retrieves the Merkle proof for a leaf given its address and amount.
+    ///
+    /// The leaf is hashed with the chain ID the tree was built with, or with `hash_node`
+    /// for trees that are not bound to a chain.
+    ///
+    /// # Returns
+    ///
+    /// An `Option` containing either the Merkle proof if the leaf is found,
+    /// or `None` if the leaf is not present in the Merkle tree.
+    pub fn get_entry_proof(&self, leaf_data: (Address, U256)) -> Option<Vec<H256>> {
+        self.get_proof(self.hash_entry(leaf_data).ok()?)
+    }
+
+    /// Computes the hash of a packed leaf with an amount of the given width.
+    ///
+    /// The leaf is `keccak256(abi.encodePacked(account, uintN(amount)))`: the 20 address
+    /// bytes followed by the amount as a big-endian integer of `width.bytes()` bytes.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_data` - A tuple containing the address and amount of the leaf.
+    /// * `width` - The width of the encoded amount.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the leaf hash, or `MerkleError::AmountOutOfRange` if the
+    /// amount does not fit in `width`.
+    pub fn hash_packed_node(
+        leaf_data: (Address, U256),
+        width: AmountWidth,
+    ) -> Result<H256, MerkleError> {
+        let (account, amount) = leaf_data;
+        if !width.fits(amount) {
+            return Err(MerkleError::AmountOutOfRange { account, width });
+        }
+        Ok(Self::packed_leaf(leaf_data, width))
+    }
+
+    pub(crate) fn packed_leaf(leaf_data: (Address, U256), width: AmountWidth) -> H256 {
+        let (account, amount) = leaf_data;
+        let mut amount_bytes = [0u8; 32];
+        amount.to_big_endian(&mut amount_bytes);
+        let amount_bytes = &amount_bytes[32 - width.bytes()..];
+        H256::from(keccak256([account.as_bytes(), amount_bytes].concat()))
+    }
+
+    /// Constructs a new Merkle tree of packed leaves with amounts of a fixed width.
+    ///
+    /// Leaves are hashed with `hash_packed_node`, then sorted and deduplicated like in
+    /// `new`. Proofs by data, such as `get_entry_proof` and `get_claim`, hash the leaf at
+    /// the same width.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - A vector containing tuples of addresses and amounts to be stored in the Merkle tree.
+    /// * `width` - The width of the encoded amounts.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the tree, or `MerkleError::AmountOutOfRange` for the first
+    /// entry whose amount does not fit in `width`.
+    pub fn new_packed(data: Vec<(Address, U256)>, width: AmountWidth) -> Result<Self, MerkleError> {
+        if let Some((account, _)) = data.iter().find(|(_, amount)| !width.fits(*amount)) {
+            return Err(MerkleError::AmountOutOfRange {
+                account: *account,
+                width,
+            });
+        }
+        let mut tree = Self::from_entries(data, |leaf_data| Self::packed_leaf(leaf_data, width));
+        tree.config.amount_width = Some(width);
+        Ok(tree)
+    }
+
+    /// Returns the amount width of the packed leaves of the tree, if it was built with
+    /// `new_packed` or in `LeafMode::PackedSingle`.
+    pub fn amount_width(&self) -> Option<AmountWidth> {
+        self.config.amount_width
+    }
+
+    /// Constructs a new Merkle tree with leaves hashed in the given mode.
+    ///
+    /// The tree remembers the mode, so `hash_entry` and the lookups by data hash leaves the
+    /// same way. Internal nodes are hashed alike in both modes.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - A vector containing tuples of addresses and amounts to be stored in the Merkle tree.
+    /// * `mode` - The leaf encoding.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `MerkleTree` containing the constructed Merkle tree.
+    pub fn new_with_leaf_mode(data: Vec<(Address, U256)>, mode: LeafMode) -> Self {
+        match mode {
+            LeafMode::AbiDouble => Self::new(data),
+            LeafMode::PackedSingle => {
+                let mut tree = Self::from_entries(data, |leaf_data| {
+                    Self::packed_leaf(leaf_data, AmountWidth::U256)
+                });
+                tree.config.amount_width = Some(AmountWidth::U256);
+                tree
+            }
+        }
+    }
+
+    /// Hashes an `(address, amount)` leaf with the encoding the tree was built with.
+    ///
+    /// This is `hash_node` for trees built with `new`, and follows the leaf mode, chain ID
+    /// or amount width of trees built with `new_with_leaf_mode`, `new_for_chain` or
+    /// `new_packed`.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_data` - A tuple containing the address and amount of the leaf.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the leaf hash, which is in the tree if the tree holds the
+    /// pair, or `MerkleError::AmountOutOfRange` if the tree packs amounts into a width the
+    /// amount does not fit in.
+    pub fn hash_entry(&self, leaf_data: (Address, U256)) -> Result<H256, MerkleError> {
+        EntryScheme::hash_with(self.config.chain_id, self.config.amount_width, leaf_data)
+    }
+
+    /// Returns how the tree hashes its entries and pairs, e.g. to verify claims without
+    /// the tree.
+    pub fn entry_scheme(&self) -> EntryScheme {
+        EntryScheme {
+            chain_id: self.config.chain_id,
+            amount_width: self.config.amount_width,
+            node_sep: self.config.node_sep.clone(),
+        }
+    }
+
+    /// Retrieves the Merkle proof for a payload leaf given its data.
+    ///
+    /// The leaf is hashed with the payload options the tree was built with.
+    ///
+    /// # Returns
+    ///
+    /// An `Option` containing either the Merkle proof if the leaf is found,
+    /// or `None` if the leaf is not present in the Merkle tree.
+    pub fn get_payload_proof(&self, leaf_data: (Address, U256, &[u8])) -> Option<Vec<H256>> {
+        self.get_proof(Self::hash_payload_node(leaf_data, self.config.payload))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Entry;
+    use std::str::FromStr;
+
+    fn account() -> (Address, U256) {
+        (
+            Address::from_str("0x00393d62f17b07e64f7cdcdf9bdc2fd925b20bba").unwrap(),
+            U256::from_dec_str("1840233889215604334017").unwrap(),
+        )
+    }
+
+    #[test]
+    fn payload_leaf_matches_solidity_encode_packed() {
+        let (account, amount) = account();
+        let leaf = MerkleTree::hash_payload_node(
+            (account, amount, &[0xde, 0xad, 0xbe, 0xef]),
+            PayloadOptions::default(),
+        );
+
+        // keccak256(abi.encodePacked(account, amount, hex"deadbeef"))
+        assert_eq!(
+            format!("{:?}", leaf),
+            "0xfbd8c882ca0e242a78ac7feeffea4864bee8b402e260f0d79955d8b712e82ca8"
+        );
+    }
+
+    #[test]
+    fn empty_payload_is_distinct_unless_configured() {
+        let (account, amount) = account();
+        let packed =
+            MerkleTree::hash_payload_node((account, amount, &[]), PayloadOptions::default());
+        let two_field = MerkleTree::hash_payload_node(
+            (account, amount, &[]),
+            PayloadOptions {
+                empty_as_two_field: true,
+            },
+        );
+
+        assert_eq!(
+            format!("{:?}", packed),
+            "0x25a757b727dce1ea6c6906d6fd8c0f1eab9e68ae2724e221f2ba5df11c903467"
+        );
+        assert_ne!(packed, MerkleTree::hash_node((account, amount)));
+        assert_eq!(two_field, MerkleTree::hash_node((account, amount)));
+    }
+
+    #[test]
+    fn payload_tree_proofs_by_data() {
+        let (account, amount) = account();
+        let data = vec![
+            (account, amount, Bytes::from(vec![1, 2, 3])),
+            (account, amount, Bytes::new()),
+            (Address::zero(), U256::one(), Bytes::from(vec![4])),
+        ];
+        let options = PayloadOptions {
+            empty_as_two_field: true,
+        };
+        let tree = MerkleTree::new_with_payload(data.clone(), options);
+        let root = tree.get_root().unwrap();
+
+        for (account, amount, payload) in &data {
+            let leaf = MerkleTree::hash_payload_node((*account, *amount, payload), options);
+            let proof = tree
+                .get_payload_proof((*account, *amount, payload))
+                .unwrap();
+            assert!(tree.verify_proof(leaf, proof, root));
+        }
+        assert!(tree
+            .get_proof(MerkleTree::hash_node((account, amount)))
+            .is_some());
+
+        let retained = tree.payload_entries();
+        assert_eq!(retained.len(), 3);
+        for (index, entry) in retained.iter().enumerate() {
+            let leaf = MerkleTree::hash_payload_node(
+                (entry.account, entry.amount, &entry.payload),
+                options,
+            );
+            assert_eq!(tree.sorted_leaves()[index], leaf);
+        }
+        assert!(tree.entries().is_empty());
+        assert!(MerkleTree::new(vec![(account, amount)])
+            .payload_entries()
+            .is_empty());
+    }
+
+    #[test]
+    fn verify_multi_encoding_reports_matching_mode() {
+        let data = vec![account(), (Address::repeat_byte(0x22), U256::from(5))];
+        let tree = MerkleTree::new(data.clone());
+        let (account, amount) = data[0];
+        let proof = tree.get_proof(MerkleTree::hash_node(data[0])).unwrap();
+        let root = tree.get_root().unwrap();
+
+        assert_eq!(
+            MerkleTree::verify_multi_encoding(
+                account,
+                amount,
+                &proof,
+                root,
+                &[LeafMode::PackedSingle, LeafMode::AbiDouble]
+            ),
+            Some(LeafMode::AbiDouble)
+        );
+        assert_eq!(
+            MerkleTree::verify_multi_encoding(
+                account,
+                amount,
+                &proof,
+                root,
+                &[LeafMode::PackedSingle]
+            ),
+            None
+        );
+        assert_eq!(
+            MerkleTree::hash_leaf(LeafMode::PackedSingle, (account, amount)),
+            MerkleTree::hash_payload_node((account, amount, &[]), PayloadOptions::default())
+        );
+    }
+
+    #[test]
+    fn chain_trees_reject_cross_chain_proofs() {
+        let data = vec![account(), (Address::repeat_byte(0x22), U256::from(5))];
+        let mainnet = MerkleTree::new_for_chain(data.clone(), 1);
+        let base = MerkleTree::new_for_chain(data.clone(), 8453);
+        assert_ne!(mainnet.get_root(), base.get_root());
+        assert_ne!(mainnet.get_root(), MerkleTree::new(data.clone()).get_root());
+        assert_eq!(base.chain_id(), Some(8453));
+        // keccak256(bytes.concat(keccak256(abi.encode(1, account, amount))))
+        assert_eq!(
+            format!("{:?}", MerkleTree::hash_chain_node(1, account())),
+            "0x2f7cd12d14b41bf3d1b113cdb922c67a1fc55acd6abcad3139530185881ff70b"
+        );
+
+        for leaf_data in data {
+            let mainnet_leaf = MerkleTree::hash_chain_node(1, leaf_data);
+            let proof = mainnet.get_entry_proof(leaf_data).unwrap();
+            assert!(mainnet.verify_proof(mainnet_leaf, proof.clone(), mainnet.get_root().unwrap()));
+            assert!(!base.verify_proof(mainnet_leaf, proof.clone(), base.get_root().unwrap()));
+
+            let base_leaf = MerkleTree::hash_chain_node(8453, leaf_data);
+            assert!(!base.verify_proof(base_leaf, proof, base.get_root().unwrap()));
+        }
+    }
+
+    #[test]
+    fn hash_entries_sorted_matches_new() {
+        let mut data: Vec<(Address, U256)> = (1..=9u64)
+            .rev()
+            .map(|i| (Address::from_low_u64_be(i), U256::from(i)))
+            .collect();
+        data.push(data[3]);
+        let leaves = MerkleTree::hash_entries_sorted(&data, LeafMode::AbiDouble);
+        let tree = MerkleTree::new(data.clone());
+
+        assert_eq!(leaves, tree.sorted_leaves());
+        assert_eq!(MerkleTree::from_leaves(leaves).get_root(), tree.get_root());
+        assert_eq!(
+            MerkleTree::hash_entries_sorted(&data, LeafMode::PackedSingle).len(),
+            9
+        );
+    }
+
+    #[test]
+    fn packed_leaves_match_solidity_at_each_width() {
+        let (account, amount) = account();
+        let u96 = |amount| MerkleTree::hash_packed_node((account, amount), AmountWidth::U96);
+        // keccak256(abi.encodePacked(account, uint96(amount)))
+        assert_eq!(
+            format!("{:?}", u96(amount).unwrap()),
+            "0x7e23fcf49d814c13bc01d07c00367a91cb83bce857f6b5b0b9259201757a387d"
+        );
+        let max = (U256::one() << 96) - 1;
+        assert_eq!(
+            format!("{:?}", u96(max).unwrap()),
+            "0xab9183aa347fc7be4ba6dc66f947f81a182f33acb4dd05a3ec589aa38e2ae63c"
+        );
+        assert_eq!(
+            u96(max + 1),
+            Err(MerkleError::AmountOutOfRange {
+                account,
+                width: AmountWidth::U96
+            })
+        );
+        // keccak256(abi.encodePacked(account, uint64(5)))
+        assert_eq!(
+            format!(
+                "{:?}",
+                MerkleTree::hash_packed_node((account, U256::from(5)), AmountWidth::U64).unwrap()
+            ),
+            "0xe3c542a8bcaf6c6dfea4d83c146410930413d1b16049b0417f87e9bb3f2ebfea"
+        );
+        assert_eq!(
+            MerkleTree::hash_packed_node((account, amount), AmountWidth::U256),
+            Ok(MerkleTree::hash_leaf(
+                LeafMode::PackedSingle,
+                (account, amount)
+            ))
+        );
+        assert_eq!(AmountWidth::U128.to_string(), "uint128");
+    }
+
+    #[test]
+    fn packed_trees_check_ranges_and_prove_by_data() {
+        let data = vec![
+            account(),
+            (Address::repeat_byte(0x22), U256::from(5)),
+            (Address::repeat_byte(0x33), U256::one() << 64),
+        ];
+        let tree = MerkleTree::new_packed(data.clone(), AmountWidth::U96).unwrap();
+        let root = tree.get_root().unwrap();
+        assert_eq!(tree.amount_width(), Some(AmountWidth::U96));
+        for leaf_data in &data {
+            let leaf = MerkleTree::hash_packed_node(*leaf_data, AmountWidth::U96).unwrap();
+            let proof = tree.get_entry_proof(*leaf_data).unwrap();
+            assert!(tree.verify_proof(leaf, proof, root));
+            let bundle = tree.get_proof_bundle(Entry::from(*leaf_data)).unwrap();
+            assert_eq!(tree.sorted_leaves()[bundle.index], leaf);
+        }
+        assert_eq!(
+            tree.get_entry_proof((Address::repeat_byte(0x22), U256::MAX)),
+            None
+        );
+        assert_eq!(
+            MerkleTree::new_packed(data[1..].to_vec(), AmountWidth::U64)
+                .map(|tree| tree.get_root()),
+            Err(MerkleError::AmountOutOfRange {
+                account: Address::repeat_byte(0x33),
+                width: AmountWidth::U64
+            })
+        );
+    }
+
+    #[test]
+    fn leaf_mode_is_kept_by_the_tree() {
+        let data = vec![
+            (Address::repeat_byte(0x11), U256::exp10(18) * 5),
+            (Address::repeat_byte(0x22), U256::exp10(17) * 25),
+        ];
+        let standard = MerkleTree::new(data.clone());
+        assert_eq!(
+            format!("{:?}", standard.get_root().unwrap()),
+            "0xd4dee0beab2d53f2cc83e567171bd2820e49898130a22622b10ead383e90bd77"
+        );
+        assert_eq!(
+            MerkleTree::new_with_leaf_mode(data.clone(), LeafMode::AbiDouble).get_root(),
+            standard.get_root()
+        );
+
+        let packed = MerkleTree::new_with_leaf_mode(data.clone(), LeafMode::PackedSingle);
+        assert_ne!(packed.get_root(), standard.get_root());
+        for (tree, mode) in [
+            (&standard, LeafMode::AbiDouble),
+            (&packed, LeafMode::PackedSingle),
+        ] {
+            let root = tree.get_root().unwrap();
+            for leaf_data in &data {
+                let leaf = tree.hash_entry(*leaf_data).unwrap();
+                assert_eq!(leaf, MerkleTree::hash_leaf(mode, *leaf_data));
+                let proof = tree.get_entry_proof(*leaf_data).unwrap();
+                assert!(tree.verify_proof(leaf, proof, root));
+            }
+        }
+    }
+}
@@ -1,11 +1,183 @@
 use ethers::abi::ethabi;
 use ethers::core::utils::keccak256;
 use ethers::types::{Address, H256, U256};
+use std::collections::HashMap;
+use std::hash::BuildHasher;
 
+mod accumulator;
+mod amount;
+#[cfg(feature = "rkyv")]
+mod archive;
+mod arena;
+mod batch;
+mod bloom;
+mod builder;
+mod cache;
+mod canonical;
+mod checkpoint;
+mod claim;
+mod commitment;
+mod compact;
+mod error;
+#[cfg(feature = "serde")]
+mod export;
+#[cfg(feature = "uniffi")]
+pub mod ffi;
+mod fixed;
+#[cfg(feature = "csv")]
+pub mod import;
+mod leaf;
+mod merge;
+mod multiproof;
+mod no_alloc;
+mod nodes;
+mod ordered;
+#[cfg(feature = "proto")]
+pub mod proto;
+mod registry;
+mod rlp;
+mod rolling;
+mod roots;
+#[cfg(feature = "rpc")]
+mod rpc;
+#[cfg(feature = "rs-merkle-compat")]
+pub mod rs_merkle_compat;
+#[cfg(feature = "utoipa")]
+mod schema;
+mod scheme;
+mod separator;
+#[cfg(feature = "serde")]
+mod serialize;
+mod sets;
+mod shard;
+#[cfg(any(feature = "csv", feature = "rpc", feature = "serde"))]
+pub mod snapshot;
+mod split;
+mod standard;
+mod storage;
+#[cfg(feature = "tokio")]
+mod stream;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
+mod trace;
+mod transfers;
+mod truncate;
+mod typed;
+mod update;
+pub mod vesting;
+mod wide;
+
+pub use accumulator::MerkleAccumulator;
+pub use amount::{Amount, TokenMetadata};
+#[cfg(feature = "rkyv")]
+pub use archive::ArchivedMerkleTree;
+pub use arena::RootView;
+pub use batch::{BatchReport, FailureDetail, FailureReason};
+pub use builder::{
+    BuildReport, DuplicateAction, DuplicateEvent, DuplicateKind, DuplicatePolicy,
+    MerkleTreeBuilder, SelfCheckMode,
+};
+pub use cache::{CacheEvent, CacheStats, CachedTree, ProofCacheOptions, VerificationCache};
+pub use checkpoint::CheckpointOptions;
+pub use claim::{
+    ClaimRecord, Entry, ExportOptions, ExportOrder, PrefixSearchOptions, Proof, ProofBundle,
+    ProofSet,
+};
+pub use error::{MerkleError, RegistryError, SplitError};
+#[cfg(feature = "serde")]
+pub use export::{audit_distribution, AuditFailure, AuditFailureReason, AuditReport};
+pub use fixed::FixedTree;
+pub use leaf::{AmountWidth, LeafMode, PayloadEntry, PayloadOptions};
+pub use merge::merge_sorted;
+pub use multiproof::{verify_multi_proof, MultiProof};
+pub use no_alloc::{verify_ordered_proof_no_alloc, verify_proof_no_alloc, MAX_PROOF_DEPTH};
+pub use nodes::NodeRef;
+pub use ordered::Side;
+pub use registry::TreeRegistry;
+pub use rlp::{proof_from_rlp, proof_to_rlp};
+pub use rolling::RollingTree;
+pub use roots::{verify_against, verify_against_at, AcceptedRoot, RootMatch, RootSet};
+#[cfg(feature = "rpc")]
+pub use rpc::ClaimStatusReport;
+pub use scheme::{verify_and_derive, EntryScheme, HashScheme};
+pub use separator::NodeSep;
+pub use sets::AmountMapReport;
+pub use shard::shard_by_prefix;
+pub use split::{verify_split, SplitStrategy};
+pub use standard::{StandardMerkleTree, StandardValue};
+#[cfg(feature = "mmap")]
+pub use storage::MmapMerkleTree;
+pub use storage::SnapshotOptions;
+pub use trace::{ProofTrace, TraceStep};
+pub use typed::{Leaf, Packed};
+pub use update::LeafChange;
+pub use wide::{MerkleTree32, WideClaim, WideEntry};
+
+#[cfg(feature = "uniffi")]
+uniffi::setup_scaffolding!();
+
+#[derive(Debug, Clone)]
 pub struct MerkleTree {
     elements: Vec<H256>,
+    // the data behind each leaf, in leaf order; empty for trees built from hashes
+    entries: Vec<Entry>,
+    // the data behind each leaf of a payload tree, in leaf order; empty for other trees
+    payload_entries: Vec<PayloadEntry>,
+    // the indices of `entries` ordered by address, then amount
+    address_order: Vec<usize>,
     layers: Vec<Vec<H256>>,
     leaves: usize,
+    // the number of leaves given before deduplication
+    inputs: usize,
+    bloom: Option<bloom::BloomFilter>,
+    config: TreeConfig,
+}
+
+/// Trees compare by root only, not by contents or options: two trees with the same root
+/// are equal, and empty trees, which have no root, order before all others. This lets
+/// trees of several rounds be kept in a `BTreeSet` keyed by root.
+impl PartialEq for MerkleTree {
+    fn eq(&self, other: &Self) -> bool {
+        self.get_root() == other.get_root()
+    }
+}
+
+impl Eq for MerkleTree {}
+
+impl PartialOrd for MerkleTree {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for MerkleTree {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.get_root().cmp(&other.get_root())
+    }
+}
+
+/// The options a tree was built with, used to hash leaves given by their data.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+struct TreeConfig {
+    payload: PayloadOptions,
+    chain_id: Option<u64>,
+    ordered: bool,
+    node_sep: NodeSep,
+    token: Option<TokenMetadata>,
+    self_check: Option<SelfCheckMode>,
+    amount_width: Option<AmountWidth>,
+}
+
+impl TreeConfig {
+    /// Whether two configs hash the same data to the same leaves and nodes; token
+    /// metadata and self-check modes do not take part.
+    fn same_hashing(&self, other: &TreeConfig) -> bool {
+        self.payload == other.payload
+            && self.chain_id == other.chain_id
+            && self.ordered == other.ordered
+            && self.node_sep == other.node_sep
+            && self.amount_width == other.amount_width
+    }
 }
 
 impl MerkleTree {
@@ -41,19 +213,193 @@ impl MerkleTree {
     /// let merkle_tree = MerkleTree::new(data);
     ///
     pub fn new(data: Vec<(Address, U256)>) -> Self {
-        let mut elements: Vec<H256> = data.iter().map(|x| Self::hash_node(*x)).collect();
+        Self::from_entries(data, Self::hash_node)
+    }
+    /// Constructs a new Merkle tree like `new` and returns it together with its root.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - A vector containing tuples of addresses and amounts to be stored in the Merkle tree.
+    ///
+    /// # Returns
+    ///
+    /// A tuple of the constructed `MerkleTree` and its root hash.
+    ///
+    /// # Panics
+    ///
+    /// If `data` is empty, since an empty tree has no root.
+    pub fn build(data: Vec<(Address, U256)>) -> (Self, H256) {
+        let tree = Self::new(data);
+        let root = tree
+            .get_root()
+            .expect("cannot build a tree without entries");
+        (tree, root)
+    }
+    /// Constructs a new Merkle tree from a map of balances.
+    ///
+    /// The root does not depend on the map's iteration order or hasher seed: leaves are
+    /// sorted during construction, so this yields the same tree as `new` with the same
+    /// pairs in any order.
+    ///
+    /// # Arguments
+    ///
+    /// * `map` - The amount of every address.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `MerkleTree` containing the constructed Merkle tree.
+    pub fn from_balances<S: BuildHasher>(map: HashMap<Address, U256, S>) -> Self {
+        let mut data = Vec::with_capacity(map.len());
+        data.extend(map);
+        Self::from_entries(data, Self::hash_node)
+    }
+    /// Constructs a new Merkle tree from precomputed leaf hashes.
+    ///
+    /// The leaves are sorted and deduplicated exactly like the hashed data in `new`.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaves` - A vector containing the leaf hashes to be stored in the Merkle tree.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `MerkleTree` containing the constructed Merkle tree.
+    pub fn from_leaves(mut leaves: Vec<H256>) -> Self {
+        let inputs = leaves.len();
         // sort and deduplicate to get the correct order of elements
-        elements.sort();
-        elements.dedup();
+        leaves.sort();
+        leaves.dedup();
+        let mut tree = Self::from_sorted_elements(leaves);
+        tree.inputs = inputs;
+        tree
+    }
+    /// Constructs a new Merkle tree from encoded leaf preimages, such as the output of
+    /// Solidity's `abi.encode`.
+    ///
+    /// Each preimage is hashed into a leaf, then the leaves are sorted and deduplicated
+    /// like in `from_leaves`. With `double_hash`, a leaf is `keccak256(keccak256(preimage))`,
+    /// the OpenZeppelin leaf of `new` when the preimage is `abi.encode(account, amount)`;
+    /// otherwise it is `keccak256(preimage)`.
+    ///
+    /// # Arguments
+    ///
+    /// * `preimages` - The encoded leaf data, in any order.
+    /// * `double_hash` - Whether each leaf is hashed twice.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `MerkleTree` containing the constructed Merkle tree.
+    pub fn from_encoded_leaves(preimages: Vec<Vec<u8>>, double_hash: bool) -> Self {
+        let leaves = preimages
+            .iter()
+            .map(|preimage| {
+                let hash = keccak256(preimage);
+                H256(if double_hash { keccak256(hash) } else { hash })
+            })
+            .collect();
+        Self::from_leaves(leaves)
+    }
+    /// Constructs a new Merkle tree from precomputed leaf hashes and reports where each landed.
+    ///
+    /// This function behaves like `from_leaves`, and additionally returns, for every input
+    /// position, the index of that leaf in `sorted_leaves()`. Later duplicates of a leaf
+    /// are removed by deduplication and map to `usize::MAX`.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaves` - A vector containing the leaf hashes to be stored in the Merkle tree.
+    ///
+    /// # Returns
+    ///
+    /// A tuple of the constructed `MerkleTree` and the permutation from input position to
+    /// final leaf index.
+    pub fn from_leaves_tracked(leaves: Vec<H256>) -> (Self, Vec<usize>) {
+        let (elements, permutation) = Self::sort_tracked(&leaves);
+        let mut tree = Self::from_sorted_elements(elements);
+        tree.inputs = leaves.len();
+        (tree, permutation)
+    }
+    /// Sorts and deduplicates leaves, returning the permutation of `from_leaves_tracked`.
+    fn sort_tracked(leaves: &[H256]) -> (Vec<H256>, Vec<usize>) {
+        let mut order: Vec<usize> = (0..leaves.len()).collect();
+        order.sort_by_key(|&i| (leaves[i], i));
+
+        let mut permutation = vec![usize::MAX; leaves.len()];
+        let mut elements: Vec<H256> = Vec::with_capacity(leaves.len());
+        for i in order {
+            if elements.last() != Some(&leaves[i]) {
+                permutation[i] = elements.len();
+                elements.push(leaves[i]);
+            }
+        }
+        (elements, permutation)
+    }
+    /// Builds a tree from data hashed with `hash`, retaining the data of every leaf.
+    fn from_entries(data: Vec<(Address, U256)>, hash: impl Fn((Address, U256)) -> H256) -> Self {
+        let (mut tree, permutation) =
+            Self::from_leaves_tracked(data.iter().map(|x| hash(*x)).collect());
+        tree.retain_entries(data.into_iter().map(Entry::from), &permutation);
+        tree
+    }
+    /// Stores input entries at the leaf indices given by `from_leaves_tracked`, and counts
+    /// every input position of the permutation as an input.
+    fn retain_entries(&mut self, entries: impl Iterator<Item = Entry>, permutation: &[usize]) {
+        self.inputs = permutation.len();
+        self.entries = vec![Entry::default(); self.leaves];
+        for (entry, &index) in entries.zip(permutation) {
+            if index != usize::MAX {
+                self.entries[index] = entry;
+            }
+        }
+        self.index_addresses();
+    }
+    /// Orders the entry indices by address for range searches over addresses.
+    fn index_addresses(&mut self) {
+        let mut order: Vec<usize> = (0..self.entries.len()).collect();
+        order.sort_by_key(|&index| self.entries[index]);
+        self.address_order = order;
+    }
+    /// Stores payload entries at the leaf indices given by `from_leaves_tracked`, like
+    /// `retain_entries`.
+    fn retain_payload_entries(
+        &mut self,
+        entries: impl Iterator<Item = PayloadEntry>,
+        permutation: &[usize],
+    ) {
+        self.inputs = permutation.len();
+        self.payload_entries = vec![PayloadEntry::default(); self.leaves];
+        for (entry, &index) in entries.zip(permutation) {
+            if index != usize::MAX {
+                self.payload_entries[index] = entry;
+            }
+        }
+    }
+    fn from_sorted_elements(elements: Vec<H256>) -> Self {
+        Self::from_sorted_elements_with(elements, NodeSep::default())
+    }
+    /// Builds the layers over sorted leaves, hashing pairs with the given separator.
+    fn from_sorted_elements_with(elements: Vec<H256>, node_sep: NodeSep) -> Self {
         let leaves = elements.len();
         let mut layers = vec![elements.clone()];
         while layers.last().unwrap().len() > 1 {
-            layers.push(Self::next_layer(layers.last().unwrap()));
+            layers.push(Self::fold_layer_with(
+                layers.last().unwrap(),
+                node_sep.as_bytes(),
+            ));
         }
         MerkleTree {
             elements,
+            entries: Vec::new(),
+            payload_entries: Vec::new(),
+            address_order: Vec::new(),
             layers,
             leaves,
+            inputs: leaves,
+            bloom: None,
+            config: TreeConfig {
+                node_sep,
+                ..Default::default()
+            },
         }
     }
     /// Retrieves the root hash of the Merkle tree.
@@ -69,6 +415,16 @@ impl MerkleTree {
             .last()
             .and_then(|last_layer| last_layer.first().cloned())
     }
+    /// Returns whether the root differs from a previously stored root.
+    ///
+    /// An empty tree has no root, so it is always considered changed.
+    ///
+    /// # Arguments
+    ///
+    /// * `prev_root` - The root hash observed earlier.
+    pub fn root_changed_since(&self, prev_root: H256) -> bool {
+        self.get_root() != Some(prev_root)
+    }
     /// Retrieves the Merkle proof for a given element.
     ///
     /// This function takes an element and returns the Merkle proof for that element,
@@ -83,17 +439,109 @@ impl MerkleTree {
     /// An `Option` containing either the Merkle proof as a vector of hashes if the element is found,
     /// or `None` if the element is not present in the Merkle tree.
     pub fn get_proof(&self, element: H256) -> Option<Vec<H256>> {
-        let mut index = self.elements.iter().position(|&e| e == element)?;
+        self.try_proof_at(self.position(element)?).ok()
+    }
+    /// Retrieves the Merkle proof for a given element, rejecting internal node hashes.
+    ///
+    /// Leaves and internal nodes are indistinguishable 32-byte hashes, so a caller may be
+    /// handed an internal node posing as a leaf. Unlike `get_proof`, which simply finds no
+    /// such leaf, this searches the internal layers too and reports the forgery attempt.
+    /// A leaf promoted unchanged into a higher layer is still treated as a leaf.
+    ///
+    /// # Arguments
+    ///
+    /// * `element` - The hash of the element for which the proof is to be retrieved.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the proof, or `None` if the hash is not in the tree at all, or
+    /// `MerkleError::NotALeaf` if the hash is an internal node, or
+    /// `MerkleError::SelfCheckFailed` if self-checking in `SelfCheckMode::Error` rejected
+    /// the proof.
+    pub fn get_proof_strict(&self, element: H256) -> Result<Option<Vec<H256>>, MerkleError> {
+        if let Some(index) = self.position(element) {
+            return self.try_proof_at(index).map(Some);
+        }
+        if self
+            .layers
+            .iter()
+            .skip(1)
+            .flatten()
+            .any(|node| *node == element)
+        {
+            return Err(MerkleError::NotALeaf(element));
+        }
+        Ok(None)
+    }
+    /// Builds the proof of the leaf at `index` of `sorted_leaves()`, panicking if
+    /// self-checking rejects it whatever the mode; for paths that cannot report an error.
+    fn proof_at(&self, index: usize) -> Vec<H256> {
+        self.try_proof_at(index)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+    /// Builds the proof of the leaf at `index` of `sorted_leaves()` and self-checks it.
+    fn try_proof_at(&self, index: usize) -> Result<Vec<H256>, MerkleError> {
+        let proof = self.build_proof(index);
+        self.self_check(index, &proof)?;
+        Ok(proof)
+    }
+    /// Collects the siblings of the leaf at `index` of `sorted_leaves()`, one per layer
+    /// below the root except where the node on the path was promoted without a sibling.
+    fn build_proof(&self, mut index: usize) -> Vec<H256> {
         let mut proof = Vec::new();
-
         for layer in &self.layers[..self.layers.len() - 1] {
-            let pair_index = if index % 2 == 0 { index + 1 } else { index - 1 };
+            let pair_index = index ^ 1; // the sibling of an even index is the next one, and vice versa
             if pair_index < layer.len() {
                 proof.push(layer[pair_index]);
             }
             index /= 2; // move up to the next layer.
         }
-        Some(proof)
+        proof
+    }
+    /// Checks that the proof of the leaf at `index` folds back to the root.
+    fn proof_is_valid(&self, index: usize, proof: &[H256]) -> bool {
+        let leaf = self.layers[0][index];
+        let root = self.layers[self.layers.len() - 1][0];
+        if self.config.ordered {
+            Self::verify_ordered_proof(leaf, index, self.leaves, proof, root)
+        } else {
+            Self::process_proof_with(leaf, proof, self.config.node_sep.as_bytes()) == root
+        }
+    }
+    /// Checks that the proof of every leaf verifies against the root.
+    ///
+    /// Odd nodes are promoted unchanged and their proofs skip the level, so a leaf can be
+    /// promoted through several consecutive odd layers and have a proof shorter than the
+    /// tree depth. Forks changing how layers or proofs are built can run this to check
+    /// that both still agree.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` that is `Ok` if every proof verifies, including for an empty tree, or
+    /// the index and hash of the first leaf whose proof does not.
+    pub fn verify_all_proofs(&self) -> Result<(), (usize, H256)> {
+        for index in 0..self.leaves {
+            if !self.proof_is_valid(index, &self.build_proof(index)) {
+                return Err((index, self.layers[0][index]));
+            }
+        }
+        Ok(())
+    }
+    /// Re-verifies a proof about to be handed out if the tree was built with
+    /// `MerkleTreeBuilder::self_check`, panicking or returning an error on failure.
+    fn self_check(&self, index: usize, proof: &[H256]) -> Result<(), MerkleError> {
+        let Some(mode) = self.config.self_check else {
+            return Ok(());
+        };
+        if self.proof_is_valid(index, proof) {
+            return Ok(());
+        }
+        let leaf = self.layers[0][index];
+        let err = MerkleError::SelfCheckFailed { index, leaf };
+        match mode {
+            SelfCheckMode::Panic => panic!("{}", err),
+            SelfCheckMode::Error => Err(err),
+        }
     }
     /// Verifies a proof for a given element in a Merkle tree.
     ///
@@ -110,17 +558,249 @@ impl MerkleTree {
     ///
     /// `true` if the proof is valid for the given element and root hash,
     pub fn verify_proof(&self, element: H256, proof: Vec<H256>, root: H256) -> bool {
-        let mut computed_hash = element;
-
-        for proof_element in proof.into_iter() {
-            computed_hash = if computed_hash < proof_element {
-                Self::hash_pair(&computed_hash, &proof_element)
-            } else {
-                Self::hash_pair(&proof_element, &computed_hash)
-            };
+        Self::process_proof_with(element, &proof, self.config.node_sep.as_bytes()) == root
+    }
+    /// Verifies a proof, rejecting proofs that contain the same hash more than once.
+    ///
+    /// Repeated proof elements are a common ingredient of padded or crafted proofs. Some
+    /// trees pair an odd node with itself and legitimately repeat a hash; this crate
+    /// promotes odd nodes instead, so its proofs never contain that exception and any
+    /// repeated hash is rejected before folding.
+    ///
+    /// # Arguments
+    ///
+    /// * `element` - The hash of the element to be verified.
+    /// * `proof` - A vector containing the hashes forming the Merkle proof.
+    /// * `root` - The root hash of the Merkle tree.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the proof has no repeated hash and is valid for the given element and root hash.
+    pub fn verify_proof_strict(&self, element: H256, proof: Vec<H256>, root: H256) -> bool {
+        let repeated = proof
+            .iter()
+            .enumerate()
+            .any(|(i, hash)| proof[..i].contains(hash));
+        !repeated && self.verify_proof(element, proof, root)
+    }
+    /// Verifies a proof, rejecting proofs that contain a zero hash as malformed.
+    ///
+    /// A zero sibling could in theory be a real node, but finding a keccak preimage for it
+    /// is infeasible, so in practice it is an uninitialized slot or a decoding bug. This
+    /// reports it instead of silently folding to a wrong root.
+    ///
+    /// # Arguments
+    ///
+    /// * `element` - The hash of the element to be verified.
+    /// * `proof` - A vector containing the hashes forming the Merkle proof.
+    /// * `root` - The root hash of the Merkle tree.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing whether the proof is valid for the given element and root
+    /// hash, or `MerkleError::ZeroProofElement` with the index of the first zero hash.
+    pub fn verify_proof_nonzero(
+        &self,
+        element: H256,
+        proof: Vec<H256>,
+        root: H256,
+    ) -> Result<bool, MerkleError> {
+        if let Some(index) = proof.iter().position(H256::is_zero) {
+            return Err(MerkleError::ZeroProofElement(index));
         }
+        Ok(self.verify_proof(element, proof, root))
+    }
+    /// Verifies a proof whose length must match a known tree depth.
+    ///
+    /// Verifiers of fixed-size distributions know how many hashes a proof has, so any
+    /// other length, such as a padded proof, is rejected before folding.
+    ///
+    /// # Arguments
+    ///
+    /// * `element` - The hash of the element to be verified.
+    /// * `proof` - A vector containing the hashes forming the Merkle proof.
+    /// * `root` - The root hash of the Merkle tree.
+    /// * `expected_proof_len` - The required number of proof hashes, i.e. the tree depth minus one.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the proof has the expected length and is valid for the given element and root hash.
+    pub fn verify_proof_exact_depth(
+        &self,
+        element: H256,
+        proof: Vec<H256>,
+        root: H256,
+        expected_proof_len: usize,
+    ) -> bool {
+        proof.len() == expected_proof_len && self.verify_proof(element, proof, root)
+    }
+    /// Retrieves the Merkle proof for a given element, ordered from the root down to the leaf.
+    ///
+    /// This is the proof returned by `get_proof` in reverse order, for verifiers that
+    /// consume proofs starting at the root.
+    ///
+    /// # Arguments
+    ///
+    /// * `element` - The hash of the element for which the proof is to be retrieved.
+    ///
+    /// # Returns
+    ///
+    /// An `Option` containing either the reversed Merkle proof if the element is found,
+    /// or `None` if the element is not present in the Merkle tree.
+    pub fn get_proof_reversed(&self, element: H256) -> Option<Vec<H256>> {
+        let mut proof = self.get_proof(element)?;
+        proof.reverse();
+        Some(proof)
+    }
+    /// Verifies a proof ordered from the root down to the leaf.
+    ///
+    /// The proof is folded starting from its last hash (the sibling of the leaf), so a
+    /// proof returned by `get_proof_reversed` verifies here but not with `verify_proof`.
+    ///
+    /// # Arguments
+    ///
+    /// * `element` - The hash of the element to be verified.
+    /// * `proof` - A vector containing the hashes forming the reversed Merkle proof.
+    /// * `root` - The root hash of the Merkle tree.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the reversed proof is valid for the given element and root hash.
+    pub fn verify_proof_reversed(&self, element: H256, proof: Vec<H256>, root: H256) -> bool {
+        let computed_hash = oz_merkle_core::process_proof(
+            element.0,
+            proof.iter().rev().map(|hash| &hash.0),
+            self.config.node_sep.as_bytes(),
+        );
+        H256(computed_hash) == root
+    }
+    /// Retrieves the Merkle proof for a given element as raw byte arrays.
+    ///
+    /// This is `get_proof` for consumers that do not depend on the `ethers` hash types.
+    ///
+    /// # Arguments
+    ///
+    /// * `element` - The 32 bytes of the element for which the proof is to be retrieved.
+    ///
+    /// # Returns
+    ///
+    /// An `Option` containing either the Merkle proof if the element is found,
+    /// or `None` if the element is not present in the Merkle tree.
+    pub fn get_proof_bytes(&self, element: [u8; 32]) -> Option<Vec<[u8; 32]>> {
+        let proof = self.get_proof(H256(element))?;
+        Some(proof.into_iter().map(|hash| hash.0).collect())
+    }
+    /// Verifies a proof given as raw byte arrays, like `verify_proof`.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf` - The 32 bytes of the leaf to be verified.
+    /// * `proof` - The proof hashes returned by `get_proof_bytes`.
+    /// * `root` - The 32 bytes of the root hash.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the proof is valid for the given leaf and root hash.
+    pub fn verify_proof_bytes(&self, leaf: [u8; 32], proof: &[[u8; 32]], root: [u8; 32]) -> bool {
+        oz_merkle_core::process_proof(leaf, proof, self.config.node_sep.as_bytes()) == root
+    }
+    /// Verifies a proof in a tight loop meant to be inlined into verifier hot paths.
+    ///
+    /// Unlike `verify_proof`, this borrows the proof and needs no tree, so nothing is
+    /// allocated or copied: every level is one comparison and one keccak over a stack
+    /// buffer. Pairs are hashed without a separator, like OpenZeppelin's `MerkleProof`, so
+    /// the proofs of a tree built with a node separator do not verify here; check them
+    /// with `verify_proof` or `EntryScheme::verify`.
+    ///
+    /// # Arguments
+    ///
+    /// * `element` - The hash of the element to be verified.
+    /// * `proof` - The hashes forming the Merkle proof.
+    /// * `root` - The root hash of the Merkle tree.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the proof is valid for the given element and root hash.
+    #[inline(always)]
+    pub fn verify_proof_inline(element: H256, proof: &[H256], root: H256) -> bool {
+        Self::process_proof_with(element, proof, &[]) == root
+    }
+    /// Verifies a proof like `verify_proof`, reporting every intermediate hash.
+    ///
+    /// Monitors can compare each step with the one computed on chain and tell at which
+    /// level a disputed proof diverges.
+    ///
+    /// # Arguments
+    ///
+    /// * `element` - The hash of the element to be verified.
+    /// * `proof` - A vector containing the hashes forming the Merkle proof.
+    /// * `root` - The root hash of the Merkle tree.
+    /// * `observer` - Called after each fold step with the level, starting at 0 for the
+    ///   pair of the leaf, and the hash computed at that level. The last hash it receives
+    ///   is the computed root; an empty proof is not reported.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the proof is valid for the given element and root hash.
+    pub fn verify_proof_observed(
+        &self,
+        element: H256,
+        proof: Vec<H256>,
+        root: H256,
+        mut observer: impl FnMut(usize, H256),
+    ) -> bool {
+        let sep = self.config.node_sep.as_bytes();
+        let computed_hash =
+            proof
+                .iter()
+                .enumerate()
+                .fold(element, |hash, (level, proof_element)| {
+                    let hash = Self::hash_pair_with(&hash, proof_element, sep);
+                    observer(level, hash);
+                    hash
+                });
         computed_hash == root
     }
+    /// Verifies a proof from a tree that promotes odd nodes or from one that pairs the
+    /// last odd node with itself.
+    ///
+    /// This crate promotes an odd node unchanged, while other builders duplicate it, so
+    /// their proofs contain a step whose hash equals the node computed so far. Some
+    /// generators also emit the node itself as a placeholder where it was promoted. Every
+    /// such self-referential step is read both ways, as a pair of equal hashes and as a
+    /// promotion that leaves the node unchanged, and the proof verifies if any reading
+    /// folds to the root. The readings are tracked as a set that grows by at most one hash
+    /// per step, so this stays linear in the proof length.
+    ///
+    /// The tradeoff: a self-referential step becomes a no-op that anyone can insert, so a
+    /// leaf has many accepted proofs and proofs can no longer be compared or deduplicated
+    /// by value, and the proof length says nothing about the depth of the tree. Which
+    /// leaves the root commits to is unchanged, since skipping a step only drops a hash
+    /// that added nothing. Prefer `verify_proof` once the data has been migrated.
+    ///
+    /// # Arguments
+    ///
+    /// * `element` - The hash of the element to be verified.
+    /// * `proof` - A vector containing the hashes forming the Merkle proof.
+    /// * `root` - The root hash of the Merkle tree.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the proof folds to the root under either odd-node policy.
+    pub fn verify_proof_odd_tolerant(&self, element: H256, proof: Vec<H256>, root: H256) -> bool {
+        let sep = self.config.node_sep.as_bytes();
+        let mut candidates = vec![element];
+        for proof_element in &proof {
+            let promoted = candidates.contains(proof_element);
+            for hash in candidates.iter_mut() {
+                *hash = Self::hash_pair_with(hash, proof_element, sep);
+            }
+            if promoted && !candidates.contains(proof_element) {
+                candidates.push(*proof_element);
+            }
+        }
+        candidates.contains(&root)
+    }
     /// Returns the number of leaves in the Merkle tree.
     ///
     /// This function returns the total number of leaves (i.e., elements) in the Merkle tree.
@@ -132,15 +812,49 @@ impl MerkleTree {
     pub fn leaves_length(&self) -> usize {
         self.leaves
     }
+    /// Returns the number of leaves or entries the tree was built from, before deduplication.
+    ///
+    /// Identical leaves collapse into one, so this is at least `leaves_length()`. Trees
+    /// reloaded from a snapshot or a serialized form count their stored leaves only.
+    pub fn input_length(&self) -> usize {
+        self.inputs
+    }
+    /// Returns the number of inputs dropped as duplicates of another leaf.
+    ///
+    /// # Returns
+    ///
+    /// `input_length() - leaves_length()`.
+    pub fn deduped_count(&self) -> usize {
+        self.inputs - self.leaves
+    }
+    /// Returns whether the element is a leaf of the Merkle tree.
+    ///
+    /// If a Bloom filter was attached with `with_bloom_filter`, it is consulted first so
+    /// that absent elements are usually rejected without searching the leaves.
+    pub fn contains(&self, element: H256) -> bool {
+        if self.config.ordered {
+            return self.elements.contains(&element);
+        }
+        self.position(element).is_some()
+    }
+    /// Returns the leaf hashes in their final sorted and deduplicated order.
+    pub fn sorted_leaves(&self) -> &[H256] {
+        &self.elements
+    }
     /// Computes the hash of a leaf node in a Merkle tree.
     ///
-    /// This function takes the index and leaf data (address and amount) as input,
-    /// concatenates them together, and computes the hash of the resulting byte array.
-    /// The hash is returned as a `H256` value.
+    /// The leaf is `keccak256(keccak256(abi.encode(account, amount)))`, the leaf of
+    /// OpenZeppelin's `StandardMerkleTree` with the encoding `["address", "uint256"]`: the
+    /// address is left-padded to 32 bytes and the amount is a 32-byte big-endian word.
+    /// Hashing twice keeps a leaf from being confused with an internal node, whose preimage
+    /// is also 64 bytes, so proofs verify with OZ's `MerkleProof.verify` as they are.
+    ///
+    /// The leaves are OZ's, but the layout of odd layers is not: a tree of these leaves has
+    /// the root of OZ's `StandardMerkleTree.of` only for some leaf counts. Use
+    /// `StandardMerkleTree` to reproduce OZ's tree for any count.
     ///
     /// # Arguments
     ///
-    /// * `index` - The index of the leaf node in the Merkle tree.
     /// * `leaf_data` - A tuple containing the address (`Address`) and amount (`U256`) of the leaf node.
     ///
     /// # Returns
@@ -148,45 +862,88 @@ impl MerkleTree {
     /// A `H256` value representing the hash of the leaf node.
     pub fn hash_node(leaf_data: (Address, U256)) -> H256 {
         let (account, amount) = leaf_data;
-
-        let mut account_bytes = [0u8; 20];
-        account_bytes.copy_from_slice(account.as_bytes());
-
-        let mut amount_bytes = [0u8; 32];
-        amount.to_big_endian(&mut amount_bytes);
-
         let encoded_data =
             ethabi::encode(&[ethabi::Token::Address(account), ethabi::Token::Uint(amount)]);
-
-        let hashed_data = keccak256(encoded_data);
-
-        let mut concatenated_data = Vec::new();
-        concatenated_data.extend_from_slice(&hashed_data);
-
-        let final_hash = keccak256(concatenated_data);
-        H256::from(final_hash)
+        H256(keccak256(keccak256(encoded_data)))
     }
 
-    fn next_layer(elements: &[H256]) -> Vec<H256> {
+    /// Folds a layer of nodes into the layer above it.
+    ///
+    /// This is the function the tree uses internally to build each layer: adjacent nodes
+    /// are combined with the sorted pair hash, and an odd last node is promoted unchanged.
+    ///
+    /// # Arguments
+    ///
+    /// * `elements` - The nodes of the layer to fold.
+    ///
+    /// # Returns
+    ///
+    /// The parent layer, half the length of `elements` rounded up.
+    pub fn fold_layer(elements: &[H256]) -> Vec<H256> {
+        Self::fold_layer_with(elements, &[])
+    }
+    fn fold_layer_with(elements: &[H256], sep: &[u8]) -> Vec<H256> {
         elements
             .chunks(2)
             .map(|chunk| {
                 if chunk.len() == 2 {
-                    Self::hash_pair(&chunk[0], &chunk[1])
+                    Self::hash_pair_with(&chunk[0], &chunk[1], sep)
                 } else {
-                    // if there are odd layers we hash the last element with itself
+                    // if there are odd layers we promote the last element unchanged
                     *chunk.first().unwrap()
                 }
             })
             .collect()
     }
+    /// Folds a layer of nodes all the way up to a single root.
+    ///
+    /// Repeatedly applies `fold_layer`, so folding the sorted leaves reproduces the tree root.
+    /// Subtree roots can be folded the same way, as long as every subtree covers an aligned,
+    /// power-of-two sized range of leaves (only the last one may be shorter).
+    ///
+    /// # Arguments
+    ///
+    /// * `nodes` - The nodes of the layer to fold.
+    ///
+    /// # Returns
+    ///
+    /// An `Option` containing the root, or `None` if `nodes` is empty.
+    pub fn fold_to_root(mut nodes: Vec<H256>) -> Option<H256> {
+        while nodes.len() > 1 {
+            nodes = Self::fold_layer(&nodes);
+        }
+        nodes.first().copied()
+    }
+
+    fn process_proof(element: H256, proof: &[H256]) -> H256 {
+        Self::process_proof_with(element, proof, &[])
+    }
+
+    /// Folds a proof from the leaf up with the shared fold of `oz_merkle_core`.
+    #[inline]
+    fn process_proof_with(element: H256, proof: &[H256], sep: &[u8]) -> H256 {
+        H256(oz_merkle_core::process_proof(
+            element.0,
+            proof.iter().map(|hash| &hash.0),
+            sep,
+        ))
+    }
+
+    /// Finds a leaf among the sorted leaves; ordered trees have no hash lookups.
+    fn position(&self, element: H256) -> Option<usize> {
+        if self.config.ordered {
+            return None;
+        }
+        if let Some(bloom) = &self.bloom {
+            if !bloom.check(&element) {
+                return None;
+            }
+        }
+        self.elements.binary_search(&element).ok()
+    }
 
     fn hash_pair(a: &H256, b: &H256) -> H256 {
-        let mut pairs = [a, b];
-        // Ensure lexicographical order
-        pairs.sort();
-        let concatenated = [pairs[0].as_bytes(), pairs[1].as_bytes()].concat();
-        H256::from(keccak256(&concatenated))
+        Self::hash_pair_with(a, b, &[])
     }
 }
 
@@ -228,6 +985,31 @@ mod test {
         );
     }
     #[test]
+    fn matches_the_openzeppelin_standard_merkle_tree() {
+        // the example of the @openzeppelin/merkle-tree README
+        let data = vec![
+            (Address::repeat_byte(0x11), U256::exp10(18) * 5),
+            (Address::repeat_byte(0x22), U256::exp10(17) * 25),
+        ];
+        let tree = MerkleTree::new(data.clone());
+        let root = tree.get_root().unwrap();
+        assert_eq!(
+            format!("{:?}", root),
+            "0xd4dee0beab2d53f2cc83e567171bd2820e49898130a22622b10ead383e90bd77"
+        );
+
+        let mut preimage = [0u8; 64];
+        preimage[12..32].copy_from_slice(data[0].0.as_bytes());
+        data[0].1.to_big_endian(&mut preimage[32..]);
+        let leaf = MerkleTree::hash_node(data[0]);
+        assert_eq!(leaf, H256(keccak256(keccak256(preimage))));
+        let proof = tree.get_proof(leaf).unwrap();
+        assert_eq!(proof, vec![MerkleTree::hash_node(data[1])]);
+        assert!(tree.verify_proof(leaf, proof, root));
+        // a single hash of the encoding is not a leaf of the tree
+        assert!(!tree.contains(H256(keccak256(preimage))));
+    }
+    #[test]
     fn get_proof_for_valid_index() {
         let data = (
             Address::from_str("0x00393d62f17b07e64f7cdcdf9bdc2fd925b20bba").unwrap(),
@@ -288,4 +1070,472 @@ mod test {
             "Proof should be valid and verification should succeed"
         );
     }
+
+    #[test]
+    fn verify_reversed_proof() {
+        let data: Vec<(Address, U256)> = (1..=5u64)
+            .map(|i| (Address::from_low_u64_be(i), U256::from(i)))
+            .collect();
+        let tree = MerkleTree::new(data.clone());
+        let root = tree.get_root().unwrap();
+        let node = MerkleTree::hash_node(data[0]);
+        let mut proof = tree.get_proof_reversed(node).unwrap();
+
+        assert!(tree.verify_proof_reversed(node, proof.clone(), root));
+        assert!(!tree.verify_proof(node, proof.clone(), root));
+        proof.reverse();
+        assert_eq!(Some(proof), tree.get_proof(node));
+    }
+
+    #[test]
+    fn from_leaves_tracked_permutation() {
+        let leaves: Vec<H256> = [3u8, 1, 2, 1, 3]
+            .iter()
+            .map(|&b| H256::repeat_byte(b))
+            .collect();
+        let (tree, permutation) = MerkleTree::from_leaves_tracked(leaves.clone());
+
+        assert_eq!(permutation, vec![2, 0, 1, usize::MAX, usize::MAX]);
+        let mut reproduced = vec![H256::zero(); tree.leaves_length()];
+        for (input, &index) in permutation.iter().enumerate() {
+            if index != usize::MAX {
+                reproduced[index] = leaves[input];
+            }
+        }
+        assert_eq!(reproduced, tree.sorted_leaves());
+        assert_eq!(tree.get_root(), MerkleTree::from_leaves(leaves).get_root());
+    }
+
+    #[test]
+    fn fold_shard_roots() {
+        let data: Vec<(Address, U256)> = (1..=10u64)
+            .map(|i| (Address::from_low_u64_be(i), U256::from(i)))
+            .collect();
+        let tree = MerkleTree::new(data);
+        let leaves = tree.sorted_leaves().to_vec();
+
+        assert_eq!(MerkleTree::fold_to_root(leaves.clone()), tree.get_root());
+        let shard_roots: Vec<H256> = leaves
+            .chunks(4)
+            .map(|shard| MerkleTree::fold_to_root(shard.to_vec()).unwrap())
+            .collect();
+        assert_eq!(MerkleTree::fold_to_root(shard_roots), tree.get_root());
+        assert_eq!(MerkleTree::fold_to_root(Vec::new()), None);
+    }
+
+    #[test]
+    fn root_changed_since_previous_root() {
+        let tree = setup_tree();
+        let root = tree.get_root().unwrap();
+
+        assert!(!tree.root_changed_since(root));
+        assert!(tree.root_changed_since(H256::zero()));
+        assert!(MerkleTree::new(Vec::new()).root_changed_since(root));
+    }
+
+    #[test]
+    fn verify_strict_rejects_repeated_elements() {
+        let data: Vec<(Address, U256)> = (1..=4u64)
+            .map(|i| (Address::from_low_u64_be(i), U256::from(i)))
+            .collect();
+        let tree = MerkleTree::new(data.clone());
+        let node = MerkleTree::hash_node(data[0]);
+        let proof = tree.get_proof(node).unwrap();
+        assert!(tree.verify_proof_strict(node, proof.clone(), tree.get_root().unwrap()));
+
+        let mut padded = proof.clone();
+        padded.push(proof[0]);
+        let padded_root = MerkleTree::process_proof(node, &padded);
+        assert!(tree.verify_proof(node, padded.clone(), padded_root));
+        assert!(!tree.verify_proof_strict(node, padded, padded_root));
+    }
+
+    #[test]
+    fn verify_exact_depth_rejects_other_lengths() {
+        let data: Vec<(Address, U256)> = (1..=8u64)
+            .map(|i| (Address::from_low_u64_be(i), U256::from(i)))
+            .collect();
+        let tree = MerkleTree::new(data.clone());
+        let root = tree.get_root().unwrap();
+        let node = MerkleTree::hash_node(data[2]);
+        let proof = tree.get_proof(node).unwrap();
+
+        assert!(tree.verify_proof_exact_depth(node, proof.clone(), root, 3));
+        assert!(!tree.verify_proof_exact_depth(node, proof.clone(), root, 4));
+
+        let mut padded = proof.clone();
+        padded.push(H256::repeat_byte(0x42));
+        let padded_root = MerkleTree::process_proof(node, &padded);
+        assert!(tree.verify_proof(node, padded.clone(), padded_root));
+        assert!(!tree.verify_proof_exact_depth(node, padded, padded_root, 3));
+    }
+
+    #[test]
+    fn from_balances_is_independent_of_hasher_seed() {
+        use std::collections::hash_map::RandomState;
+
+        let data: Vec<(Address, U256)> = (1..=40u64)
+            .map(|i| (Address::from_low_u64_be(i * 31), U256::from(i * 1_000)))
+            .collect();
+        let mut first = HashMap::with_hasher(RandomState::new());
+        let mut second = HashMap::with_hasher(RandomState::new());
+        first.extend(data.iter().copied());
+        second.extend(data.iter().rev().copied());
+
+        let root = MerkleTree::new(data).get_root();
+        assert_eq!(MerkleTree::from_balances(first).get_root(), root);
+        let tree = MerkleTree::from_balances(second);
+        assert_eq!(tree.get_root(), root);
+        assert_eq!(tree.entries().len(), 40);
+    }
+
+    #[test]
+    fn proof_bytes_match_hash_proofs() {
+        let data: Vec<(Address, U256)> = (1..=7u64)
+            .map(|i| (Address::from_low_u64_be(i), U256::from(i)))
+            .collect();
+        let tree = MerkleTree::new(data.clone());
+        let root = tree.get_root().unwrap();
+        for leaf_data in data {
+            let leaf = MerkleTree::hash_node(leaf_data);
+            let proof = tree.get_proof(leaf).unwrap();
+            let bytes = tree.get_proof_bytes(leaf.0).unwrap();
+            assert_eq!(bytes, proof.iter().map(|hash| hash.0).collect::<Vec<_>>());
+            assert!(tree.verify_proof_bytes(leaf.0, &bytes, root.0));
+            assert!(!tree.verify_proof_bytes(leaf.0, &bytes, [0; 32]));
+        }
+        assert_eq!(tree.get_proof_bytes([0; 32]), None);
+    }
+
+    #[test]
+    fn verify_nonzero_rejects_zero_elements() {
+        let data: Vec<(Address, U256)> = (1..=6u64)
+            .map(|i| (Address::from_low_u64_be(i), U256::from(i)))
+            .collect();
+        let tree = MerkleTree::new(data.clone());
+        let root = tree.get_root().unwrap();
+        let node = MerkleTree::hash_node(data[1]);
+        let proof = tree.get_proof(node).unwrap();
+        assert_eq!(
+            tree.verify_proof_nonzero(node, proof.clone(), root),
+            Ok(true)
+        );
+        assert_eq!(
+            tree.verify_proof_nonzero(node, proof.clone(), H256::zero()),
+            Ok(false)
+        );
+
+        let mut zeroed = proof;
+        zeroed[1] = H256::zero();
+        assert_eq!(
+            tree.verify_proof_nonzero(node, zeroed, root),
+            Err(MerkleError::ZeroProofElement(1))
+        );
+    }
+
+    #[test]
+    fn verify_proof_inline_matches_verify_proof() {
+        for count in [1u64, 2, 5, 16, 33] {
+            let tree = MerkleTree::new(
+                (1..=count)
+                    .map(|i| (Address::from_low_u64_be(i), U256::from(i)))
+                    .collect(),
+            );
+            let root = tree.get_root().unwrap();
+            for (i, leaf) in tree.sorted_leaves().iter().enumerate() {
+                let mut proof = tree.get_proof(*leaf).unwrap();
+                assert!(MerkleTree::verify_proof_inline(*leaf, &proof, root));
+                if let Some(first) = proof.first_mut() {
+                    first.0[i % 32] ^= 1;
+                }
+                let mut other = *leaf;
+                other.0[31] ^= 1;
+                for (element, proof) in [(*leaf, &proof), (other, &proof)] {
+                    assert_eq!(
+                        MerkleTree::verify_proof_inline(element, proof, root),
+                        tree.verify_proof(element, proof.clone(), root)
+                    );
+                }
+            }
+        }
+        let data: Vec<(Address, U256)> = (1..=5u64)
+            .map(|i| (Address::from_low_u64_be(i), U256::from(i)))
+            .collect();
+        let separated = MerkleTree::new_with_node_sep(data, NodeSep(b"sep".to_vec()));
+        let root = separated.get_root().unwrap();
+        let leaf = separated.sorted_leaves()[0];
+        let proof = separated.get_proof(leaf).unwrap();
+        assert!(separated.verify_proof(leaf, proof.clone(), root));
+        assert!(!MerkleTree::verify_proof_inline(leaf, &proof, root));
+
+        let (a, b) = (H256::repeat_byte(1), H256::repeat_byte(2));
+        let reference = H256(keccak256([a.as_bytes(), b.as_bytes()].concat()));
+        assert_eq!(MerkleTree::hash_pair(&b, &a), reference);
+        assert_eq!(MerkleTree::hash_pair_with(&b, &a, &[]), reference);
+    }
+
+    #[test]
+    fn verify_proof_observed_reports_each_level() {
+        let tree = MerkleTree::new(
+            (1..=11u64)
+                .map(|i| (Address::from_low_u64_be(i), U256::from(i)))
+                .collect(),
+        );
+        let root = tree.get_root().unwrap();
+        let leaf = tree.sorted_leaves()[0];
+        let proof = tree.get_proof(leaf).unwrap();
+
+        let mut observed = Vec::new();
+        assert!(
+            tree.verify_proof_observed(leaf, proof.clone(), root, |level, hash| {
+                observed.push((level, hash))
+            })
+        );
+        assert_eq!(observed.len(), proof.len());
+        assert_eq!(observed.last().unwrap().1, root);
+        assert_eq!(observed[0], (0, MerkleTree::hash_pair(&leaf, &proof[0])));
+        let levels: Vec<usize> = observed.iter().map(|(level, _)| *level).collect();
+        assert_eq!(levels, (0..proof.len()).collect::<Vec<_>>());
+
+        let mut tampered = proof;
+        tampered[2].0[0] ^= 1;
+        let mut diverged = Vec::new();
+        assert!(
+            !tree.verify_proof_observed(leaf, tampered, root, |level, hash| {
+                diverged.push((level, hash))
+            })
+        );
+        assert_eq!(diverged[..2], observed[..2]);
+        assert_ne!(diverged[2], observed[2]);
+    }
+
+    #[test]
+    fn odd_tolerant_accepts_both_odd_node_policies() {
+        let tree = MerkleTree::new(
+            (1..=5u64)
+                .map(|i| (Address::from_low_u64_be(i), U256::from(i)))
+                .collect(),
+        );
+        let leaves = tree.sorted_leaves();
+        let last = leaves[4];
+        let pair = MerkleTree::hash_pair;
+
+        // a tree that pairs the odd node with itself instead of promoting it
+        let (left, right) = (pair(&leaves[0], &leaves[1]), pair(&leaves[2], &leaves[3]));
+        let upper = pair(&left, &right);
+        let doubled = pair(&last, &last);
+        let duplicated_root = pair(&upper, &pair(&doubled, &doubled));
+        let duplicated_proof = vec![last, doubled, upper];
+
+        let promoted_root = tree.get_root().unwrap();
+        let promoted_proof = tree.get_proof(last).unwrap();
+        assert_eq!(promoted_proof, vec![upper]);
+        let placeholder_proof = vec![last, last, upper];
+
+        for (proof, root) in [
+            (&promoted_proof, promoted_root),
+            (&placeholder_proof, promoted_root),
+            (&duplicated_proof, duplicated_root),
+        ] {
+            assert!(tree.verify_proof_odd_tolerant(last, proof.clone(), root));
+        }
+        assert!(!tree.verify_proof(last, placeholder_proof.clone(), promoted_root));
+        assert!(!tree.verify_proof_odd_tolerant(last, duplicated_proof, promoted_root));
+        assert!(!tree.verify_proof_odd_tolerant(leaves[0], placeholder_proof, promoted_root));
+        for leaf in leaves {
+            let proof = tree.get_proof(*leaf).unwrap();
+            assert!(tree.verify_proof_odd_tolerant(*leaf, proof, promoted_root));
+        }
+    }
+
+    #[test]
+    fn from_encoded_leaves_hashes_preimages() {
+        let data = [
+            (Address::from_low_u64_be(1), U256::from(100)),
+            (Address::from_low_u64_be(2), U256::from(200)),
+        ];
+        let preimages: Vec<Vec<u8>> = data
+            .iter()
+            .map(|(account, amount)| {
+                ethabi::encode(&[
+                    ethabi::Token::Address(*account),
+                    ethabi::Token::Uint(*amount),
+                ])
+            })
+            .collect();
+
+        let doubled = MerkleTree::from_encoded_leaves(preimages.clone(), true);
+        assert_eq!(
+            doubled.get_root(),
+            MerkleTree::new(data.to_vec()).get_root()
+        );
+        assert_eq!(
+            doubled.get_root(),
+            Some(
+                H256::from_str(
+                    "0x6e43a2ea21587c6d220455cb2e43c6dadc504d5b71792b83a599113ad25f649a"
+                )
+                .unwrap()
+            )
+        );
+
+        let single = MerkleTree::from_encoded_leaves(preimages.clone(), false);
+        let (a, b) = (
+            H256(keccak256(&preimages[0])),
+            H256(keccak256(&preimages[1])),
+        );
+        assert_eq!(single.get_root(), Some(MerkleTree::hash_pair(&a, &b)));
+    }
+
+    #[test]
+    fn build_returns_the_root() {
+        let data = vec![
+            (Address::from_low_u64_be(1), U256::from(10)),
+            (Address::from_low_u64_be(2), U256::from(20)),
+            (Address::from_low_u64_be(3), U256::from(30)),
+        ];
+        let (tree, root) = MerkleTree::build(data.clone());
+        assert_eq!(root, tree.get_root().unwrap());
+        assert_eq!(Some(root), MerkleTree::new(data).get_root());
+    }
+
+    #[test]
+    #[should_panic(expected = "without entries")]
+    fn build_panics_on_empty_data() {
+        MerkleTree::build(Vec::new());
+    }
+
+    #[test]
+    fn input_length_counts_duplicates() {
+        let (a, b) = (Address::from_low_u64_be(1), Address::from_low_u64_be(2));
+        let data = vec![
+            (a, U256::one()),
+            (b, U256::one()),
+            (a, U256::one()),
+            (a, U256::from(2)),
+            (b, U256::one()),
+        ];
+        let tree = MerkleTree::new(data.clone());
+        assert_eq!(tree.input_length(), 5);
+        assert_eq!(tree.leaves_length(), 3);
+        assert_eq!(tree.deduped_count(), 2);
+
+        let leaves: Vec<H256> = data.into_iter().map(MerkleTree::hash_node).collect();
+        let from_leaves = MerkleTree::from_leaves(leaves.clone());
+        assert_eq!(from_leaves.input_length(), 5);
+        assert_eq!(from_leaves.deduped_count(), 2);
+        assert_eq!(MerkleTree::from_leaves_tracked(leaves).0.input_length(), 5);
+        assert_eq!(
+            MerkleTree::from_leaves(tree.sorted_leaves().to_vec()).deduped_count(),
+            0
+        );
+    }
+
+    #[test]
+    fn trees_sort_by_root() {
+        let mut trees: Vec<MerkleTree> = (1..=6u64)
+            .map(|round| {
+                MerkleTree::new(
+                    (1..=round)
+                        .map(|i| (Address::from_low_u64_be(i), U256::from(round)))
+                        .collect(),
+                )
+            })
+            .collect();
+        trees.push(MerkleTree::from_leaves(Vec::new()));
+        trees.sort();
+
+        assert_eq!(trees[0].get_root(), None);
+        let roots: Vec<H256> = trees[1..]
+            .iter()
+            .map(|tree| tree.get_root().unwrap())
+            .collect();
+        assert!(roots.windows(2).all(|pair| pair[0] < pair[1]));
+
+        let set: std::collections::BTreeSet<MerkleTree> = trees.iter().cloned().collect();
+        assert_eq!(set.len(), 7);
+        let same_root = MerkleTree::from_leaves(trees[3].sorted_leaves().to_vec());
+        assert!(set.contains(&same_root));
+    }
+
+    #[test]
+    fn get_proof_strict_rejects_internal_nodes() {
+        let data: Vec<(Address, U256)> = (1..=5u64)
+            .map(|i| (Address::from_low_u64_be(i), U256::from(i)))
+            .collect();
+        let tree = MerkleTree::new(data.clone());
+        let leaves = tree.sorted_leaves();
+        let internal = MerkleTree::hash_pair(&leaves[0], &leaves[1]);
+
+        assert_eq!(
+            tree.get_proof_strict(internal),
+            Err(MerkleError::NotALeaf(internal))
+        );
+        assert_eq!(tree.get_proof(internal), None);
+        assert_eq!(
+            tree.get_proof_strict(tree.get_root().unwrap()),
+            Err(MerkleError::NotALeaf(tree.get_root().unwrap()))
+        );
+        // the fifth leaf is promoted unchanged, but it is still a leaf
+        assert_eq!(
+            tree.get_proof_strict(leaves[4]),
+            Ok(tree.get_proof(leaves[4]))
+        );
+        assert_eq!(tree.get_proof_strict(H256::repeat_byte(7)), Ok(None));
+    }
+
+    fn synthetic(count: usize) -> Vec<(Address, U256)> {
+        (1..=count as u64)
+            .map(|i| (Address::from_low_u64_be(i), U256::from(i * 7)))
+            .collect()
+    }
+    #[test]
+    fn every_proof_verifies_for_small_trees() {
+        for count in 1..=64 {
+            let tree = MerkleTree::new(synthetic(count));
+            let root = tree.get_root().unwrap();
+            assert_eq!(tree.verify_all_proofs(), Ok(()), "{} leaves", count);
+            for (index, leaf) in tree.sorted_leaves().iter().enumerate() {
+                let proof = tree.get_proof(*leaf).unwrap();
+                // one sibling per layer, except where the node on the path is promoted
+                let mut expected = 0;
+                let (mut node, mut width) = (index, count);
+                while width > 1 {
+                    expected += usize::from(node ^ 1 < width);
+                    node /= 2;
+                    width = width.div_ceil(2);
+                }
+                assert_eq!(proof.len(), expected, "leaf {} of {}", index, count);
+                assert!(tree.verify_proof(*leaf, proof, root));
+            }
+
+            let ordered =
+                MerkleTree::new_ordered(tree.sorted_leaves().iter().rev().copied().collect());
+            assert_eq!(
+                ordered.verify_all_proofs(),
+                Ok(()),
+                "{} ordered leaves",
+                count
+            );
+        }
+        assert_eq!(MerkleTree::new(Vec::new()).verify_all_proofs(), Ok(()));
+    }
+    #[test]
+    fn every_proof_verifies_for_random_sizes() {
+        for seed in 0..6u64 {
+            let digest = keccak256(seed.to_be_bytes());
+            let count = 65 + usize::from(u16::from_be_bytes([digest[0], digest[1]])) % 3000;
+            let tree = MerkleTree::new(synthetic(count));
+            assert_eq!(tree.verify_all_proofs(), Ok(()), "{} leaves", count);
+        }
+    }
+    #[test]
+    fn verify_all_proofs_reports_the_first_broken_leaf() {
+        // the last of 11 leaves is promoted without a pair and becomes the sibling of
+        // leaves 8 and 9 one layer up
+        let mut tree = MerkleTree::new(synthetic(11));
+        assert_eq!(tree.layers[1][5], tree.layers[0][10]);
+        tree.layers[1][5].0[0] ^= 1;
+        assert_eq!(tree.verify_all_proofs(), Err((8, tree.sorted_leaves()[8])));
+    }
 }
@@ -0,0 +1,108 @@
+use crate::{MerkleError, MerkleTree};
+use ethers::types::H256;
+use std::cmp::Ordering;
+
+/// Merges two ascending lists of leaf hashes into one ascending list without duplicates.
+///
+/// This combines pre-sorted shards in linear time, ready for `MerkleTree::from_sorted_leaves`.
+///
+/// # Arguments
+///
+/// * `a` - Leaf hashes in ascending order.
+/// * `b` - Leaf hashes in ascending order.
+///
+/// # Returns
+///
+/// The union of both lists, sorted and deduplicated.
+pub fn merge_sorted(a: &[H256], b: &[H256]) -> Vec<H256> {
+    let mut merged: Vec<H256> = Vec::with_capacity(a.len() + b.len());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        let next = match (a.get(i), b.get(j)) {
+            (Some(x), Some(y)) => match x.cmp(y) {
+                Ordering::Less => {
+                    i += 1;
+                    *x
+                }
+                Ordering::Greater => {
+                    j += 1;
+                    *y
+                }
+                Ordering::Equal => {
+                    i += 1;
+                    j += 1;
+                    *x
+                }
+            },
+            (Some(x), None) => {
+                i += 1;
+                *x
+            }
+            (None, Some(y)) => {
+                j += 1;
+                *y
+            }
+            (None, None) => unreachable!(),
+        };
+        if merged.last() != Some(&next) {
+            merged.push(next);
+        }
+    }
+    merged
+}
+
+impl MerkleTree {
+    /// Constructs a new Merkle tree from leaf hashes that are already sorted and deduplicated.
+    ///
+    /// This skips the sort of `from_leaves`, e.g. for the output of `merge_sorted`.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaves` - Leaf hashes in strictly ascending order.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the tree, or `MerkleError::UnsortedLeaves` with the index of
+    /// the first leaf that is not greater than its predecessor.
+    pub fn from_sorted_leaves(leaves: Vec<H256>) -> Result<Self, MerkleError> {
+        if let Some(index) = leaves.windows(2).position(|pair| pair[0] >= pair[1]) {
+            return Err(MerkleError::UnsortedLeaves(index + 1));
+        }
+        Ok(Self::from_sorted_elements(leaves))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn hashes(bytes: &[u8]) -> Vec<H256> {
+        bytes.iter().map(|byte| H256::repeat_byte(*byte)).collect()
+    }
+
+    #[test]
+    fn merge_sorted_dedups_overlapping_shards() {
+        let a = hashes(&[1, 3, 5, 5, 7, 9]);
+        let b = hashes(&[2, 3, 4, 9, 10]);
+        let merged = merge_sorted(&a, &b);
+
+        assert_eq!(merged, hashes(&[1, 2, 3, 4, 5, 7, 9, 10]));
+        assert_eq!(merge_sorted(&a, &[]), hashes(&[1, 3, 5, 7, 9]));
+        assert_eq!(
+            MerkleTree::from_sorted_leaves(merged).unwrap().get_root(),
+            MerkleTree::from_leaves([a, b].concat()).get_root()
+        );
+    }
+
+    #[test]
+    fn from_sorted_leaves_rejects_unsorted_input() {
+        assert_eq!(
+            MerkleTree::from_sorted_leaves(hashes(&[1, 2, 2, 3])).unwrap_err(),
+            MerkleError::UnsortedLeaves(2)
+        );
+        assert_eq!(
+            MerkleTree::from_sorted_leaves(hashes(&[3, 1])).unwrap_err(),
+            MerkleError::UnsortedLeaves(1)
+        );
+    }
+}
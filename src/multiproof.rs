@@ -0,0 +1,565 @@
+use crate::standard::{parent, sibling};
+use crate::{MerkleTree, StandardMerkleTree};
+use ethers::types::H256;
+use std::collections::VecDeque;
+
+/// A proof of several leaves at once, in the format of OpenZeppelin's
+/// `MerkleProof.multiProofVerify`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MultiProof {
+    /// The proven leaves, in the order the verifier consumes them.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::hashes"))]
+    pub leaves: Vec<H256>,
+    /// The sibling hashes that are not computed from the leaves, in the order they are used.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::hashes"))]
+    pub proof: Vec<H256>,
+    /// For every hashing step, whether the second hash is taken from the leaves and
+    /// computed hashes (`true`) or from `proof` (`false`).
+    pub proof_flags: Vec<bool>,
+}
+
+/// Splits a multiproof into the `proof` and `proofFlags` arguments of OZ's verifier.
+impl From<MultiProof> for (Vec<H256>, Vec<bool>) {
+    fn from(multi_proof: MultiProof) -> Self {
+        (multi_proof.proof, multi_proof.proof_flags)
+    }
+}
+
+/// Verifies a multiproof given as the `proof` and `proofFlags` arguments of OpenZeppelin's
+/// `MerkleProof.multiProofVerify`.
+///
+/// This is `StandardMerkleTree::verify_multi_proof` for a multiproof split with
+/// `get_multi_proof_parts`, and verifies the multiproofs of both tree types.
+///
+/// # Arguments
+///
+/// * `leaves` - The proven leaves, ascending, as in `MultiProof::leaves`.
+/// * `proof` - The sibling hashes not computed from the leaves.
+/// * `flags` - The flag of every hashing step.
+/// * `root` - The expected root.
+///
+/// # Returns
+///
+/// `true` if the leaves, proof and flags lead to `root`; `false` if they lead to another
+/// root or are inconsistent.
+pub fn verify_multi_proof(leaves: &[H256], proof: Vec<H256>, flags: Vec<bool>, root: H256) -> bool {
+    StandardMerkleTree::process_multi_proof(leaves, &proof, &flags) == Some(root)
+}
+
+impl MerkleTree {
+    /// Retrieves a proof of several leaves against the root, for OpenZeppelin's
+    /// `MerkleProof.multiProofVerify`.
+    ///
+    /// OZ's verifier replays a multiproof with a queue in which every step hashes two
+    /// nodes, see `StandardMerkleTree::get_multi_proof`. This tree promotes the last node
+    /// of an odd layer to the layer above without hashing it, and a promoted leaf or node
+    /// can reach the front of that queue before the node it is hashed with has been
+    /// computed. Such sets of leaves cannot be proven in OZ's format: for them, and only
+    /// them, this returns `None`. When the number of leaves is a power of two or three
+    /// times one, the tree has OZ's layout, every set is proven and the multiproof is the
+    /// one `StandardMerkleTree` gives for the same leaves. `StandardMerkleTree` proves
+    /// every set for any number of leaves.
+    ///
+    /// The leaves of the result are ascending, whatever order they were requested in, and
+    /// a leaf requested twice is proven once.
+    ///
+    /// # Arguments
+    ///
+    /// * `elements` - The leaf hashes to prove, in any order.
+    ///
+    /// # Returns
+    ///
+    /// An `Option` containing the multiproof, or `None` if `elements` is empty, holds a
+    /// hash that is not a leaf of the tree, or cannot be proven in OZ's format as above.
+    /// Trees with a node separator or built with `new_ordered` have no multiproofs, as OZ's
+    /// verifier hashes sorted pairs with nothing in between.
+    pub fn get_multi_proof(&self, elements: &[H256]) -> Option<MultiProof> {
+        if !self.config.node_sep.as_bytes().is_empty() {
+            return None;
+        }
+        let mut indices = elements
+            .iter()
+            .map(|element| self.position(*element))
+            .collect::<Option<Vec<usize>>>()?;
+        if indices.is_empty() {
+            return None;
+        }
+        indices.sort_unstable();
+        indices.dedup();
+
+        let top = self.layers.len() - 1;
+        // a node is named by its layer and index in the highest layer it is promoted to,
+        // where it is finally hashed with a sibling
+        let promote = |(mut layer, mut index): (usize, usize)| {
+            while layer < top && index % 2 == 0 && index == self.layers[layer].len() - 1 {
+                layer += 1;
+                index /= 2;
+            }
+            (layer, index)
+        };
+        let mut multi_proof = MultiProof {
+            leaves: indices.iter().map(|&i| self.elements[i]).collect(),
+            ..Default::default()
+        };
+        let mut queue: VecDeque<(usize, usize)> =
+            indices.into_iter().map(|i| promote((0, i))).collect();
+        while let Some((layer, index)) = queue.pop_front().filter(|&(layer, _)| layer < top) {
+            let sibling = (layer, index ^ 1);
+            if queue.front() == Some(&sibling) {
+                queue.pop_front();
+                multi_proof.proof_flags.push(true);
+            } else {
+                multi_proof.proof.push(self.layers[layer][index ^ 1]);
+                multi_proof.proof_flags.push(false);
+            }
+            queue.push_back(promote((layer + 1, index / 2)));
+        }
+        // a promoted node hashed out of order leaves a proof the verifier rejects
+        let root = StandardMerkleTree::process_multi_proof(
+            &multi_proof.leaves,
+            &multi_proof.proof,
+            &multi_proof.proof_flags,
+        );
+        (root == self.get_root()).then_some(multi_proof)
+    }
+
+    /// Retrieves a multiproof like `get_multi_proof`, split into the `proof` and
+    /// `proofFlags` arguments of OpenZeppelin's verifier.
+    ///
+    /// # Returns
+    ///
+    /// An `Option` containing the proof hashes and flags, for the requested leaves sorted
+    /// ascending and deduplicated, or `None` as for `get_multi_proof`.
+    pub fn get_multi_proof_parts(&self, elements: &[H256]) -> Option<(Vec<H256>, Vec<bool>)> {
+        self.get_multi_proof(elements).map(Into::into)
+    }
+
+    /// Verifies a multiproof like OpenZeppelin's `MerkleProof.multiProofVerify`, as
+    /// `StandardMerkleTree::verify_multi_proof` does.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaves` - The proven leaves, in the order of `multi_proof.leaves`.
+    /// * `multi_proof` - The multiproof; only its proof and flags are used.
+    /// * `root` - The expected root.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the leaves, proof and flags lead to `root`.
+    pub fn verify_multi_proof(leaves: &[H256], multi_proof: &MultiProof, root: H256) -> bool {
+        StandardMerkleTree::verify_multi_proof(leaves, multi_proof, root)
+    }
+}
+
+impl StandardMerkleTree {
+    /// Retrieves a proof of several leaves against the root, like OpenZeppelin's
+    /// `getMultiProof`, for `MerkleProof.multiProofVerify`.
+    ///
+    /// The nodes known so far are kept in a queue, deepest first, starting with the leaves
+    /// in descending `treeIndex` order. When the sibling of a node is the next node in the
+    /// queue, the two are hashed together and the flag is `true`; otherwise the sibling is
+    /// taken from the proof and the flag is `false`. The parent joins the back of the
+    /// queue. In OZ's flat-array layout every subset of the leaves can be proven.
+    ///
+    /// The leaves of the result are in the order the verifier consumes them, ascending by
+    /// hash, whatever order they were requested in, and a leaf requested twice is proven
+    /// once. A single leaf yields its ordinary proof with every flag `false`, and all the
+    /// leaves yield an empty proof with every flag `true`.
+    ///
+    /// # Arguments
+    ///
+    /// * `elements` - The leaf hashes to prove, in any order.
+    ///
+    /// # Returns
+    ///
+    /// An `Option` containing the multiproof, or `None` if `elements` is empty or holds a
+    /// hash that is not a leaf of the tree.
+    pub fn get_multi_proof(&self, elements: &[H256]) -> Option<MultiProof> {
+        let mut indices = elements
+            .iter()
+            .map(|element| self.tree_index(*element))
+            .collect::<Option<Vec<usize>>>()?;
+        if indices.is_empty() {
+            return None;
+        }
+        indices.sort_unstable_by(|a, b| b.cmp(a));
+        indices.dedup();
+
+        let tree = self.tree();
+        let mut multi_proof = MultiProof {
+            leaves: indices.iter().map(|&i| tree[i]).collect(),
+            ..Default::default()
+        };
+        let mut queue = VecDeque::from(indices);
+        while let Some(node) = queue.pop_front().filter(|&node| node > 0) {
+            let sibling = sibling(node);
+            if queue.front() == Some(&sibling) {
+                queue.pop_front();
+                multi_proof.proof_flags.push(true);
+            } else {
+                multi_proof.proof.push(tree[sibling]);
+                multi_proof.proof_flags.push(false);
+            }
+            queue.push_back(parent(node));
+        }
+        Some(multi_proof)
+    }
+
+    /// Retrieves a multiproof like `get_multi_proof`, split into the `proof` and
+    /// `proofFlags` arguments of OpenZeppelin's verifier, like the result of OZ's
+    /// `getMultiProof` without its `leaves`.
+    ///
+    /// # Returns
+    ///
+    /// An `Option` containing the proof hashes and flags, for the requested leaves sorted
+    /// ascending and deduplicated, or `None` as for `get_multi_proof`.
+    pub fn get_multi_proof_parts(&self, elements: &[H256]) -> Option<(Vec<H256>, Vec<bool>)> {
+        self.get_multi_proof(elements).map(Into::into)
+    }
+
+    /// Verifies a multiproof like OpenZeppelin's `MerkleProof.multiProofVerify`.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaves` - The proven leaves, in the order of `multi_proof.leaves`.
+    /// * `multi_proof` - The multiproof; only its proof and flags are used.
+    /// * `root` - The expected root.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the leaves, proof and flags lead to `root`; `false` if they lead to
+    /// another root or are inconsistent.
+    pub fn verify_multi_proof(leaves: &[H256], multi_proof: &MultiProof, root: H256) -> bool {
+        Self::process_multi_proof(leaves, &multi_proof.proof, &multi_proof.proof_flags)
+            == Some(root)
+    }
+
+    /// Computes the root of a multiproof like OpenZeppelin's `MerkleProof.processMultiProof`.
+    ///
+    /// This replays `get_multi_proof` on hashes: a queue starts with the leaves, and every
+    /// flag hashes the front of the queue with the next node of the queue if set, or with
+    /// the next hash of `proof`, pushing the parent to the back.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaves` - The proven leaves, in the order of `MultiProof::leaves`.
+    /// * `proof` - The sibling hashes not computed from the leaves.
+    /// * `flags` - The flag of every hashing step.
+    ///
+    /// # Returns
+    ///
+    /// An `Option` containing the computed root, or `None` if the lengths do not add up, a
+    /// step reads past the queue or the proof, or a proof hash is left unused.
+    pub fn process_multi_proof(leaves: &[H256], proof: &[H256], flags: &[bool]) -> Option<H256> {
+        if leaves.len() + proof.len() != flags.len() + 1 {
+            return None;
+        }
+        let mut queue: VecDeque<H256> = leaves.iter().copied().collect();
+        let mut proof = proof.iter().copied();
+        for &flag in flags {
+            let node = queue.pop_front()?;
+            let sibling = if flag {
+                queue.pop_front()?
+            } else {
+                proof.next()?
+            };
+            queue.push_back(MerkleTree::hash_pair(&node, &sibling));
+        }
+        // with no flags, the root is the single leaf or the single proof hash
+        let root = queue.pop_back().or_else(|| proof.next())?;
+        proof.next().is_none().then_some(root)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ethers::types::{Address, U256};
+    use std::str::FromStr;
+
+    fn data(count: u64) -> Vec<(Address, U256)> {
+        (1..=count)
+            .map(|i| (Address::from_low_u64_be(i), U256::exp10(18) * i))
+            .collect()
+    }
+
+    fn tree(count: u64) -> StandardMerkleTree {
+        StandardMerkleTree::of(data(count)).unwrap()
+    }
+
+    /// Every non-empty subset of `leaves`, in descending order.
+    fn subsets(leaves: &[H256]) -> impl Iterator<Item = Vec<H256>> + '_ {
+        (1..1u32 << leaves.len()).map(move |mask| {
+            (0..leaves.len())
+                .rev()
+                .filter(|i| mask & (1 << i) != 0)
+                .map(|i| leaves[i])
+                .collect()
+        })
+    }
+
+    /// The leaf hashes of a tree, ascending.
+    fn leaves(tree: &StandardMerkleTree) -> Vec<H256> {
+        tree.tree()[tree.len() - 1..]
+            .iter()
+            .rev()
+            .copied()
+            .collect()
+    }
+
+    fn h256(hex: &str) -> H256 {
+        H256::from_str(hex).unwrap()
+    }
+
+    #[test]
+    fn matches_openzeppelin_multiproofs() {
+        // the output of OZ's getMultiProof for the 5-leaf tree of `standard.rs`, computed
+        // with a transcription of @openzeppelin/merkle-tree's core.ts; tests/oz_vectors.rs
+        // compares every multiproof of trees of up to 9 leaves with the package itself
+        let five = tree(5);
+        let [l4, l3, l2, l1, l0] = [4, 5, 6, 7, 8].map(|i| five.tree()[i]);
+        let value = |i: usize| five.tree()[five.values()[i].tree_index];
+
+        let all = five.get_multi_proof(&leaves(&five)).unwrap();
+        assert_eq!(
+            all,
+            MultiProof {
+                leaves: vec![l0, l1, l2, l3, l4],
+                proof: Vec::new(),
+                proof_flags: vec![true; 4],
+            }
+        );
+        assert_eq!(
+            five.get_multi_proof(&[value(3), value(0)]).unwrap(),
+            MultiProof {
+                leaves: vec![l2, l3],
+                proof: vec![h256(
+                    "0x38a644aa6fe72298c50692076fb175996dcedf8066a4ffe567351c606badf865"
+                )],
+                proof_flags: vec![true, false],
+            }
+        );
+        assert_eq!(
+            five.get_multi_proof(&[value(4), value(1), value(2)])
+                .unwrap(),
+            MultiProof {
+                leaves: vec![l0, l1, l4],
+                proof: vec![h256(
+                    "0x762999e645e450d1ed0f1cf08a900c645ac320d5f4a81ae0a4e4320234365e66"
+                )],
+                proof_flags: vec![true, true, false],
+            }
+        );
+
+        // the first and third of four leaves
+        let four = tree(4);
+        let leaves = leaves(&four);
+        assert_eq!(
+            four.get_multi_proof(&[leaves[2], leaves[0]]).unwrap(),
+            MultiProof {
+                leaves: vec![leaves[0], leaves[2]],
+                proof: vec![leaves[1], leaves[3]],
+                proof_flags: vec![false, false, true],
+            }
+        );
+    }
+
+    #[test]
+    fn single_leaves_reduce_to_ordinary_proofs() {
+        for count in 1..=9 {
+            let tree = tree(count);
+            for leaf in leaves(&tree) {
+                let multi_proof = tree.get_multi_proof(&[leaf]).unwrap();
+                assert_eq!(Some(multi_proof.proof.clone()), tree.get_proof(leaf));
+                assert!(multi_proof.proof_flags.iter().all(|flag| !flag));
+                assert!(StandardMerkleTree::verify_multi_proof(
+                    &[leaf],
+                    &multi_proof,
+                    tree.root()
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn every_subset_is_proven() {
+        for count in 1..=9 {
+            let tree = tree(count);
+            let root = tree.root();
+            let leaves = leaves(&tree);
+
+            let all = tree.get_multi_proof(&leaves).unwrap();
+            assert!(all.proof.is_empty());
+            assert_eq!(all.proof_flags, vec![true; leaves.len() - 1]);
+
+            for subset in subsets(&leaves) {
+                let multi_proof = tree.get_multi_proof(&subset).unwrap();
+                assert_eq!(multi_proof.leaves.len(), subset.len());
+                assert!(multi_proof.leaves.windows(2).all(|pair| pair[0] < pair[1]));
+                assert!(StandardMerkleTree::verify_multi_proof(
+                    &multi_proof.leaves,
+                    &multi_proof,
+                    root
+                ));
+
+                let mut tampered = multi_proof.clone();
+                tampered.leaves[0].0[0] ^= 1;
+                assert!(!StandardMerkleTree::verify_multi_proof(
+                    &tampered.leaves,
+                    &multi_proof,
+                    root
+                ));
+                if let Some(flag) = tampered.proof_flags.first_mut() {
+                    *flag = !*flag;
+                    assert!(!StandardMerkleTree::verify_multi_proof(
+                        &multi_proof.leaves,
+                        &tampered,
+                        root
+                    ));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_leaves_are_rejected() {
+        let tree = tree(6);
+        let leaves = leaves(&tree);
+        assert_eq!(tree.get_multi_proof(&[]), None);
+        assert_eq!(tree.get_multi_proof(&[leaves[0], H256::zero()]), None);
+        assert_eq!(tree.get_multi_proof(&[H256::zero(), H256::zero()]), None);
+        assert_eq!(tree.get_multi_proof(&[tree.root()]), None);
+    }
+
+    #[test]
+    fn inconsistent_multiproofs_have_no_root() {
+        let tree = tree(5);
+        let leaves = leaves(&tree);
+        let process = StandardMerkleTree::process_multi_proof;
+        assert_eq!(process(&[leaves[0]], &[], &[]), Some(leaves[0]));
+        assert_eq!(process(&[], &[tree.root()], &[]), Some(tree.root()));
+        assert_eq!(process(&[], &[], &[]), None);
+
+        let multi_proof = tree.get_multi_proof(&[leaves[0], leaves[3]]).unwrap();
+        let (mut proof, mut flags) = multi_proof.clone().into();
+        assert_eq!(
+            process(&multi_proof.leaves, &proof, &flags),
+            Some(tree.root())
+        );
+        proof.push(tree.root());
+        assert_eq!(process(&multi_proof.leaves, &proof, &flags), None);
+        proof.pop();
+        flags.iter_mut().for_each(|flag| *flag = true);
+        assert_eq!(process(&multi_proof.leaves, &proof, &flags), None);
+    }
+
+    #[test]
+    fn repeated_leaves_are_proven_once() {
+        let tree = tree(8);
+        let leaves = leaves(&tree);
+        let repeated = [leaves[5], leaves[1], leaves[5], leaves[1], leaves[2]];
+        let multi_proof = tree.get_multi_proof(&repeated).unwrap();
+        assert_eq!(
+            Some(multi_proof.clone()),
+            tree.get_multi_proof(&[leaves[1], leaves[2], leaves[5]])
+        );
+        let (proof, flags) = multi_proof.clone().into();
+        assert_eq!(
+            StandardMerkleTree::process_multi_proof(&multi_proof.leaves, &proof, &flags),
+            Some(tree.root())
+        );
+        assert_ne!(
+            StandardMerkleTree::process_multi_proof(&repeated[..3], &proof, &flags),
+            Some(tree.root())
+        );
+
+        // OZ's arguments, for the requested leaves sorted and deduplicated
+        let (proof, flags) = tree.get_multi_proof_parts(&repeated).unwrap();
+        let mut sorted = repeated.to_vec();
+        sorted.sort();
+        sorted.dedup();
+        assert!(verify_multi_proof(
+            &sorted,
+            proof.clone(),
+            flags.clone(),
+            tree.root()
+        ));
+        assert!(!verify_multi_proof(&repeated, proof, flags, tree.root()));
+    }
+
+    #[test]
+    fn merkle_trees_in_oz_layout_prove_like_openzeppelin() {
+        for count in [1, 2, 3, 4, 6, 8] {
+            let standard = tree(count);
+            let tree = MerkleTree::new(data(count));
+            assert_eq!(tree.get_root(), Some(standard.root()));
+            for subset in subsets(tree.sorted_leaves()) {
+                let multi_proof = tree.get_multi_proof(&subset).unwrap();
+                assert_eq!(
+                    Some(&multi_proof),
+                    standard.get_multi_proof(&subset).as_ref()
+                );
+                let (proof, flags) = tree.get_multi_proof_parts(&subset).unwrap();
+                assert_eq!(
+                    (&proof, &flags),
+                    (&multi_proof.proof, &multi_proof.proof_flags)
+                );
+                assert!(verify_multi_proof(
+                    &multi_proof.leaves,
+                    proof,
+                    flags,
+                    standard.root()
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn merkle_trees_prove_the_sets_oz_can_replay() {
+        for count in [5, 7, 9, 10] {
+            let tree = MerkleTree::new(data(count));
+            let root = tree.get_root().unwrap();
+            let (mut proven, mut unprovable) = (0, 0);
+            for subset in subsets(tree.sorted_leaves()) {
+                match tree.get_multi_proof(&subset) {
+                    Some(multi_proof) => {
+                        proven += 1;
+                        assert_eq!(multi_proof.leaves.len(), subset.len());
+                        assert!(multi_proof.leaves.windows(2).all(|pair| pair[0] < pair[1]));
+                        assert!(MerkleTree::verify_multi_proof(
+                            &multi_proof.leaves,
+                            &multi_proof,
+                            root
+                        ));
+                    }
+                    None => unprovable += 1,
+                }
+            }
+            assert!(proven > 0 && unprovable > 0, "{} leaves", count);
+        }
+
+        // the last of five leaves is promoted twice, and reaches the front of the queue
+        // before the other four are hashed together
+        let tree = MerkleTree::new(data(5));
+        let leaves = tree.sorted_leaves();
+        assert_eq!(tree.get_multi_proof(leaves), None);
+        assert_eq!(
+            tree.get_multi_proof(&leaves[..4]).unwrap(),
+            MultiProof {
+                leaves: leaves[..4].to_vec(),
+                proof: vec![leaves[4]],
+                proof_flags: vec![true, true, true, false],
+            }
+        );
+        assert!(tree.get_multi_proof(&leaves[4..]).is_some());
+
+        let separated = MerkleTree::new_with_node_sep(data(4), crate::NodeSep(vec![0x01]));
+        assert_eq!(
+            separated.get_multi_proof(&separated.sorted_leaves()[..2]),
+            None
+        );
+        let ordered = MerkleTree::new_ordered(separated.sorted_leaves().to_vec());
+        assert_eq!(ordered.get_multi_proof(&ordered.sorted_leaves()[..2]), None);
+    }
+}
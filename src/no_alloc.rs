@@ -0,0 +1,132 @@
+//! Proof verification without heap allocation, e.g. for firmware without an allocator.
+//!
+//! These are `H256` front ends of the `oz_merkle_core` crate, which is `#![no_std]` and
+//! is what every verifier of this crate folds proofs with. Proofs are borrowed slices,
+//! pairs are hashed without a buffer on the heap, and proofs longer than
+//! [`MAX_PROOF_DEPTH`] are rejected up front, so the work per call is bounded.
+
+use ethers::types::H256;
+
+pub use oz_merkle_core::MAX_PROOF_DEPTH;
+
+/// Verifies a proof of a sorted-pair tree, such as those built by `MerkleTree::new`,
+/// without allocating.
+///
+/// # Arguments
+///
+/// * `root` - The root hash of the tree.
+/// * `leaf` - The hash of the leaf.
+/// * `proof` - The sibling hashes from the leaf up.
+///
+/// # Returns
+///
+/// `true` if the proof has at most `MAX_PROOF_DEPTH` hashes and is valid for the leaf.
+pub fn verify_proof_no_alloc(root: &H256, leaf: &H256, proof: &[H256]) -> bool {
+    proof.len() <= MAX_PROOF_DEPTH
+        && oz_merkle_core::process_proof(leaf.0, proof.iter().map(|hash| &hash.0), &[]) == root.0
+}
+
+/// Verifies a proof of a tree built with `MerkleTree::new_ordered`, where the index of
+/// the leaf decides the side of every sibling, without allocating.
+///
+/// # Arguments
+///
+/// * `root` - The root hash of the tree.
+/// * `leaf` - The hash of the leaf.
+/// * `index` - The position of the leaf in the tree.
+/// * `leaves` - The number of leaves of the tree.
+/// * `proof` - The sibling hashes returned by `get_proof_at`.
+///
+/// # Returns
+///
+/// `true` if the proof has at most `MAX_PROOF_DEPTH` hashes and is valid for the leaf
+/// at `index`.
+pub fn verify_ordered_proof_no_alloc(
+    root: &H256,
+    leaf: &H256,
+    index: usize,
+    leaves: usize,
+    proof: &[H256],
+) -> bool {
+    proof.len() <= MAX_PROOF_DEPTH
+        && oz_merkle_core::process_ordered_proof(
+            leaf.0,
+            index,
+            leaves,
+            proof.iter().map(|hash| &hash.0),
+        ) == Some(root.0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::MerkleTree;
+    use ethers::types::{Address, U256};
+
+    #[test]
+    fn verifies_proofs_of_standard_and_ordered_trees() {
+        for count in [1u64, 2, 5, 16, 33] {
+            let tree = MerkleTree::new(
+                (1..=count)
+                    .map(|i| (Address::from_low_u64_be(i), U256::from(i)))
+                    .collect(),
+            );
+            let root = tree.get_root().unwrap();
+            for leaf in tree.sorted_leaves() {
+                let proof = tree.get_proof(*leaf).unwrap();
+                assert!(verify_proof_no_alloc(&root, leaf, &proof));
+                assert!(!verify_proof_no_alloc(&root, &H256::zero(), &proof));
+            }
+
+            let ordered = MerkleTree::new_ordered(tree.sorted_leaves().to_vec());
+            let root = ordered.get_root().unwrap();
+            let leaves = ordered.leaves_length();
+            for (index, leaf) in ordered.sorted_leaves().iter().enumerate() {
+                let proof = ordered.get_proof_at(index).unwrap();
+                assert!(verify_ordered_proof_no_alloc(
+                    &root, leaf, index, leaves, &proof
+                ));
+                if leaves > 1 {
+                    let other = (index + 1) % leaves;
+                    assert!(!verify_ordered_proof_no_alloc(
+                        &root, leaf, other, leaves, &proof
+                    ));
+                }
+            }
+        }
+
+        let leaf = H256::repeat_byte(1);
+        let padded = [H256::zero(); MAX_PROOF_DEPTH + 1];
+        let root = padded
+            .iter()
+            .fold(leaf, |hash, sibling| MerkleTree::hash_pair(&hash, sibling));
+        assert!(!verify_proof_no_alloc(&root, &leaf, &padded));
+        let root = MerkleTree::process_proof(leaf, &padded[1..]);
+        assert!(verify_proof_no_alloc(&root, &leaf, &padded[1..]));
+    }
+
+    /// The fold behind these verifiers must build without `std`, `alloc` or any default
+    /// feature; `oz_merkle_core` is `#![no_std]`, so building it alone is the proof.
+    #[test]
+    fn core_fold_builds_without_std() {
+        let manifest = concat!(env!("CARGO_MANIFEST_DIR"), "/oz_merkle_core/Cargo.toml");
+        let target = std::env::temp_dir().join("oz_merkle_core_no_std_check");
+        let output = std::process::Command::new(env!("CARGO"))
+            .args([
+                "build",
+                "--offline",
+                "--no-default-features",
+                "--manifest-path",
+            ])
+            .arg(manifest)
+            .arg("--target-dir")
+            .arg(target)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+}
@@ -0,0 +1,126 @@
+use crate::MerkleTree;
+use ethers::types::H256;
+
+/// A node of a tree with its coordinates, as yielded by [`MerkleTree::nodes`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NodeRef {
+    /// The layer of the node, `0` for the leaves.
+    pub layer: usize,
+    /// The position of the node within its layer.
+    pub index: usize,
+    /// The hash of the node.
+    pub hash: H256,
+    /// The position of the parent in the next layer, `None` for the root.
+    pub parent_index: Option<usize>,
+    /// Whether the node is the last of an odd layer, promoted to its parent without a sibling.
+    pub promoted: bool,
+}
+
+impl MerkleTree {
+    /// Iterates over every node of the tree, layer by layer from the leaves to the root.
+    ///
+    /// Nodes are yielded lazily from the stored layers, without collecting them.
+    pub fn nodes(&self) -> impl Iterator<Item = NodeRef> + '_ {
+        let root_layer = self.layers.len() - 1;
+        self.layers
+            .iter()
+            .enumerate()
+            .flat_map(move |(layer, nodes)| {
+                nodes.iter().enumerate().map(move |(index, hash)| {
+                    let is_root = layer == root_layer;
+                    NodeRef {
+                        layer,
+                        index,
+                        hash: *hash,
+                        parent_index: (!is_root).then_some(index / 2),
+                        promoted: !is_root && index ^ 1 >= nodes.len(),
+                    }
+                })
+            })
+    }
+
+    /// Iterates over every node of the tree with its index in a flat, top-down array.
+    ///
+    /// The root is at index 0 and every layer follows the one above it, left to right, so
+    /// the leaves take the last `leaves_length()` indices. With a power-of-two number of
+    /// leaves this is the heap layout of OpenZeppelin's flat `tree` array, where the
+    /// children of node `i` are at `2i + 1` and `2i + 2`; other sizes promote odd nodes,
+    /// so a promoted node appears once in every layer it is carried through.
+    pub fn all_nodes(&self) -> impl Iterator<Item = (usize, H256)> + '_ {
+        self.layers.iter().rev().flatten().copied().enumerate()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ethers::types::{Address, U256};
+
+    #[test]
+    fn nodes_match_layers_and_parents() {
+        for count in [1u64, 2, 3, 5, 8, 13] {
+            let tree = MerkleTree::new(
+                (1..=count)
+                    .map(|i| (Address::from_low_u64_be(i), U256::from(i)))
+                    .collect(),
+            );
+            let nodes: Vec<NodeRef> = tree.nodes().collect();
+            assert_eq!(nodes.len(), MerkleTree::scratch_len(count as usize));
+
+            let layers: Vec<Vec<H256>> = (0..=nodes.last().unwrap().layer)
+                .map(|layer| {
+                    nodes
+                        .iter()
+                        .filter(|node| node.layer == layer)
+                        .map(|node| node.hash)
+                        .collect()
+                })
+                .collect();
+            for node in &nodes {
+                let Some(parent_index) = node.parent_index else {
+                    assert_eq!(Some(node.hash), tree.get_root());
+                    continue;
+                };
+                let parent = layers[node.layer + 1][parent_index];
+                if node.promoted {
+                    assert_eq!(parent, node.hash, "{} leaves", count);
+                } else {
+                    let sibling = layers[node.layer][node.index ^ 1];
+                    assert_eq!(parent, MerkleTree::hash_pair(&node.hash, &sibling));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn all_nodes_start_at_the_root_and_end_with_the_leaves() {
+        for count in [1u64, 4, 7, 8] {
+            let tree = MerkleTree::new(
+                (1..=count)
+                    .map(|i| (Address::from_low_u64_be(i), U256::from(i)))
+                    .collect(),
+            );
+            let nodes: Vec<(usize, H256)> = tree.all_nodes().collect();
+            assert_eq!(nodes.len(), MerkleTree::scratch_len(count as usize));
+            assert_eq!(Some(nodes[0].1), tree.get_root());
+            assert!(nodes.iter().enumerate().all(|(i, (index, _))| i == *index));
+
+            let first_leaf = nodes.len() - count as usize;
+            let leaves: Vec<H256> = nodes[first_leaf..].iter().map(|(_, hash)| *hash).collect();
+            assert_eq!(leaves, tree.sorted_leaves());
+        }
+
+        let tree = MerkleTree::new(
+            (1..=8u64)
+                .map(|i| (Address::from_low_u64_be(i), U256::from(i)))
+                .collect(),
+        );
+        let nodes: Vec<H256> = tree.all_nodes().map(|(_, hash)| hash).collect();
+        for i in 0..7 {
+            assert_eq!(
+                nodes[i],
+                MerkleTree::hash_pair(&nodes[2 * i + 1], &nodes[2 * i + 2])
+            );
+        }
+    }
+}
@@ -0,0 +1,292 @@
+use crate::{verify_ordered_proof_no_alloc, MerkleTree, TreeConfig};
+use ethers::core::utils::keccak256;
+use ethers::types::H256;
+
+/// The side a proof hash is hashed on, relative to the node computed so far.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    /// The proof hash is the left child: the computed node is a right child.
+    Left,
+    /// The proof hash is the right child: the computed node is a left child.
+    Right,
+}
+
+impl MerkleTree {
+    /// Constructs a positional Merkle tree that keeps the leaves in the given order.
+    ///
+    /// Unlike `from_leaves`, leaves are neither sorted nor deduplicated, and pairs are
+    /// hashed as `keccak256(left ++ right)` without sorting, so the root commits to the
+    /// position of every leaf. An odd node at the end of a layer is promoted unchanged.
+    /// This suits append-only commitment logs where order and repeated entries matter.
+    ///
+    /// Proofs of an ordered tree are index-based: use `get_proof_at` and
+    /// `verify_ordered_proof`. Hash lookups such as `get_proof` return `None`, since a
+    /// leaf hash does not identify a position when duplicates are allowed.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaves` - The leaf hashes, in commitment order.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `MerkleTree` containing the constructed Merkle tree.
+    pub fn new_ordered(leaves: Vec<H256>) -> Self {
+        let mut layers = vec![leaves.clone()];
+        while layers.last().unwrap().len() > 1 {
+            layers.push(Self::fold_ordered_layer(layers.last().unwrap()));
+        }
+        MerkleTree {
+            leaves: leaves.len(),
+            inputs: leaves.len(),
+            elements: leaves,
+            entries: Vec::new(),
+            payload_entries: Vec::new(),
+            address_order: Vec::new(),
+            layers,
+            bloom: None,
+            config: TreeConfig {
+                ordered: true,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Returns whether the tree was built with `new_ordered`.
+    pub fn is_ordered(&self) -> bool {
+        self.config.ordered
+    }
+
+    /// Retrieves the Merkle proof of the leaf at a given index.
+    ///
+    /// For ordered trees the index is the position in the input; otherwise it is the
+    /// position in `sorted_leaves()`.
+    ///
+    /// # Returns
+    ///
+    /// An `Option` containing the sibling hashes from the leaf up, or `None` if the index
+    /// is out of bounds.
+    pub fn get_proof_at(&self, index: usize) -> Option<Vec<H256>> {
+        if index >= self.leaves {
+            return None;
+        }
+        self.try_proof_at(index).ok()
+    }
+
+    /// Retrieves the proof of the leaf at a given index with the side of every hash.
+    ///
+    /// The sides carry the position of the leaf, so a verifier can recover it with
+    /// `implied_index` without being told the index.
+    ///
+    /// # Returns
+    ///
+    /// An `Option` containing the sibling hashes and their sides from the leaf up, or
+    /// `None` if the index is out of bounds.
+    pub fn get_path_proof_at(&self, index: usize) -> Option<Vec<(H256, Side)>> {
+        if index >= self.leaves {
+            return None;
+        }
+        let proof: Vec<(H256, Side)> = self.layers[..self.layers.len() - 1]
+            .iter()
+            .enumerate()
+            .filter_map(|(level, layer)| {
+                let node = index >> level;
+                let side = if node % 2 == 1 {
+                    Side::Left
+                } else {
+                    Side::Right
+                };
+                layer.get(node ^ 1).map(|sibling| (*sibling, side))
+            })
+            .collect();
+        let hashes: Vec<H256> = proof.iter().map(|(hash, _)| *hash).collect();
+        self.self_check(index, &hashes).ok()?;
+        Some(proof)
+    }
+
+    /// Reconstructs the index of a leaf from the sides of its positional proof.
+    ///
+    /// A node promoted without a sibling leaves no hash in the proof, so the levels the
+    /// sides belong to depend on the size of the tree. The path is walked from the root
+    /// down: a level where the node has no sibling is skipped, and every other level takes
+    /// the next side from the end of the proof.
+    ///
+    /// # Arguments
+    ///
+    /// * `path_proof` - The sibling hashes and sides from the leaf up, as returned by
+    ///   `get_path_proof_at`.
+    /// * `leaves` - The number of leaves of the tree.
+    ///
+    /// # Returns
+    ///
+    /// An `Option` containing the index of the leaf, or `None` if the tree is empty or the
+    /// proof has more or fewer hashes than the path it describes.
+    pub fn implied_index(path_proof: &[(H256, Side)], leaves: usize) -> Option<usize> {
+        if leaves == 0 {
+            return None;
+        }
+        let mut sizes = vec![leaves];
+        while *sizes.last().unwrap() > 1 {
+            sizes.push(sizes.last().unwrap().div_ceil(2));
+        }
+        let mut sides = path_proof.iter().rev().map(|(_, side)| *side);
+        let mut index = 0;
+        for size in sizes.iter().rev().skip(1) {
+            index *= 2;
+            if index + 1 < *size && sides.next()? == Side::Left {
+                index += 1;
+            }
+        }
+        sides.next().is_none().then_some(index)
+    }
+
+    /// Verifies an index-based proof of an ordered tree.
+    ///
+    /// The index tells at every level whether the current node is a left or right child.
+    /// The leaf count is needed to know at which levels the node was promoted without a
+    /// sibling, in which case the proof holds no hash for that level.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf` - The hash of the leaf.
+    /// * `index` - The position of the leaf in the tree.
+    /// * `leaves` - The number of leaves of the tree.
+    /// * `proof` - The sibling hashes returned by `get_proof_at`.
+    /// * `root` - The root hash of the tree.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the proof is valid for the leaf at `index`.
+    pub fn verify_ordered_proof(
+        leaf: H256,
+        index: usize,
+        leaves: usize,
+        proof: &[H256],
+        root: H256,
+    ) -> bool {
+        verify_ordered_proof_no_alloc(&root, &leaf, index, leaves, proof)
+    }
+
+    fn fold_ordered_layer(elements: &[H256]) -> Vec<H256> {
+        elements
+            .chunks(2)
+            .map(|chunk| match chunk {
+                [left, right] => Self::hash_ordered_pair(left, right),
+                // promote the last element unchanged
+                [single] => *single,
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    pub(crate) fn hash_ordered_pair(left: &H256, right: &H256) -> H256 {
+        H256::from(keccak256([left.as_bytes(), right.as_bytes()].concat()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn leaf(byte: u8) -> H256 {
+        H256::from(keccak256([byte]))
+    }
+
+    #[test]
+    fn ordered_tree_proves_duplicates_by_index() {
+        let leaves = vec![
+            leaf(3),
+            leaf(1),
+            leaf(3),
+            leaf(2),
+            leaf(1),
+            leaf(3),
+            leaf(9),
+        ];
+        let tree = MerkleTree::new_ordered(leaves.clone());
+        let root = tree.get_root().unwrap();
+
+        assert!(tree.is_ordered());
+        assert_eq!(tree.leaves_length(), 7);
+        assert_ne!(
+            MerkleTree::from_leaves(leaves.clone()).get_root(),
+            Some(root)
+        );
+
+        let proofs: Vec<Vec<H256>> = (0..leaves.len())
+            .map(|index| tree.get_proof_at(index).unwrap())
+            .collect();
+        for (index, proof) in proofs.iter().enumerate() {
+            assert!(MerkleTree::verify_ordered_proof(
+                leaves[index],
+                index,
+                leaves.len(),
+                proof,
+                root
+            ));
+        }
+        // leaf(3) sits at 0, 2 and 5; each position has its own proof
+        assert_ne!(proofs[0], proofs[2]);
+        assert!(!MerkleTree::verify_ordered_proof(
+            leaves[0], 2, 7, &proofs[0], root
+        ));
+        assert!(!MerkleTree::verify_ordered_proof(
+            leaves[5], 5, 7, &proofs[2], root
+        ));
+
+        assert_eq!(tree.get_proof(leaf(3)), None);
+        assert!(tree.contains(leaf(9)));
+        assert_eq!(tree.get_proof_at(7), None);
+    }
+
+    #[test]
+    fn implied_index_matches_the_position() {
+        for count in [1u8, 2, 5, 7, 8, 13] {
+            let leaves: Vec<H256> = (0..count).map(leaf).collect();
+            let tree = MerkleTree::new_ordered(leaves.clone());
+            let root = tree.get_root().unwrap();
+            for index in 0..leaves.len() {
+                let path = tree.get_path_proof_at(index).unwrap();
+                let hashes: Vec<H256> = path.iter().map(|(hash, _)| *hash).collect();
+                assert_eq!(hashes, tree.get_proof_at(index).unwrap());
+                assert!(MerkleTree::verify_ordered_proof(
+                    leaves[index],
+                    index,
+                    leaves.len(),
+                    &hashes,
+                    root
+                ));
+                assert_eq!(
+                    MerkleTree::implied_index(&path, leaves.len()),
+                    Some(index),
+                    "leaf {} of {}",
+                    index,
+                    count
+                );
+            }
+        }
+
+        let tree = MerkleTree::new_ordered((0..5).map(leaf).collect());
+        let mut path = tree.get_path_proof_at(4).unwrap();
+        assert_eq!(path, vec![(tree.layers[2][0], Side::Left)]);
+        path.push((leaf(9), Side::Right));
+        assert_eq!(MerkleTree::implied_index(&path, 5), None);
+        assert_eq!(MerkleTree::implied_index(&path[..0], 5), None);
+        assert_eq!(MerkleTree::implied_index(&[], 1), Some(0));
+        assert_eq!(MerkleTree::implied_index(&[], 0), None);
+        assert_eq!(tree.get_path_proof_at(5), None);
+    }
+
+    #[test]
+    fn ordered_root_commits_to_order() {
+        let forward = MerkleTree::new_ordered(vec![leaf(1), leaf(2), leaf(3)]);
+        let backward = MerkleTree::new_ordered(vec![leaf(3), leaf(2), leaf(1)]);
+        assert_ne!(forward.get_root(), backward.get_root());
+        assert_eq!(
+            forward.get_root(),
+            Some(MerkleTree::hash_ordered_pair(
+                &MerkleTree::hash_ordered_pair(&leaf(1), &leaf(2)),
+                &leaf(3)
+            ))
+        );
+    }
+}
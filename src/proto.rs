@@ -0,0 +1,565 @@
+//! Protobuf messages for proofs and claims, available with the `proto` feature.
+//!
+//! The messages are defined in `proto/merkle.proto`, which services in other languages
+//! can compile from [`INCLUDE_DIR`]. The Rust types below are the prost messages of that
+//! file, written out rather than generated so the crate builds without `protoc`; the tests
+//! check every message and field of the file against them. Conversions from the crate's
+//! types are infallible, while conversions back check the length of every hash, address
+//! and amount.
+
+use crate::{AmountWidth, Entry, MerkleError, NodeSep};
+use ethers::types::{Address, H256, U256};
+use std::str::FromStr;
+
+/// The directory holding `merkle.proto`, for use as a `protoc` include path.
+pub const INCLUDE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/proto");
+
+/// The contents of `merkle.proto`.
+pub const MERKLE_PROTO: &str = include_str!("../proto/merkle.proto");
+
+/// The root hash of a tree.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Root {
+    #[prost(bytes = "vec", tag = "1")]
+    pub hash: Vec<u8>,
+}
+
+/// Sibling hashes from the leaf up.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Proof {
+    #[prost(bytes = "vec", repeated, tag = "1")]
+    pub hashes: Vec<Vec<u8>>,
+}
+
+/// An `(address, amount)` leaf.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ClaimEntry {
+    #[prost(bytes = "vec", tag = "1")]
+    pub account: Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub amount: Vec<u8>,
+}
+
+/// How a tree hashes its entries and pairs; an `amount_bits` of 0 means `abi.encode` leaves.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct EntryScheme {
+    #[prost(uint64, optional, tag = "1")]
+    pub chain_id: Option<u64>,
+    #[prost(uint32, tag = "2")]
+    pub amount_bits: u32,
+    #[prost(bytes = "vec", tag = "3")]
+    pub node_sep: Vec<u8>,
+}
+
+/// A claim with its leaf index, proof and hashing scheme.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ProofBundle {
+    #[prost(bytes = "vec", tag = "1")]
+    pub account: Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub amount: Vec<u8>,
+    #[prost(uint64, tag = "3")]
+    pub index: u64,
+    #[prost(message, optional, tag = "4")]
+    pub proof: Option<Proof>,
+    #[prost(message, optional, tag = "5")]
+    pub scheme: Option<EntryScheme>,
+}
+
+impl From<H256> for Root {
+    fn from(root: H256) -> Self {
+        Self {
+            hash: root.as_bytes().to_vec(),
+        }
+    }
+}
+
+impl TryFrom<Root> for H256 {
+    type Error = MerkleError;
+
+    fn try_from(message: Root) -> Result<Self, Self::Error> {
+        hash(&message.hash, "root")
+    }
+}
+
+impl From<crate::Proof> for Proof {
+    fn from(proof: crate::Proof) -> Self {
+        Self {
+            hashes: proof
+                .as_slice()
+                .iter()
+                .map(|hash| hash.as_bytes().to_vec())
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<Proof> for crate::Proof {
+    type Error = MerkleError;
+
+    fn try_from(message: Proof) -> Result<Self, Self::Error> {
+        let hashes = message
+            .hashes
+            .iter()
+            .map(|bytes| hash(bytes, "proof hash"))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(hashes.into())
+    }
+}
+
+impl From<Entry> for ClaimEntry {
+    fn from(entry: Entry) -> Self {
+        Self {
+            account: entry.account.as_bytes().to_vec(),
+            amount: amount_bytes(entry.amount),
+        }
+    }
+}
+
+impl TryFrom<ClaimEntry> for Entry {
+    type Error = MerkleError;
+
+    fn try_from(message: ClaimEntry) -> Result<Self, Self::Error> {
+        Ok(Entry::new(
+            account(&message.account)?,
+            amount(&message.amount)?,
+        ))
+    }
+}
+
+impl From<crate::EntryScheme> for EntryScheme {
+    fn from(scheme: crate::EntryScheme) -> Self {
+        Self {
+            chain_id: scheme.chain_id,
+            amount_bits: scheme
+                .amount_width
+                .map_or(0, |width| 8 * width.bytes() as u32),
+            node_sep: scheme.node_sep.0,
+        }
+    }
+}
+
+impl TryFrom<EntryScheme> for crate::EntryScheme {
+    type Error = MerkleError;
+
+    fn try_from(message: EntryScheme) -> Result<Self, Self::Error> {
+        let amount_width = match message.amount_bits {
+            0 => None,
+            bits => Some(AmountWidth::from_str(&format!("uint{}", bits))?),
+        };
+        Ok(crate::EntryScheme {
+            chain_id: message.chain_id,
+            amount_width,
+            node_sep: NodeSep(message.node_sep),
+        })
+    }
+}
+
+impl From<crate::ProofBundle> for ProofBundle {
+    fn from(bundle: crate::ProofBundle) -> Self {
+        Self {
+            account: bundle.account.as_bytes().to_vec(),
+            amount: amount_bytes(bundle.amount),
+            index: bundle.index as u64,
+            proof: Some(bundle.proof.into()),
+            scheme: Some(bundle.scheme.into()),
+        }
+    }
+}
+
+impl TryFrom<ProofBundle> for crate::ProofBundle {
+    type Error = MerkleError;
+
+    fn try_from(message: ProofBundle) -> Result<Self, Self::Error> {
+        let index = usize::try_from(message.index).map_err(|_| {
+            MerkleError::InvalidMessage(format!("index {} does not fit usize", message.index))
+        })?;
+        Ok(crate::ProofBundle {
+            account: account(&message.account)?,
+            amount: amount(&message.amount)?,
+            index,
+            proof: message.proof.unwrap_or_default().try_into()?,
+            scheme: message.scheme.unwrap_or_default().try_into()?,
+        })
+    }
+}
+
+fn hash(bytes: &[u8], field: &str) -> Result<H256, MerkleError> {
+    fixed::<32>(bytes, field).map(H256)
+}
+
+fn account(bytes: &[u8]) -> Result<Address, MerkleError> {
+    fixed::<20>(bytes, "account").map(Address::from)
+}
+
+fn amount(bytes: &[u8]) -> Result<U256, MerkleError> {
+    fixed::<32>(bytes, "amount").map(|bytes| U256::from_big_endian(&bytes))
+}
+
+fn amount_bytes(amount: U256) -> Vec<u8> {
+    let mut bytes = [0u8; 32];
+    amount.to_big_endian(&mut bytes);
+    bytes.to_vec()
+}
+
+fn fixed<const N: usize>(bytes: &[u8], field: &str) -> Result<[u8; N], MerkleError> {
+    bytes.try_into().map_err(|_| {
+        MerkleError::InvalidMessage(format!(
+            "{} has {} bytes instead of {}",
+            field,
+            bytes.len(),
+            N
+        ))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::MerkleTree;
+    use prost::Message;
+    use std::collections::HashMap;
+
+    #[test]
+    fn bundles_round_trip_through_protobuf() {
+        let data: Vec<(Address, U256)> = (1..=6u64)
+            .map(|i| (Address::from_low_u64_be(i), U256::exp10(18) * i))
+            .collect();
+        let tree = MerkleTree::new(data);
+        let root = tree.get_root().unwrap();
+
+        let bytes = Root::from(root).encode_to_vec();
+        let decoded: H256 = Root::decode(bytes.as_slice()).unwrap().try_into().unwrap();
+        assert_eq!(decoded, root);
+
+        for bundle in tree.claim_records() {
+            let bytes = ProofBundle::from(bundle.clone()).encode_to_vec();
+            let decoded: crate::ProofBundle = ProofBundle::decode(bytes.as_slice())
+                .unwrap()
+                .try_into()
+                .unwrap();
+            assert_eq!(decoded, bundle);
+            assert!(decoded.verify(root));
+
+            let bytes = ClaimEntry::from(bundle.entry()).encode_to_vec();
+            let entry: Entry = ClaimEntry::decode(bytes.as_slice())
+                .unwrap()
+                .try_into()
+                .unwrap();
+            assert_eq!(entry, bundle.entry());
+        }
+
+        let packed = MerkleTree::new_packed(
+            vec![(Address::repeat_byte(1), U256::one()); 2],
+            AmountWidth::U96,
+        )
+        .unwrap();
+        let mut chained =
+            MerkleTree::new_for_chain(vec![(Address::repeat_byte(2), U256::one())], 5)
+                .get_claim(Address::repeat_byte(2))
+                .unwrap();
+        chained.scheme.node_sep = NodeSep(vec![7, 7]);
+        for bundle in [packed.get_claim(Address::repeat_byte(1)).unwrap(), chained] {
+            let bytes = ProofBundle::from(bundle.clone()).encode_to_vec();
+            let decoded: crate::ProofBundle = ProofBundle::decode(bytes.as_slice())
+                .unwrap()
+                .try_into()
+                .unwrap();
+            assert_eq!(decoded, bundle);
+        }
+        assert!(std::path::Path::new(INCLUDE_DIR)
+            .join("merkle.proto")
+            .exists());
+    }
+
+    /// A field value, encoded with the wire type its field has in `merkle.proto`.
+    enum Value {
+        Varint(u64),
+        Bytes(Vec<u8>),
+    }
+
+    /// The type, number and label of every field of `merkle.proto`, by message and field
+    /// name. The label is `optional`, `repeated` or empty.
+    fn schema_fields() -> HashMap<(String, String), (String, u64, String)> {
+        let mut fields = HashMap::new();
+        let mut message = "";
+        for line in MERKLE_PROTO.lines().map(str::trim) {
+            if let Some(name) = line.strip_prefix("message ") {
+                message = name.trim_end_matches('{').trim();
+            } else if let Some(field) = line.strip_suffix(';') {
+                let mut words: Vec<&str> = field.split_whitespace().collect();
+                let label = match words.first() {
+                    Some(&label @ ("optional" | "repeated")) => {
+                        words.remove(0);
+                        label
+                    }
+                    _ => "",
+                };
+                if let [kind, name, "=", number] = words[..] {
+                    fields.insert(
+                        (message.to_string(), name.to_string()),
+                        (kind.to_string(), number.parse().unwrap(), label.to_string()),
+                    );
+                }
+            }
+        }
+        fields
+    }
+
+    fn push_varint(bytes: &mut Vec<u8>, mut value: u64) {
+        while value >= 0x80 {
+            bytes.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        bytes.push(value as u8);
+    }
+
+    /// Encodes a message field by field, taking numbers and wire types from the schema
+    /// rather than from the prost attributes.
+    fn encode(message: &str, values: Vec<(&str, Value)>) -> Vec<u8> {
+        let fields = schema_fields();
+        let mut bytes = Vec::new();
+        for (name, value) in values {
+            let (kind, number, _) = &fields[&(message.to_string(), name.to_string())];
+            match (kind.as_str(), value) {
+                ("uint32" | "uint64", Value::Varint(value)) => {
+                    push_varint(&mut bytes, number << 3);
+                    push_varint(&mut bytes, value);
+                }
+                ("uint32" | "uint64", Value::Bytes(_)) => panic!("{} is a varint", name),
+                (_, Value::Bytes(value)) => {
+                    push_varint(&mut bytes, number << 3 | 2);
+                    push_varint(&mut bytes, value.len() as u64);
+                    bytes.extend(value);
+                }
+                (kind, Value::Varint(_)) => panic!("{} is a {}", name, kind),
+            }
+        }
+        bytes
+    }
+
+    #[test]
+    fn decodes_bytes_encoded_from_the_schema() {
+        let data: Vec<(Address, U256)> = (1..=5u64)
+            .map(|i| (Address::from_low_u64_be(i), U256::from(i) << 80))
+            .collect();
+        let tree = MerkleTree::new_packed(data, AmountWidth::U96).unwrap();
+        let packed = tree.get_claim(Address::from_low_u64_be(4)).unwrap();
+        let chained = MerkleTree::new_for_chain(vec![(Address::repeat_byte(2), U256::one())], 5)
+            .get_claim(Address::repeat_byte(2))
+            .unwrap();
+
+        let cases = [
+            (
+                packed.clone(),
+                vec![("amount_bits", Value::Varint(96))],
+                tree.get_root(),
+            ),
+            (chained.clone(), vec![("chain_id", Value::Varint(5))], None),
+        ];
+        for (bundle, scheme, root) in cases {
+            let hashes = bundle
+                .proof
+                .as_slice()
+                .iter()
+                .map(|hash| ("hashes", Value::Bytes(hash.as_bytes().to_vec())))
+                .collect();
+            let mut values = vec![
+                ("account", Value::Bytes(bundle.account.as_bytes().to_vec())),
+                ("amount", Value::Bytes(amount_bytes(bundle.amount))),
+            ];
+            if bundle.index != 0 {
+                values.push(("index", Value::Varint(bundle.index as u64)));
+            }
+            values.push(("proof", Value::Bytes(encode("Proof", hashes))));
+            values.push(("scheme", Value::Bytes(encode("EntryScheme", scheme))));
+            let bytes = encode("ProofBundle", values);
+
+            let decoded: crate::ProofBundle = ProofBundle::decode(bytes.as_slice())
+                .unwrap()
+                .try_into()
+                .unwrap();
+            assert_eq!(decoded, bundle);
+            if let Some(root) = root {
+                assert!(decoded.verify(root));
+            }
+            assert_eq!(ProofBundle::from(bundle).encode_to_vec(), bytes);
+        }
+        assert_eq!(
+            Root::decode(encode("Root", vec![("hash", Value::Bytes(vec![9; 32]))]).as_slice())
+                .unwrap(),
+            Root::from(H256::repeat_byte(9))
+        );
+        assert_eq!(
+            ClaimEntry::from(packed.entry()).encode_to_vec(),
+            encode(
+                "ClaimEntry",
+                vec![
+                    ("account", Value::Bytes(packed.account.as_bytes().to_vec())),
+                    ("amount", Value::Bytes(amount_bytes(packed.amount))),
+                ]
+            )
+        );
+    }
+
+    /// Decodes a message with its prost type and encodes it again.
+    fn reencode(message: &str, bytes: &[u8]) -> Vec<u8> {
+        match message {
+            "Root" => Root::decode(bytes).unwrap().encode_to_vec(),
+            "Proof" => Proof::decode(bytes).unwrap().encode_to_vec(),
+            "ClaimEntry" => ClaimEntry::decode(bytes).unwrap().encode_to_vec(),
+            "EntryScheme" => EntryScheme::decode(bytes).unwrap().encode_to_vec(),
+            "ProofBundle" => ProofBundle::decode(bytes).unwrap().encode_to_vec(),
+            other => panic!("no prost type for message {}", other),
+        }
+    }
+
+    /// A non-default value of a field, two for repeated fields. Optional scalars are
+    /// zero, which is only encoded when the field has presence.
+    fn sample(kind: &str, label: &str) -> Vec<Value> {
+        let value = |seed: u8| match kind {
+            "uint32" | "uint64" if label == "optional" => Value::Varint(0),
+            "uint32" | "uint64" => Value::Varint(300 + u64::from(seed)),
+            "bytes" => Value::Bytes(vec![seed; 3]),
+            message => Value::Bytes(encode_sample(message)),
+        };
+        match label {
+            "repeated" => vec![value(1), value(2)],
+            _ => vec![value(1)],
+        }
+    }
+
+    /// Encodes a message with a sample value in every field, in field number order.
+    fn encode_sample(message: &str) -> Vec<u8> {
+        let mut fields: Vec<_> = schema_fields()
+            .into_iter()
+            .filter(|((owner, _), _)| owner == message)
+            .collect();
+        fields.sort_by_key(|(_, (_, number, _))| *number);
+        let values = fields
+            .iter()
+            .flat_map(|((_, name), (kind, _, label))| {
+                sample(kind, label)
+                    .into_iter()
+                    .map(move |value| (name.as_str(), value))
+            })
+            .collect();
+        encode(message, values)
+    }
+
+    #[test]
+    fn prost_types_match_the_schema() {
+        let fields = schema_fields();
+        let mut messages: Vec<&str> = fields.keys().map(|(message, _)| message.as_str()).collect();
+        messages.sort();
+        messages.dedup();
+        assert_eq!(
+            messages,
+            ["ClaimEntry", "EntryScheme", "Proof", "ProofBundle", "Root"]
+        );
+
+        // every field of the schema survives the prost type with its number, wire type
+        // and label, alone and together with the others
+        for ((message, name), (kind, _, label)) in &fields {
+            let values = sample(kind, label)
+                .into_iter()
+                .map(|value| (name.as_str(), value))
+                .collect();
+            let bytes = encode(message, values);
+            assert_eq!(reencode(message, &bytes), bytes, "{}.{}", message, name);
+        }
+        for message in messages {
+            let bytes = encode_sample(message);
+            assert_eq!(reencode(message, &bytes), bytes, "{}", message);
+        }
+    }
+
+    /// Checks that every field on the wire is a field of the message in the schema, with
+    /// the wire type of its type, recursing into message fields.
+    fn assert_schema_fields(message: &str, mut bytes: &[u8]) {
+        let read_varint = |bytes: &mut &[u8]| {
+            let mut value = 0u64;
+            for shift in (0..64).step_by(7) {
+                let (byte, rest) = bytes.split_first().unwrap();
+                *bytes = rest;
+                value |= u64::from(byte & 0x7f) << shift;
+                if byte & 0x80 == 0 {
+                    break;
+                }
+            }
+            value
+        };
+        let fields = schema_fields();
+        while !bytes.is_empty() {
+            let key = read_varint(&mut bytes);
+            let (number, wire_type) = (key >> 3, key & 7);
+            let (kind, _, _) = fields
+                .iter()
+                .find(|((owner, _), (_, field, _))| owner == message && *field == number)
+                .map(|(_, field)| field)
+                .unwrap_or_else(|| panic!("{} has no field {}", message, number));
+            match kind.as_str() {
+                "uint32" | "uint64" => {
+                    assert_eq!(wire_type, 0, "{}.{}", message, number);
+                    read_varint(&mut bytes);
+                }
+                kind => {
+                    assert_eq!(wire_type, 2, "{}.{}", message, number);
+                    let len = read_varint(&mut bytes) as usize;
+                    let (value, rest) = bytes.split_at(len);
+                    if kind != "bytes" {
+                        assert_schema_fields(kind, value);
+                    }
+                    bytes = rest;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn prost_types_encode_only_schema_fields() {
+        let bundle = crate::ProofBundle {
+            account: Address::repeat_byte(1),
+            amount: U256::MAX,
+            index: 3,
+            proof: vec![H256::repeat_byte(2), H256::repeat_byte(3)].into(),
+            scheme: crate::EntryScheme {
+                chain_id: Some(5),
+                amount_width: Some(AmountWidth::U96),
+                node_sep: NodeSep(vec![7]),
+            },
+        };
+        assert_schema_fields(
+            "ProofBundle",
+            &ProofBundle::from(bundle.clone()).encode_to_vec(),
+        );
+        assert_schema_fields(
+            "ClaimEntry",
+            &ClaimEntry::from(bundle.entry()).encode_to_vec(),
+        );
+        assert_schema_fields("Root", &Root::from(H256::repeat_byte(4)).encode_to_vec());
+    }
+
+    #[test]
+    fn rejects_wrong_lengths() {
+        let mut bundle = ProofBundle::from(crate::ProofBundle {
+            account: Address::repeat_byte(1),
+            amount: U256::one(),
+            index: 0,
+            proof: vec![H256::repeat_byte(2)].into(),
+            scheme: Default::default(),
+        });
+        bundle.proof.as_mut().unwrap().hashes[0].pop();
+        assert_eq!(
+            crate::ProofBundle::try_from(bundle.clone()).unwrap_err(),
+            MerkleError::InvalidMessage("proof hash has 31 bytes instead of 32".to_string())
+        );
+
+        bundle.account.push(0);
+        assert_eq!(
+            crate::ProofBundle::try_from(bundle).unwrap_err(),
+            MerkleError::InvalidMessage("account has 21 bytes instead of 20".to_string())
+        );
+        assert!(H256::try_from(Root::default()).is_err());
+    }
+}
@@ -0,0 +1,167 @@
+use crate::{MerkleTree, ProofBundle, RegistryError};
+use ethers::types::{Address, H256};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// A thread-safe collection of named trees, one per distribution campaign.
+///
+/// Trees are shared as `Arc<MerkleTree>`, so a lookup holds the lock only long enough to
+/// clone the `Arc`, and replacing a campaign's tree is atomic: every lookup sees either
+/// the old tree or the new one, never a mix.
+#[derive(Debug, Default)]
+pub struct TreeRegistry {
+    trees: RwLock<HashMap<String, Arc<MerkleTree>>>,
+}
+
+impl TreeRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a campaign, or atomically swaps in a new tree for an existing one.
+    ///
+    /// # Returns
+    ///
+    /// The previous tree of the campaign, if any.
+    pub fn insert(&self, id: String, tree: MerkleTree) -> Option<Arc<MerkleTree>> {
+        self.trees.write().unwrap().insert(id, Arc::new(tree))
+    }
+
+    /// Atomically swaps in a new tree for an existing campaign.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the previous tree, or `RegistryError::UnknownCampaign` if no
+    /// campaign is registered under `id`, in which case nothing is inserted.
+    pub fn replace(&self, id: &str, tree: MerkleTree) -> Result<Arc<MerkleTree>, RegistryError> {
+        let mut trees = self.trees.write().unwrap();
+        let slot = trees
+            .get_mut(id)
+            .ok_or_else(|| RegistryError::UnknownCampaign(id.to_string()))?;
+        Ok(std::mem::replace(slot, Arc::new(tree)))
+    }
+
+    /// Removes a campaign, returning its tree if it was registered.
+    pub fn remove(&self, id: &str) -> Option<Arc<MerkleTree>> {
+        self.trees.write().unwrap().remove(id)
+    }
+
+    /// Returns the current tree of a campaign.
+    pub fn get(&self, id: &str) -> Option<Arc<MerkleTree>> {
+        self.trees.read().unwrap().get(id).cloned()
+    }
+
+    /// Returns the IDs of every registered campaign, sorted.
+    pub fn ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self.trees.read().unwrap().keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+
+    /// Retrieves the root of a campaign's current tree.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the root, which is `None` for an empty tree, or
+    /// `RegistryError::UnknownCampaign`.
+    pub fn root(&self, id: &str) -> Result<Option<H256>, RegistryError> {
+        Ok(self.tree(id)?.get_root())
+    }
+
+    /// Retrieves the proof bundle of an account in a campaign.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The campaign ID.
+    /// * `account` - The claiming address.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the bundle, built from a single version of the campaign's
+    /// tree, or a `RegistryError` telling an unknown campaign from an unknown address.
+    pub fn proof(&self, id: &str, account: Address) -> Result<ProofBundle, RegistryError> {
+        self.tree(id)?
+            .get_claim(account)
+            .ok_or_else(|| RegistryError::UnknownAddress {
+                campaign: id.to_string(),
+                account,
+            })
+    }
+
+    fn tree(&self, id: &str) -> Result<Arc<MerkleTree>, RegistryError> {
+        self.get(id)
+            .ok_or_else(|| RegistryError::UnknownCampaign(id.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ethers::types::U256;
+
+    fn campaign(multiplier: u64) -> MerkleTree {
+        MerkleTree::new(
+            (1..=9u64)
+                .map(|i| (Address::from_low_u64_be(i), U256::from(i * multiplier)))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn lookups_across_campaigns_with_overlapping_addresses() {
+        let registry = TreeRegistry::new();
+        registry.insert("spring".to_string(), campaign(10));
+        registry.insert("autumn".to_string(), campaign(20));
+        let account = Address::from_low_u64_be(3);
+
+        let spring = registry.proof("spring", account).unwrap();
+        let autumn = registry.proof("autumn", account).unwrap();
+        assert_eq!(spring.amount, U256::from(30));
+        assert_eq!(autumn.amount, U256::from(60));
+        assert!(spring.verify(registry.root("spring").unwrap().unwrap()));
+        assert!(autumn.verify(registry.root("autumn").unwrap().unwrap()));
+        assert_eq!(registry.ids(), vec!["autumn", "spring"]);
+
+        assert_eq!(
+            registry.proof("winter", account),
+            Err(RegistryError::UnknownCampaign("winter".to_string()))
+        );
+        assert_eq!(
+            registry.proof("spring", Address::repeat_byte(0xee)),
+            Err(RegistryError::UnknownAddress {
+                campaign: "spring".to_string(),
+                account: Address::repeat_byte(0xee),
+            })
+        );
+        assert!(registry.remove("spring").is_some());
+        assert!(registry.replace("spring", campaign(1)).is_err());
+        assert_eq!(registry.ids(), vec!["autumn"]);
+    }
+
+    #[test]
+    fn concurrent_reads_during_tree_swap() {
+        let registry = TreeRegistry::new();
+        let (old, new) = (campaign(1), campaign(2));
+        let roots = [old.get_root().unwrap(), new.get_root().unwrap()];
+        registry.insert("live".to_string(), old);
+
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for i in 1..=200u64 {
+                        let account = Address::from_low_u64_be(i % 9 + 1);
+                        let bundle = registry.proof("live", account).unwrap();
+                        assert!(roots.iter().any(|root| bundle.verify(*root)));
+                    }
+                });
+            }
+            scope.spawn(|| {
+                for i in 0..50 {
+                    registry.replace("live", campaign(1 + i % 2)).unwrap();
+                }
+            });
+        });
+        assert_eq!(registry.root("live").unwrap(), Some(roots[1]));
+    }
+}
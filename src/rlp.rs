@@ -0,0 +1,97 @@
+use crate::MerkleError;
+use ethers::types::H256;
+use ethers::utils::rlp::{self, Rlp};
+
+/// Encodes a proof as an RLP list of 32-byte strings, the encoding of a `bytes32[]` on
+/// the wire, e.g. for storing proofs in a file.
+///
+/// A proof of `n` hashes takes `33 * n` bytes plus a list header of at most 3 bytes for
+/// proofs of fewer than 2,000 hashes.
+///
+/// # Arguments
+///
+/// * `proof` - The proof hashes.
+///
+/// # Returns
+///
+/// The RLP encoding of the proof.
+pub fn proof_to_rlp(proof: &[H256]) -> Vec<u8> {
+    rlp::encode_list::<H256, H256>(proof).to_vec()
+}
+
+/// Decodes a proof encoded by `proof_to_rlp`.
+///
+/// # Arguments
+///
+/// * `bytes` - The RLP encoding of the proof.
+///
+/// # Returns
+///
+/// A `Result` containing the proof hashes, or `MerkleError::InvalidMessage` if the bytes
+/// are not exactly one RLP list of 32-byte strings.
+pub fn proof_from_rlp(bytes: &[u8]) -> Result<Vec<H256>, MerkleError> {
+    let invalid =
+        |err: rlp::DecoderError| MerkleError::InvalidMessage(format!("rlp proof: {}", err));
+    let list = Rlp::new(bytes);
+    let info = list.payload_info().map_err(invalid)?;
+    if !list.is_list() {
+        return Err(invalid(rlp::DecoderError::RlpExpectedToBeList));
+    }
+    if info.header_len + info.value_len != bytes.len() {
+        return Err(invalid(rlp::DecoderError::RlpInconsistentLengthAndData));
+    }
+    list.as_list().map_err(invalid)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rlp_proofs_round_trip() {
+        for len in [0u8, 1, 2, 20, 255] {
+            let proof: Vec<H256> = (0..len).map(H256::repeat_byte).collect();
+            let bytes = proof_to_rlp(&proof);
+            assert_eq!(proof_from_rlp(&bytes).unwrap(), proof);
+            let payload = 33 * proof.len();
+            let header = match payload {
+                0..=55 => 1,
+                56..=255 => 2,
+                _ => 3,
+            };
+            assert_eq!(bytes.len(), header + payload);
+        }
+        assert_eq!(proof_to_rlp(&[]), [0xc0]);
+        let proof = [H256::repeat_byte(0xab)];
+        let mut expected = vec![0xe1, 0xa0];
+        expected.extend_from_slice(&[0xab; 32]);
+        assert_eq!(proof_to_rlp(&proof), expected);
+    }
+
+    #[test]
+    fn malformed_rlp_is_rejected() {
+        let proof: Vec<H256> = (0..3u8).map(H256::repeat_byte).collect();
+        let bytes = proof_to_rlp(&proof);
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        let mut short_hash = vec![0xdf, 0x9f];
+        short_hash.extend_from_slice(&[1; 31]);
+        let cases: [&[u8]; 6] = [
+            &[],
+            &bytes[..bytes.len() - 1],
+            &trailing,
+            // a string, not a list
+            &bytes[1..34],
+            &short_hash,
+            // a nested list
+            &[0xc1, 0xc0],
+        ];
+        for case in cases {
+            assert!(
+                matches!(proof_from_rlp(case), Err(MerkleError::InvalidMessage(_))),
+                "{:?}",
+                case
+            );
+        }
+    }
+}
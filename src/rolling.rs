@@ -0,0 +1,165 @@
+use crate::{Entry, MerkleTree};
+use ethers::types::H256;
+
+/// An append-only positional tree that can reproduce its root at any earlier size.
+///
+/// Entries keep their insertion order and pairs are hashed like [`MerkleTree::new_ordered`],
+/// so `root_at_size(n)` equals the root of `new_ordered` over the first `n` leaves. Every
+/// complete subtree is hashed once when its last leaf is appended and kept, which takes
+/// about twice the memory of the leaves; a historical root or proof then only hashes the
+/// O(log n) incomplete nodes on the right edge of the tree of that size.
+#[derive(Debug, Clone, Default)]
+pub struct RollingTree {
+    // levels[l] holds the roots of the complete subtrees of 2^l leaves, in order
+    levels: Vec<Vec<H256>>,
+}
+
+impl RollingTree {
+    /// Creates an empty tree.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends an entry, hashed with `hash_node`.
+    pub fn append(&mut self, entry: Entry) {
+        self.append_leaf(entry.leaf());
+    }
+
+    /// Appends a leaf hash.
+    pub fn append_leaf(&mut self, leaf: H256) {
+        let mut node = leaf;
+        let mut level = 0;
+        loop {
+            if self.levels.len() == level {
+                self.levels.push(Vec::new());
+            }
+            let nodes = &mut self.levels[level];
+            nodes.push(node);
+            if nodes.len() % 2 == 1 {
+                return;
+            }
+            node = MerkleTree::hash_ordered_pair(&nodes[nodes.len() - 2], &node);
+            level += 1;
+        }
+    }
+
+    /// Returns the number of leaves appended so far.
+    pub fn len(&self) -> usize {
+        self.levels.first().map_or(0, Vec::len)
+    }
+
+    /// Returns whether no leaf has been appended yet.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the root of all leaves appended so far.
+    pub fn root(&self) -> Option<H256> {
+        self.root_at_size(self.len())
+    }
+
+    /// Returns the root as it stood after the first `n` leaves were appended.
+    ///
+    /// # Returns
+    ///
+    /// An `Option` containing the root, or `None` if `n` is zero or more than `len()`.
+    pub fn root_at_size(&self, n: usize) -> Option<H256> {
+        self.right_edge(n)?.last().copied()
+    }
+
+    /// Retrieves the proof of a leaf in the tree as it stood after `n` leaves.
+    ///
+    /// The proof verifies with `MerkleTree::verify_ordered_proof(leaf, index, n, ..)`
+    /// against `root_at_size(n)`.
+    ///
+    /// # Arguments
+    ///
+    /// * `n` - The historical size of the tree.
+    /// * `index` - The position of the leaf in insertion order.
+    ///
+    /// # Returns
+    ///
+    /// An `Option` containing the sibling hashes from the leaf up, or `None` if `n` is
+    /// more than `len()` or `index` is not less than `n`.
+    pub fn proof_at_size(&self, n: usize, index: usize) -> Option<Vec<H256>> {
+        if index >= n {
+            return None;
+        }
+        let edge = self.right_edge(n)?;
+        let mut proof = Vec::new();
+        let (mut index, mut count) = (index, n);
+        for (level, last) in edge.iter().enumerate() {
+            if count == 1 {
+                break;
+            }
+            let sibling = index ^ 1;
+            if sibling + 1 < count {
+                proof.push(self.levels[level][sibling]);
+            } else if sibling + 1 == count {
+                proof.push(*last);
+            }
+            index /= 2;
+            count = count.div_ceil(2);
+        }
+        Some(proof)
+    }
+
+    /// Computes the last node of every layer of the tree of size `n`, from the leaves up.
+    ///
+    /// All other nodes of that tree cover complete subtrees and are already stored.
+    fn right_edge(&self, n: usize) -> Option<Vec<H256>> {
+        if n == 0 || n > self.len() {
+            return None;
+        }
+        let mut last = self.levels[0][n - 1];
+        let mut edge = vec![last];
+        let (mut level, mut count) = (0, n);
+        while count > 1 {
+            let index = count - 1;
+            if index % 2 == 1 {
+                last = MerkleTree::hash_ordered_pair(&self.levels[level][index - 1], &last);
+            }
+            edge.push(last);
+            level += 1;
+            count = count.div_ceil(2);
+        }
+        Some(edge)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ethers::types::{Address, U256};
+
+    #[test]
+    fn historical_roots_and_proofs_match_fresh_trees() {
+        let entries: Vec<Entry> = (1..=21u64)
+            .map(|i| Entry::new(Address::from_low_u64_be(i % 6), U256::from(i)))
+            .collect();
+        let mut rolling = RollingTree::new();
+        for entry in &entries {
+            rolling.append(*entry);
+        }
+        let leaves: Vec<H256> = entries.iter().map(Entry::leaf).collect();
+        assert_eq!(rolling.len(), 21);
+        assert_eq!(rolling.root_at_size(0), None);
+        assert_eq!(rolling.root_at_size(22), None);
+
+        for size in 1..=leaves.len() {
+            let tree = MerkleTree::new_ordered(leaves[..size].to_vec());
+            let root = rolling.root_at_size(size).unwrap();
+            assert_eq!(Some(root), tree.get_root(), "{} leaves", size);
+
+            for (index, leaf) in leaves[..size].iter().enumerate() {
+                let proof = rolling.proof_at_size(size, index).unwrap();
+                assert_eq!(Some(&proof), tree.get_proof_at(index).as_ref());
+                assert!(MerkleTree::verify_ordered_proof(
+                    *leaf, index, size, &proof, root
+                ));
+            }
+            assert_eq!(rolling.proof_at_size(size, size), None);
+        }
+        assert_eq!(rolling.root(), rolling.root_at_size(21));
+    }
+}
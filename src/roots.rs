@@ -0,0 +1,223 @@
+use crate::MerkleTree;
+use ethers::types::H256;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A root accepted by a distributor, e.g. one of the old and new roots during a rotation.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AcceptedRoot {
+    /// The root hash.
+    pub root: H256,
+    /// A name for reports, such as `"round-3"`.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub label: Option<String>,
+    /// The unix timestamp in seconds from which the root is no longer accepted.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub expires_at: Option<u64>,
+}
+
+impl AcceptedRoot {
+    /// Creates an unlabeled root that never expires.
+    pub fn new(root: H256) -> Self {
+        AcceptedRoot {
+            root,
+            label: None,
+            expires_at: None,
+        }
+    }
+
+    /// Sets the label of the root.
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// Sets the timestamp from which the root is no longer accepted.
+    pub fn expiring_at(mut self, timestamp: u64) -> Self {
+        self.expires_at = Some(timestamp);
+        self
+    }
+
+    /// Returns whether the root is still accepted at `now`, in unix seconds.
+    pub fn is_live_at(&self, now: u64) -> bool {
+        self.expires_at.is_none_or(|expires_at| now < expires_at)
+    }
+}
+
+/// The roots a checker accepts, in order of preference.
+///
+/// With the `serde` feature it serializes as a plain list of roots, so it can be shipped
+/// as configuration.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct RootSet {
+    roots: Vec<AcceptedRoot>,
+}
+
+impl RootSet {
+    /// Creates an empty set, which accepts no proof.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a root, after the roots already in the set.
+    pub fn push(&mut self, root: AcceptedRoot) {
+        self.roots.push(root);
+    }
+
+    /// Returns the roots in order.
+    pub fn roots(&self) -> &[AcceptedRoot] {
+        &self.roots
+    }
+}
+
+impl From<Vec<AcceptedRoot>> for RootSet {
+    fn from(roots: Vec<AcceptedRoot>) -> Self {
+        RootSet { roots }
+    }
+}
+
+/// The root of a `RootSet` a proof verified against.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RootMatch {
+    /// The position of the root in the set.
+    pub index: usize,
+    /// The root hash.
+    pub root: H256,
+    /// The label of the root, if it has one.
+    pub label: Option<String>,
+}
+
+/// Verifies a proof against every root of a set that has not expired yet.
+///
+/// This is `verify_against_at` at the current system time.
+///
+/// # Arguments
+///
+/// * `roots` - The accepted roots.
+/// * `leaf` - The hash of the leaf.
+/// * `proof` - The sibling hashes from the leaf up.
+///
+/// # Returns
+///
+/// An `Option` containing the first live root the proof leads to, or `None` if there is
+/// none.
+pub fn verify_against(roots: &RootSet, leaf: H256, proof: &[H256]) -> Option<RootMatch> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    verify_against_at(roots, leaf, proof, now)
+}
+
+/// Verifies a proof against every root of a set that is live at a given time.
+///
+/// The proof is folded once with sorted pairs, like `verify_proof`, and the computed
+/// root compared with each root in order.
+///
+/// # Arguments
+///
+/// * `roots` - The accepted roots.
+/// * `leaf` - The hash of the leaf.
+/// * `proof` - The sibling hashes from the leaf up.
+/// * `now` - The unix timestamp in seconds against which expiries are checked.
+///
+/// # Returns
+///
+/// An `Option` containing the first live root the proof leads to, or `None` if there is
+/// none.
+pub fn verify_against_at(
+    roots: &RootSet,
+    leaf: H256,
+    proof: &[H256],
+    now: u64,
+) -> Option<RootMatch> {
+    let computed = MerkleTree::process_proof(leaf, proof);
+    let (index, accepted) = roots
+        .roots
+        .iter()
+        .enumerate()
+        .find(|(_, accepted)| accepted.root == computed && accepted.is_live_at(now))?;
+    Some(RootMatch {
+        index,
+        root: accepted.root,
+        label: accepted.label.clone(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ethers::types::{Address, U256};
+
+    fn tree(amounts: &[u64]) -> MerkleTree {
+        MerkleTree::new(
+            amounts
+                .iter()
+                .enumerate()
+                .map(|(i, amount)| (Address::from_low_u64_be(i as u64 + 1), U256::from(*amount)))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn proofs_match_the_root_they_belong_to() {
+        let old = tree(&[10, 20, 30]);
+        let new = tree(&[10, 20, 30, 40]);
+        let other = tree(&[1, 2, 3]);
+        let roots = RootSet::from(vec![
+            AcceptedRoot::new(new.get_root().unwrap()).with_label("new"),
+            AcceptedRoot::new(old.get_root().unwrap())
+                .with_label("old")
+                .expiring_at(1_000),
+        ]);
+        let proof_of = |tree: &MerkleTree| {
+            let leaf = tree.sorted_leaves()[1];
+            (leaf, tree.get_proof(leaf).unwrap())
+        };
+
+        let (leaf, proof) = proof_of(&new);
+        assert_eq!(
+            verify_against_at(&roots, leaf, &proof, 0),
+            Some(RootMatch {
+                index: 0,
+                root: new.get_root().unwrap(),
+                label: Some("new".to_string()),
+            })
+        );
+        assert_eq!(verify_against(&roots, leaf, &proof).unwrap().index, 0);
+
+        let (leaf, proof) = proof_of(&old);
+        let matched = verify_against_at(&roots, leaf, &proof, 999).unwrap();
+        assert_eq!((matched.index, matched.label.as_deref()), (1, Some("old")));
+        assert_eq!(verify_against_at(&roots, leaf, &proof, 1_000), None);
+
+        let (leaf, proof) = proof_of(&other);
+        assert_eq!(verify_against_at(&roots, leaf, &proof, 0), None);
+        assert_eq!(verify_against_at(&RootSet::new(), leaf, &proof, 0), None);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn root_sets_round_trip_as_config() {
+        let mut roots = RootSet::new();
+        roots.push(AcceptedRoot::new(H256::repeat_byte(1)).with_label("new"));
+        roots.push(AcceptedRoot::new(H256::repeat_byte(2)).expiring_at(1_700_000_000));
+        let json = serde_json::to_string(&roots).unwrap();
+        assert_eq!(
+            json,
+            format!(
+                r#"[{{"root":"{:?}","label":"new"}},{{"root":"{:?}","expires_at":1700000000}}]"#,
+                H256::repeat_byte(1),
+                H256::repeat_byte(2)
+            )
+        );
+        assert_eq!(serde_json::from_str::<RootSet>(&json).unwrap(), roots);
+    }
+}
@@ -0,0 +1,155 @@
+//! On-chain reconciliation against a deployed distributor, available with the `rpc` feature.
+
+use crate::{Entry, MerkleError, MerkleTree};
+use ethers::abi::ethabi;
+use ethers::providers::Middleware;
+use ethers::types::{Address, TransactionRequest, U256};
+use ethers::utils::id;
+
+/// Which leaves of a tree have been claimed on-chain.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ClaimStatusReport {
+    /// The claimed entries with their leaf index, in index order.
+    pub claimed: Vec<(usize, Entry)>,
+    /// The unclaimed entries with their leaf index, in index order.
+    pub unclaimed: Vec<(usize, Entry)>,
+    /// The sum of the claimed amounts.
+    pub claimed_total: U256,
+    /// The sum of the unclaimed amounts.
+    pub unclaimed_total: U256,
+}
+
+impl MerkleTree {
+    /// Reads a distributor's claimed bitmap and joins it with the retained entries.
+    ///
+    /// The distributor is expected to expose `claimedBitMap(uint256) returns (uint256)`
+    /// like Uniswap's `MerkleDistributor`, where bit `index % 256` of word `index / 256`
+    /// is set once the leaf at `index` has been claimed. Leaf indices are positions in
+    /// `sorted_leaves()`, as in `ProofBundle::index`. One word is read per 256 leaves.
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - The middleware used for the `eth_call`s.
+    /// * `distributor` - The address of the distributor contract.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the report, `MerkleError::NoEntries` if the tree has no
+    /// retained entries, or `MerkleError::Rpc` if a call fails.
+    pub async fn claim_status<M: Middleware>(
+        &self,
+        provider: &M,
+        distributor: Address,
+    ) -> Result<ClaimStatusReport, MerkleError> {
+        if self.entries.is_empty() {
+            return Err(MerkleError::NoEntries);
+        }
+
+        let mut report = ClaimStatusReport::default();
+        for (word_index, chunk) in self.entries.chunks(256).enumerate() {
+            let word = claimed_word(provider, distributor, word_index).await?;
+            for (bit, entry) in chunk.iter().enumerate() {
+                let index = word_index * 256 + bit;
+                let (list, total) = if word.bit(bit) {
+                    (&mut report.claimed, &mut report.claimed_total)
+                } else {
+                    (&mut report.unclaimed, &mut report.unclaimed_total)
+                };
+                list.push((index, *entry));
+                *total = total
+                    .checked_add(entry.amount)
+                    .ok_or(MerkleError::AmountOverflow(entry.account))?;
+            }
+        }
+        Ok(report)
+    }
+}
+
+async fn claimed_word<M: Middleware>(
+    provider: &M,
+    distributor: Address,
+    word_index: usize,
+) -> Result<U256, MerkleError> {
+    let mut data = id("claimedBitMap(uint256)").to_vec();
+    data.extend(ethabi::encode(&[ethabi::Token::Uint(word_index.into())]));
+    let call = TransactionRequest::new().to(distributor).data(data).into();
+
+    let output = provider
+        .call(&call, None)
+        .await
+        .map_err(|err| MerkleError::Rpc(err.to_string()))?;
+    if output.len() != 32 {
+        return Err(MerkleError::Rpc(format!(
+            "claimedBitMap({}) returned {} bytes",
+            word_index,
+            output.len()
+        )));
+    }
+    Ok(U256::from_big_endian(&output))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ethers::providers::Provider;
+    use ethers::types::Bytes;
+
+    fn word(bits: &[usize]) -> Bytes {
+        let mut word = U256::zero();
+        for bit in bits {
+            word |= U256::one() << *bit;
+        }
+        let mut bytes = [0u8; 32];
+        word.to_big_endian(&mut bytes);
+        Bytes::from(bytes.to_vec())
+    }
+
+    #[tokio::test]
+    async fn claim_status_maps_bits_across_word_boundaries() {
+        let tree = MerkleTree::new(
+            (1..=520u64)
+                .map(|i| (Address::from_low_u64_be(i), U256::from(i)))
+                .collect(),
+        );
+        let (provider, mock) = Provider::mocked();
+        // responses are served last-in first-out
+        mock.push::<Bytes, _>(word(&[0, 7])).unwrap();
+        mock.push::<Bytes, _>(word(&[0, 255])).unwrap();
+        mock.push::<Bytes, _>(word(&[0, 255])).unwrap();
+
+        let report = tree
+            .claim_status(&provider, Address::repeat_byte(0xd1))
+            .await
+            .unwrap();
+        let indices: Vec<usize> = report.claimed.iter().map(|(index, _)| *index).collect();
+        assert_eq!(indices, vec![0, 255, 256, 511, 512, 519]);
+        assert_eq!(report.unclaimed.len(), 520 - 6);
+
+        let amount = |index: usize| tree.entries()[index].amount;
+        let claimed_total = indices
+            .iter()
+            .fold(U256::zero(), |sum, index| sum + amount(*index));
+        assert_eq!(report.claimed_total, claimed_total);
+        assert_eq!(
+            report.claimed_total + report.unclaimed_total,
+            U256::from(520 * 521 / 2)
+        );
+    }
+
+    #[tokio::test]
+    async fn claim_status_requires_entries_and_valid_words() {
+        let (provider, mock) = Provider::mocked();
+        let hashed = MerkleTree::from_leaves(vec![ethers::types::H256::repeat_byte(1)]);
+        assert_eq!(
+            hashed.claim_status(&provider, Address::zero()).await,
+            Err(MerkleError::NoEntries)
+        );
+
+        let tree = MerkleTree::new(vec![(Address::zero(), U256::one())]);
+        mock.push::<Bytes, _>(Bytes::from(vec![1u8; 4])).unwrap();
+        assert!(matches!(
+            tree.claim_status(&provider, Address::zero()).await,
+            Err(MerkleError::Rpc(_))
+        ));
+    }
+}
@@ -0,0 +1,107 @@
+//! Conversions to and from the `rs_merkle` crate, available with the `rs-merkle-compat` feature.
+//!
+//! `rs_merkle` hashes pairs positionally, as `hash(left ++ right)`, and promotes the last
+//! node of an odd layer unchanged. That is exactly the layout of
+//! [`MerkleTree::new_ordered`], so with the [`Keccak256`] hasher below:
+//!
+//! | this crate                                   | `rs_merkle`                             | interoperable |
+//! |----------------------------------------------|-----------------------------------------|---------------|
+//! | `new_ordered(leaves)`                        | `MerkleTree::<Keccak256>::from_leaves`  | yes           |
+//! | `get_proof_at(i)` / `verify_ordered_proof`   | `proof(&[i])` / `MerkleProof::verify`   | yes           |
+//! | `new`, `from_leaves` (sorted leaves and pairs) | any                                   | no            |
+//!
+//! Sorted trees hash every pair in ascending order, which `rs_merkle` cannot express, so
+//! their proofs are never converted. Only single-leaf proofs are supported; `rs_merkle`
+//! multi-proofs have no counterpart here.
+
+use crate::MerkleTree;
+use ethers::core::utils::keccak256;
+use ethers::types::H256;
+use rs_merkle::{Hasher, MerkleProof};
+
+/// The keccak256 hasher for `rs_merkle`, matching this crate's pair hashing.
+#[derive(Debug, Clone, Copy)]
+pub struct Keccak256;
+
+impl Hasher for Keccak256 {
+    type Hash = [u8; 32];
+
+    fn hash(data: &[u8]) -> [u8; 32] {
+        keccak256(data)
+    }
+}
+
+impl MerkleTree {
+    /// Constructs an ordered tree from the leaves of an `rs_merkle` tree.
+    ///
+    /// The tree has the same root as `rs_merkle::MerkleTree::<Keccak256>::from_leaves(leaves)`.
+    pub fn from_rs_merkle_leaves(leaves: &[[u8; 32]]) -> Self {
+        Self::new_ordered(leaves.iter().copied().map(H256::from).collect())
+    }
+
+    /// Exports the proof of the leaf at `index` as an `rs_merkle` proof.
+    ///
+    /// # Returns
+    ///
+    /// An `Option` containing the proof, or `None` if the index is out of bounds or the
+    /// tree was not built with `new_ordered`, whose hashing `rs_merkle` cannot verify.
+    pub fn to_rs_merkle_proof(&self, index: usize) -> Option<MerkleProof<Keccak256>> {
+        if !self.is_ordered() {
+            return None;
+        }
+        let proof = self.get_proof_at(index)?;
+        Some(MerkleProof::new(
+            proof.into_iter().map(|hash| hash.0).collect(),
+        ))
+    }
+}
+
+/// Converts an `rs_merkle` single-leaf proof into proof hashes for `verify_ordered_proof`.
+pub fn proof_from_rs_merkle(proof: &MerkleProof<Keccak256>) -> Vec<H256> {
+    proof
+        .proof_hashes()
+        .iter()
+        .copied()
+        .map(H256::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn leaves(count: u8) -> Vec<[u8; 32]> {
+        (0..count).map(|i| keccak256([i])).collect()
+    }
+
+    #[test]
+    fn proofs_verify_across_both_crates() {
+        for count in [1u8, 2, 5, 7, 8, 13] {
+            let leaves = leaves(count);
+            let ours = MerkleTree::from_rs_merkle_leaves(&leaves);
+            let theirs = rs_merkle::MerkleTree::<Keccak256>::from_leaves(&leaves);
+            let root = ours.get_root().unwrap();
+            assert_eq!(theirs.root(), Some(root.0), "{} leaves", count);
+
+            for (index, leaf) in leaves.iter().enumerate() {
+                let exported = ours.to_rs_merkle_proof(index).unwrap();
+                assert!(exported.verify(root.0, &[index], &[*leaf], leaves.len()));
+
+                let imported = proof_from_rs_merkle(&theirs.proof(&[index]));
+                assert!(MerkleTree::verify_ordered_proof(
+                    H256::from(*leaf),
+                    index,
+                    leaves.len(),
+                    &imported,
+                    root
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn sorted_trees_are_not_exported() {
+        let tree = MerkleTree::from_leaves(leaves(4).into_iter().map(H256::from).collect());
+        assert!(tree.to_rs_merkle_proof(0).is_none());
+    }
+}
@@ -0,0 +1,123 @@
+//! OpenAPI schemas for the serde types, available with the `utoipa` feature.
+//!
+//! `Entry`, `ProofBundle` and its `EntryScheme` derive `ToSchema` with their hex formats; [`Proof`] is
+//! described here so that every item carries the pattern of a 32-byte hash. Register the
+//! types with `#[openapi(components(schemas(Entry, Proof, ProofBundle)))]`.
+
+use crate::Proof;
+use serde_json::json;
+use std::borrow::Cow;
+use utoipa::openapi::{ArrayBuilder, ObjectBuilder, RefOr, Schema, Type};
+
+impl utoipa::PartialSchema for Proof {
+    fn schema() -> RefOr<Schema> {
+        let hash = ObjectBuilder::new()
+            .schema_type(Type::String)
+            .pattern(Some("^0x[0-9a-fA-F]{64}$"));
+        ArrayBuilder::new()
+            .items(hash)
+            .description(Some(
+                "The sibling hashes from a leaf up to the root, as 0x-prefixed 32-byte hex strings.",
+            ))
+            .examples([json!([
+                "0x7c5f1ad3f2e4c1a5d7c3e6f0b9a8c7d6e5f4a3b2c1d0e9f8a7b6c5d4e3f2a1b0"
+            ])])
+            .into()
+    }
+}
+
+impl utoipa::ToSchema for Proof {
+    fn name() -> Cow<'static, str> {
+        Cow::Borrowed("Proof")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Entry, Proof, ProofBundle};
+    use serde_json::json;
+    use utoipa::OpenApi;
+
+    #[derive(OpenApi)]
+    #[openapi(components(schemas(Entry, Proof, ProofBundle)))]
+    struct ClaimApi;
+
+    #[test]
+    fn components_document_hex_formats() {
+        let components = serde_json::to_value(ClaimApi::openapi().components).unwrap();
+        let address = json!({
+            "type": "string",
+            "description": "The claiming account.",
+            "example": "0x00393d62f17b07e64f7cdcdf9bdc2fd925b20bba",
+            "pattern": "^0x[0-9a-fA-F]{40}$"
+        });
+        let amount = json!({
+            "type": "string",
+            "description": "The claimable amount in base units.",
+            "example": "0x63c2601c43bba41dc1",
+            "pattern": "^0x[0-9a-f]{1,64}$"
+        });
+        let mut schemas = components["schemas"].clone();
+        let scheme = schemas
+            .as_object_mut()
+            .unwrap()
+            .remove("EntryScheme")
+            .unwrap();
+        assert_eq!(
+            scheme["properties"]["node_sep"],
+            json!({
+                "type": "string",
+                "description": "The bytes inserted between the two sorted children of a pair.",
+                "example": "0x"
+            })
+        );
+        assert_eq!(
+            schemas["AmountWidth"]["enum"],
+            json!(["U256", "U128", "U96", "U64"])
+        );
+        schemas.as_object_mut().unwrap().remove("AmountWidth");
+        assert_eq!(
+            json!({ "schemas": schemas }),
+            json!({
+                "schemas": {
+                    "Entry": {
+                        "type": "object",
+                        "description": "An account and the amount it is entitled to, i.e. the data behind a leaf.",
+                        "required": ["account", "amount"],
+                        "properties": { "account": address, "amount": amount }
+                    },
+                    "Proof": {
+                        "type": "array",
+                        "items": { "type": "string", "pattern": "^0x[0-9a-fA-F]{64}$" },
+                        "description": "The sibling hashes from a leaf up to the root, as 0x-prefixed 32-byte hex strings.",
+                        "examples": [[
+                            "0x7c5f1ad3f2e4c1a5d7c3e6f0b9a8c7d6e5f4a3b2c1d0e9f8a7b6c5d4e3f2a1b0"
+                        ]]
+                    },
+                    "ProofBundle": {
+                        "type": "object",
+                        "description": "Everything a claimant needs to submit a claim: the entry, its leaf index and its proof.",
+                        "required": ["account", "amount", "index", "proof"],
+                        "properties": {
+                            "account": address,
+                            "amount": amount,
+                            "index": {
+                                "type": "integer",
+                                "description": "The index of the leaf among the sorted leaves of the tree.",
+                                "minimum": 0
+                            },
+                            "proof": {
+                                "$ref": "#/components/schemas/Proof",
+                                "description": "The proof of the leaf."
+                            },
+                            "scheme": {
+                                "$ref": "#/components/schemas/EntryScheme",
+                                "description": "How the tree hashes the entry and the pairs of the proof."
+                            }
+                        }
+                    }
+                }
+            })
+        );
+    }
+}
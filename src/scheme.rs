@@ -0,0 +1,185 @@
+use crate::{AmountWidth, MerkleError, MerkleTree, NodeSep};
+use ethers::core::utils::keccak256;
+use ethers::types::{Address, H256, U256};
+
+/// How a leaf preimage and its proof are hashed, for verifiers serving several trees.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct HashScheme {
+    /// Hash the preimage twice into the leaf, `keccak256(keccak256(preimage))`, like
+    /// OpenZeppelin's `StandardMerkleTree`; otherwise the leaf is `keccak256(preimage)`.
+    /// `true` by default.
+    pub double_hash_leaves: bool,
+    /// The bytes inserted between the two sorted children of a pair, empty by default.
+    pub node_sep: NodeSep,
+}
+
+impl Default for HashScheme {
+    fn default() -> Self {
+        HashScheme {
+            double_hash_leaves: true,
+            node_sep: NodeSep::default(),
+        }
+    }
+}
+
+impl HashScheme {
+    /// Hashes a leaf preimage, such as `abi.encode(account, amount)`, into its leaf.
+    pub fn hash_leaf(&self, preimage: &[u8]) -> H256 {
+        let hash = keccak256(preimage);
+        H256(if self.double_hash_leaves {
+            keccak256(hash)
+        } else {
+            hash
+        })
+    }
+}
+
+/// How a tree hashes its `(address, amount)` entries into leaves and its pairs into nodes,
+/// so a claim can be verified without the tree.
+///
+/// The default is the scheme of `MerkleTree::new`: `hash_node` leaves and plain sorted
+/// pairs.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "utoipa", derive(utoipa::ToSchema))]
+pub struct EntryScheme {
+    /// The chain ID the leaves are bound to, as in `MerkleTree::new_for_chain`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub chain_id: Option<u64>,
+    /// The width of the amount of packed leaves, as in `MerkleTree::new_packed`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub amount_width: Option<AmountWidth>,
+    /// The bytes inserted between the two sorted children of a pair.
+    #[cfg_attr(feature = "serde", serde(default, with = "crate::serialize::node_sep"))]
+    #[cfg_attr(feature = "utoipa", schema(value_type = String, example = "0x"))]
+    pub node_sep: NodeSep,
+}
+
+impl EntryScheme {
+    /// Hashes an `(address, amount)` entry into its leaf.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the leaf, or `MerkleError::AmountOutOfRange` if the scheme
+    /// packs amounts into a width the amount does not fit in.
+    pub fn hash_entry(&self, leaf_data: (Address, U256)) -> Result<H256, MerkleError> {
+        Self::hash_with(self.chain_id, self.amount_width, leaf_data)
+    }
+
+    /// Verifies the proof of an entry against `root`.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the entry fits the scheme and its proof folds to `root`.
+    pub fn verify(&self, leaf_data: (Address, U256), proof: &[H256], root: H256) -> bool {
+        self.hash_entry(leaf_data).is_ok_and(|leaf| {
+            MerkleTree::process_proof_with(leaf, proof, self.node_sep.as_bytes()) == root
+        })
+    }
+
+    pub(crate) fn hash_with(
+        chain_id: Option<u64>,
+        amount_width: Option<AmountWidth>,
+        leaf_data: (Address, U256),
+    ) -> Result<H256, MerkleError> {
+        match (amount_width, chain_id) {
+            (Some(width), _) => MerkleTree::hash_packed_node(leaf_data, width),
+            (None, Some(chain_id)) => Ok(MerkleTree::hash_chain_node(chain_id, leaf_data)),
+            (None, None) => Ok(MerkleTree::hash_node(leaf_data)),
+        }
+    }
+}
+
+/// Verifies a proof given the preimage of its leaf rather than the leaf hash.
+///
+/// The leaf is derived from the preimage and the proof folded with the pair hash of
+/// `scheme`, so one entry point serves trees of any of the supported schemes.
+///
+/// # Arguments
+///
+/// * `preimage` - The encoded leaf data, e.g. `abi.encode(account, amount)`.
+/// * `proof` - The sibling hashes from the leaf up.
+/// * `root` - The root hash the proof should lead to.
+/// * `scheme` - How the leaf and the pairs are hashed.
+///
+/// # Returns
+///
+/// A `Result` containing the derived leaf if the proof is valid, or
+/// `MerkleError::RootMismatch` with the root the proof actually leads to.
+pub fn verify_and_derive(
+    preimage: &[u8],
+    proof: &[H256],
+    root: H256,
+    scheme: &HashScheme,
+) -> Result<H256, MerkleError> {
+    let leaf = scheme.hash_leaf(preimage);
+    let computed = MerkleTree::process_proof_with(leaf, proof, scheme.node_sep.as_bytes());
+    if computed != root {
+        return Err(MerkleError::RootMismatch {
+            expected: root,
+            computed,
+        });
+    }
+    Ok(leaf)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ethers::abi::{encode, Token};
+    use ethers::types::{Address, U256};
+
+    fn preimages() -> Vec<Vec<u8>> {
+        (1..=5u64)
+            .map(|i| {
+                encode(&[
+                    Token::Address(Address::from_low_u64_be(i)),
+                    Token::Uint(U256::from(i * 100)),
+                ])
+            })
+            .collect()
+    }
+
+    #[test]
+    fn verify_and_derive_follows_the_scheme() {
+        let preimages = preimages();
+        let oz = HashScheme::default();
+        let tree = MerkleTree::new(
+            (1..=5u64)
+                .map(|i| (Address::from_low_u64_be(i), U256::from(i * 100)))
+                .collect(),
+        );
+        let root = tree.get_root().unwrap();
+        for preimage in &preimages {
+            let leaf = oz.hash_leaf(preimage);
+            let proof = tree.get_proof(leaf).unwrap();
+            assert_eq!(verify_and_derive(preimage, &proof, root, &oz), Ok(leaf));
+        }
+
+        let tagged = HashScheme {
+            double_hash_leaves: false,
+            node_sep: NodeSep(vec![0x01]),
+        };
+        let mut leaves: Vec<H256> = preimages.iter().map(|p| tagged.hash_leaf(p)).collect();
+        leaves.sort();
+        let tagged_tree = MerkleTree::from_sorted_elements_with(leaves, tagged.node_sep.clone());
+        let tagged_root = tagged_tree.get_root().unwrap();
+        let leaf = tagged.hash_leaf(&preimages[2]);
+        let proof = tagged_tree.get_proof(leaf).unwrap();
+        assert_eq!(
+            verify_and_derive(&preimages[2], &proof, tagged_root, &tagged),
+            Ok(leaf)
+        );
+
+        // the same proof under the other scheme leads elsewhere
+        let computed = MerkleTree::process_proof_with(oz.hash_leaf(&preimages[2]), &proof, &[]);
+        assert_eq!(
+            verify_and_derive(&preimages[2], &proof, tagged_root, &oz),
+            Err(MerkleError::RootMismatch {
+                expected: tagged_root,
+                computed
+            })
+        );
+        assert!(verify_and_derive(&preimages[3], &proof, tagged_root, &tagged).is_err());
+    }
+}
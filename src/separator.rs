@@ -0,0 +1,125 @@
+use crate::{Entry, MerkleTree};
+use ethers::types::{Address, H256, U256};
+
+/// Bytes inserted between the two child hashes before hashing a pair.
+///
+/// Some schemes domain-separate internal nodes with a tag byte or a length between the
+/// children, hashing `keccak256(min ++ sep ++ max)`. The default separator is empty, which
+/// is the plain sorted pair hash of OpenZeppelin's `MerkleProof`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct NodeSep(pub Vec<u8>);
+
+impl NodeSep {
+    /// Returns the separator bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl MerkleTree {
+    /// Computes the sorted pair hash of two nodes with a separator between them.
+    ///
+    /// # Arguments
+    ///
+    /// * `a` - One child hash.
+    /// * `b` - The other child hash.
+    /// * `sep` - The bytes inserted between the smaller and the larger hash.
+    ///
+    /// # Returns
+    ///
+    /// `keccak256(min(a, b) ++ sep ++ max(a, b))`; with an empty `sep` this is the pair
+    /// hash the tree uses by default.
+    #[inline]
+    pub fn hash_pair_with(a: &H256, b: &H256, sep: &[u8]) -> H256 {
+        H256(oz_merkle_core::hash_pair(&a.0, &b.0, sep))
+    }
+
+    /// Constructs a new Merkle tree whose pairs are hashed with a separator.
+    ///
+    /// Leaves are hashed and sorted exactly like in `new`. Proofs are retrieved as usual,
+    /// and `verify_proof` on this tree folds them with the same separator.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - A vector containing tuples of addresses and amounts to be stored in the Merkle tree.
+    /// * `node_sep` - The separator inserted between the children of every pair.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `MerkleTree` containing the constructed Merkle tree.
+    pub fn new_with_node_sep(data: Vec<(Address, U256)>, node_sep: NodeSep) -> Self {
+        let leaves: Vec<H256> = data.iter().map(|x| Self::hash_node(*x)).collect();
+        let (elements, permutation) = Self::sort_tracked(&leaves);
+        let mut tree = Self::from_sorted_elements_with(elements, node_sep);
+        tree.retain_entries(data.into_iter().map(Entry::from), &permutation);
+        tree
+    }
+
+    /// Returns the separator the tree hashes pairs with, empty unless built with `new_with_node_sep`.
+    pub fn node_sep(&self) -> &NodeSep {
+        &self.config.node_sep
+    }
+
+    /// Verifies a proof against a root, folding pairs with a separator.
+    ///
+    /// # Arguments
+    ///
+    /// * `element` - The hash of the element to be verified.
+    /// * `proof` - The hashes forming the Merkle proof.
+    /// * `root` - The root hash of the Merkle tree.
+    /// * `node_sep` - The separator the tree was built with.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the proof is valid for the given element and root hash.
+    pub fn verify_proof_with_sep(
+        element: H256,
+        proof: &[H256],
+        root: H256,
+        node_sep: &NodeSep,
+    ) -> bool {
+        Self::process_proof_with(element, proof, node_sep.as_bytes()) == root
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn separator_changes_root_and_proofs_verify_with_it() {
+        let data: Vec<(Address, U256)> = (1..=5u64)
+            .map(|i| (Address::from_low_u64_be(i), U256::from(i)))
+            .collect();
+        let plain = MerkleTree::new(data.clone());
+        let sep = NodeSep(vec![0x01]);
+        let tree = MerkleTree::new_with_node_sep(data.clone(), sep.clone());
+        let root = tree.get_root().unwrap();
+        assert_ne!(Some(root), plain.get_root());
+        assert_eq!(tree.node_sep(), &sep);
+        assert_eq!(
+            MerkleTree::new_with_node_sep(data.clone(), NodeSep::default()).get_root(),
+            plain.get_root()
+        );
+
+        for leaf_data in data {
+            let leaf = MerkleTree::hash_node(leaf_data);
+            let proof = tree.get_proof(leaf).unwrap();
+            assert!(MerkleTree::verify_proof_with_sep(leaf, &proof, root, &sep));
+            assert!(tree.verify_proof(leaf, proof.clone(), root));
+            let mut reversed = proof.clone();
+            reversed.reverse();
+            assert!(tree.verify_proof_reversed(leaf, reversed.clone(), root));
+            assert!(!plain.verify_proof_reversed(leaf, reversed, root));
+            let bytes = tree.get_proof_bytes(leaf.0).unwrap();
+            assert!(tree.verify_proof_bytes(leaf.0, &bytes, root.0));
+            assert!(!plain.verify_proof_bytes(leaf.0, &bytes, root.0));
+            assert!(!MerkleTree::verify_proof_with_sep(
+                leaf,
+                &proof,
+                root,
+                &NodeSep::default()
+            ));
+        }
+    }
+}
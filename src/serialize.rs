@@ -0,0 +1,443 @@
+//! Serde support, available with the `serde` feature.
+//!
+//! Human-readable formats such as JSON get `0x`-prefixed hex strings, while binary formats
+//! such as bincode get raw fixed-size byte arrays, so the same derives serve both.
+
+use crate::{AmountWidth, Entry, MerkleTree, NodeSep, PayloadEntry, TokenMetadata};
+use ethers::types::{Address, H256, U256};
+use ethers::utils::hex;
+use serde::de::{self, Deserializer, SeqAccess, Visitor};
+use serde::ser::{SerializeSeq, SerializeTuple, Serializer};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A fixed-size byte array: hex in human-readable formats, a raw tuple of bytes otherwise.
+struct FixedBytes<const N: usize>([u8; N]);
+
+impl<const N: usize> Serialize for FixedBytes<N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            return serializer.serialize_str(&format!("0x{}", hex::encode(self.0)));
+        }
+        let mut tuple = serializer.serialize_tuple(N)?;
+        for byte in &self.0 {
+            tuple.serialize_element(byte)?;
+        }
+        tuple.end()
+    }
+}
+
+impl<'de, const N: usize> Deserialize<'de> for FixedBytes<N> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(FixedBytesVisitor)
+        } else {
+            deserializer.deserialize_tuple(N, FixedBytesVisitor)
+        }
+    }
+}
+
+struct FixedBytesVisitor<const N: usize>;
+
+impl<'de, const N: usize> Visitor<'de> for FixedBytesVisitor<N> {
+    type Value = FixedBytes<N>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} bytes as a 0x-prefixed hex string or a byte array", N)
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+        let digits = value
+            .strip_prefix("0x")
+            .ok_or_else(|| E::invalid_value(de::Unexpected::Str(value), &self))?;
+        let bytes =
+            hex::decode(digits).map_err(|_| E::invalid_value(de::Unexpected::Str(value), &self))?;
+        self.visit_bytes(&bytes)
+    }
+
+    fn visit_bytes<E: de::Error>(self, value: &[u8]) -> Result<Self::Value, E> {
+        let bytes = value
+            .try_into()
+            .map_err(|_| E::invalid_length(value.len(), &self))?;
+        Ok(FixedBytes(bytes))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut bytes = [0u8; N];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(i, &self))?;
+        }
+        Ok(FixedBytes(bytes))
+    }
+}
+
+pub(crate) mod hashes {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(hashes: &[H256], serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(hashes.len()))?;
+        for hash in hashes {
+            seq.serialize_element(&FixedBytes(hash.0))?;
+        }
+        seq.end()
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<H256>, D::Error> {
+        let hashes = Vec::<FixedBytes<32>>::deserialize(deserializer)?;
+        Ok(hashes.into_iter().map(|bytes| H256(bytes.0)).collect())
+    }
+}
+
+pub(crate) mod hash {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(hash: &H256, serializer: S) -> Result<S::Ok, S::Error> {
+        FixedBytes(hash.0).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<H256, D::Error> {
+        FixedBytes::deserialize(deserializer).map(|bytes| H256(bytes.0))
+    }
+}
+
+pub(crate) mod address {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(address: &Address, serializer: S) -> Result<S::Ok, S::Error> {
+        FixedBytes(address.0).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Address, D::Error> {
+        FixedBytes::deserialize(deserializer).map(|bytes| Address::from(bytes.0))
+    }
+}
+
+/// Amounts are `0x` quantities in human-readable formats and 32 big-endian bytes otherwise.
+pub(crate) mod amount {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(amount: &U256, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            return serializer.serialize_str(&format!("{:#x}", amount));
+        }
+        let mut bytes = [0u8; 32];
+        amount.to_big_endian(&mut bytes);
+        FixedBytes(bytes).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<U256, D::Error> {
+        if !deserializer.is_human_readable() {
+            return FixedBytes::<32>::deserialize(deserializer)
+                .map(|bytes| U256::from_big_endian(&bytes.0));
+        }
+        let value = String::deserialize(deserializer)?;
+        value
+            .strip_prefix("0x")
+            .and_then(|digits| U256::from_str_radix(digits, 16).ok())
+            .ok_or_else(|| de::Error::custom(format!("invalid amount {:?}", value)))
+    }
+}
+
+/// Separators are `0x` hex strings in human-readable formats and length-prefixed bytes otherwise.
+pub(crate) mod node_sep {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(sep: &NodeSep, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            return serializer.serialize_str(&format!("0x{}", hex::encode(sep.as_bytes())));
+        }
+        sep.0.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<NodeSep, D::Error> {
+        if !deserializer.is_human_readable() {
+            return Vec::deserialize(deserializer).map(NodeSep);
+        }
+        let value = String::deserialize(deserializer)?;
+        value
+            .strip_prefix("0x")
+            .and_then(|digits| hex::decode(digits).ok())
+            .map(NodeSep)
+            .ok_or_else(|| de::Error::custom(format!("invalid node separator {:?}", value)))
+    }
+}
+
+/// Byte strings are `0x` hex strings in human-readable formats and length-prefixed bytes
+/// otherwise.
+pub(crate) mod bytes {
+    use super::*;
+    use ethers::types::Bytes;
+
+    pub fn serialize<S: Serializer>(bytes: &Bytes, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            return serializer.serialize_str(&format!("0x{}", hex::encode(bytes)));
+        }
+        bytes.to_vec().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Bytes, D::Error> {
+        if !deserializer.is_human_readable() {
+            return Vec::deserialize(deserializer).map(Bytes::from);
+        }
+        let value = String::deserialize(deserializer)?;
+        value
+            .strip_prefix("0x")
+            .and_then(|digits| hex::decode(digits).ok())
+            .map(Bytes::from)
+            .ok_or_else(|| de::Error::custom(format!("invalid bytes {:?}", value)))
+    }
+}
+
+#[derive(Serialize)]
+struct TreeRef<'a> {
+    #[serde(serialize_with = "hashes::serialize")]
+    leaves: &'a [H256],
+    entries: &'a [Entry],
+    payload_entries: &'a [PayloadEntry],
+    empty_payload_as_two_field: bool,
+    chain_id: Option<u64>,
+    ordered: bool,
+    #[serde(with = "node_sep")]
+    node_sep: &'a NodeSep,
+    token: Option<&'a TokenMetadata>,
+    amount_width: Option<AmountWidth>,
+}
+
+#[derive(Deserialize)]
+struct TreeOwned {
+    #[serde(deserialize_with = "hashes::deserialize")]
+    leaves: Vec<H256>,
+    entries: Vec<Entry>,
+    #[serde(default)]
+    payload_entries: Vec<PayloadEntry>,
+    #[serde(default)]
+    empty_payload_as_two_field: bool,
+    chain_id: Option<u64>,
+    ordered: bool,
+    #[serde(default, with = "node_sep")]
+    node_sep: NodeSep,
+    #[serde(default)]
+    token: Option<TokenMetadata>,
+    #[serde(default)]
+    amount_width: Option<AmountWidth>,
+}
+
+/// A tree is serialized as its leaves, retained entries and options, and rebuilt from them
+/// on deserialization.
+impl Serialize for MerkleTree {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        TreeRef {
+            leaves: &self.elements,
+            entries: &self.entries,
+            payload_entries: &self.payload_entries,
+            empty_payload_as_two_field: self.config.payload.empty_as_two_field,
+            chain_id: self.config.chain_id,
+            ordered: self.config.ordered,
+            node_sep: &self.config.node_sep,
+            token: self.config.token.as_ref(),
+            amount_width: self.config.amount_width,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for MerkleTree {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let owned = TreeOwned::deserialize(deserializer)?;
+        if owned.ordered {
+            return Ok(MerkleTree::new_ordered(owned.leaves));
+        }
+        if !owned.entries.is_empty() && owned.entries.len() != owned.leaves.len() {
+            return Err(de::Error::custom(format!(
+                "{} entries for {} leaves",
+                owned.entries.len(),
+                owned.leaves.len()
+            )));
+        }
+
+        if !owned.payload_entries.is_empty() && owned.payload_entries.len() != owned.leaves.len() {
+            return Err(de::Error::custom(format!(
+                "{} payload entries for {} leaves",
+                owned.payload_entries.len(),
+                owned.leaves.len()
+            )));
+        }
+
+        let (elements, permutation) = MerkleTree::sort_tracked(&owned.leaves);
+        let mut tree = MerkleTree::from_sorted_elements_with(elements, owned.node_sep);
+        if !owned.entries.is_empty() {
+            tree.retain_entries(owned.entries.into_iter(), &permutation);
+        }
+        if !owned.payload_entries.is_empty() {
+            tree.retain_payload_entries(owned.payload_entries.into_iter(), &permutation);
+        }
+        tree.config.payload.empty_as_two_field = owned.empty_payload_as_two_field;
+        tree.config.chain_id = owned.chain_id;
+        tree.config.token = owned.token;
+        tree.config.amount_width = owned.amount_width;
+        Ok(tree)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{PayloadOptions, ProofBundle};
+    use ethers::types::Bytes;
+    use serde_json::json;
+
+    fn sample_tree() -> MerkleTree {
+        MerkleTree::new(
+            (1..=7u64)
+                .map(|i| (Address::from_low_u64_be(i), U256::exp10(18) * i))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn tree_round_trips_through_json_and_bincode() {
+        let tree = sample_tree();
+
+        let json = serde_json::to_string(&tree).unwrap();
+        assert!(json.contains(&format!("{:?}", tree.sorted_leaves()[0])));
+        let from_json: MerkleTree = serde_json::from_str(&json).unwrap();
+        assert_eq!(from_json.get_root(), tree.get_root());
+
+        let binary = bincode::serialize(&tree).unwrap();
+        // leaves and entries as raw bytes with a length prefix each, plus the options
+        assert_eq!(
+            binary.len(),
+            8 + 32 * 7 + 8 + (20 + 32) * 7 + 8 + 1 + 1 + 1 + 8 + 1 + 1
+        );
+        let from_binary: MerkleTree = bincode::deserialize(&binary).unwrap();
+        assert_eq!(from_binary.get_root(), tree.get_root());
+        assert_eq!(from_binary.claim_records(), tree.claim_records());
+    }
+
+    #[test]
+    fn node_separator_survives_round_trip() {
+        let data = (1..=5u64)
+            .map(|i| (Address::from_low_u64_be(i), U256::from(i)))
+            .collect();
+        let tree = MerkleTree::new_with_node_sep(data, NodeSep(vec![0xab, 0x40]));
+
+        let json = serde_json::to_string(&tree).unwrap();
+        assert!(json.contains("\"node_sep\":\"0xab40\""));
+        let from_json: MerkleTree = serde_json::from_str(&json).unwrap();
+        assert_eq!(from_json.get_root(), tree.get_root());
+        let from_binary: MerkleTree =
+            bincode::deserialize(&bincode::serialize(&tree).unwrap()).unwrap();
+        assert_eq!(from_binary.get_root(), tree.get_root());
+        assert_eq!(from_binary.node_sep(), tree.node_sep());
+    }
+
+    #[test]
+    fn payload_entries_survive_round_trip() {
+        let data = (1..=4u64)
+            .map(|i| {
+                let payload = Bytes::from(vec![i as u8; i as usize - 1]);
+                (Address::from_low_u64_be(i), U256::from(i), payload)
+            })
+            .collect();
+        let options = PayloadOptions {
+            empty_as_two_field: true,
+        };
+        let tree = MerkleTree::new_with_payload(data, options);
+
+        let json = serde_json::to_value(&tree).unwrap();
+        let third = tree
+            .payload_entries()
+            .iter()
+            .position(|entry| entry.account == Address::from_low_u64_be(3))
+            .unwrap();
+        assert_eq!(json["payload_entries"][third]["payload"], json!("0x0303"));
+        assert_eq!(json["empty_payload_as_two_field"], json!(true));
+        let from_json: MerkleTree = serde_json::from_value(json).unwrap();
+        let from_binary: MerkleTree =
+            bincode::deserialize(&bincode::serialize(&tree).unwrap()).unwrap();
+        for copy in [from_json, from_binary] {
+            assert_eq!(copy.get_root(), tree.get_root());
+            assert_eq!(copy.payload_entries(), tree.payload_entries());
+            let entry = &tree.payload_entries()[0];
+            assert_eq!(
+                copy.get_payload_proof((entry.account, entry.amount, &entry.payload)),
+                tree.get_payload_proof((entry.account, entry.amount, &entry.payload))
+            );
+        }
+    }
+
+    #[test]
+    fn token_metadata_survives_round_trip() {
+        let tree = sample_tree().with_token(TokenMetadata::new("DROP", 18));
+        let json = serde_json::to_value(&tree).unwrap();
+        assert_eq!(json["token"], json!({ "symbol": "DROP", "decimals": 18 }));
+        let from_json: MerkleTree = serde_json::from_value(json).unwrap();
+        assert_eq!(from_json.token(), tree.token());
+        let from_binary: MerkleTree =
+            bincode::deserialize(&bincode::serialize(&tree).unwrap()).unwrap();
+        assert_eq!(from_binary.token(), tree.token());
+    }
+
+    #[test]
+    fn amount_width_survives_round_trip() {
+        let tree = MerkleTree::new_packed(
+            (1..=5u64)
+                .map(|i| (Address::from_low_u64_be(i), U256::from(i)))
+                .collect(),
+            AmountWidth::U96,
+        )
+        .unwrap();
+        let from_json: MerkleTree =
+            serde_json::from_str(&serde_json::to_string(&tree).unwrap()).unwrap();
+        let from_binary: MerkleTree =
+            bincode::deserialize(&bincode::serialize(&tree).unwrap()).unwrap();
+        for copy in [from_json, from_binary] {
+            assert_eq!(copy.amount_width(), Some(AmountWidth::U96));
+            let leaf = copy
+                .hash_entry((Address::from_low_u64_be(3), U256::from(3)))
+                .unwrap();
+            assert_eq!(copy.get_proof(leaf), tree.get_proof(leaf));
+        }
+    }
+
+    #[test]
+    fn bundle_round_trips_through_json_and_bincode() {
+        let tree = sample_tree();
+        let root = tree.get_root().unwrap();
+        let entry = Entry::new(Address::from_low_u64_be(3), U256::exp10(18) * 3);
+        let bundle = tree.get_proof_bundle(entry).unwrap();
+
+        let json = serde_json::to_value(&bundle).unwrap();
+        assert_eq!(json["amount"], "0x29a2241af62c0000");
+        assert_eq!(
+            json["account"],
+            "0x0000000000000000000000000000000000000003"
+        );
+        let from_json: ProofBundle = serde_json::from_value(json).unwrap();
+        assert_eq!(from_json, bundle);
+        assert!(from_json.verify(root));
+
+        let binary = bincode::serialize(&bundle).unwrap();
+        let from_binary: ProofBundle = bincode::deserialize(&binary).unwrap();
+        assert_eq!(from_binary, bundle);
+        assert!(from_binary.verify(root));
+
+        let entry_json = serde_json::to_string(&entry).unwrap();
+        assert_eq!(serde_json::from_str::<Entry>(&entry_json).unwrap(), entry);
+    }
+
+    #[test]
+    fn rejects_cross_format_and_malformed_input() {
+        let tree = sample_tree();
+        let binary = bincode::serialize(&tree).unwrap();
+        assert!(serde_json::from_slice::<MerkleTree>(&binary).is_err());
+
+        let short_hash = r#"{"leaves":["0x1234"]}"#;
+        assert!(serde_json::from_str::<MerkleTree>(short_hash).is_err());
+        let unprefixed = format!(r#"{{"leaves":["{}"]}}"#, "ab".repeat(32));
+        assert!(serde_json::from_str::<MerkleTree>(&unprefixed).is_err());
+        assert!(bincode::deserialize::<MerkleTree>(&binary[..binary.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,365 @@
+use crate::{Entry, MerkleError, MerkleTree};
+use ethers::types::{Address, U256};
+use std::collections::HashSet;
+use std::fmt::Display;
+
+/// The totals of a distribution before and after `MerkleTree::map_amounts`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AmountMapReport {
+    /// The sum of the amounts of the original tree, saturating at `U256::MAX`.
+    pub total_before: U256,
+    /// The sum of the mapped amounts kept in the new tree, saturating at `U256::MAX`.
+    pub total_after: U256,
+    /// The number of entries mapped to zero and dropped.
+    pub dropped: usize,
+}
+
+impl MerkleTree {
+    /// Builds a tree of the entries whose address does not appear in another tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The tree whose addresses are removed, whose leaves and nodes must be
+    ///   hashed like those of `self`; token metadata and self-check modes may differ.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a tree with the same options as `self`, or
+    /// `MerkleError::NoEntries` if either tree retains no entries, or
+    /// `MerkleError::OptionsMismatch` if the trees hash their leaves or nodes differently.
+    pub fn subtract(&self, other: &MerkleTree) -> Result<MerkleTree, MerkleError> {
+        self.filter_by_addresses(other, false)
+    }
+
+    /// Builds a tree of the entries whose address also appears in another tree.
+    ///
+    /// Amounts are taken from `self`; only the addresses of `other` matter.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The tree whose addresses are kept, hashed like `self`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a tree with the same options as `self`, or the errors of
+    /// `subtract`.
+    pub fn intersect(&self, other: &MerkleTree) -> Result<MerkleTree, MerkleError> {
+        self.filter_by_addresses(other, true)
+    }
+
+    /// Builds a tree of the entries matching a predicate, such as a minimum amount.
+    ///
+    /// The tree is left untouched; the result has the same options and its leaf indices
+    /// are those of a tree built directly from the kept entries.
+    ///
+    /// # Arguments
+    ///
+    /// * `pred` - Returns `true` for the entries to keep.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a tree with the same options as `self`, empty if no entry
+    /// matches, or `MerkleError::NoEntries` if the tree retains no entries.
+    pub fn filter(&self, pred: impl Fn(&Entry) -> bool) -> Result<MerkleTree, MerkleError> {
+        if self.entries.is_empty() {
+            return Err(MerkleError::NoEntries);
+        }
+        let entries: Vec<Entry> = self
+            .entries
+            .iter()
+            .filter(|entry| pred(entry))
+            .copied()
+            .collect();
+        self.rebuild_from_entries(entries)
+    }
+
+    /// Builds a tree without the entries of a deny-list.
+    ///
+    /// # Arguments
+    ///
+    /// * `deny` - The addresses to remove; addresses not in the tree are ignored.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a tree with the same options as `self`, or the errors of
+    /// `filter`.
+    pub fn exclude_addresses(&self, deny: &HashSet<Address>) -> Result<MerkleTree, MerkleError> {
+        self.filter(|entry| !deny.contains(&entry.account))
+    }
+
+    /// Builds a tree with every amount transformed, e.g. a pro-rata cut or a decimals
+    /// migration.
+    ///
+    /// The transform sees one entry at a time; a halving written as `amount / 2` rounds
+    /// every amount down, so the new total can be lower than half the old one.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - Maps an address and its amount to the new amount, or fails.
+    /// * `drop_zero` - Whether entries mapped to zero are left out of the new tree.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing a tree with the same options as `self` and the totals before
+    /// and after, `MerkleError::AmountMapping` with the address of the first entry the
+    /// transform failed for, `MerkleError::AmountOutOfRange` for the first new amount the
+    /// tree's packed leaves cannot hold, or `MerkleError::NoEntries` if the tree retains no
+    /// entries.
+    pub fn map_amounts<E: Display>(
+        &self,
+        f: impl Fn(Address, U256) -> Result<U256, E>,
+        drop_zero: bool,
+    ) -> Result<(MerkleTree, AmountMapReport), MerkleError> {
+        if self.entries.is_empty() {
+            return Err(MerkleError::NoEntries);
+        }
+        let mut report = AmountMapReport::default();
+        let mut entries = Vec::with_capacity(self.entries.len());
+        for entry in &self.entries {
+            report.total_before = report.total_before.saturating_add(entry.amount);
+            let amount =
+                f(entry.account, entry.amount).map_err(|err| MerkleError::AmountMapping {
+                    account: entry.account,
+                    reason: err.to_string(),
+                })?;
+            if drop_zero && amount.is_zero() {
+                report.dropped += 1;
+                continue;
+            }
+            report.total_after = report.total_after.saturating_add(amount);
+            entries.push(Entry::new(entry.account, amount));
+        }
+        Ok((self.rebuild_from_entries(entries)?, report))
+    }
+
+    fn filter_by_addresses(
+        &self,
+        other: &MerkleTree,
+        keep: bool,
+    ) -> Result<MerkleTree, MerkleError> {
+        if self.entries.is_empty() || other.entries.is_empty() {
+            return Err(MerkleError::NoEntries);
+        }
+        if !self.config.same_hashing(&other.config) {
+            return Err(MerkleError::OptionsMismatch);
+        }
+        // leaves are sorted by hash rather than address, so both sides are sorted by
+        // address once and merged
+        let mut accounts: Vec<Address> = other.entries.iter().map(|entry| entry.account).collect();
+        accounts.sort_unstable();
+        let mut order: Vec<usize> = (0..self.entries.len()).collect();
+        order.sort_unstable_by_key(|&i| self.entries[i].account);
+        let mut found = vec![false; self.entries.len()];
+        let mut others = accounts.iter().peekable();
+        for i in order {
+            let account = self.entries[i].account;
+            while others.next_if(|other| **other < account).is_some() {}
+            found[i] = others.peek() == Some(&&account);
+        }
+        let entries: Vec<Entry> = self
+            .entries
+            .iter()
+            .zip(found)
+            .filter(|(_, found)| *found == keep)
+            .map(|(entry, _)| *entry)
+            .collect();
+        self.rebuild_from_entries(entries)
+    }
+
+    /// Builds a tree from entries with the same options as `self`, or fails with
+    /// `MerkleError::AmountOutOfRange` for the first amount the tree cannot pack.
+    pub(crate) fn rebuild_from_entries(
+        &self,
+        entries: Vec<Entry>,
+    ) -> Result<MerkleTree, MerkleError> {
+        let leaves: Vec<_> = entries
+            .iter()
+            .map(|entry| self.hash_entry((entry.account, entry.amount)))
+            .collect::<Result<_, _>>()?;
+        let (elements, permutation) = Self::sort_tracked(&leaves);
+        let mut tree = Self::from_sorted_elements_with(elements, self.config.node_sep.clone());
+        tree.retain_entries(entries.into_iter(), &permutation);
+        tree.config = self.config.clone();
+        Ok(tree)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{AmountWidth, NodeSep, TokenMetadata};
+    use ethers::types::U256;
+
+    fn tree(accounts: &[u64], scale: u64) -> MerkleTree {
+        MerkleTree::new_for_chain(data(accounts, scale), 10)
+    }
+
+    fn data(accounts: &[u64], scale: u64) -> Vec<(Address, U256)> {
+        accounts
+            .iter()
+            .map(|&i| (Address::from_low_u64_be(i), U256::from(i * scale)))
+            .collect()
+    }
+
+    #[test]
+    fn subtract_and_intersect_match_fresh_builds() {
+        let old = tree(&[1, 2, 3, 4, 5], 10);
+        let new = tree(&[4, 5, 6, 7], 20);
+        let disjoint = tree(&[8, 9], 1);
+
+        let cases = [
+            (new.subtract(&old), data(&[6, 7], 20)),
+            (new.intersect(&old), data(&[4, 5], 20)),
+            (old.intersect(&new), data(&[4, 5], 10)),
+            (old.subtract(&disjoint), data(&[1, 2, 3, 4, 5], 10)),
+            (old.intersect(&old), data(&[1, 2, 3, 4, 5], 10)),
+        ];
+        for (result, expected) in cases {
+            let result = result.unwrap();
+            let fresh = MerkleTree::new_for_chain(expected, 10);
+            assert_eq!(result.get_root(), fresh.get_root());
+            assert_eq!(result.claim_records(), fresh.claim_records());
+            assert_eq!(result.chain_id(), Some(10));
+        }
+
+        let empty = old.subtract(&old).unwrap();
+        assert_eq!(empty.leaves_length(), 0);
+        assert_eq!(
+            old.intersect(&disjoint).unwrap().get_root(),
+            empty.get_root()
+        );
+    }
+
+    #[test]
+    fn filters_match_fresh_builds() {
+        let tree = tree(&[1, 2, 3, 4, 5, 6, 7], 10);
+        let root = tree.get_root();
+
+        let deny: HashSet<Address> = [2, 5, 42].map(Address::from_low_u64_be).into();
+        let allowed = tree.exclude_addresses(&deny).unwrap();
+        let threshold = tree.filter(|entry| entry.amount > U256::from(40)).unwrap();
+        let cases = [
+            (allowed, data(&[1, 3, 4, 6, 7], 10)),
+            (threshold, data(&[5, 6, 7], 10)),
+        ];
+        for (result, expected) in cases {
+            let fresh = MerkleTree::new_for_chain(expected, 10);
+            assert_eq!(result.get_root(), fresh.get_root());
+            assert_eq!(result.claim_records(), fresh.claim_records());
+            assert_eq!(result.chain_id(), Some(10));
+            for record in result.claim_records() {
+                let leaf = result.hash_entry((record.account, record.amount)).unwrap();
+                assert_eq!(result.position(leaf), Some(record.index));
+            }
+        }
+
+        let none = tree.filter(|_| false).unwrap();
+        assert_eq!((none.leaves_length(), none.get_root()), (0, None));
+        assert_eq!(tree.get_root(), root);
+        assert_eq!(tree.leaves_length(), 7);
+
+        let hashes = MerkleTree::from_leaves(tree.sorted_leaves().to_vec());
+        assert_eq!(hashes.filter(|_| true).unwrap_err(), MerkleError::NoEntries);
+    }
+
+    #[test]
+    fn map_amounts_rebuilds_with_new_amounts() {
+        let tree = tree(&[1, 2, 3, 4, 5], 1);
+
+        let (halved, report) = tree
+            .map_amounts(|_, amount| Ok::<_, String>(amount / 2), false)
+            .unwrap();
+        let expected = [(1, 0), (2, 1), (3, 1), (4, 2), (5, 2)]
+            .map(|(i, amount)| (Address::from_low_u64_be(i), U256::from(amount)));
+        let fresh = MerkleTree::new_for_chain(expected.to_vec(), 10);
+        assert_eq!(halved.get_root(), fresh.get_root());
+        assert_eq!(halved.claim_records(), fresh.claim_records());
+        assert_eq!(
+            report,
+            AmountMapReport {
+                total_before: U256::from(15),
+                total_after: U256::from(6),
+                dropped: 0,
+            }
+        );
+
+        let (kept, report) = tree
+            .map_amounts(|_, amount| Ok::<_, String>(amount / 2), true)
+            .unwrap();
+        assert_eq!(report.dropped, 1);
+        assert_eq!(report.total_after, U256::from(6));
+        assert_eq!(
+            kept.get_root(),
+            MerkleTree::new_for_chain(expected[1..].to_vec(), 10).get_root()
+        );
+
+        let failing = tree.map_amounts(
+            |account, amount| {
+                if account == Address::from_low_u64_be(3) {
+                    Err("budget exhausted")
+                } else {
+                    Ok(amount)
+                }
+            },
+            false,
+        );
+        assert_eq!(
+            failing.unwrap_err(),
+            MerkleError::AmountMapping {
+                account: Address::from_low_u64_be(3),
+                reason: "budget exhausted".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn packed_trees_keep_amounts_in_range() {
+        let tree = MerkleTree::new_packed(data(&[1, 2, 3], 1), AmountWidth::U64).unwrap();
+        let (doubled, _) = tree
+            .map_amounts(|_, amount| Ok::<_, String>(amount * 2), false)
+            .unwrap();
+        assert_eq!(
+            doubled.get_root(),
+            MerkleTree::new_packed(data(&[1, 2, 3], 2), AmountWidth::U64)
+                .unwrap()
+                .get_root()
+        );
+        assert_eq!(doubled.amount_width(), Some(AmountWidth::U64));
+
+        let overflow = tree.map_amounts(|_, amount| Ok::<_, String>(amount << 64), false);
+        assert_eq!(
+            overflow.unwrap_err(),
+            MerkleError::AmountOutOfRange {
+                account: tree.entries()[0].account,
+                width: AmountWidth::U64
+            }
+        );
+    }
+
+    #[test]
+    fn conflicting_options_are_rejected() {
+        let chained = tree(&[1, 2], 1);
+        let plain = MerkleTree::new(data(&[1, 2], 1));
+        let separated = MerkleTree::new_with_node_sep(data(&[1, 2], 1), NodeSep(vec![1]));
+        assert_eq!(
+            chained.subtract(&plain).unwrap_err(),
+            MerkleError::OptionsMismatch
+        );
+        assert_eq!(
+            plain.intersect(&separated).unwrap_err(),
+            MerkleError::OptionsMismatch
+        );
+
+        let labelled = MerkleTree::new(data(&[2, 3], 1)).with_token(TokenMetadata::new("TKN", 6));
+        assert_eq!(
+            plain.intersect(&labelled).unwrap().get_root(),
+            MerkleTree::new(data(&[2], 1)).get_root()
+        );
+
+        let hashes = MerkleTree::from_leaves(plain.sorted_leaves().to_vec());
+        assert_eq!(
+            plain.intersect(&hashes).unwrap_err(),
+            MerkleError::NoEntries
+        );
+    }
+}
@@ -0,0 +1,68 @@
+use ethers::types::{Address, U256};
+
+/// Splits a dataset into `2^bits` shards by the leading bits of each address.
+///
+/// The split depends on the addresses only, so every run puts an address in the same
+/// shard, and addresses spread evenly for uniformly distributed accounts. Each shard keeps
+/// its pairs in input order and can be built into its own tree.
+///
+/// # Arguments
+///
+/// * `data` - The pairs of addresses and amounts to split.
+/// * `bits` - The number of leading address bits selecting the shard, `0` for one shard.
+///
+/// # Returns
+///
+/// The shards, where shard `i` holds the pairs whose address starts with the bits of `i`.
+///
+/// # Panics
+///
+/// If `bits` is greater than 16, which would allocate more than 65536 shards.
+pub fn shard_by_prefix(data: Vec<(Address, U256)>, bits: u8) -> Vec<Vec<(Address, U256)>> {
+    assert!(bits <= 16, "cannot split into 2^{} shards", bits);
+    let mut shards = vec![Vec::new(); 1 << bits];
+    for (account, amount) in data {
+        let prefix = u16::from_be_bytes([account[0], account[1]]);
+        let index = prefix.checked_shr(16 - bits as u32).unwrap_or(0);
+        shards[index as usize].push((account, amount));
+    }
+    shards
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ethers::core::utils::keccak256;
+
+    #[test]
+    fn shards_cover_the_input_and_are_balanced() {
+        let data: Vec<(Address, U256)> = (0..4096u64)
+            .map(|i| {
+                let account = Address::from_slice(&keccak256(i.to_be_bytes())[12..]);
+                (account, U256::from(i))
+            })
+            .collect();
+
+        let shards = shard_by_prefix(data.clone(), 3);
+        assert_eq!(shards.len(), 8);
+        for (i, shard) in shards.iter().enumerate() {
+            assert!(
+                (384..=640).contains(&shard.len()),
+                "shard {} has {}",
+                i,
+                shard.len()
+            );
+            assert!(shard.iter().all(|(account, _)| account[0] >> 5 == i as u8));
+        }
+        let mut recombined: Vec<_> = shards.concat();
+        let mut expected = data.clone();
+        recombined.sort();
+        expected.sort();
+        assert_eq!(recombined, expected);
+
+        assert_eq!(shard_by_prefix(data.clone(), 0), vec![data.clone()]);
+        let fine = shard_by_prefix(data, 16);
+        assert_eq!(fine.len(), 65536);
+        assert_eq!(fine.iter().map(Vec::len).sum::<usize>(), 4096);
+    }
+}
@@ -0,0 +1,758 @@
+//! Importers turning chain state and explorer exports into entries for
+//! [`MerkleTree::new`](crate::MerkleTree::new).
+//!
+//! `from_geth_alloc` is available with the `serde` feature, `from_etherscan_csv` with
+//! the `csv` feature and `from_balance_of` with the `rpc` feature.
+
+#[cfg(feature = "serde")]
+use crate::amount::parse_amount;
+#[cfg(feature = "csv")]
+use crate::amount::parse_decimal;
+#[cfg(any(feature = "csv", feature = "serde"))]
+use crate::MerkleError;
+#[cfg(feature = "rpc")]
+use ethers::abi::{ethabi, ParamType, Token};
+#[cfg(feature = "rpc")]
+use ethers::providers::Middleware;
+use ethers::types::{Address, U256};
+#[cfg(feature = "rpc")]
+use ethers::types::{BlockId, TransactionRequest};
+#[cfg(feature = "rpc")]
+use ethers::utils::id;
+#[cfg(feature = "rpc")]
+use futures::stream::{self, StreamExt};
+#[cfg(feature = "serde")]
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, Visitor};
+#[cfg(feature = "serde")]
+use serde::Deserialize;
+#[cfg(feature = "rpc")]
+use std::collections::HashMap;
+#[cfg(feature = "serde")]
+use std::fmt;
+#[cfg(any(feature = "csv", feature = "serde"))]
+use std::io::{BufReader, Read};
+#[cfg(any(feature = "csv", feature = "serde"))]
+use std::str::FromStr;
+
+/// Reads the balances of a geth `alloc` section or of a `geth dump` state dump.
+///
+/// The input is either a map of addresses to accounts, as in the `alloc` of a genesis
+/// file, or an object holding such a map under `alloc` or `accounts`, as written by
+/// `geth dump`. Only the `balance` of an account is read; code and storage are skipped
+/// without being kept, and entries are decoded as they are read, so dumps larger than
+/// memory can be imported.
+///
+/// Addresses may omit the `0x` prefix. Balances are `0x`-prefixed hex or decimal strings,
+/// and accounts with a zero balance are left out.
+///
+/// # Arguments
+///
+/// * `reader` - The source of the JSON document.
+///
+/// # Returns
+///
+/// A `Result` containing the entries in file order, or
+/// `MerkleError::InvalidAllocEntry` with the key of the first malformed account, or
+/// `MerkleError::InvalidAlloc` if the document is not valid JSON of that shape.
+#[cfg(feature = "serde")]
+pub fn from_geth_alloc<R: Read>(reader: R) -> Result<Vec<(Address, U256)>, MerkleError> {
+    let mut entries = Vec::new();
+    let mut failure = None;
+    let mut deserializer = serde_json::Deserializer::from_reader(BufReader::new(reader));
+    let result = AllocSeed {
+        entries: &mut entries,
+        failure: &mut failure,
+        nested: true,
+    }
+    .deserialize(&mut deserializer)
+    .and_then(|()| deserializer.end());
+    match (result, failure) {
+        (_, Some(failure)) => Err(failure),
+        (Err(err), None) => Err(MerkleError::InvalidAlloc(err.to_string())),
+        (Ok(()), None) => Ok(entries),
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct AllocAccount {
+    balance: Option<String>,
+}
+
+/// Visits a map of accounts, pushing every entry as soon as it is decoded.
+#[cfg(feature = "serde")]
+struct AllocSeed<'a> {
+    entries: &'a mut Vec<(Address, U256)>,
+    // the typed error of a malformed account, which serde can only carry as a string
+    failure: &'a mut Option<MerkleError>,
+    // whether the map may hold an `alloc` or `accounts` map instead of accounts
+    nested: bool,
+}
+
+#[cfg(feature = "serde")]
+impl<'de> DeserializeSeed<'de> for AllocSeed<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Visitor<'de> for AllocSeed<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a map of addresses to accounts")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        while let Some(key) = map.next_key::<String>()? {
+            if self.nested && (key == "alloc" || key == "accounts") {
+                map.next_value_seed(AllocSeed {
+                    entries: self.entries,
+                    failure: self.failure,
+                    nested: false,
+                })?;
+                continue;
+            }
+            if self.nested && key == "root" {
+                map.next_value::<IgnoredAny>()?;
+                continue;
+            }
+
+            let mut fail = |reason: String| {
+                let error = de::Error::custom(&reason);
+                *self.failure = Some(MerkleError::InvalidAllocEntry {
+                    key: key.clone(),
+                    reason,
+                });
+                error
+            };
+            let account = Address::from_str(&key)
+                .ok()
+                .filter(|_| key.trim_start_matches("0x").len() == 40)
+                .ok_or_else(|| fail("invalid address".to_string()))?;
+            let balance = map
+                .next_value::<AllocAccount>()
+                .map_err(|err| fail(err.to_string()))?
+                .balance
+                .ok_or_else(|| fail("missing balance".to_string()))?;
+            let amount = parse_amount(&balance, None, false).map_err(&mut fail)?;
+            if !amount.is_zero() {
+                self.entries.push((account, amount));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A row of an Etherscan export that `from_etherscan_csv` left out.
+#[cfg(feature = "csv")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedRow {
+    /// The line number of the row, the header being line 1.
+    pub row: usize,
+    /// The address of the row.
+    pub account: Address,
+    /// Why the row was left out.
+    pub reason: String,
+}
+
+/// The outcome of `from_etherscan_csv`.
+#[cfg(feature = "csv")]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct EtherscanImport {
+    /// The holders and their balances in base units, in file order.
+    pub entries: Vec<(Address, U256)>,
+    /// The rows left out, in file order.
+    pub skipped: Vec<SkippedRow>,
+}
+
+/// Reads the holders export of a token page on Etherscan.
+///
+/// The export quotes every field and names its columns `HolderAddress` and `Balance`,
+/// sometimes followed by `PendingBalanceUpdate`; column names are matched ignoring case,
+/// spaces and underscores, so `Holder Address` is accepted too, and extra columns are
+/// ignored. Balances are in token units and may use thousands separators or scientific
+/// notation such as `1.5E+21`; they are scaled by `10^decimals` exactly, without floating
+/// point. Holders with a zero balance are skipped and reported.
+///
+/// # Arguments
+///
+/// * `reader` - The source of the CSV export.
+/// * `decimals` - The decimals of the token.
+///
+/// # Returns
+///
+/// A `Result` containing the entries and the skipped rows, or
+/// `MerkleError::InvalidRow` with the line number of the first row that cannot be parsed,
+/// or of the header if a column is missing.
+#[cfg(feature = "csv")]
+pub fn from_etherscan_csv<R: Read>(
+    reader: R,
+    decimals: u8,
+) -> Result<EtherscanImport, MerkleError> {
+    let mut csv_reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_reader(BufReader::new(reader));
+    let header_error = |reason: String| MerkleError::InvalidRow { row: 1, reason };
+    let headers = csv_reader
+        .headers()
+        .map_err(|err| header_error(err.to_string()))?
+        .clone();
+    let column = |names: &[&str]| {
+        headers.iter().position(|header| {
+            let header: String = header
+                .trim_start_matches('\u{feff}')
+                .chars()
+                .filter(|c| !matches!(c, ' ' | '_'))
+                .collect::<String>()
+                .to_ascii_lowercase();
+            names.contains(&header.as_str())
+        })
+    };
+    let address_column = column(&["holderaddress", "address"])
+        .ok_or_else(|| header_error("missing a HolderAddress column".to_string()))?;
+    let balance_column = column(&["balance", "quantity"])
+        .ok_or_else(|| header_error("missing a Balance column".to_string()))?;
+
+    let mut import = EtherscanImport::default();
+    for record in csv_reader.records() {
+        let record = record.map_err(|err| MerkleError::InvalidRow {
+            row: err
+                .position()
+                .map_or(0, |position| position.line() as usize),
+            reason: err.to_string(),
+        })?;
+        let row = record
+            .position()
+            .map_or(0, |position| position.line() as usize);
+        let invalid = |reason: String| MerkleError::InvalidRow { row, reason };
+
+        let (account, balance) = match (record.get(address_column), record.get(balance_column)) {
+            (Some(account), Some(balance)) => (account, balance),
+            _ => return Err(invalid("expected an address and a balance".to_string())),
+        };
+        let account = Address::from_str(account)
+            .map_err(|_| invalid(format!("invalid address {:?}", account)))?;
+        let amount = parse_explorer_amount(balance, decimals).map_err(invalid)?;
+        if amount.is_zero() {
+            import.skipped.push(SkippedRow {
+                row,
+                account,
+                reason: "zero balance".to_string(),
+            });
+            continue;
+        }
+        import.entries.push((account, amount));
+    }
+    Ok(import)
+}
+
+/// Scales an explorer balance such as `"1,024.5"` or `"1.5E+21"` by `10^decimals`.
+#[cfg(feature = "csv")]
+fn parse_explorer_amount(value: &str, decimals: u8) -> Result<U256, String> {
+    let value = value.replace(',', "");
+    let Some((mantissa, exponent)) = value.split_once(['e', 'E']) else {
+        return parse_decimal(&value, decimals);
+    };
+    let invalid = || format!("invalid scientific amount {:?}", value);
+    let exponent: i64 = exponent
+        .strip_prefix('+')
+        .unwrap_or(exponent)
+        .parse()
+        .map_err(|_| invalid())?;
+    let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    // beyond 10^100 no amount fits a uint256 nor has a digit left above the decimals
+    if whole.is_empty() || exponent.abs() > 100 {
+        return Err(invalid());
+    }
+    // move the decimal point of the mantissa digits by the exponent
+    let digits = format!("{}{}", whole, fraction);
+    let point = whole.len() as i64 + exponent;
+    let plain = if point <= 0 {
+        format!("0.{}{}", "0".repeat(-point as usize), digits)
+    } else if point as usize >= digits.len() {
+        format!("{}{}", digits, "0".repeat(point as usize - digits.len()))
+    } else {
+        format!(
+            "{}.{}",
+            &digits[..point as usize],
+            &digits[point as usize..]
+        )
+    };
+    let plain = match plain.split_once('.') {
+        Some((whole, fraction)) if fraction.bytes().all(|b| b == b'0') => whole.to_string(),
+        Some((whole, fraction)) => format!("{}.{}", whole, fraction.trim_end_matches('0')),
+        None => plain,
+    };
+    parse_decimal(&plain, decimals).map_err(|_| invalid())
+}
+
+/// The address of Multicall3, deployed at the same address on most EVM chains.
+#[cfg(feature = "rpc")]
+pub const MULTICALL3: Address = ethers::types::H160([
+    0xca, 0x11, 0xbd, 0xe0, 0x59, 0x77, 0xb3, 0x63, 0x11, 0x67, 0x02, 0x88, 0x62, 0xbe, 0x2a, 0x17,
+    0x39, 0x76, 0xca, 0x11,
+]);
+
+/// How `from_balance_of` batches and retries its calls.
+#[cfg(feature = "rpc")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BalanceOfOptions {
+    /// The number of `balanceOf` calls aggregated into one `eth_call`, 500 by default.
+    pub batch_size: usize,
+    /// How many more times calls that failed are tried, 2 by default.
+    pub retries: usize,
+    /// The number of `eth_call`s in flight at once, 4 by default.
+    pub concurrency: usize,
+    /// The Multicall3 contract, `MULTICALL3` by default.
+    pub multicall: Address,
+}
+
+#[cfg(feature = "rpc")]
+impl Default for BalanceOfOptions {
+    fn default() -> Self {
+        BalanceOfOptions {
+            batch_size: 500,
+            retries: 2,
+            concurrency: 4,
+            multicall: MULTICALL3,
+        }
+    }
+}
+
+/// A holder whose balance `from_balance_of` could not read.
+#[cfg(feature = "rpc")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BalanceFailure {
+    /// The holder.
+    pub account: Address,
+    /// Why the last attempt failed: the RPC error, or the revert of `balanceOf`.
+    pub reason: String,
+}
+
+/// The outcome of `from_balance_of`.
+#[cfg(feature = "rpc")]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BalanceSnapshot {
+    /// The holders with a non-zero balance, in the order they were given.
+    pub entries: Vec<(Address, U256)>,
+    /// The holders whose balance could not be read after every retry, in the order they
+    /// were given.
+    pub failures: Vec<BalanceFailure>,
+}
+
+/// Reads the token balance of every holder at a block, through Multicall3.
+///
+/// Summing `Transfer` events misses rebasing and fee-on-transfer tokens; `balanceOf` at
+/// the snapshot block is what holders actually had. Calls are aggregated with
+/// `aggregate3`, each allowed to fail on its own, and every `eth_call` is pinned to
+/// `block`. Holders whose call reverts or whose batch fails are tried again up to
+/// `options.retries` times, then reported in `failures` instead of failing the snapshot.
+///
+/// # Arguments
+///
+/// * `provider` - The middleware used for the `eth_call`s.
+/// * `token` - The ERC-20 token.
+/// * `holders` - The accounts to read, e.g. the recipients of `Transfer` events.
+/// * `block` - The snapshot block number.
+/// * `options` - How calls are batched and retried.
+///
+/// # Returns
+///
+/// The holders with a non-zero balance, which can be passed to `MerkleTree::new`, and the
+/// holders that could not be read.
+#[cfg(feature = "rpc")]
+pub async fn from_balance_of<M: Middleware>(
+    provider: &M,
+    token: Address,
+    holders: Vec<Address>,
+    block: u64,
+    options: &BalanceOfOptions,
+) -> BalanceSnapshot {
+    let mut balances: HashMap<Address, U256> = HashMap::new();
+    let mut failed: HashMap<Address, String> = HashMap::new();
+    let mut pending = holders.clone();
+    for _ in 0..=options.retries {
+        if pending.is_empty() {
+            break;
+        }
+        let batches: Vec<Vec<Address>> = pending
+            .chunks(options.batch_size.max(1))
+            .map(<[Address]>::to_vec)
+            .collect();
+        let results: Vec<_> = stream::iter(batches)
+            .map(|batch| async move {
+                let result = aggregate_balances(provider, token, &batch, block, options).await;
+                (batch, result)
+            })
+            .buffered(options.concurrency.max(1))
+            .collect()
+            .await;
+        pending.clear();
+        for (batch, result) in results {
+            match result {
+                Ok(results) => {
+                    for (account, result) in batch.into_iter().zip(results) {
+                        match result {
+                            Ok(balance) => {
+                                failed.remove(&account);
+                                balances.insert(account, balance);
+                            }
+                            Err(reason) => {
+                                failed.insert(account, reason);
+                                pending.push(account);
+                            }
+                        }
+                    }
+                }
+                Err(reason) => {
+                    for account in batch {
+                        failed.insert(account, reason.clone());
+                        pending.push(account);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut snapshot = BalanceSnapshot::default();
+    for account in holders {
+        if let Some(balance) = balances.get(&account) {
+            if !balance.is_zero() {
+                snapshot.entries.push((account, *balance));
+            }
+        } else if let Some(reason) = failed.remove(&account) {
+            snapshot.failures.push(BalanceFailure { account, reason });
+        }
+    }
+    snapshot
+}
+
+/// Encodes the `aggregate3` call reading the balance of every holder of a batch.
+#[cfg(feature = "rpc")]
+fn aggregate_call(token: Address, holders: &[Address], multicall: Address) -> TransactionRequest {
+    let selector = id("balanceOf(address)");
+    let calls = holders
+        .iter()
+        .map(|holder| {
+            let mut data = selector.to_vec();
+            data.extend(ethabi::encode(&[Token::Address(*holder)]));
+            Token::Tuple(vec![
+                Token::Address(token),
+                Token::Bool(true),
+                Token::Bytes(data),
+            ])
+        })
+        .collect();
+    let mut data = id("aggregate3((address,bool,bytes)[])").to_vec();
+    data.extend(ethabi::encode(&[Token::Array(calls)]));
+    TransactionRequest::new().to(multicall).data(data)
+}
+
+/// Reads the balances of a batch, with one result per holder.
+#[cfg(feature = "rpc")]
+async fn aggregate_balances<M: Middleware>(
+    provider: &M,
+    token: Address,
+    holders: &[Address],
+    block: u64,
+    options: &BalanceOfOptions,
+) -> Result<Vec<Result<U256, String>>, String> {
+    let call = aggregate_call(token, holders, options.multicall).into();
+    let output = provider
+        .call(&call, Some(BlockId::Number(block.into())))
+        .await
+        .map_err(|err| err.to_string())?;
+    let result_type = ParamType::Array(Box::new(ParamType::Tuple(vec![
+        ParamType::Bool,
+        ParamType::Bytes,
+    ])));
+    let decoded = ethabi::decode(&[result_type], &output)
+        .map_err(|err| format!("invalid aggregate3 output: {}", err))?;
+    let Some(Token::Array(results)) = decoded.into_iter().next() else {
+        return Err("invalid aggregate3 output".to_string());
+    };
+    if results.len() != holders.len() {
+        return Err(format!(
+            "aggregate3 returned {} results for {} calls",
+            results.len(),
+            holders.len()
+        ));
+    }
+    Ok(results
+        .into_iter()
+        .map(|result| match result {
+            Token::Tuple(fields) => match fields.as_slice() {
+                [Token::Bool(true), Token::Bytes(data)] if data.len() == 32 => {
+                    Ok(U256::from_big_endian(data))
+                }
+                [Token::Bool(true), Token::Bytes(data)] => {
+                    Err(format!("balanceOf returned {} bytes", data.len()))
+                }
+                [_, Token::Bytes(data)] => Err(format!(
+                    "balanceOf reverted with 0x{}",
+                    ethers::utils::hex::encode(data)
+                )),
+                _ => Err("invalid aggregate3 result".to_string()),
+            },
+            _ => Err("invalid aggregate3 result".to_string()),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[cfg(any(feature = "csv", feature = "serde"))]
+    use crate::MerkleTree;
+
+    #[cfg(feature = "serde")]
+    const ALLOC: &str = r#"{
+        "0x1111111111111111111111111111111111111111": { "balance": "0x3635c9adc5dea00000" },
+        "00393d62f17b07e64f7cdcdf9bdc2fd925b20bba": { "balance": "7840233889215604334017", "nonce": "0x1" },
+        "0x2222222222222222222222222222222222222222": {
+            "balance": "0x0",
+            "code": "0x6080",
+            "storage": { "0x00": "0x01" }
+        },
+        "0x3333333333333333333333333333333333333333": { "balance": "25", "code": "0x6080" }
+    }"#;
+
+    #[cfg(feature = "serde")]
+    fn expected() -> Vec<(Address, U256)> {
+        vec![
+            (
+                Address::from_str("0x1111111111111111111111111111111111111111").unwrap(),
+                U256::exp10(21),
+            ),
+            (
+                Address::from_str("0x00393d62f17b07e64f7cdcdf9bdc2fd925b20bba").unwrap(),
+                U256::from_dec_str("7840233889215604334017").unwrap(),
+            ),
+            (
+                Address::from_str("0x3333333333333333333333333333333333333333").unwrap(),
+                U256::from(25),
+            ),
+        ]
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn reads_alloc_sections_and_dumps() {
+        let entries = from_geth_alloc(ALLOC.as_bytes()).unwrap();
+        assert_eq!(entries, expected());
+        assert_eq!(
+            MerkleTree::new(entries).get_root(),
+            MerkleTree::new(expected()).get_root()
+        );
+
+        let dump = format!(
+            r#"{{"root": "0x{}", "accounts": {}}}"#,
+            "ab".repeat(32),
+            ALLOC
+        );
+        assert_eq!(from_geth_alloc(dump.as_bytes()).unwrap(), expected());
+        let genesis = format!(r#"{{"alloc": {}}}"#, ALLOC);
+        assert_eq!(from_geth_alloc(genesis.as_bytes()).unwrap(), expected());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn malformed_entries_name_their_key() {
+        let cases = [
+            (r#"{"0x12": {"balance": "1"}}"#, "0x12"),
+            (
+                r#"{"0x1111111111111111111111111111111111111111": {"balance": "1.5"}}"#,
+                "0x1111111111111111111111111111111111111111",
+            ),
+            (
+                r#"{"0x1111111111111111111111111111111111111111": {"nonce": "0x1"}}"#,
+                "0x1111111111111111111111111111111111111111",
+            ),
+            (r#"{"accounts": {"zz": {"balance": "1"}}}"#, "zz"),
+        ];
+        for (json, key) in cases {
+            match from_geth_alloc(json.as_bytes()) {
+                Err(MerkleError::InvalidAllocEntry { key: found, .. }) => assert_eq!(found, key),
+                other => panic!("unexpected result {:?} for {}", other, json),
+            }
+        }
+        let truncated = r#"{"0x1111111111111111111111111111111111111111": {"balance": "1"}"#;
+        for json in [truncated, "[]"] {
+            assert!(matches!(
+                from_geth_alloc(json.as_bytes()),
+                Err(MerkleError::InvalidAlloc(_))
+            ));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "csv")]
+    fn reads_the_etherscan_holders_export() {
+        let export = include_str!("../fixtures/etherscan_holders.csv");
+        let EtherscanImport { entries, skipped } =
+            from_etherscan_csv(export.as_bytes(), 18).unwrap();
+        let amount = |value: &str| U256::from_dec_str(value).unwrap();
+        let expected = vec![
+            (
+                Address::repeat_byte(0x11),
+                amount("1250000000000000000000000"),
+            ),
+            (Address::repeat_byte(0x22), amount("3141592653589793238462")),
+            (
+                Address::repeat_byte(0x33),
+                amount("1500000000000000000000000000000000000000"),
+            ),
+            (Address::repeat_byte(0x44), amount("250000000000")),
+            (Address::repeat_byte(0x66), amount("1024500000000000000000")),
+        ];
+        assert_eq!(entries, expected);
+        assert_eq!(
+            skipped,
+            vec![SkippedRow {
+                row: 6,
+                account: Address::repeat_byte(0x55),
+                reason: "zero balance".to_string(),
+            }]
+        );
+        assert_eq!(
+            MerkleTree::new(entries).get_root(),
+            MerkleTree::new(expected).get_root()
+        );
+
+        let renamed = "\u{feff}\"Holder Address\",\"Quantity\"\n\"0x1111111111111111111111111111111111111111\",\"1e-18\"\n";
+        assert_eq!(
+            from_etherscan_csv(renamed.as_bytes(), 18).unwrap().entries,
+            vec![(Address::repeat_byte(0x11), U256::one())]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "csv")]
+    fn rejects_unscalable_etherscan_rows() {
+        let precise = "\"HolderAddress\",\"Balance\"\n\"0x1111111111111111111111111111111111111111\",\"1.5e-7\"\n";
+        assert!(matches!(
+            from_etherscan_csv(precise.as_bytes(), 6),
+            Err(MerkleError::InvalidRow { row: 2, .. })
+        ));
+        let headless = "\"Account\",\"Balance\"\n";
+        assert!(matches!(
+            from_etherscan_csv(headless.as_bytes(), 6),
+            Err(MerkleError::InvalidRow { row: 1, .. })
+        ));
+    }
+
+    #[cfg(feature = "rpc")]
+    fn aggregate_output(results: &[Result<u64, &[u8]>]) -> ethers::types::Bytes {
+        let results = results
+            .iter()
+            .map(|result| {
+                let (success, data) = match result {
+                    Ok(balance) => {
+                        let mut word = [0u8; 32];
+                        U256::from(*balance).to_big_endian(&mut word);
+                        (true, word.to_vec())
+                    }
+                    Err(revert) => (false, revert.to_vec()),
+                };
+                Token::Tuple(vec![Token::Bool(success), Token::Bytes(data)])
+            })
+            .collect();
+        ethabi::encode(&[Token::Array(results)]).into()
+    }
+
+    #[cfg(feature = "rpc")]
+    #[tokio::test]
+    async fn balance_of_batches_and_pins_the_block() {
+        use ethers::providers::Provider;
+
+        let (provider, mock) = Provider::mocked();
+        let token = Address::repeat_byte(0x70);
+        let holders: Vec<Address> = (1..=5u64).map(Address::from_low_u64_be).collect();
+        let options = BalanceOfOptions {
+            batch_size: 2,
+            concurrency: 1,
+            ..Default::default()
+        };
+        // responses are served last-in first-out
+        mock.push::<ethers::types::Bytes, _>(aggregate_output(&[Ok(500)]))
+            .unwrap();
+        mock.push::<ethers::types::Bytes, _>(aggregate_output(&[Ok(300), Ok(400)]))
+            .unwrap();
+        mock.push::<ethers::types::Bytes, _>(aggregate_output(&[Ok(100), Ok(0)]))
+            .unwrap();
+
+        let snapshot =
+            from_balance_of(&provider, token, holders.clone(), 17_000_000, &options).await;
+        assert_eq!(
+            snapshot.entries,
+            [
+                (holders[0], U256::from(100)),
+                (holders[2], U256::from(300)),
+                (holders[3], U256::from(400)),
+                (holders[4], U256::from(500)),
+            ]
+        );
+        assert!(snapshot.failures.is_empty());
+        assert_eq!(
+            format!("{:?}", MULTICALL3),
+            "0xca11bde05977b3631167028862be2a173976ca11"
+        );
+
+        for batch in holders.chunks(2) {
+            let call: ethers::types::transaction::eip2718::TypedTransaction =
+                aggregate_call(token, batch, MULTICALL3).into();
+            mock.assert_request(
+                "eth_call",
+                [
+                    serde_json::to_value(call).unwrap(),
+                    serde_json::to_value(BlockId::Number(17_000_000u64.into())).unwrap(),
+                ],
+            )
+            .unwrap();
+        }
+    }
+
+    #[cfg(feature = "rpc")]
+    #[tokio::test]
+    async fn reverting_calls_are_retried_then_reported() {
+        use ethers::providers::{MockResponse, Provider};
+
+        let (provider, mock) = Provider::mocked();
+        let holders: Vec<Address> = (1..=3u64).map(Address::from_low_u64_be).collect();
+        let options = BalanceOfOptions {
+            batch_size: 2,
+            retries: 1,
+            concurrency: 1,
+            ..Default::default()
+        };
+        // the second round retries the reverting holder and the failed batch together
+        mock.push::<ethers::types::Bytes, _>(aggregate_output(&[Err(&[0xde, 0xad]), Ok(30)]))
+            .unwrap();
+        mock.push_response(MockResponse::Error(ethers::providers::JsonRpcError {
+            code: -32000,
+            message: "header not found".to_string(),
+            data: None,
+        }));
+        mock.push::<ethers::types::Bytes, _>(aggregate_output(&[Ok(10), Err(&[0xde, 0xad])]))
+            .unwrap();
+
+        let snapshot =
+            from_balance_of(&provider, Address::zero(), holders.clone(), 1, &options).await;
+        assert_eq!(
+            snapshot.entries,
+            [(holders[0], U256::from(10)), (holders[2], U256::from(30))]
+        );
+        assert_eq!(
+            snapshot.failures,
+            [BalanceFailure {
+                account: holders[1],
+                reason: "balanceOf reverted with 0xdead".to_string(),
+            }]
+        );
+    }
+}
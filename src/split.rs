@@ -0,0 +1,251 @@
+use crate::{Entry, MerkleTree, SplitError};
+use ethers::core::utils::keccak256;
+use ethers::types::U256;
+use std::collections::HashMap;
+
+/// How `MerkleTree::split` assigns entries to parts.
+pub enum SplitStrategy {
+    /// Sorts the entries by address and cuts them into consecutive ranges whose sizes
+    /// differ by at most one.
+    AddressRange,
+    /// Assigns each entry by a keccak hash of the seed and its address, so parts are
+    /// random-looking but identical for the same seed.
+    Seeded(u64),
+    /// Assigns each entry to the part returned by the function, modulo the part count.
+    Group(Box<dyn Fn(&Entry) -> usize>),
+}
+
+impl MerkleTree {
+    /// Splits the entries into `k` trees, e.g. for phased claims.
+    ///
+    /// Every entry lands in exactly one part, and each part is built with the options of
+    /// `self`. Parts keep their entries in the order of `self` and may be empty.
+    ///
+    /// # Arguments
+    ///
+    /// * `k` - The number of parts.
+    /// * `strategy` - How entries are assigned to parts.
+    ///
+    /// # Returns
+    ///
+    /// The `k` parts, all empty if the tree retains no entries.
+    ///
+    /// # Panics
+    ///
+    /// If `k` is zero.
+    pub fn split(&self, k: usize, strategy: SplitStrategy) -> Vec<MerkleTree> {
+        assert!(k > 0, "cannot split a tree into zero parts");
+        let mut parts: Vec<Vec<Entry>> = vec![Vec::new(); k];
+        match strategy {
+            SplitStrategy::AddressRange => {
+                let mut order: Vec<usize> = (0..self.entries.len()).collect();
+                order.sort_by_key(|&i| self.entries[i]);
+                // the first `len % k` parts take one entry more
+                let (size, rest) = (order.len() / k, order.len() % k);
+                let mut assigned = vec![0; order.len()];
+                let mut start = 0;
+                for part in 0..k {
+                    let end = start + size + usize::from(part < rest);
+                    for &i in &order[start..end] {
+                        assigned[i] = part;
+                    }
+                    start = end;
+                }
+                for (entry, part) in self.entries.iter().zip(assigned) {
+                    parts[part].push(*entry);
+                }
+            }
+            SplitStrategy::Seeded(seed) => {
+                for entry in &self.entries {
+                    let digest =
+                        keccak256([&seed.to_be_bytes()[..], entry.account.as_bytes()].concat());
+                    let part = U256::from_big_endian(&digest) % U256::from(k);
+                    parts[part.as_usize()].push(*entry);
+                }
+            }
+            SplitStrategy::Group(group) => {
+                for entry in &self.entries {
+                    parts[group(entry) % k].push(*entry);
+                }
+            }
+        }
+        parts
+            .into_iter()
+            .map(|entries| {
+                self.rebuild_from_entries(entries)
+                    .expect("the entries of a tree fit its amount width")
+            })
+            .collect()
+    }
+}
+
+/// Checks that parts hold exactly the entries of the original tree, e.g. the output of
+/// `MerkleTree::split`.
+///
+/// The entries of all parts together must equal those of `original` as a multiset: no
+/// entry missing, none repeated in two parts, none changed. The totals are compared as
+/// well, so a check of a split can be reported in terms of amounts.
+///
+/// # Arguments
+///
+/// * `original` - The tree that was split.
+/// * `parts` - The trees it was split into.
+///
+/// # Returns
+///
+/// A `Result` that is `Ok` if the split conserves every entry, or the first `SplitError`
+/// found.
+pub fn verify_split(original: &MerkleTree, parts: &[MerkleTree]) -> Result<(), SplitError> {
+    if original.entries.is_empty() && original.leaves > 0 {
+        return Err(SplitError::NoEntries);
+    }
+    let mut remaining: HashMap<Entry, usize> = HashMap::with_capacity(original.entries.len());
+    for entry in &original.entries {
+        *remaining.entry(*entry).or_default() += 1;
+    }
+    for (index, part) in parts.iter().enumerate() {
+        if part.config != original.config {
+            return Err(SplitError::OptionsMismatch { part: index });
+        }
+        if part.entries.is_empty() && part.leaves > 0 {
+            return Err(SplitError::NoEntries);
+        }
+        for entry in &part.entries {
+            match remaining.get_mut(entry) {
+                Some(count) if *count > 0 => *count -= 1,
+                _ => {
+                    return Err(SplitError::UnexpectedEntry {
+                        part: index,
+                        entry: *entry,
+                    })
+                }
+            }
+        }
+    }
+    if let Some(entry) = original.entries.iter().find(|entry| remaining[entry] > 0) {
+        return Err(SplitError::MissingEntry(*entry));
+    }
+
+    let total = |entries: &[Entry]| {
+        entries
+            .iter()
+            .try_fold(U256::zero(), |total, entry| total.checked_add(entry.amount))
+    };
+    let expected = total(&original.entries);
+    let found = parts.iter().try_fold(U256::zero(), |sum, part| {
+        sum.checked_add(total(&part.entries)?)
+    });
+    if expected != found {
+        return Err(SplitError::TotalMismatch { expected, found });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ethers::types::Address;
+
+    fn tree(count: u64) -> MerkleTree {
+        MerkleTree::new_for_chain(
+            (1..=count)
+                .map(|i| {
+                    let account = Address::from_slice(&keccak256(i.to_be_bytes())[12..]);
+                    (account, U256::from(i))
+                })
+                .collect(),
+            5,
+        )
+    }
+
+    #[test]
+    fn splits_conserve_every_entry() {
+        let original = tree(10_000);
+        let strategies = [
+            SplitStrategy::AddressRange,
+            SplitStrategy::Seeded(42),
+            SplitStrategy::Group(Box::new(|entry: &Entry| entry.amount.as_usize())),
+        ];
+        let splits: Vec<Vec<MerkleTree>> = strategies
+            .into_iter()
+            .map(|strategy| original.split(3, strategy))
+            .collect();
+        for parts in &splits {
+            assert_eq!(parts.len(), 3);
+            verify_split(&original, parts).unwrap();
+            for part in parts {
+                assert!((3000..=3700).contains(&part.leaves_length()));
+                assert_eq!(part.chain_id(), Some(5));
+                let root = part.get_root().unwrap();
+                for entry in part.entries().iter().step_by(97) {
+                    let leaf = part.hash_entry((entry.account, entry.amount)).unwrap();
+                    let proof = part.get_proof(leaf).unwrap();
+                    assert!(part.verify_proof(leaf, proof, root));
+                }
+            }
+        }
+
+        let ranges = &splits[0];
+        let sizes: Vec<usize> = ranges.iter().map(MerkleTree::leaves_length).collect();
+        assert_eq!(sizes, [3334, 3333, 3333]);
+        let last = |part: &MerkleTree| part.entries().iter().map(|entry| entry.account).max();
+        let first = |part: &MerkleTree| part.entries().iter().map(|entry| entry.account).min();
+        assert!(last(&ranges[0]) < first(&ranges[1]));
+        assert!(last(&ranges[1]) < first(&ranges[2]));
+
+        let small = tree(100);
+        let roots = |seed| -> Vec<_> {
+            small
+                .split(3, SplitStrategy::Seeded(seed))
+                .iter()
+                .map(MerkleTree::get_root)
+                .collect()
+        };
+        assert_eq!(roots(7), roots(7));
+        assert_ne!(roots(7), roots(8));
+    }
+
+    #[test]
+    fn broken_splits_are_reported() {
+        let original = tree(20);
+        let entries = original.entries().to_vec();
+        let parts = original.split(2, SplitStrategy::AddressRange);
+
+        assert_eq!(
+            verify_split(&original, &parts[..1]),
+            Err(SplitError::MissingEntry(
+                *entries
+                    .iter()
+                    .find(|entry| !parts[0].entries().contains(entry))
+                    .unwrap()
+            ))
+        );
+        let repeated = [parts[0].clone(), parts[1].clone(), parts[1].clone()];
+        assert_eq!(
+            verify_split(&original, &repeated),
+            Err(SplitError::UnexpectedEntry {
+                part: 2,
+                entry: parts[1].entries()[0]
+            })
+        );
+        let changed = parts[1]
+            .map_amounts(|_, amount| Ok::<_, String>(amount + 1), false)
+            .unwrap()
+            .0;
+        assert!(matches!(
+            verify_split(&original, &[parts[0].clone(), changed]),
+            Err(SplitError::UnexpectedEntry { part: 1, .. })
+        ));
+        let plain = MerkleTree::new(
+            parts[1]
+                .entries()
+                .iter()
+                .map(|entry| (entry.account, entry.amount))
+                .collect(),
+        );
+        assert_eq!(
+            verify_split(&original, &[parts[0].clone(), plain]),
+            Err(SplitError::OptionsMismatch { part: 1 })
+        );
+    }
+}
@@ -0,0 +1,290 @@
+//! OpenZeppelin's `StandardMerkleTree`, in the flat-array layout of `@openzeppelin/merkle-tree`.
+//!
+//! `MerkleTree` promotes the last node of an odd layer to the layer above. OZ's
+//! `makeMerkleTree` instead fills a single array from the back: the sorted leaves are
+//! stored in reverse at its end, and every other node is
+//! `tree[i] = hash(tree[2i + 1], tree[2i + 2])`. Both layouts give the same root when the
+//! number of leaves is a power of two or three times one, and different roots otherwise,
+//! e.g. for 5, 7 or 9 leaves. `StandardMerkleTree` follows OZ's layout for every size.
+
+use crate::{Entry, MerkleError, MerkleTree};
+use ethers::types::{Address, H256, U256};
+
+/// A value of a `StandardMerkleTree` and the index of its leaf in `tree()`, like the
+/// entries of OZ's `values` dump.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StandardValue {
+    /// The account and amount of the leaf.
+    pub value: Entry,
+    /// The index of the leaf in `tree()`.
+    pub tree_index: usize,
+}
+
+/// A Merkle tree with the exact layout, root and proofs of OpenZeppelin's
+/// `StandardMerkleTree.of(values, ["address", "uint256"])`.
+///
+/// Leaves are hashed with `MerkleTree::hash_node` and sorted, but not deduplicated: like
+/// OZ, a value given twice has two leaves.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StandardMerkleTree {
+    // OZ's `tree` array: the root at 0 and the children of node i at 2i + 1 and 2i + 2
+    tree: Vec<H256>,
+    // the values in input order
+    values: Vec<StandardValue>,
+}
+
+impl StandardMerkleTree {
+    /// Constructs a tree like OZ's `StandardMerkleTree.of`.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - A vector containing tuples of addresses and amounts, in any order.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the tree, or `MerkleError::NoEntries` if `data` is empty,
+    /// which OZ rejects as well.
+    pub fn of(data: Vec<(Address, U256)>) -> Result<Self, MerkleError> {
+        if data.is_empty() {
+            return Err(MerkleError::NoEntries);
+        }
+        let mut hashed: Vec<(H256, usize)> = data
+            .iter()
+            .enumerate()
+            .map(|(value_index, leaf_data)| (MerkleTree::hash_node(*leaf_data), value_index))
+            .collect();
+        hashed.sort();
+
+        let len = 2 * hashed.len() - 1;
+        let mut tree = vec![H256::zero(); len];
+        let mut values = vec![
+            StandardValue {
+                value: Entry::default(),
+                tree_index: 0,
+            };
+            data.len()
+        ];
+        for (leaf_index, (leaf, value_index)) in hashed.into_iter().enumerate() {
+            let tree_index = leaf_tree_index(data.len(), leaf_index);
+            tree[tree_index] = leaf;
+            values[value_index] = StandardValue {
+                value: data[value_index].into(),
+                tree_index,
+            };
+        }
+        for i in (0..len - data.len()).rev() {
+            tree[i] = MerkleTree::hash_pair(&tree[2 * i + 1], &tree[2 * i + 2]);
+        }
+        Ok(Self { tree, values })
+    }
+
+    /// Returns the root hash, `tree()[0]`.
+    pub fn root(&self) -> H256 {
+        self.tree[0]
+    }
+
+    /// Returns every node in OZ's array layout, as in the `tree` of OZ's dump.
+    pub fn tree(&self) -> &[H256] {
+        &self.tree
+    }
+
+    /// Returns the values in the order they were given, with the index of their leaf.
+    pub fn values(&self) -> &[StandardValue] {
+        &self.values
+    }
+
+    /// Returns the number of leaves, which is the number of values.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Always `false`: a tree has at least one leaf.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Retrieves the proof of a leaf, like OZ's `getProof`.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf` - The leaf hash, as computed by `MerkleTree::hash_node`.
+    ///
+    /// # Returns
+    ///
+    /// An `Option` containing the sibling hashes from the leaf up, or `None` if `leaf` is
+    /// not a leaf of the tree.
+    pub fn get_proof(&self, leaf: H256) -> Option<Vec<H256>> {
+        Some(self.proof_at(self.tree_index(leaf)?))
+    }
+
+    /// Retrieves the proof of a leaf given by its address and amount.
+    ///
+    /// # Returns
+    ///
+    /// An `Option` containing the proof, or `None` if the tree does not hold the pair.
+    pub fn get_entry_proof(&self, leaf_data: (Address, U256)) -> Option<Vec<H256>> {
+        self.get_proof(MerkleTree::hash_node(leaf_data))
+    }
+
+    /// Verifies a proof like OZ's `MerkleProof.verify`.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf` - The leaf hash.
+    /// * `proof` - The sibling hashes from the leaf up.
+    /// * `root` - The expected root.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the proof leads from `leaf` to `root`.
+    pub fn verify_proof(leaf: H256, proof: &[H256], root: H256) -> bool {
+        MerkleTree::process_proof(leaf, proof) == root
+    }
+
+    /// Returns the proof of the node at `index` in `tree()`.
+    pub(crate) fn proof_at(&self, mut index: usize) -> Vec<H256> {
+        let mut proof = Vec::new();
+        while index > 0 {
+            proof.push(self.tree[sibling(index)]);
+            index = parent(index);
+        }
+        proof
+    }
+
+    /// Finds the index in `tree()` of a leaf.
+    pub(crate) fn tree_index(&self, leaf: H256) -> Option<usize> {
+        // the leaves are the last `len()` nodes, in descending order
+        let first = self.len() - 1;
+        self.tree[first..]
+            .binary_search_by(|probe| leaf.cmp(probe))
+            .ok()
+            .map(|offset| first + offset)
+    }
+}
+
+/// The index in OZ's `tree` array of the leaf at `leaf_index` among `leaves` sorted
+/// leaves: the leaves fill the end of the array in reverse.
+fn leaf_tree_index(leaves: usize, leaf_index: usize) -> usize {
+    2 * leaves - 2 - leaf_index
+}
+
+/// The index of the other child of the parent of node `i`.
+pub(crate) fn sibling(i: usize) -> usize {
+    if i % 2 == 1 {
+        i + 1
+    } else {
+        i - 1
+    }
+}
+
+/// The index of the parent of node `i`.
+pub(crate) fn parent(i: usize) -> usize {
+    (i - 1) / 2
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+
+    fn data(count: u64) -> Vec<(Address, U256)> {
+        (1..=count)
+            .map(|i| (Address::from_low_u64_be(i), U256::exp10(18) * i))
+            .collect()
+    }
+
+    fn h256(hex: &str) -> H256 {
+        H256::from_str(hex).unwrap()
+    }
+
+    #[test]
+    fn matches_openzeppelin_for_odd_leaf_counts() {
+        // the output of `StandardMerkleTree.of(values, ["address", "uint256"])` for
+        // `[address(i), i * 1e18]` with i from 1. These were computed with a transcription
+        // of @openzeppelin/merkle-tree's makeMerkleTree and getProof; tests/oz_vectors.rs
+        // compares every tree of 1 to 17 such values with the package itself
+        let five = StandardMerkleTree::of(data(5)).unwrap();
+        assert_eq!(
+            five.tree(),
+            [
+                "0x0f83651191d9c2e31f3653a21da41e131e85621ccfbb17d45643e01a72de6f91",
+                "0x38a644aa6fe72298c50692076fb175996dcedf8066a4ffe567351c606badf865",
+                "0x762999e645e450d1ed0f1cf08a900c645ac320d5f4a81ae0a4e4320234365e66",
+                "0x9f9e7de63aa1bd344850f18376b07ca6201ed15213a0d839ef955523c5ac7832",
+                "0xb087824e64c5eb01d09207d4a703256c1b0d5c2145276f486085e3e4d39df4f1",
+                "0x6081e60a560cf21a6ea538bd62fec907bc40d5711c5e8c7e8fbe6dc134b72608",
+                "0x5463d804106c8cca5acf27ebf485b4b55ceb2e62854eaef3a7c5ee3ed22b1af6",
+                "0x52fde9620166090d16073b89092702f9d9ded0e2350a680cad51cd3cd64d6075",
+                "0x09ef46ce7299321a1324f25b2d659db10365f7155d3b3af2012d212aa90c6792",
+            ]
+            .map(h256)
+        );
+        let tree_indices: Vec<usize> = five.values().iter().map(|v| v.tree_index).collect();
+        assert_eq!(tree_indices, [5, 8, 4, 6, 7]);
+        assert_eq!(
+            five.get_entry_proof(data(5)[4]).unwrap(),
+            [
+                "0x09ef46ce7299321a1324f25b2d659db10365f7155d3b3af2012d212aa90c6792",
+                "0xb087824e64c5eb01d09207d4a703256c1b0d5c2145276f486085e3e4d39df4f1",
+                "0x762999e645e450d1ed0f1cf08a900c645ac320d5f4a81ae0a4e4320234365e66",
+            ]
+            .map(h256)
+        );
+        assert_ne!(MerkleTree::new(data(5)).get_root(), Some(five.root()));
+
+        let seven = StandardMerkleTree::of(data(7)).unwrap();
+        assert_eq!(
+            seven.root(),
+            h256("0x92452f12e9360f3f933f353589e2360d476c41d2444af8dfaa0cf6c87b9633de")
+        );
+        let tree_indices: Vec<usize> = seven.values().iter().map(|v| v.tree_index).collect();
+        assert_eq!(tree_indices, [8, 12, 6, 10, 11, 9, 7]);
+    }
+
+    #[test]
+    fn proves_every_value() {
+        for count in 1..=17 {
+            let tree = StandardMerkleTree::of(data(count)).unwrap();
+            for (value, leaf_data) in tree.values().iter().zip(data(count)) {
+                assert_eq!(value.value, Entry::from(leaf_data));
+                let leaf = MerkleTree::hash_node(leaf_data);
+                assert_eq!(tree.tree()[value.tree_index], leaf);
+                let proof = tree.get_proof(leaf).unwrap();
+                assert!(StandardMerkleTree::verify_proof(leaf, &proof, tree.root()));
+            }
+            // the layouts agree for powers of two and three times them
+            let same = MerkleTree::new(data(count)).get_root() == Some(tree.root());
+            assert_eq!(same, [1, 2, 3, 4, 6, 8, 12, 16].contains(&count));
+        }
+
+        // the root printed by the README of @openzeppelin/merkle-tree
+        let readme = StandardMerkleTree::of(vec![
+            (Address::repeat_byte(0x11), U256::exp10(18) * 5),
+            (Address::repeat_byte(0x22), U256::exp10(17) * 25),
+        ])
+        .unwrap();
+        assert_eq!(
+            readme.root(),
+            h256("0xd4dee0beab2d53f2cc83e567171bd2820e49898130a22622b10ead383e90bd77")
+        );
+    }
+
+    #[test]
+    fn keeps_repeated_values_and_rejects_empty_data() {
+        let mut repeated = data(3);
+        repeated.push(repeated[1]);
+        let tree = StandardMerkleTree::of(repeated.clone()).unwrap();
+        assert_eq!(tree.len(), 4);
+        assert_eq!(tree.tree().len(), 7);
+        let leaf = MerkleTree::hash_node(repeated[1]);
+        let proof = tree.get_proof(leaf).unwrap();
+        assert!(StandardMerkleTree::verify_proof(leaf, &proof, tree.root()));
+        assert_eq!(tree.get_proof(tree.root()), None);
+        assert_eq!(tree.get_entry_proof(data(4)[3]), None);
+
+        assert_eq!(
+            StandardMerkleTree::of(Vec::new()).unwrap_err(),
+            MerkleError::NoEntries
+        );
+    }
+}
@@ -0,0 +1,493 @@
+//! Binary tree snapshots.
+//!
+//! A snapshot stores every layer of a tree so it can be reloaded without rehashing:
+//!
+//! | offset | size       | content                                           |
+//! |--------|------------|---------------------------------------------------|
+//! | 0      | 4          | magic bytes `OZMT`                                |
+//! | 4      | 4          | format version, little-endian `u32`               |
+//! | 8      | 8          | leaf count `n`, little-endian `u64`               |
+//! | 16     | 32 * nodes | every layer back to back, from the leaves to root |
+//! | end    | 32         | keccak256 of every preceding byte                 |
+//!
+//! The layer lengths follow from `n` (each layer is half the previous one, rounded
+//! up), so `nodes` equals [`MerkleTree::scratch_len`]`(n)`. The footer covers the
+//! header and every node, including the leaf count and the root, so a damaged file is
+//! rejected with `MerkleError::SnapshotCorrupted` instead of serving wrong proofs. A
+//! truncated file is rejected with `MerkleError::InvalidSnapshot`.
+//!
+//! The header integers are little-endian on every platform, while hashes are stored as
+//! their raw 32 bytes, i.e. in the big-endian order in which they are compared and
+//! hashed. Nodes start at offset 16 and are contiguous, so a mapped file is used in place:
+//! node `i` is bytes `16 + 32 * i .. 48 + 32 * i`. Nodes are byte arrays and need no
+//! alignment, so the file can be mapped at any address and read on any architecture.
+//!
+//! Snapshots hold the layers only, not construction options: a tree built with
+//! `new_ordered` reloads as a sorted tree, so rebuild it with `new_ordered` instead.
+//! Proofs of a tree built with `new_with_node_sep` are still served from the stored layers,
+//! but verify them with `verify_proof_with_sep`, since the reloaded tree has no separator.
+//!
+//! Version 1 snapshots have no footer. They are only read when
+//! [`SnapshotOptions::allow_unchecked`] is set.
+
+use crate::{MerkleError, MerkleTree};
+use ethers::types::H256;
+use std::io::{Read, Write};
+use tiny_keccak::{Hasher, Keccak};
+
+const MAGIC: &[u8; 4] = b"OZMT";
+const VERSION: u32 = 2;
+const UNCHECKED_VERSION: u32 = 1;
+const HEADER_LEN: usize = 16;
+const FOOTER_LEN: usize = 32;
+
+/// Options for reading snapshots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SnapshotOptions {
+    /// Accept version 1 snapshots, which have no integrity footer and cannot be checked.
+    /// Snapshots with a footer are always verified.
+    pub allow_unchecked: bool,
+}
+
+impl MerkleTree {
+    /// Writes a binary snapshot of the tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - The destination of the snapshot bytes.
+    pub fn write_snapshot<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        let mut hasher = Keccak::v256();
+        let mut write = |bytes: &[u8]| {
+            hasher.update(bytes);
+            writer.write_all(bytes)
+        };
+        write(MAGIC)?;
+        write(&VERSION.to_le_bytes())?;
+        write(&(self.leaves as u64).to_le_bytes())?;
+        for node in self.layers.iter().flatten() {
+            write(node.as_bytes())?;
+        }
+
+        let mut checksum = [0u8; FOOTER_LEN];
+        hasher.finalize(&mut checksum);
+        writer.write_all(&checksum)?;
+        writer.flush()
+    }
+
+    /// Reads a tree from a binary snapshot written by `write_snapshot`.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The source of the snapshot bytes.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the tree, `MerkleError::InvalidSnapshot` if the bytes are
+    /// not a complete snapshot, or `MerkleError::SnapshotCorrupted` if they fail the
+    /// integrity check.
+    pub fn read_snapshot<R: Read>(reader: R) -> Result<MerkleTree, MerkleError> {
+        Self::read_snapshot_with_options(reader, &SnapshotOptions::default())
+    }
+
+    /// Reads a tree from a binary snapshot, with options such as accepting unchecked
+    /// version 1 snapshots.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The source of the snapshot bytes.
+    /// * `options` - Which snapshots are accepted.
+    pub fn read_snapshot_with_options<R: Read>(
+        mut reader: R,
+        options: &SnapshotOptions,
+    ) -> Result<MerkleTree, MerkleError> {
+        let mut hasher = Keccak::v256();
+        let mut header = [0u8; HEADER_LEN];
+        reader.read_exact(&mut header).map_err(invalid)?;
+        let (checked, leaves) = parse_header(&header, options)?;
+        hasher.update(&header);
+
+        let mut layers = Vec::new();
+        let mut node = [0u8; 32];
+        for len in layer_lengths(leaves) {
+            let mut layer = Vec::new();
+            for _ in 0..len {
+                reader.read_exact(&mut node).map_err(invalid)?;
+                hasher.update(&node);
+                layer.push(H256::from(node));
+            }
+            layers.push(layer);
+        }
+        if checked {
+            reader.read_exact(&mut node).map_err(invalid)?;
+            verify_checksum(hasher, H256::from(node))?;
+        }
+        if reader.read(&mut node).map_err(invalid)? != 0 {
+            return Err(MerkleError::InvalidSnapshot(
+                "trailing bytes after the last layer".to_string(),
+            ));
+        }
+
+        Ok(MerkleTree {
+            elements: layers[0].clone(),
+            entries: Vec::new(),
+            payload_entries: Vec::new(),
+            address_order: Vec::new(),
+            layers,
+            leaves,
+            inputs: leaves,
+            bloom: None,
+            config: Default::default(),
+        })
+    }
+
+    /// Writes a snapshot of the tree to a file, for instant reloading with `mmap_load`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The file to create or overwrite.
+    pub fn mmap_dump<P: AsRef<std::path::Path>>(&self, path: P) -> std::io::Result<()> {
+        let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+        self.write_snapshot(&mut writer)?;
+        writer.into_inner()?.sync_all()
+    }
+
+    /// Maps a file written by `mmap_dump`, available with the `mmap` feature.
+    ///
+    /// No node is parsed or copied; only the integrity footer is checked. See `open_mmap`.
+    #[cfg(feature = "mmap")]
+    pub fn mmap_load<P: AsRef<std::path::Path>>(path: P) -> Result<MmapMerkleTree, MerkleError> {
+        Self::open_mmap(path)
+    }
+
+    /// Opens a snapshot file as a read-only memory map, available with the `mmap` feature.
+    ///
+    /// Proofs and lookups are served directly from the mapped pages, so several processes
+    /// can share one snapshot file without loading it into anonymous memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of a snapshot written by `write_snapshot`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the mapped tree, `MerkleError::InvalidSnapshot` if the file
+    /// cannot be mapped or is not a complete snapshot, or `MerkleError::SnapshotCorrupted`
+    /// if it fails the integrity check. The check reads the whole file once on open.
+    #[cfg(feature = "mmap")]
+    pub fn open_mmap<P: AsRef<std::path::Path>>(path: P) -> Result<MmapMerkleTree, MerkleError> {
+        Self::open_mmap_with_options(path, &SnapshotOptions::default())
+    }
+
+    /// Opens a snapshot file as a memory map, with options such as accepting unchecked
+    /// version 1 snapshots. Available with the `mmap` feature.
+    #[cfg(feature = "mmap")]
+    pub fn open_mmap_with_options<P: AsRef<std::path::Path>>(
+        path: P,
+        options: &SnapshotOptions,
+    ) -> Result<MmapMerkleTree, MerkleError> {
+        let file = std::fs::File::open(path).map_err(invalid)?;
+        // SAFETY: the map is read-only; snapshot files must not be modified while mapped.
+        let map = unsafe { memmap2::Mmap::map(&file) }.map_err(invalid)?;
+        MmapMerkleTree::new(map, options)
+    }
+}
+
+/// A tree served directly from a memory-mapped snapshot file.
+///
+/// Returned by [`MerkleTree::open_mmap`]. Hashes are read from 32-byte windows of the
+/// map with `H256::from_slice`, so no alignment is required of the file contents.
+#[cfg(feature = "mmap")]
+pub struct MmapMerkleTree {
+    map: memmap2::Mmap,
+    leaves: usize,
+}
+
+#[cfg(feature = "mmap")]
+impl MmapMerkleTree {
+    fn new(map: memmap2::Mmap, options: &SnapshotOptions) -> Result<Self, MerkleError> {
+        let header = map
+            .get(..HEADER_LEN)
+            .ok_or_else(|| MerkleError::InvalidSnapshot("missing header".to_string()))?;
+        let (checked, leaves) = parse_header(header.try_into().unwrap(), options)?;
+        let footer = if checked { FOOTER_LEN } else { 0 };
+        // every leaf takes 32 bytes, so larger counts cannot fit and would overflow below
+        let expected = if leaves <= map.len() / 32 {
+            HEADER_LEN + MerkleTree::scratch_len(leaves) * 32 + footer
+        } else {
+            usize::MAX
+        };
+        if expected != map.len() {
+            return Err(MerkleError::InvalidSnapshot(format!(
+                "file of {} bytes is not a complete snapshot of {} leaves",
+                map.len(),
+                leaves
+            )));
+        }
+        if checked {
+            let (payload, checksum) = map.split_at(map.len() - FOOTER_LEN);
+            let mut hasher = Keccak::v256();
+            hasher.update(payload);
+            verify_checksum(hasher, H256::from_slice(checksum))?;
+        }
+        Ok(MmapMerkleTree { map, leaves })
+    }
+
+    /// Retrieves the root hash of the mapped tree.
+    ///
+    /// # Returns
+    ///
+    /// An `Option` containing either the root hash if the tree is not empty,
+    /// or `None` if the tree is empty.
+    pub fn get_root(&self) -> Option<H256> {
+        (self.leaves > 0).then(|| self.node(MerkleTree::scratch_len(self.leaves) - 1))
+    }
+
+    /// Retrieves the Merkle proof for a given element from the mapped layers.
+    ///
+    /// # Returns
+    ///
+    /// An `Option` containing either the Merkle proof if the element is found,
+    /// or `None` if the element is not present in the tree.
+    pub fn get_proof(&self, element: H256) -> Option<Vec<H256>> {
+        let mut index = self.position(element)?;
+        let mut proof = Vec::new();
+        let mut offset = 0;
+
+        let lengths = layer_lengths(self.leaves);
+        for len in &lengths[..lengths.len() - 1] {
+            let pair_index = if index % 2 == 0 { index + 1 } else { index - 1 };
+            if pair_index < *len {
+                proof.push(self.node(offset + pair_index));
+            }
+            offset += len;
+            index /= 2;
+        }
+        Some(proof)
+    }
+
+    /// Returns whether the element is one of the mapped leaves.
+    pub fn contains(&self, element: H256) -> bool {
+        self.position(element).is_some()
+    }
+
+    /// Returns the number of leaves in the mapped tree.
+    pub fn leaves_length(&self) -> usize {
+        self.leaves
+    }
+
+    fn node(&self, index: usize) -> H256 {
+        let start = HEADER_LEN + index * 32;
+        H256::from_slice(&self.map[start..start + 32])
+    }
+
+    fn position(&self, element: H256) -> Option<usize> {
+        let (mut low, mut high) = (0, self.leaves);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            match self.node(mid).cmp(&element) {
+                std::cmp::Ordering::Less => low = mid + 1,
+                std::cmp::Ordering::Greater => high = mid,
+                std::cmp::Ordering::Equal => return Some(mid),
+            }
+        }
+        None
+    }
+}
+
+/// Parses a header, returning whether the snapshot has a checksum footer and its leaf count.
+fn parse_header(
+    header: &[u8; HEADER_LEN],
+    options: &SnapshotOptions,
+) -> Result<(bool, usize), MerkleError> {
+    if &header[..4] != MAGIC {
+        return Err(MerkleError::InvalidSnapshot("bad magic bytes".to_string()));
+    }
+    let checked = match u32::from_le_bytes(header[4..8].try_into().unwrap()) {
+        VERSION => true,
+        UNCHECKED_VERSION if options.allow_unchecked => false,
+        UNCHECKED_VERSION => {
+            return Err(MerkleError::InvalidSnapshot(
+                "version 1 snapshots have no checksum; set allow_unchecked to read them"
+                    .to_string(),
+            ))
+        }
+        version => {
+            return Err(MerkleError::InvalidSnapshot(format!(
+                "unsupported version {}",
+                version
+            )))
+        }
+    };
+    let leaves = u64::from_le_bytes(header[8..16].try_into().unwrap());
+    let leaves = usize::try_from(leaves)
+        .map_err(|_| MerkleError::InvalidSnapshot(format!("leaf count {} too large", leaves)))?;
+    Ok((checked, leaves))
+}
+
+fn verify_checksum(hasher: Keccak, expected: H256) -> Result<(), MerkleError> {
+    let mut found = [0u8; FOOTER_LEN];
+    hasher.finalize(&mut found);
+    let found = H256::from(found);
+    if found != expected {
+        return Err(MerkleError::SnapshotCorrupted { expected, found });
+    }
+    Ok(())
+}
+
+fn layer_lengths(leaves: usize) -> Vec<usize> {
+    let mut lengths = vec![leaves];
+    let mut len = leaves;
+    while len > 1 {
+        len = len.div_ceil(2);
+        lengths.push(len);
+    }
+    lengths
+}
+
+fn invalid(err: std::io::Error) -> MerkleError {
+    MerkleError::InvalidSnapshot(err.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ethers::types::{Address, U256};
+
+    fn sample_data() -> Vec<(Address, U256)> {
+        (1..=13u64)
+            .map(|i| (Address::from_low_u64_be(i), U256::from(i * 3)))
+            .collect()
+    }
+
+    #[test]
+    fn snapshot_round_trip() {
+        let tree = MerkleTree::new(sample_data());
+        let mut bytes = Vec::new();
+        tree.write_snapshot(&mut bytes).unwrap();
+        let loaded = MerkleTree::read_snapshot(bytes.as_slice()).unwrap();
+
+        assert_eq!(loaded.get_root(), tree.get_root());
+        for leaf in sample_data() {
+            let node = MerkleTree::hash_node(leaf);
+            assert_eq!(loaded.get_proof(node), tree.get_proof(node));
+        }
+    }
+
+    #[test]
+    fn snapshot_rejects_truncated_bytes() {
+        let mut bytes = Vec::new();
+        MerkleTree::new(sample_data())
+            .write_snapshot(&mut bytes)
+            .unwrap();
+        bytes.truncate(bytes.len() - 1);
+
+        assert!(matches!(
+            MerkleTree::read_snapshot(bytes.as_slice()),
+            Err(MerkleError::InvalidSnapshot(_))
+        ));
+    }
+
+    #[test]
+    fn snapshot_rejects_flipped_bit() {
+        let tree = MerkleTree::new(sample_data());
+        let mut bytes = Vec::new();
+        tree.write_snapshot(&mut bytes).unwrap();
+        let middle = bytes.len() / 2;
+        bytes[middle] ^= 0x01;
+
+        assert!(matches!(
+            MerkleTree::read_snapshot(bytes.as_slice()),
+            Err(MerkleError::SnapshotCorrupted { expected, found }) if expected != found
+        ));
+    }
+
+    #[test]
+    fn unchecked_snapshots_require_opt_in() {
+        let tree = MerkleTree::new(sample_data());
+        let mut bytes = Vec::new();
+        tree.write_snapshot(&mut bytes).unwrap();
+        // rewrite as a footer-less version 1 snapshot
+        bytes[4..8].copy_from_slice(&UNCHECKED_VERSION.to_le_bytes());
+        bytes.truncate(bytes.len() - FOOTER_LEN);
+
+        assert!(matches!(
+            MerkleTree::read_snapshot(bytes.as_slice()),
+            Err(MerkleError::InvalidSnapshot(_))
+        ));
+        let options = SnapshotOptions {
+            allow_unchecked: true,
+        };
+        let loaded = MerkleTree::read_snapshot_with_options(bytes.as_slice(), &options).unwrap();
+        assert_eq!(loaded.get_root(), tree.get_root());
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn mmap_serves_identical_proofs() {
+        let tree = MerkleTree::new(sample_data());
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        tree.write_snapshot(&mut file).unwrap();
+        let mapped = MerkleTree::open_mmap(file.path()).unwrap();
+
+        assert_eq!(mapped.get_root(), tree.get_root());
+        assert_eq!(mapped.leaves_length(), tree.leaves_length());
+        for leaf in sample_data() {
+            let node = MerkleTree::hash_node(leaf);
+            assert_eq!(mapped.get_proof(node), tree.get_proof(node));
+        }
+        assert!(!mapped.contains(H256::repeat_byte(0x11)));
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn mmap_dump_and_load_verify_proofs() {
+        let tree = MerkleTree::new(sample_data());
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tree.ozmt");
+        tree.mmap_dump(&path).unwrap();
+        let mapped = MerkleTree::mmap_load(&path).unwrap();
+
+        let root = mapped.get_root().unwrap();
+        let node = MerkleTree::hash_node(sample_data()[5]);
+        let proof = mapped.get_proof(node).unwrap();
+        assert!(tree.verify_proof(node, proof, root));
+        assert_eq!(
+            std::fs::metadata(&path).unwrap().len() as usize,
+            HEADER_LEN + MerkleTree::scratch_len(13) * 32 + FOOTER_LEN
+        );
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn mmap_rejects_truncated_file() {
+        let mut bytes = Vec::new();
+        MerkleTree::new(sample_data())
+            .write_snapshot(&mut bytes)
+            .unwrap();
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), &bytes[..bytes.len() - 32]).unwrap();
+
+        assert!(matches!(
+            MerkleTree::open_mmap(file.path()),
+            Err(MerkleError::InvalidSnapshot(_))
+        ));
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn mmap_rejects_flipped_bit() {
+        let mut bytes = Vec::new();
+        MerkleTree::new(sample_data())
+            .write_snapshot(&mut bytes)
+            .unwrap();
+        let middle = bytes.len() / 2;
+        bytes[middle] ^= 0x80;
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), &bytes).unwrap();
+
+        assert!(matches!(
+            MerkleTree::open_mmap(file.path()),
+            Err(MerkleError::SnapshotCorrupted { .. })
+        ));
+    }
+}
@@ -0,0 +1,145 @@
+//! Asynchronous construction from a stream of entries, available with the `tokio` feature.
+
+use crate::{Entry, MerkleError, MerkleTree};
+use ethers::types::{Address, H256, U256};
+use futures::{Stream, StreamExt};
+use std::fmt::Display;
+
+const CHUNK_SIZE: usize = 4096;
+
+impl MerkleTree {
+    /// Constructs a new Merkle tree from an asynchronous stream of entries.
+    ///
+    /// Leaves are hashed as chunks of entries arrive, and the tree is built at the end of
+    /// the stream. The entries are kept like those of `new`, so the tree serves
+    /// by-address claims.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - A stream of addresses and amounts, e.g. rows from a paginated API.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the tree, or `MerkleError::Stream` if the stream yields an
+    /// error, in which case construction is aborted.
+    pub async fn from_stream<S, E>(stream: S) -> Result<MerkleTree, MerkleError>
+    where
+        S: Stream<Item = Result<(Address, U256), E>>,
+        E: Display,
+    {
+        Self::consume_stream(stream, false).await
+    }
+
+    /// Constructs a new Merkle tree from an asynchronous stream, hashing on the blocking pool.
+    ///
+    /// This behaves like `from_stream`, but every chunk of entries is hashed with
+    /// `tokio::task::spawn_blocking` so large inputs don't stall the async runtime.
+    pub async fn from_stream_offloaded<S, E>(stream: S) -> Result<MerkleTree, MerkleError>
+    where
+        S: Stream<Item = Result<(Address, U256), E>>,
+        E: Display,
+    {
+        Self::consume_stream(stream, true).await
+    }
+
+    async fn consume_stream<S, E>(stream: S, offload: bool) -> Result<MerkleTree, MerkleError>
+    where
+        S: Stream<Item = Result<(Address, U256), E>>,
+        E: Display,
+    {
+        let mut chunks = std::pin::pin!(stream.ready_chunks(CHUNK_SIZE));
+        let mut rows: Vec<(Address, U256)> = Vec::new();
+        let mut leaves: Vec<H256> = Vec::new();
+
+        while let Some(chunk) = chunks.next().await {
+            let mut entries = Vec::with_capacity(chunk.len());
+            for item in chunk {
+                let entry = item.map_err(|err| MerkleError::Stream {
+                    index: leaves.len() + entries.len(),
+                    reason: err.to_string(),
+                })?;
+                entries.push(entry);
+            }
+            rows.extend_from_slice(&entries);
+
+            if offload {
+                let hashed = tokio::task::spawn_blocking(move || {
+                    entries.into_iter().map(Self::hash_node).collect::<Vec<_>>()
+                })
+                .await
+                .map_err(|err| MerkleError::Stream {
+                    index: leaves.len(),
+                    reason: err.to_string(),
+                })?;
+                leaves.extend(hashed);
+            } else {
+                leaves.extend(entries.into_iter().map(Self::hash_node));
+            }
+        }
+        let (mut tree, permutation) = Self::from_leaves_tracked(leaves);
+        tree.retain_entries(rows.into_iter().map(Entry::from), &permutation);
+        Ok(tree)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use futures::stream;
+
+    fn sample_data() -> Vec<(Address, U256)> {
+        (1..=100u64)
+            .map(|i| (Address::from_low_u64_be(i), U256::from(i)))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn stream_root_matches_sync_build() {
+        let expected = MerkleTree::new(sample_data()).get_root();
+        let items = || stream::iter(sample_data().into_iter().map(Ok::<_, String>));
+
+        let tree = MerkleTree::from_stream(items()).await.unwrap();
+        assert_eq!(tree.get_root(), expected);
+        let tree = MerkleTree::from_stream_offloaded(items()).await.unwrap();
+        assert_eq!(tree.get_root(), expected);
+    }
+
+    #[tokio::test]
+    async fn streamed_tree_keeps_its_entries() {
+        let expected = MerkleTree::new(sample_data());
+        let items = || stream::iter(sample_data().into_iter().map(Ok::<_, String>));
+
+        for tree in [
+            MerkleTree::from_stream(items()).await.unwrap(),
+            MerkleTree::from_stream_offloaded(items()).await.unwrap(),
+        ] {
+            assert_eq!(tree.entries(), expected.entries());
+            for (account, amount) in sample_data() {
+                let claim = tree.get_claim(account).unwrap();
+                assert_eq!(claim.amount, amount);
+                assert_eq!(Some(claim), expected.get_claim(account));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn stream_error_aborts_construction() {
+        let items = sample_data().into_iter().enumerate().map(|(i, entry)| {
+            if i == 42 {
+                Err("page 3 timed out")
+            } else {
+                Ok(entry)
+            }
+        });
+
+        assert_eq!(
+            MerkleTree::from_stream(stream::iter(items))
+                .await
+                .unwrap_err(),
+            MerkleError::Stream {
+                index: 42,
+                reason: "page 3 timed out".to_string()
+            }
+        );
+    }
+}
@@ -0,0 +1,183 @@
+//! Helpers for negative tests, available with the `test-utils` feature.
+//!
+//! Hand-mutated proofs sometimes still verify, e.g. when two equal hashes are swapped,
+//! and then a negative test passes for the wrong reason. [`tamper`] checks every mutation
+//! against the root and panics when it would not break the proof.
+
+use crate::{MerkleTree, Proof, ProofBundle};
+use ethers::core::utils::keccak256;
+use ethers::types::{H256, U256};
+
+/// How [`tamper`] breaks a proof.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TamperMode {
+    /// Flip the lowest bit of the proof hash at this index.
+    FlipBitInElement(usize),
+    /// Swap the proof hashes at these two indices.
+    SwapElements(usize, usize),
+    /// Keep only this many proof hashes.
+    Truncate(usize),
+    /// Append a hash derived from the proof, which no real proof contains.
+    AppendRandom,
+    /// Replace the sibling of the leaf, the first proof hash, with an unrelated hash.
+    ReplaceLeafSibling,
+}
+
+/// Returns a mutated copy of a proof that no longer verifies.
+///
+/// # Arguments
+///
+/// * `proof` - A proof of `leaf` that verifies against `root`.
+/// * `leaf` - The leaf the proof belongs to.
+/// * `root` - The root the proof verifies against.
+/// * `mode` - The mutation to apply.
+///
+/// # Panics
+///
+/// If `proof` does not verify to begin with, if the mode refers to a missing proof hash or
+/// would not change the proof, or if the mutated proof still verifies.
+pub fn tamper(proof: &Proof, leaf: H256, root: H256, mode: TamperMode) -> Proof {
+    assert!(
+        proof.verify(leaf, root),
+        "the proof must verify before it is tampered with"
+    );
+    let mut hashes = proof.as_slice().to_vec();
+    let len = hashes.len();
+    match mode {
+        TamperMode::FlipBitInElement(i) => {
+            assert!(
+                i < len,
+                "cannot flip a bit of hash {} of a {}-hash proof",
+                i,
+                len
+            );
+            hashes[i].0[31] ^= 1;
+        }
+        TamperMode::SwapElements(i, j) => {
+            assert!(
+                i < len && j < len,
+                "cannot swap hashes {} and {} of a {}-hash proof",
+                i,
+                j,
+                len
+            );
+            assert!(
+                hashes[i] != hashes[j],
+                "swapping hashes {} and {} is a no-op: they are equal",
+                i,
+                j
+            );
+            hashes.swap(i, j);
+        }
+        TamperMode::Truncate(n) => {
+            assert!(
+                n < len,
+                "truncating a {}-hash proof to {} is a no-op",
+                len,
+                n
+            );
+            hashes.truncate(n);
+        }
+        TamperMode::AppendRandom => {
+            let seed: Vec<u8> = hashes.iter().flat_map(|hash| hash.0).collect();
+            hashes.push(H256(keccak256([b"tamper".as_slice(), &seed].concat())));
+        }
+        TamperMode::ReplaceLeafSibling => {
+            assert!(len > 0, "a single-leaf tree has no sibling to replace");
+            hashes[0] = H256(keccak256(hashes[0]));
+        }
+    }
+    let tampered = Proof::from(hashes);
+    assert!(
+        !tampered.verify(leaf, root),
+        "{:?} produced a proof that still verifies",
+        mode
+    );
+    tampered
+}
+
+/// Returns a copy of a bundle whose amount is off by one, so it no longer verifies.
+///
+/// The amount is increased, or decreased if it is `U256::MAX`.
+pub fn wrong_amount_bundle(bundle: &ProofBundle) -> ProofBundle {
+    let amount = match bundle.amount.checked_add(U256::one()) {
+        Some(amount) => amount,
+        None => bundle.amount - 1,
+    };
+    ProofBundle {
+        amount,
+        ..bundle.clone()
+    }
+}
+
+/// Flips the lowest bit of a node without updating the layers above it, so proofs
+/// through the node no longer verify, e.g. to exercise `MerkleTreeBuilder::self_check`.
+///
+/// # Arguments
+///
+/// * `tree` - The tree to corrupt.
+/// * `layer` - The layer of the node, 0 being the leaves.
+/// * `index` - The position of the node in its layer.
+///
+/// # Panics
+///
+/// If the layer or the index is out of range.
+pub fn corrupt_node(tree: &mut MerkleTree, layer: usize, index: usize) {
+    tree.layers[layer][index].0[31] ^= 1;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ethers::types::Address;
+
+    #[test]
+    fn every_mode_breaks_the_proof() {
+        let tree = MerkleTree::new(
+            (1..=16u64)
+                .map(|i| (Address::from_low_u64_be(i), U256::from(i)))
+                .collect(),
+        );
+        let root = tree.get_root().unwrap();
+        let modes = [
+            TamperMode::FlipBitInElement(0),
+            TamperMode::FlipBitInElement(2),
+            TamperMode::SwapElements(0, 1),
+            TamperMode::SwapElements(1, 2),
+            TamperMode::Truncate(0),
+            TamperMode::Truncate(2),
+            TamperMode::AppendRandom,
+            TamperMode::ReplaceLeafSibling,
+        ];
+
+        for bundle in tree.claim_records() {
+            let leaf = bundle.entry().leaf();
+            assert!(bundle.verify(root));
+            for mode in modes {
+                let tampered = tamper(&bundle.proof, leaf, root, mode);
+                assert_ne!(tampered, bundle.proof);
+                assert!(!tampered.verify(leaf, root), "{:?}", mode);
+            }
+            let wrong = wrong_amount_bundle(&bundle);
+            assert_eq!(wrong.amount, bundle.amount + 1);
+            assert!(!wrong.verify(root));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "is a no-op")]
+    fn no_op_mutations_panic() {
+        let tree = MerkleTree::new(vec![
+            (Address::from_low_u64_be(1), U256::one()),
+            (Address::from_low_u64_be(2), U256::one()),
+        ]);
+        let bundle = tree.get_claim(Address::from_low_u64_be(1)).unwrap();
+        let root = tree.get_root().unwrap();
+        tamper(
+            &bundle.proof,
+            bundle.entry().leaf(),
+            root,
+            TamperMode::Truncate(1),
+        );
+    }
+}
@@ -0,0 +1,124 @@
+use crate::{MerkleTree, Side};
+use ethers::types::H256;
+use std::fmt;
+
+/// One fold step of a `ProofTrace`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceStep {
+    /// The proof hash used at this step.
+    pub sibling: H256,
+    /// The side the sibling was hashed on: `Left` if it sorted before the node computed
+    /// so far.
+    pub side: Side,
+    /// The hash computed at this step.
+    pub hash: H256,
+}
+
+/// Every intermediate hash of folding a proof, to find where a failing proof diverges
+/// from the tree.
+///
+/// `Display` renders a table with one row per step, followed by the computed root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProofTrace {
+    /// The leaf the proof was folded from.
+    pub leaf: H256,
+    /// The fold steps, from the leaf up.
+    pub steps: Vec<TraceStep>,
+}
+
+impl ProofTrace {
+    /// Returns the computed root: the hash of the last step, or the leaf for an empty
+    /// proof.
+    pub fn root(&self) -> H256 {
+        self.steps.last().map_or(self.leaf, |step| step.hash)
+    }
+}
+
+impl fmt::Display for ProofTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "leaf  {:?}", self.leaf)?;
+        for (level, step) in self.steps.iter().enumerate() {
+            let side = match step.side {
+                Side::Left => "left ",
+                Side::Right => "right",
+            };
+            writeln!(
+                f,
+                "{:<5} {} {:?} -> {:?}",
+                level, side, step.sibling, step.hash
+            )?;
+        }
+        write!(f, "root  {:?}", self.root())
+    }
+}
+
+impl MerkleTree {
+    /// Folds a proof like `process_proof`, recording every step.
+    ///
+    /// This is meant for support and debugging; verification does not go through it and
+    /// does not record anything.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf` - The hash of the leaf.
+    /// * `proof` - The sibling hashes from the leaf up.
+    ///
+    /// # Returns
+    ///
+    /// The trace, whose `root()` is the root `process_proof` computes.
+    pub fn process_proof_trace(leaf: H256, proof: &[H256]) -> ProofTrace {
+        let mut computed = leaf;
+        let steps = proof
+            .iter()
+            .map(|sibling| {
+                let side = if computed < *sibling {
+                    Side::Right
+                } else {
+                    Side::Left
+                };
+                computed = Self::hash_pair(&computed, sibling);
+                TraceStep {
+                    sibling: *sibling,
+                    side,
+                    hash: computed,
+                }
+            })
+            .collect();
+        ProofTrace { leaf, steps }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn trace_matches_process_proof() {
+        let tree = MerkleTree::from_leaves((1..=8u8).map(H256::repeat_byte).collect());
+        let leaf = H256::repeat_byte(6);
+        let proof = tree.get_proof(leaf).unwrap();
+        let trace = MerkleTree::process_proof_trace(leaf, &proof);
+        assert_eq!(trace.steps.len(), 3);
+        assert_eq!(trace.root(), MerkleTree::process_proof(leaf, &proof));
+        assert_eq!(Some(trace.root()), tree.get_root());
+        let h = |byte: &str| format!("0x{}", byte.repeat(32));
+        let expected = [
+            format!("leaf  {}", h("06")),
+            format!(
+                "0     left  {} -> 0x75e9139531d958d0d597c7a5d1c879cb3b852eb58b0dab15a98cc72735b69ac7",
+                h("05")
+            ),
+            "1     right 0xfe040ff362c4dd00731de08ecee6d4008e75bf70c25e6105c645ddd97ea846d5 -> \
+             0xba842e51aa84a9b86aa42e0f5f2935dca655316cac3e0100414f7742f4df4edc"
+                .to_string(),
+            "2     left  0x0b242b9a6559f2d9f8563485a0697b746ec58ce879e0e5ac94d4c8a250723121 -> \
+             0xc5a76c92abcd19784a85c9d6bc0005c8215abbdfcf60c7a365dce0ce912d77c5"
+                .to_string(),
+            "root  0xc5a76c92abcd19784a85c9d6bc0005c8215abbdfcf60c7a365dce0ce912d77c5".to_string(),
+        ];
+        assert_eq!(trace.to_string(), expected.join("\n"));
+
+        let empty = MerkleTree::process_proof_trace(leaf, &[]);
+        assert_eq!(empty.root(), leaf);
+    }
+}
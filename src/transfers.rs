@@ -0,0 +1,109 @@
+use crate::{DuplicatePolicy, MerkleError, MerkleTree, MerkleTreeBuilder};
+use ethers::core::utils::keccak256;
+use ethers::types::{Address, Log, H256, U256};
+
+impl MerkleTree {
+    /// Constructs a new Merkle tree from transfers decoded from event logs.
+    ///
+    /// Every recipient gets one leaf holding the sum of the amounts it received, as with
+    /// `DuplicatePolicy::Merge`, so repeated transfers to an address add up instead of
+    /// becoming separate leaves.
+    ///
+    /// # Arguments
+    ///
+    /// * `logs` - `(recipient, amount)` pairs, one per transfer.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the tree, or `MerkleError::AmountOverflow` if the total of a
+    /// recipient overflows `U256`.
+    pub fn from_transfers(
+        logs: impl IntoIterator<Item = (Address, U256)>,
+    ) -> Result<Self, MerkleError> {
+        MerkleTreeBuilder::new()
+            .duplicate_policy(DuplicatePolicy::Merge)
+            .build(logs.into_iter().collect())
+    }
+
+    /// Constructs a new Merkle tree from raw ERC-20 `Transfer` logs, as returned by `eth_getLogs`.
+    ///
+    /// Logs are decoded as `Transfer(address indexed from, address indexed to, uint256 value)`
+    /// and passed to `from_transfers`. Logs of other events are skipped, including ERC-721
+    /// transfers, whose token ID is a fourth topic rather than data.
+    ///
+    /// # Arguments
+    ///
+    /// * `logs` - The logs to decode.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the tree, or `MerkleError::AmountOverflow` if the total of a
+    /// recipient overflows `U256`.
+    pub fn from_transfer_logs<'a>(
+        logs: impl IntoIterator<Item = &'a Log>,
+    ) -> Result<Self, MerkleError> {
+        let signature = H256::from(keccak256("Transfer(address,address,uint256)"));
+        Self::from_transfers(logs.into_iter().filter_map(|log| {
+            match (log.topics.as_slice(), log.data.len()) {
+                ([topic, _, to], 32) if *topic == signature => {
+                    Some((Address::from(*to), U256::from_big_endian(&log.data)))
+                }
+                _ => None,
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn transfer_log(to: Address, value: U256) -> Log {
+        let mut data = [0u8; 32];
+        value.to_big_endian(&mut data);
+        Log {
+            topics: vec![
+                H256::from(keccak256("Transfer(address,address,uint256)")),
+                H256::from(Address::zero()),
+                H256::from(to),
+            ],
+            data: data.to_vec().into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn transfers_to_the_same_recipient_add_up() {
+        let (alice, bob) = (Address::from_low_u64_be(1), Address::from_low_u64_be(2));
+        let transfers = vec![
+            (alice, U256::from(100)),
+            (bob, U256::from(5)),
+            (alice, U256::from(100)),
+            (alice, U256::from(1)),
+        ];
+        let tree = MerkleTree::from_transfers(transfers.clone()).unwrap();
+        let expected = MerkleTree::new(vec![(alice, U256::from(201)), (bob, U256::from(5))]);
+        assert_eq!(tree.get_root(), expected.get_root());
+
+        let mut logs: Vec<Log> = transfers
+            .iter()
+            .map(|(to, value)| transfer_log(*to, *value))
+            .collect();
+        let mut nft = transfer_log(bob, U256::from(7));
+        nft.topics.push(H256::from_low_u64_be(7));
+        nft.data = Default::default();
+        logs.push(nft);
+        let mut approval = transfer_log(bob, U256::from(9));
+        approval.topics[0] = H256::from(keccak256("Approval(address,address,uint256)"));
+        logs.push(approval);
+        assert_eq!(
+            MerkleTree::from_transfer_logs(&logs).unwrap().get_root(),
+            expected.get_root()
+        );
+
+        assert_eq!(
+            MerkleTree::from_transfers([(alice, U256::MAX), (alice, U256::one())]).unwrap_err(),
+            MerkleError::AmountOverflow(alice)
+        );
+    }
+}
@@ -0,0 +1,174 @@
+use crate::MerkleTree;
+use ethers::types::H256;
+
+impl MerkleTree {
+    /// Returns a new tree over the first `n` leaves, e.g. for a distribution published in
+    /// waves.
+    ///
+    /// Leaves are taken in canonical order: the order of `sorted_leaves()`, or the input
+    /// order for trees built with `new_ordered`. The new tree keeps the options of `self`
+    /// and the entries or payload entries of the kept leaves, so it equals a tree built
+    /// directly from those entries. A Bloom filter is rebuilt over the kept leaves with the
+    /// same bits per leaf.
+    ///
+    /// # Arguments
+    ///
+    /// * `n` - The number of leaves to keep. With `n >= leaves_length()` this is a clone,
+    ///   and with `n == 0` the empty tree, which has no root.
+    pub fn truncate(&self, n: usize) -> MerkleTree {
+        if n >= self.leaves {
+            return self.clone();
+        }
+        let elements = self.elements[..n].to_vec();
+        let mut tree = if self.config.ordered {
+            Self::new_ordered(elements)
+        } else {
+            Self::from_sorted_elements_with(elements, self.config.node_sep.clone())
+        };
+        if !self.entries.is_empty() {
+            tree.entries = self.entries[..n].to_vec();
+            tree.index_addresses();
+        }
+        if !self.payload_entries.is_empty() {
+            tree.payload_entries = self.payload_entries[..n].to_vec();
+        }
+        tree.bloom = self
+            .bloom
+            .as_ref()
+            .map(|bloom| bloom.rebuild(&tree.elements));
+        tree.config = self.config.clone();
+        tree
+    }
+
+    /// Computes the root of `truncate(n)` without building the tree.
+    ///
+    /// Every node of the truncated tree but the last of each layer covers the same leaves
+    /// as in `self` and is reused, so only one pair per layer is hashed.
+    ///
+    /// # Returns
+    ///
+    /// An `Option` containing the root of the first `n` leaves, or `None` if `n` is zero.
+    pub fn truncated_root(&self, n: usize) -> Option<H256> {
+        let n = n.min(self.leaves);
+        if n == 0 {
+            return None;
+        }
+        let hash = |left: &H256, right: &H256| {
+            if self.config.ordered {
+                Self::hash_ordered_pair(left, right)
+            } else {
+                Self::hash_pair_with(left, right, self.config.node_sep.as_bytes())
+            }
+        };
+
+        // the last node of the current layer, the only one that differs from `self`
+        let mut edge = self.layers[0][n - 1];
+        let mut len = n;
+        for layer in &self.layers {
+            if len == 1 {
+                break;
+            }
+            let last = len - 1;
+            edge = if last % 2 == 1 {
+                hash(&layer[last - 1], &edge)
+            } else {
+                edge
+            };
+            len = len.div_ceil(2);
+        }
+        Some(edge)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Entry, NodeSep, PayloadOptions};
+    use ethers::types::{Address, Bytes, U256};
+
+    fn data() -> Vec<(Address, U256)> {
+        (1..=23u64)
+            .map(|i| (Address::from_low_u64_be(i), U256::from(i * 7)))
+            .collect()
+    }
+
+    #[test]
+    fn waves_match_trees_built_from_entry_slices() {
+        let tree = MerkleTree::new_for_chain(data(), 3);
+        let canonical: Vec<(Address, U256)> = tree
+            .entries()
+            .iter()
+            .map(|entry| (entry.account, entry.amount))
+            .collect();
+
+        for n in 1..=canonical.len() {
+            let wave = tree.truncate(n);
+            let direct = MerkleTree::new_for_chain(canonical[..n].to_vec(), 3);
+            assert_eq!(wave.get_root(), direct.get_root(), "{} leaves", n);
+            assert_eq!(tree.truncated_root(n), direct.get_root(), "{} leaves", n);
+            assert_eq!(wave.entries(), direct.entries());
+            assert_eq!(wave.chain_id(), Some(3));
+            let claim = wave.get_claim(canonical[n - 1].0).unwrap();
+            assert!(wave.verify_proof(
+                wave.hash_entry((claim.account, claim.amount)).unwrap(),
+                claim.proof.into_inner(),
+                wave.get_root().unwrap()
+            ));
+        }
+
+        assert_eq!(tree.truncate(100).get_root(), tree.get_root());
+        assert_eq!(tree.truncated_root(100), tree.get_root());
+        let empty = tree.truncate(0);
+        assert_eq!((empty.get_root(), empty.leaves_length()), (None, 0));
+        assert_eq!(tree.truncated_root(0), None);
+    }
+
+    #[test]
+    fn truncation_keeps_separators_and_positions() {
+        let separated = MerkleTree::new_with_node_sep(data(), NodeSep(vec![9]));
+        let entries: Vec<(Address, U256)> = separated.entries()[..10]
+            .iter()
+            .map(|entry: &Entry| (entry.account, entry.amount))
+            .collect();
+        let direct = MerkleTree::new_with_node_sep(entries, NodeSep(vec![9]));
+        assert_eq!(separated.truncate(10).get_root(), direct.get_root());
+        assert_eq!(separated.truncated_root(10), direct.get_root());
+
+        let leaves: Vec<H256> = (1..=13u8).map(H256::repeat_byte).rev().collect();
+        let ordered = MerkleTree::new_ordered(leaves.clone());
+        for n in 1..=13 {
+            let direct = MerkleTree::new_ordered(leaves[..n].to_vec());
+            assert_eq!(ordered.truncate(n).get_root(), direct.get_root());
+            assert_eq!(ordered.truncated_root(n), direct.get_root());
+            assert!(ordered.truncate(n).is_ordered());
+        }
+    }
+
+    #[test]
+    fn truncation_keeps_payloads_and_rebuilds_the_bloom_filter() {
+        let options = PayloadOptions::default();
+        let tree = MerkleTree::new_with_payload(
+            data()
+                .into_iter()
+                .map(|(account, amount)| (account, amount, Bytes::from(account.0[19..].to_vec())))
+                .collect(),
+            options,
+        );
+        let payloads: Vec<(Address, U256, Bytes)> = tree.payload_entries()[..8]
+            .iter()
+            .map(|entry| (entry.account, entry.amount, entry.payload.clone()))
+            .collect();
+        let wave = tree.truncate(8);
+        let direct = MerkleTree::new_with_payload(payloads, options);
+        assert_eq!(wave.get_root(), direct.get_root());
+        assert_eq!(wave.payload_entries(), direct.payload_entries());
+
+        let filtered = MerkleTree::new(data()).with_bloom_filter(10);
+        let wave = filtered.truncate(5);
+        assert!(wave.bloom.is_some());
+        for (index, leaf) in filtered.sorted_leaves().iter().enumerate() {
+            assert_eq!(wave.maybe_contains(*leaf), index < 5, "leaf {}", index);
+        }
+        assert!(MerkleTree::new(data()).truncate(5).bloom.is_none());
+    }
+}
@@ -0,0 +1,299 @@
+//! Leaves of arbitrary Solidity-encodable types.
+//!
+//! A tuple of [`Leaf`] values is a leaf of OpenZeppelin's `StandardMerkleTree`: it hashes
+//! to `keccak256(bytes.concat(keccak256(abi.encode(...))))` of its fields, so an
+//! `(Address, U256)` pair is the leaf of `MerkleTree::hash_node` and
+//! `(account, amount, deadline)` of types `(Address, U256, u64)` the leaf of OZ's
+//! `["address", "uint256", "uint64"]` encoding. Wrapping a tuple in [`Packed`] hashes it
+//! to `keccak256(abi.encodePacked(...))` instead.
+
+use crate::MerkleTree;
+use ethers::abi::ethabi::{self, Token};
+use ethers::core::utils::keccak256;
+use ethers::types::{Address, Bytes, H256, U256};
+
+/// A value that can be hashed into a leaf.
+pub trait Leaf {
+    /// Encodes the value: like Solidity's `abi.encodePacked` for single values and
+    /// `Packed` tuples, and like `abi.encode` for plain tuples.
+    fn encode(&self) -> Vec<u8>;
+
+    /// Returns the value as an ABI token, for the `abi.encode` of the tuples it is part of.
+    fn token(&self) -> Token;
+
+    /// Hashes the value into a leaf, `keccak256(encode())` unless overridden.
+    fn hash(&self) -> H256 {
+        H256(keccak256(self.encode()))
+    }
+}
+
+/// A tuple hashed like Solidity's `keccak256(abi.encodePacked(...))` of its fields, for
+/// contracts that hash their leaves that way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Packed<T>(pub T);
+
+impl Leaf for Address {
+    fn encode(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+
+    fn token(&self) -> Token {
+        Token::Address(*self)
+    }
+}
+
+/// A `bytes32`.
+impl Leaf for H256 {
+    fn encode(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+
+    fn token(&self) -> Token {
+        Token::FixedBytes(self.as_bytes().to_vec())
+    }
+}
+
+impl Leaf for U256 {
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = vec![0u8; 32];
+        self.to_big_endian(&mut bytes);
+        bytes
+    }
+
+    fn token(&self) -> Token {
+        Token::Uint(*self)
+    }
+}
+
+impl Leaf for bool {
+    fn encode(&self) -> Vec<u8> {
+        vec![u8::from(*self)]
+    }
+
+    fn token(&self) -> Token {
+        Token::Bool(*self)
+    }
+}
+
+/// Dynamic `bytes`, encoded in place without a length.
+impl Leaf for Bytes {
+    fn encode(&self) -> Vec<u8> {
+        self.to_vec()
+    }
+
+    fn token(&self) -> Token {
+        Token::Bytes(self.to_vec())
+    }
+}
+
+macro_rules! impl_leaf_for_uint {
+    ($($uint:ty),*) => {
+        $(
+            impl Leaf for $uint {
+                fn encode(&self) -> Vec<u8> {
+                    self.to_be_bytes().to_vec()
+                }
+
+                fn token(&self) -> Token {
+                    Token::Uint(U256::from(*self))
+                }
+            }
+        )*
+    };
+}
+
+impl_leaf_for_uint!(u8, u16, u32, u64, u128);
+
+macro_rules! impl_leaf_for_tuple {
+    ($($field:ident),+) => {
+        impl<$($field: Leaf),+> Leaf for ($($field,)+) {
+            #[allow(non_snake_case)]
+            fn encode(&self) -> Vec<u8> {
+                let ($($field,)+) = self;
+                ethabi::encode(&[$($field.token()),+])
+            }
+
+            #[allow(non_snake_case)]
+            fn token(&self) -> Token {
+                let ($($field,)+) = self;
+                Token::Tuple(vec![$($field.token()),+])
+            }
+
+            fn hash(&self) -> H256 {
+                H256(keccak256(keccak256(self.encode())))
+            }
+        }
+
+        impl<$($field: Leaf),+> Leaf for Packed<($($field,)+)> {
+            #[allow(non_snake_case)]
+            fn encode(&self) -> Vec<u8> {
+                let ($($field,)+) = &self.0;
+                let mut bytes = Vec::new();
+                $(bytes.extend($field.encode());)+
+                bytes
+            }
+
+            fn token(&self) -> Token {
+                self.0.token()
+            }
+        }
+    };
+}
+
+impl_leaf_for_tuple!(A, B);
+impl_leaf_for_tuple!(A, B, C);
+impl_leaf_for_tuple!(A, B, C, D);
+impl_leaf_for_tuple!(A, B, C, D, E);
+
+impl MerkleTree {
+    /// Constructs a new Merkle tree from typed leaves.
+    ///
+    /// Each value is hashed with `Leaf::hash`, then the leaves are sorted and deduplicated
+    /// like in `from_leaves`. `(Address, U256)` pairs give the root of `new` for the same
+    /// data; `Packed` pairs give that of `new_with_leaf_mode` with `LeafMode::PackedSingle`.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The leaf values, in any order.
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `MerkleTree` containing the constructed Merkle tree.
+    pub fn from_leaf_data<T: Leaf>(data: &[T]) -> Self {
+        Self::from_leaves(data.iter().map(Leaf::hash).collect())
+    }
+
+    /// Retrieves the Merkle proof of a typed leaf.
+    ///
+    /// An `(Address, U256)` pair is found in trees built with `new` as well as with
+    /// `from_leaf_data`.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf` - The leaf value, hashed with `Leaf::hash`.
+    ///
+    /// # Returns
+    ///
+    /// An `Option` containing either the Merkle proof if the leaf is found,
+    /// or `None` if the leaf is not present in the Merkle tree.
+    pub fn get_leaf_proof<T: Leaf>(&self, leaf: &T) -> Option<Vec<H256>> {
+        self.get_proof(leaf.hash())
+    }
+
+    /// Verifies the Merkle proof of a typed leaf against a root.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf` - The leaf value, hashed with `Leaf::hash`.
+    /// * `proof` - The hashes forming the Merkle proof.
+    /// * `root` - The root hash of the Merkle tree.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the proof is valid, `false` otherwise.
+    pub fn verify_leaf_proof<T: Leaf>(&self, leaf: &T, proof: Vec<H256>, root: H256) -> bool {
+        self.verify_proof(leaf.hash(), proof, root)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vesting::VestingEntry;
+    use crate::LeafMode;
+
+    #[test]
+    fn packed_tuples_encode_like_abi_encode_packed() {
+        let claim = Packed((
+            Address::repeat_byte(0x11),
+            U256::from(1000),
+            1_700_000_000u64,
+        ));
+        assert_eq!(claim.encode().len(), 20 + 32 + 8);
+        assert_eq!(
+            format!("{:?}", claim.hash()),
+            "0x4b3ce3212c5a854b84915d4bc05421a3550718a75284332bcfa50c02d3d18cfb"
+        );
+        assert_eq!(
+            Packed((Address::repeat_byte(0x11), true, 7u16)).encode(),
+            [&[0x11; 20][..], &[1], &[0, 7]].concat()
+        );
+
+        let pair = (Address::repeat_byte(0x22), U256::exp10(18));
+        assert_eq!(
+            Packed(pair).hash(),
+            MerkleTree::hash_leaf(LeafMode::PackedSingle, pair)
+        );
+    }
+
+    #[test]
+    fn tuples_hash_like_the_standard_merkle_tree() {
+        let pair = (Address::repeat_byte(0x22), U256::exp10(18));
+        assert_eq!(pair.encode().len(), 64);
+        assert_eq!(pair.hash(), MerkleTree::hash_node(pair));
+
+        let tranche = VestingEntry {
+            account: pair.0,
+            amount: pair.1,
+            unlock: 1_700_000_000,
+        };
+        assert_eq!(
+            (tranche.account, tranche.amount, tranche.unlock).hash(),
+            tranche.leaf()
+        );
+
+        // dynamic fields are encoded after the heads, as by `abi.encode`
+        let with_bytes = (Address::repeat_byte(1), Bytes::from(vec![0xab; 3]));
+        let encoded = with_bytes.encode();
+        assert_eq!(encoded.len(), 32 * 4);
+        assert_eq!(U256::from_big_endian(&encoded[32..64]), U256::from(64));
+        assert_eq!(U256::from_big_endian(&encoded[64..96]), U256::from(3));
+    }
+
+    #[test]
+    fn typed_pairs_prove_in_trees_built_from_entries() {
+        let data: Vec<(Address, U256)> = (1..=5u64)
+            .map(|i| (Address::from_low_u64_be(i), U256::from(i) * 100))
+            .collect();
+        let tree = MerkleTree::new(data.clone());
+        let root = tree.get_root().unwrap();
+        assert_eq!(MerkleTree::from_leaf_data(&data).get_root(), Some(root));
+        for pair in &data {
+            let proof = tree.get_leaf_proof(pair).unwrap();
+            assert!(tree.verify_leaf_proof(pair, proof.clone(), root));
+            assert!(!tree.verify_leaf_proof(&Packed(*pair), proof, root));
+        }
+
+        let packed: Vec<_> = data.iter().copied().map(Packed).collect();
+        assert_eq!(
+            MerkleTree::from_leaf_data(&packed).get_root(),
+            MerkleTree::new_with_leaf_mode(data, LeafMode::PackedSingle).get_root()
+        );
+    }
+
+    #[test]
+    fn typed_leaves_prove_and_verify() {
+        let data: Vec<(Address, U256, u64)> = (1..=7u64)
+            .map(|i| {
+                (
+                    Address::from_low_u64_be(i),
+                    U256::from(i) * 100,
+                    1_700_000_000 + i,
+                )
+            })
+            .collect();
+        let tree = MerkleTree::from_leaf_data(&data);
+        let root = tree.get_root().unwrap();
+        for leaf in &data {
+            let proof = tree.get_leaf_proof(leaf).unwrap();
+            assert_eq!(Some(proof.clone()), tree.get_proof(leaf.hash()));
+            assert!(tree.verify_leaf_proof(leaf, proof.clone(), root));
+            let late = (leaf.0, leaf.1, leaf.2 + 1);
+            assert!(!tree.verify_leaf_proof(&late, proof, root));
+        }
+        assert_eq!(
+            tree.get_leaf_proof(&(Address::zero(), U256::zero(), 0u64)),
+            None
+        );
+    }
+}
@@ -0,0 +1,264 @@
+use crate::{Entry, MerkleError, MerkleTree};
+use ethers::types::{Address, H256, U256};
+use std::collections::HashSet;
+
+/// A new amount for an address, as applied by `MerkleTree::apply_updates`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LeafChange {
+    pub account: Address,
+    pub amount: U256,
+}
+
+impl LeafChange {
+    pub fn new(account: Address, amount: U256) -> Self {
+        Self { account, amount }
+    }
+}
+
+impl MerkleTree {
+    /// Applies a batch of amount changes, either all of them or none.
+    ///
+    /// Each change goes through `set_amount`, so the result is identical to building a
+    /// fresh tree from the updated entries.
+    ///
+    /// # Arguments
+    ///
+    /// * `changes` - The new amounts. An address may appear at most once in the batch.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` that is `MerkleError::DuplicateAddress` if an address appears twice in
+    /// the batch, or any error of `set_amount`. On error the tree is left unchanged.
+    pub fn apply_updates(&mut self, changes: &[LeafChange]) -> Result<(), MerkleError> {
+        *self = self.updated(changes)?;
+        Ok(())
+    }
+
+    /// Computes the root that `apply_updates` would produce, without modifying the tree.
+    ///
+    /// # Arguments
+    ///
+    /// * `changes` - The new amounts, validated exactly as `apply_updates` does.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the root after the changes, or the error `apply_updates`
+    /// would return.
+    pub fn dry_run(&self, changes: &[LeafChange]) -> Result<H256, MerkleError> {
+        self.updated(changes)?
+            .get_root()
+            .ok_or(MerkleError::NoEntries)
+    }
+
+    fn updated(&self, changes: &[LeafChange]) -> Result<MerkleTree, MerkleError> {
+        let mut seen = HashSet::with_capacity(changes.len());
+        if let Some(change) = changes.iter().find(|change| !seen.insert(change.account)) {
+            return Err(MerkleError::DuplicateAddress(change.account));
+        }
+        let mut tree = self.clone();
+        for change in changes {
+            tree.set_amount(change.account, change.amount)?;
+        }
+        Ok(tree)
+    }
+
+    /// Changes the amount of an address and updates the root in place.
+    ///
+    /// The leaf is rehashed with the tree's encoding and moved to its new sorted position.
+    /// Only the nodes above the leaves between its old and new position are recomputed,
+    /// so the result is identical to building a fresh tree from the updated entries.
+    ///
+    /// # Arguments
+    ///
+    /// * `account` - The address whose amount changes.
+    /// * `new_amount` - The new amount.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` that is `MerkleError::NoEntries` if the tree retains no entries,
+    /// `MerkleError::UnknownAddress` if the address has no entry, or
+    /// `MerkleError::DuplicateAddress` if it has several leaves and the one to change is
+    /// ambiguous, or `MerkleError::AmountOutOfRange` if the new amount does not fit the
+    /// tree's packed leaves. The tree is unchanged on error.
+    pub fn set_amount(&mut self, account: Address, new_amount: U256) -> Result<(), MerkleError> {
+        if self.entries.is_empty() || self.config.ordered {
+            return Err(MerkleError::NoEntries);
+        }
+        let mut matches = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.account == account)
+            .map(|(index, _)| index);
+        let old_index = matches.next().ok_or(MerkleError::UnknownAddress(account))?;
+        if matches.next().is_some() {
+            return Err(MerkleError::DuplicateAddress(account));
+        }
+
+        let leaf = self.hash_entry((account, new_amount))?;
+        self.elements.remove(old_index);
+        self.entries.remove(old_index);
+        // the address is unique, so no other leaf can equal the new one
+        let new_index = self.elements.binary_search(&leaf).unwrap_err();
+        self.elements.insert(new_index, leaf);
+        self.entries
+            .insert(new_index, Entry::new(account, new_amount));
+        if let Some(bloom) = &mut self.bloom {
+            bloom.insert(&leaf);
+        }
+        // the address is unique, so its entry keeps its place in the address order and
+        // only the entries between its old and new index shift by one
+        for index in &mut self.address_order {
+            *index = match *index {
+                index if index == old_index => new_index,
+                index if old_index < index && index <= new_index => index - 1,
+                index if new_index <= index && index < old_index => index + 1,
+                index => index,
+            };
+        }
+
+        let (mut first, mut last) = (old_index.min(new_index), old_index.max(new_index));
+        self.layers[0][first..=last].copy_from_slice(&self.elements[first..=last]);
+        for layer in 1..self.layers.len() {
+            first /= 2;
+            last /= 2;
+            for index in first..=last {
+                let children = &self.layers[layer - 1][index * 2..];
+                self.layers[layer][index] = match children {
+                    [left, right, ..] => {
+                        Self::hash_pair_with(left, right, self.config.node_sep.as_bytes())
+                    }
+                    [single] => *single,
+                    [] => unreachable!(),
+                };
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{AmountWidth, NodeSep};
+
+    fn data() -> Vec<(Address, U256)> {
+        (1..=11u64)
+            .map(|i| (Address::from_low_u64_be(i), U256::from(i * 100)))
+            .collect()
+    }
+
+    #[test]
+    fn set_amount_matches_fresh_build() {
+        let mut tree = MerkleTree::new(data()).with_bloom_filter(10);
+        let mut expected = data();
+        for (i, amount) in [(0, 7u64), (10, 1), (4, 123_456), (4, 5), (7, 800)] {
+            let account = expected[i].0;
+            tree.set_amount(account, U256::from(amount)).unwrap();
+            expected[i].1 = U256::from(amount);
+
+            let fresh = MerkleTree::new(expected.clone());
+            assert_eq!(tree.get_root(), fresh.get_root());
+            assert_eq!(tree.sorted_leaves(), fresh.sorted_leaves());
+            let claim = tree.get_claim(account).unwrap();
+            assert_eq!(claim.amount, U256::from(amount));
+            assert!(claim.verify(tree.get_root().unwrap()));
+        }
+
+        let mut separated = MerkleTree::new_with_node_sep(data(), NodeSep(vec![1]));
+        separated
+            .set_amount(Address::from_low_u64_be(3), U256::one())
+            .unwrap();
+        expected = data();
+        expected[2].1 = U256::one();
+        assert_eq!(
+            separated.get_root(),
+            MerkleTree::new_with_node_sep(expected, NodeSep(vec![1])).get_root()
+        );
+    }
+
+    #[test]
+    fn packed_trees_reject_amounts_wider_than_their_leaves() {
+        let mut tree = MerkleTree::new_packed(data(), AmountWidth::U96).unwrap();
+        let before = tree.get_root();
+        let account = Address::from_low_u64_be(5);
+        assert_eq!(
+            tree.set_amount(account, U256::one() << 100),
+            Err(MerkleError::AmountOutOfRange {
+                account,
+                width: AmountWidth::U96
+            })
+        );
+        assert_eq!(tree.get_root(), before);
+
+        tree.set_amount(account, (U256::one() << 96) - 1).unwrap();
+        let mut expected = data();
+        expected[4].1 = (U256::one() << 96) - 1;
+        let fresh = MerkleTree::new_packed(expected, AmountWidth::U96).unwrap();
+        assert_eq!(tree.get_root(), fresh.get_root());
+        assert_eq!(tree.entries(), fresh.entries());
+    }
+
+    #[test]
+    fn dry_run_matches_applied_updates() {
+        let mut tree = MerkleTree::new(data());
+        let before = tree.get_root();
+        let changes = [
+            LeafChange::new(Address::from_low_u64_be(2), U256::from(5)),
+            LeafChange::new(Address::from_low_u64_be(9), U256::from(123_456)),
+        ];
+        let preview = tree.dry_run(&changes).unwrap();
+        assert_eq!(tree.get_root(), before);
+        assert_eq!(
+            tree.sorted_leaves(),
+            MerkleTree::new(data()).sorted_leaves()
+        );
+
+        tree.apply_updates(&changes).unwrap();
+        assert_eq!(tree.get_root(), Some(preview));
+        assert_ne!(Some(preview), before);
+    }
+
+    #[test]
+    fn dry_run_rejects_like_apply_updates() {
+        let mut tree = MerkleTree::new(data());
+        let before = tree.get_root();
+        let repeated = [
+            LeafChange::new(Address::from_low_u64_be(2), U256::from(5)),
+            LeafChange::new(Address::from_low_u64_be(2), U256::from(6)),
+        ];
+        let unknown = [
+            LeafChange::new(Address::from_low_u64_be(2), U256::from(5)),
+            LeafChange::new(Address::zero(), U256::one()),
+        ];
+        for changes in [&repeated, &unknown] {
+            let error = tree.dry_run(changes).unwrap_err();
+            assert_eq!(tree.apply_updates(changes), Err(error));
+            assert_eq!(tree.get_root(), before);
+        }
+        assert_eq!(
+            tree.dry_run(&repeated),
+            Err(MerkleError::DuplicateAddress(Address::from_low_u64_be(2)))
+        );
+    }
+
+    #[test]
+    fn set_amount_rejects_unknown_and_ambiguous_addresses() {
+        let mut data = data();
+        data.push((Address::from_low_u64_be(2), U256::one()));
+        let mut tree = MerkleTree::new(data);
+        assert_eq!(
+            tree.set_amount(Address::zero(), U256::one()),
+            Err(MerkleError::UnknownAddress(Address::zero()))
+        );
+        assert_eq!(
+            tree.set_amount(Address::from_low_u64_be(2), U256::one()),
+            Err(MerkleError::DuplicateAddress(Address::from_low_u64_be(2)))
+        );
+        let mut hashes = MerkleTree::from_leaves(tree.sorted_leaves().to_vec());
+        assert_eq!(
+            hashes.set_amount(Address::from_low_u64_be(1), U256::one()),
+            Err(MerkleError::NoEntries)
+        );
+    }
+}
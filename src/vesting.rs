@@ -0,0 +1,156 @@
+//! Vesting schedules with one claimable leaf per recipient and unlock epoch.
+//!
+//! Every tranche becomes an `(address, amount, unlock)` leaf, hashed like
+//! OpenZeppelin's `StandardMerkleTree` with `["address", "uint256", "uint64"]`:
+//! `keccak256(bytes.concat(keccak256(abi.encode(account, amount, unlock))))`.
+
+use crate::{MerkleError, MerkleTree};
+use ethers::abi::ethabi;
+use ethers::core::utils::keccak256;
+use ethers::types::{Address, H256, U256};
+
+/// One tranche of a recipient's allocation, claimable from `unlock` on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VestingEntry {
+    pub account: Address,
+    pub amount: U256,
+    /// The unlock timestamp, in seconds.
+    pub unlock: u64,
+}
+
+impl VestingEntry {
+    /// Returns the leaf hash of the tranche.
+    pub fn leaf(&self) -> H256 {
+        let encoded_data = ethabi::encode(&[
+            ethabi::Token::Address(self.account),
+            ethabi::Token::Uint(self.amount),
+            ethabi::Token::Uint(U256::from(self.unlock)),
+        ]);
+        H256::from(keccak256(keccak256(encoded_data)))
+    }
+}
+
+/// Splits every allocation into one tranche per epoch of a schedule.
+///
+/// Each epoch gets `amount * weight / total_weight`, rounded down, and the final epoch
+/// also gets the rounding remainder, so the tranches of every recipient always add up to
+/// exactly its amount. A tranche may be zero for tiny amounts; it is kept so every
+/// recipient has one leaf per epoch.
+///
+/// # Arguments
+///
+/// * `entries` - The total allocation of every recipient.
+/// * `schedule` - `(unlock, weight)` pairs, one per epoch. Twelve equal monthly epochs are
+///   twelve pairs with weight `1`.
+///
+/// # Returns
+///
+/// A `Result` containing the tranches, grouped by recipient in input order and by epoch in
+/// schedule order, or `MerkleError::InvalidSchedule` if the schedule is empty or its
+/// weights add up to zero or overflow.
+pub fn split(
+    entries: &[(Address, U256)],
+    schedule: &[(u64, u64)],
+) -> Result<Vec<VestingEntry>, MerkleError> {
+    let total_weight = schedule
+        .iter()
+        .try_fold(0u64, |total, (_, weight)| total.checked_add(*weight))
+        .ok_or_else(|| MerkleError::InvalidSchedule("weights overflow u64".to_string()))?;
+    if total_weight == 0 {
+        return Err(MerkleError::InvalidSchedule(
+            "the schedule has no weight".to_string(),
+        ));
+    }
+
+    let mut tranches = Vec::with_capacity(entries.len() * schedule.len());
+    for &(account, amount) in entries {
+        let mut remaining = amount;
+        for (epoch, &(unlock, weight)) in schedule.iter().enumerate() {
+            let tranche = if epoch + 1 == schedule.len() {
+                remaining
+            } else {
+                // weight <= total_weight, so the quotient never exceeds amount
+                let share = amount.full_mul(U256::from(weight)) / U256::from(total_weight);
+                U256::try_from(share).expect("tranche exceeds amount")
+            };
+            remaining -= tranche;
+            tranches.push(VestingEntry {
+                account,
+                amount: tranche,
+                unlock,
+            });
+        }
+    }
+    Ok(tranches)
+}
+
+impl MerkleTree {
+    /// Constructs a new Merkle tree from vesting tranches.
+    ///
+    /// # Arguments
+    ///
+    /// * `entries` - The tranches, such as the output of [`split`].
+    ///
+    /// # Returns
+    ///
+    /// A new instance of `MerkleTree` whose leaves are the tranche leaves.
+    pub fn from_vesting(entries: &[VestingEntry]) -> Self {
+        Self::from_leaves(entries.iter().map(VestingEntry::leaf).collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tranches_add_up_to_every_amount() {
+        let entries: Vec<(Address, U256)> = [1u64, 11, 12, 1_000_003, 0]
+            .iter()
+            .enumerate()
+            .map(|(i, amount)| (Address::from_low_u64_be(i as u64 + 1), U256::from(*amount)))
+            .chain([(Address::repeat_byte(0xff), U256::MAX)])
+            .collect();
+        let monthly: Vec<(u64, u64)> = (1..=12).map(|month| (month * 2_592_000, 1)).collect();
+        let tranches = split(&entries, &monthly).unwrap();
+        assert_eq!(tranches.len(), entries.len() * 12);
+
+        for (recipient, (account, amount)) in tranches.chunks(12).zip(&entries) {
+            assert!(recipient.iter().all(|tranche| tranche.account == *account));
+            let total = recipient
+                .iter()
+                .fold(U256::zero(), |total, tranche| total + tranche.amount);
+            assert_eq!(total, *amount);
+            assert_eq!(recipient[11].unlock, 12 * 2_592_000);
+        }
+        // 1_000_003 = 11 * 83_333 + 83_340
+        assert_eq!(tranches[36].amount, U256::from(83_333));
+        assert_eq!(tranches[47].amount, U256::from(83_340));
+
+        let tree = MerkleTree::from_vesting(&tranches);
+        let root = tree.get_root().unwrap();
+        let leaf = tranches[40].leaf();
+        assert!(tree.verify_proof(leaf, tree.get_proof(leaf).unwrap(), root));
+    }
+
+    #[test]
+    fn weighted_schedule_and_invalid_schedules() {
+        let entries = [(Address::from_low_u64_be(1), U256::from(1_000))];
+        let cliff = split(&entries, &[(100, 1), (200, 3)]).unwrap();
+        assert_eq!(cliff[0].amount, U256::from(250));
+        assert_eq!(cliff[1].amount, U256::from(750));
+
+        assert!(matches!(
+            split(&entries, &[]),
+            Err(MerkleError::InvalidSchedule(_))
+        ));
+        assert!(matches!(
+            split(&entries, &[(100, 0)]),
+            Err(MerkleError::InvalidSchedule(_))
+        ));
+        assert!(matches!(
+            split(&entries, &[(100, u64::MAX), (200, 1)]),
+            Err(MerkleError::InvalidSchedule(_))
+        ));
+    }
+}
@@ -0,0 +1,288 @@
+//! Trees over 32-byte account ids, for chains whose accounts are public keys rather than
+//! 20-byte EVM addresses.
+//!
+//! A leaf is `keccak256(keccak256(id ++ amount))`, with the id as its 32 raw bytes and the
+//! amount as 32 big-endian bytes. That is the leaf of OpenZeppelin's `StandardMerkleTree`
+//! with the encoding `["bytes32", "uint256"]`, and, since `abi.encode` left-pads an address
+//! to 32 bytes, the leaf of `MerkleTree::hash_node` for an id holding a zero-padded address.
+
+use crate::{MerkleError, MerkleTree, Proof};
+use ethers::core::utils::keccak256;
+use ethers::types::{H256, U256};
+use std::collections::{HashMap, HashSet};
+
+/// A 32-byte account id and its amount, the data behind a leaf of a `MerkleTree32`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WideEntry {
+    /// The account id.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::hash"))]
+    pub account: H256,
+    /// The amount in base units.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::amount"))]
+    pub amount: U256,
+}
+
+/// Everything a claimant of a `MerkleTree32` needs: the entry, its leaf index and its proof.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WideClaim {
+    /// The claiming account id.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::hash"))]
+    pub account: H256,
+    /// The claimable amount in base units.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::amount"))]
+    pub amount: U256,
+    /// The index of the leaf among the sorted leaves of the tree.
+    pub index: usize,
+    /// The proof of the leaf.
+    pub proof: Proof,
+}
+
+impl WideClaim {
+    /// Verifies the claim against `root`.
+    pub fn verify(&self, root: H256) -> bool {
+        self.proof
+            .verify(MerkleTree32::hash_node((self.account, self.amount)), root)
+    }
+}
+
+/// A Merkle tree whose leaves commit to 32-byte account ids.
+///
+/// The tree itself is an ordinary `MerkleTree` built from the leaf hashes, so its root,
+/// proofs and verification are those of any other tree; this type keeps the entries and
+/// answers lookups by id.
+#[derive(Debug, Clone)]
+pub struct MerkleTree32 {
+    tree: MerkleTree,
+    // the data behind each leaf, in leaf order
+    entries: Vec<WideEntry>,
+    // the leaf index of every account id
+    accounts: HashMap<H256, usize>,
+}
+
+impl MerkleTree32 {
+    /// Hashes a 32-byte account id and its amount into a leaf.
+    ///
+    /// # Arguments
+    ///
+    /// * `leaf_data` - The account id and the amount.
+    ///
+    /// # Returns
+    ///
+    /// `keccak256(keccak256(id ++ amount))` with the amount as 32 big-endian bytes.
+    pub fn hash_node(leaf_data: (H256, U256)) -> H256 {
+        let mut preimage = [0u8; 64];
+        preimage[..32].copy_from_slice(leaf_data.0.as_bytes());
+        leaf_data.1.to_big_endian(&mut preimage[32..]);
+        H256(keccak256(keccak256(preimage)))
+    }
+
+    /// Constructs a new tree from 32-byte account ids and their amounts.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The account ids and amounts, in any order.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the tree, or `MerkleError::DuplicateAccountId` with the first
+    /// id that appears twice.
+    pub fn new(data: Vec<(H256, U256)>) -> Result<Self, MerkleError> {
+        let mut seen = HashSet::with_capacity(data.len());
+        for (account, _) in &data {
+            if !seen.insert(*account) {
+                return Err(MerkleError::DuplicateAccountId(*account));
+            }
+        }
+
+        let leaves = data
+            .iter()
+            .map(|leaf_data| Self::hash_node(*leaf_data))
+            .collect();
+        let (tree, permutation) = MerkleTree::from_leaves_tracked(leaves);
+        let mut entries = vec![WideEntry::default(); tree.leaves_length()];
+        let mut accounts = HashMap::with_capacity(data.len());
+        for ((account, amount), index) in data.into_iter().zip(permutation) {
+            entries[index] = WideEntry { account, amount };
+            accounts.insert(account, index);
+        }
+        Ok(Self {
+            tree,
+            entries,
+            accounts,
+        })
+    }
+
+    /// Returns the underlying tree of leaf hashes.
+    pub fn tree(&self) -> &MerkleTree {
+        &self.tree
+    }
+
+    /// Returns the root of the tree, or `None` if it has no leaves.
+    pub fn get_root(&self) -> Option<H256> {
+        self.tree.get_root()
+    }
+
+    /// Returns the entries in the order of `sorted_leaves()` of the underlying tree.
+    pub fn entries(&self) -> &[WideEntry] {
+        &self.entries
+    }
+
+    /// Returns the claim of an account id.
+    ///
+    /// # Arguments
+    ///
+    /// * `account` - The account id.
+    ///
+    /// # Returns
+    ///
+    /// The claim with its amount, index and proof, or `None` if the tree has no entry for
+    /// the id.
+    pub fn get_claim(&self, account: H256) -> Option<WideClaim> {
+        self.accounts
+            .get(&account)
+            .map(|&index| self.claim_at(index))
+    }
+
+    /// Returns the claims of every entry, ordered by account id.
+    pub fn claim_records(&self) -> Vec<WideClaim> {
+        let mut claims: Vec<WideClaim> = (0..self.entries.len())
+            .map(|index| self.claim_at(index))
+            .collect();
+        claims.sort_by_key(|claim| claim.account);
+        claims
+    }
+
+    /// Exports the claims with the `value` and `proof` keys of OpenZeppelin's
+    /// `StandardMerkleTree` claims, like `MerkleTree::export_oz_claims`, with the id as a
+    /// `0x`-prefixed 32-byte hex string under the `["bytes32", "uint256"]` encoding. The
+    /// proofs are this tree's, so no OZ `treeIndex` is exported.
+    ///
+    /// # Returns
+    ///
+    /// A JSON array of claims ordered by account id.
+    #[cfg(feature = "serde")]
+    pub fn export_oz_claims(&self) -> serde_json::Value {
+        self.claim_records()
+            .into_iter()
+            .map(|claim| {
+                serde_json::json!({
+                    "value": [format!("{:?}", claim.account), claim.amount.to_string()],
+                    "proof": claim
+                        .proof
+                        .as_slice()
+                        .iter()
+                        .map(|hash| format!("{:?}", hash))
+                        .collect::<Vec<_>>(),
+                })
+            })
+            .collect()
+    }
+
+    fn claim_at(&self, index: usize) -> WideClaim {
+        let entry = self.entries[index];
+        WideClaim {
+            account: entry.account,
+            amount: entry.amount,
+            index,
+            proof: Proof::from(self.tree.proof_at(index)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ethers::types::Address;
+
+    fn data() -> Vec<(H256, U256)> {
+        (1..=9u64)
+            .map(|i| (H256(keccak256(i.to_be_bytes())), U256::from(i) * 1000))
+            .collect()
+    }
+
+    #[test]
+    fn wide_claims_round_trip() {
+        let tree = MerkleTree32::new(data()).unwrap();
+        let root = tree.get_root().unwrap();
+        assert_eq!(
+            format!(
+                "{:?}",
+                MerkleTree32::hash_node((H256::repeat_byte(0xab), U256::from(5)))
+            ),
+            "0x44c1e49f1bd3314d67b1cfefb5c711e72eb271fb1c8b4c97e8b513f61e9650fb"
+        );
+
+        let claims = tree.claim_records();
+        assert_eq!(claims.len(), 9);
+        assert!(claims
+            .windows(2)
+            .all(|pair| pair[0].account < pair[1].account));
+        for (account, amount) in data() {
+            let claim = tree.get_claim(account).unwrap();
+            assert_eq!(claim.amount, amount);
+            assert!(claim.verify(root));
+            assert_eq!(
+                tree.tree().sorted_leaves()[claim.index],
+                MerkleTree32::hash_node((account, amount))
+            );
+            let wrong = WideClaim {
+                amount: amount + 1,
+                ..claim
+            };
+            assert!(!wrong.verify(root));
+        }
+        assert_eq!(tree.get_claim(H256::zero()), None);
+
+        let mut twice = data();
+        twice.push((twice[4].0, U256::one()));
+        assert_eq!(
+            MerkleTree32::new(twice).map(|tree| tree.get_root()),
+            Err(MerkleError::DuplicateAccountId(data()[4].0))
+        );
+    }
+
+    #[test]
+    fn padded_addresses_match_the_evm_tree() {
+        let evm: Vec<(Address, U256)> = (1..=6u64)
+            .map(|i| (Address::from_low_u64_be(i * 77), U256::from(i)))
+            .collect();
+        let tree = MerkleTree::new(evm.clone());
+        let wide = MerkleTree32::new(
+            evm.iter()
+                .map(|(account, amount)| (H256::from(*account), *amount))
+                .collect(),
+        )
+        .unwrap();
+        assert_eq!(wide.get_root(), tree.get_root());
+        for (account, amount) in evm {
+            let claim = wide.get_claim(H256::from(account)).unwrap();
+            let leaf = MerkleTree::hash_node((account, amount));
+            assert_eq!(tree.get_proof(leaf), Some(claim.proof.as_slice().to_vec()));
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn wide_claims_export_in_oz_shape() {
+        let tree = MerkleTree32::new(data()).unwrap();
+        let exported = tree.export_oz_claims();
+        let claims = tree.claim_records();
+        assert_eq!(exported.as_array().unwrap().len(), claims.len());
+        assert_eq!(
+            exported[0]["value"][0],
+            format!("0x{}", ethers::utils::hex::encode(claims[0].account))
+        );
+        let root = tree.get_root().unwrap();
+        for (json, claim) in exported.as_array().unwrap().iter().zip(&claims) {
+            assert!(json.get("treeIndex").is_none());
+            let proof: Vec<H256> = serde_json::from_value(json["proof"].clone()).unwrap();
+            assert_eq!(proof, claim.proof.as_slice());
+            assert!(claim.verify(root));
+        }
+        let decoded: WideClaim =
+            serde_json::from_value(serde_json::to_value(&claims[3]).unwrap()).unwrap();
+        assert_eq!(decoded, claims[3]);
+    }
+}
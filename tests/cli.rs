@@ -0,0 +1,63 @@
+//! Runs the `oz-merkle` binary on a small balance file.
+#![cfg(all(feature = "csv", feature = "serde"))]
+
+use ethers::types::{Address, U256};
+use oz_merkle_rs::MerkleTreeBuilder;
+use std::io::Write;
+use std::process::Command;
+
+const BALANCES: &str = "\
+address,amount
+0x1111111111111111111111111111111111111111,100
+0x2222222222222222222222222222222222222222,0
+0x1111111111111111111111111111111111111111,100
+0x3333333333333333333333333333333333333333,250
+";
+
+fn run(args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_oz-merkle"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn prints_the_build_report() {
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    file.write_all(BALANCES.as_bytes()).unwrap();
+    let path = file.path().to_str().unwrap();
+    let (tree, _) = MerkleTreeBuilder::new()
+        .build_with_report(vec![
+            (Address::repeat_byte(0x11), U256::from(100)),
+            (Address::repeat_byte(0x22), U256::zero()),
+            (Address::repeat_byte(0x11), U256::from(100)),
+            (Address::repeat_byte(0x33), U256::from(250)),
+        ])
+        .unwrap();
+
+    let output = run(&[path]);
+    assert!(output.status.success());
+    let text = String::from_utf8(output.stdout).unwrap();
+    assert!(text.starts_with(&format!("root: {:?}\n", tree.get_root().unwrap())));
+    assert!(text.contains("rows: 4\nleaves: 3\n"), "{}", text);
+
+    let output = run(&["--json", path]);
+    assert!(output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["rows"], 4);
+    assert_eq!(report["leaves"], 3);
+    assert_eq!(
+        report["dropped_duplicates"],
+        serde_json::json!(["0x1111111111111111111111111111111111111111"])
+    );
+    assert_eq!(report["kept_zero_amounts"].as_array().unwrap().len(), 1);
+}
+
+#[test]
+fn rejects_bad_arguments() {
+    assert!(!run(&[]).status.success());
+    assert!(!run(&["--verbose", "balances.csv"]).status.success());
+    let output = run(&["does-not-exist.csv"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("does-not-exist.csv"));
+}
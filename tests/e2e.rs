@@ -0,0 +1,228 @@
+//! End-to-end check of the crate's roots and proofs against OpenZeppelin's `MerkleProof`
+//! on a local chain.
+//!
+//! The test is ignored by default, as it needs three things besides Rust:
+//!
+//! * `anvil`, from Foundry, on `PATH`;
+//! * `solc` 0.8.24 on `PATH`;
+//! * `@openzeppelin/contracts`, installed by `npm install` at the version pinned in
+//!   `package.json`, or at the path in `OZ_CONTRACTS`.
+//!
+//! Run it with `cargo test --features e2e --test e2e -- --ignored`; it fails if any of
+//! them is missing.
+//!
+//! The contract is `fixtures/Distributor.sol`, compiled by the test. It is deployed with
+//! a root and an amount width, and its `claim(address account, uint256 amount, bytes32[]
+//! proof)` succeeds once per leaf when `MerkleProof.verify(proof, root, leaf)` does, and
+//! reverts otherwise. A width of 0 selects OZ's standard leaf,
+//! `keccak256(bytes.concat(keccak256(abi.encode(account, amount))))`; a width of 12 or 32
+//! selects `keccak256(abi.encodePacked(account, uintN(amount)))` with 12- or 32-byte
+//! amounts.
+//!
+//! To check your own trees, deploy the contract the same way with `Distributor::deploy`
+//! and submit the proofs the crate gives you.
+#![cfg(feature = "e2e")]
+
+use ethers::abi::{self, Token};
+use ethers::middleware::SignerMiddleware;
+use ethers::providers::{Http, Middleware, Provider};
+use ethers::signers::{LocalWallet, Signer};
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::types::{Address, TransactionRequest, H256, U256};
+use ethers::utils::{hex, id, Anvil, AnvilInstance};
+use oz_merkle_rs::{AmountWidth, LeafMode, MerkleTree, StandardMerkleTree};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+
+/// The version of solc `fixtures/Distributor.sol` is pinned to.
+const SOLC_VERSION: &str = "0.8.24";
+
+type Client = SignerMiddleware<Provider<Http>, LocalWallet>;
+
+/// A deployed `fixtures/Distributor.sol`.
+struct Distributor {
+    client: Arc<Client>,
+    address: Address,
+}
+
+impl Distributor {
+    /// Deploys a distributor for `root`, hashing leaves as selected by `width`.
+    async fn deploy(client: Arc<Client>, code: &[u8], root: H256, width: usize) -> Self {
+        let mut code = code.to_vec();
+        code.extend(abi::encode(&[
+            Token::FixedBytes(root.as_bytes().to_vec()),
+            Token::Uint(width.into()),
+        ]));
+        let receipt = client
+            .send_transaction(TransactionRequest::new().data(code), None)
+            .await
+            .unwrap()
+            .await
+            .unwrap()
+            .unwrap();
+        Self {
+            client,
+            address: receipt.contract_address.unwrap(),
+        }
+    }
+
+    fn claim_tx(&self, leaf_data: (Address, U256), proof: &[H256]) -> TypedTransaction {
+        let (account, amount) = leaf_data;
+        let proof = proof
+            .iter()
+            .map(|hash| Token::FixedBytes(hash.as_bytes().to_vec()))
+            .collect();
+        let mut data = id("claim(address,uint256,bytes32[])").to_vec();
+        data.extend(abi::encode(&[
+            Token::Address(account),
+            Token::Uint(amount),
+            Token::Array(proof),
+        ]));
+        TransactionRequest::new().to(self.address).data(data).into()
+    }
+
+    /// Simulates a claim and returns whether it would succeed.
+    async fn accepts(&self, leaf_data: (Address, U256), proof: &[H256]) -> bool {
+        let tx = self.claim_tx(leaf_data, proof);
+        self.client.call(&tx, None).await.is_ok()
+    }
+
+    /// Submits a claim and asserts it was mined successfully.
+    async fn claim(&self, leaf_data: (Address, U256), proof: &[H256]) {
+        let receipt = self
+            .client
+            .send_transaction(self.claim_tx(leaf_data, proof), None)
+            .await
+            .unwrap()
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(receipt.status, Some(1.into()));
+    }
+}
+
+/// Finds `@openzeppelin/contracts`.
+fn oz_contracts() -> PathBuf {
+    let path = std::env::var_os("OZ_CONTRACTS").map_or_else(
+        || Path::new(env!("CARGO_MANIFEST_DIR")).join("node_modules/@openzeppelin/contracts"),
+        PathBuf::from,
+    );
+    assert!(
+        path.join("utils/cryptography/MerkleProof.sol").is_file(),
+        "@openzeppelin/contracts not found at {}; install it or set OZ_CONTRACTS",
+        path.display()
+    );
+    path
+}
+
+/// Compiles `fixtures/Distributor.sol` with solc and returns its creation code.
+fn compile_distributor() -> Vec<u8> {
+    let oz = oz_contracts();
+    let output = Command::new("solc")
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .arg("--combined-json")
+        .arg("bin")
+        .arg("--optimize")
+        .arg("--allow-paths")
+        .arg(&oz)
+        .arg(format!("@openzeppelin/contracts/={}/", oz.display()))
+        .arg("fixtures/Distributor.sol")
+        .output()
+        .expect("solc is not installed");
+    assert!(
+        output.status.success(),
+        "solc failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let output: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let version = output["version"].as_str().unwrap();
+    assert!(
+        version.starts_with(&format!("{}+", SOLC_VERSION)),
+        "solc {} is required, found {}",
+        SOLC_VERSION,
+        version
+    );
+    let code = output["contracts"]["fixtures/Distributor.sol:Distributor"]["bin"]
+        .as_str()
+        .unwrap();
+    hex::decode(code).unwrap()
+}
+
+/// Starts anvil.
+fn anvil() -> AnvilInstance {
+    Command::new("anvil")
+        .arg("--version")
+        .output()
+        .expect("anvil is not installed");
+    Anvil::new().spawn()
+}
+
+fn data() -> Vec<(Address, U256)> {
+    (1..=5u64)
+        .map(|i| (Address::from_low_u64_be(i), U256::exp10(18) * i))
+        .collect()
+}
+
+/// Claims every entry with its proof, checking that tampered claims and second claims
+/// revert.
+async fn claim_all(distributor: &Distributor, proof_of: impl Fn((Address, U256)) -> Vec<H256>) {
+    for (account, amount) in data() {
+        let proof = proof_of((account, amount));
+        assert!(!distributor.accepts((account, amount + 1), &proof).await);
+        assert!(
+            !distributor
+                .accepts((Address::repeat_byte(0x99), amount), &proof)
+                .await
+        );
+        let mut tampered = proof.clone();
+        tampered[0].0[0] ^= 1;
+        assert!(!distributor.accepts((account, amount), &tampered).await);
+        assert!(
+            !distributor
+                .accepts((account, amount), &proof[..proof.len() - 1])
+                .await
+        );
+
+        assert!(distributor.accepts((account, amount), &proof).await);
+        distributor.claim((account, amount), &proof).await;
+        assert!(!distributor.accepts((account, amount), &proof).await);
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs anvil, solc 0.8.24 and @openzeppelin/contracts"]
+async fn proofs_are_accepted_on_chain() {
+    let code = compile_distributor();
+    let anvil = anvil();
+    let provider = Provider::<Http>::try_from(anvil.endpoint()).unwrap();
+    let wallet = LocalWallet::from(anvil.keys()[0].clone()).with_chain_id(anvil.chain_id());
+    let client = Arc::new(SignerMiddleware::new(provider, wallet));
+
+    let standard = StandardMerkleTree::of(data()).unwrap();
+    let distributor = Distributor::deploy(client.clone(), &code, standard.root(), 0).await;
+    claim_all(&distributor, |leaf_data| {
+        standard.get_entry_proof(leaf_data).unwrap()
+    })
+    .await;
+
+    let trees = [
+        (MerkleTree::new(data()), 0),
+        (
+            MerkleTree::new_with_leaf_mode(data(), LeafMode::PackedSingle),
+            AmountWidth::U256.bytes(),
+        ),
+        (
+            MerkleTree::new_packed(data(), AmountWidth::U96).unwrap(),
+            AmountWidth::U96.bytes(),
+        ),
+    ];
+    for (tree, width) in trees {
+        let distributor =
+            Distributor::deploy(client.clone(), &code, tree.get_root().unwrap(), width).await;
+        claim_all(&distributor, |leaf_data| {
+            tree.get_entry_proof(leaf_data).unwrap()
+        })
+        .await;
+    }
+}
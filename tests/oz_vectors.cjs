@@ -0,0 +1,39 @@
+// Prints the trees @openzeppelin/merkle-tree builds for `[address(i), i * 1e18]`, i from 1,
+// for 1 to 17 values, and the multiproofs of every set of values of the trees of up to 9,
+// as read by tests/oz_vectors.rs.
+//
+//   npm install && node tests/oz_vectors.cjs
+const { StandardMerkleTree } = require("@openzeppelin/merkle-tree");
+
+function multiProofs(tree, count) {
+  const proofs = [];
+  for (let mask = 1; mask < 1 << count; mask++) {
+    const indices = [];
+    for (let i = 0; i < count; i++) {
+      if (mask & (1 << i)) indices.push(i);
+    }
+    const { leaves, proof, proofFlags } = tree.getMultiProof(indices);
+    proofs.push({ indices, leaves: leaves.map((leaf) => tree.leafHash(leaf)), proof, proofFlags });
+  }
+  return proofs;
+}
+
+const vectors = [];
+for (let count = 1; count <= 17; count++) {
+  const values = [];
+  for (let i = 1; i <= count; i++) {
+    const address = "0x" + i.toString(16).padStart(40, "0");
+    values.push([address, (BigInt(i) * 10n ** 18n).toString()]);
+  }
+  const tree = StandardMerkleTree.of(values, ["address", "uint256"]);
+  const dump = tree.dump();
+  vectors.push({
+    count,
+    root: tree.root,
+    tree: dump.tree,
+    treeIndex: dump.values.map((value) => value.treeIndex),
+    proofs: values.map((_, index) => tree.getProof(index)),
+    multiProofs: count <= 9 ? multiProofs(tree, count) : [],
+  });
+}
+console.log(JSON.stringify(vectors));
@@ -0,0 +1,110 @@
+//! Cross-check of `StandardMerkleTree` against `@openzeppelin/merkle-tree` itself.
+//!
+//! The unit tests pin a few of OZ's outputs; this test compares every tree, `treeIndex`
+//! and proof OZ builds for 1 to 17 values, and the `getMultiProof` of every set of values
+//! for up to 9, with the crate's. It is ignored by default, as it needs node and the
+//! package pinned in `package.json`:
+//!
+//! ```text
+//! npm install
+//! cargo test --test oz_vectors -- --ignored
+//! ```
+//!
+//! Set `OZ_VECTORS` to the path of the output of `node tests/oz_vectors.cjs` to check
+//! vectors generated elsewhere instead of running node.
+
+use ethers::types::{Address, H256, U256};
+use oz_merkle_rs::{MerkleTree, MultiProof, StandardMerkleTree};
+use serde_json::Value;
+use std::process::Command;
+use std::str::FromStr;
+
+/// Returns the vectors printed by `tests/oz_vectors.cjs`.
+fn oz_vectors() -> Vec<Value> {
+    let json = match std::env::var_os("OZ_VECTORS") {
+        Some(path) => std::fs::read(path).unwrap(),
+        None => {
+            let output = Command::new("node")
+                .current_dir(env!("CARGO_MANIFEST_DIR"))
+                .arg("tests/oz_vectors.cjs")
+                .output()
+                .expect("node is not installed");
+            assert!(
+                output.status.success(),
+                "tests/oz_vectors.cjs failed, run `npm install` first: {}",
+                String::from_utf8_lossy(&output.stderr)
+            );
+            output.stdout
+        }
+    };
+    serde_json::from_slice(&json).unwrap()
+}
+
+fn hashes(json: &Value) -> Vec<H256> {
+    serde_json::from_value(json.clone()).unwrap()
+}
+
+fn data(count: u64) -> Vec<(Address, U256)> {
+    (1..=count)
+        .map(|i| (Address::from_low_u64_be(i), U256::exp10(18) * i))
+        .collect()
+}
+
+#[test]
+#[ignore = "needs node and `npm install`"]
+fn standard_trees_match_openzeppelin() {
+    let vectors = oz_vectors();
+    assert_eq!(vectors.len(), 17);
+    for vector in vectors {
+        let count = vector["count"].as_u64().unwrap();
+        let data = data(count);
+        let tree = StandardMerkleTree::of(data.clone()).unwrap();
+        assert_eq!(
+            tree.root(),
+            H256::from_str(vector["root"].as_str().unwrap()).unwrap(),
+            "root of {} values",
+            count
+        );
+        assert_eq!(tree.tree(), hashes(&vector["tree"]));
+        let tree_indices: Vec<usize> = tree.values().iter().map(|v| v.tree_index).collect();
+        assert_eq!(
+            tree_indices,
+            serde_json::from_value::<Vec<usize>>(vector["treeIndex"].clone()).unwrap()
+        );
+        for (leaf_data, proof) in data.iter().zip(vector["proofs"].as_array().unwrap()) {
+            assert_eq!(tree.get_entry_proof(*leaf_data).unwrap(), hashes(proof));
+        }
+    }
+}
+
+#[test]
+#[ignore = "needs node and `npm install`"]
+fn multiproofs_match_openzeppelin() {
+    for vector in oz_vectors() {
+        let count = vector["count"].as_u64().unwrap();
+        let data = data(count);
+        let standard = StandardMerkleTree::of(data.clone()).unwrap();
+        let tree = MerkleTree::new(data.clone());
+        let same_layout = tree.get_root() == Some(standard.root());
+        for oz in vector["multiProofs"].as_array().unwrap() {
+            let elements: Vec<H256> = oz["indices"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|index| MerkleTree::hash_node(data[index.as_u64().unwrap() as usize]))
+                .collect();
+            let expected = MultiProof {
+                leaves: hashes(&oz["leaves"]),
+                proof: hashes(&oz["proof"]),
+                proof_flags: serde_json::from_value(oz["proofFlags"].clone()).unwrap(),
+            };
+            assert_eq!(
+                standard.get_multi_proof(&elements).as_ref(),
+                Some(&expected)
+            );
+            if same_layout {
+                assert_eq!(tree.get_multi_proof(&elements), Some(expected));
+            }
+        }
+    }
+}